@@ -0,0 +1,300 @@
+//! Named bearer tokens for the HTTP transport, the scopes they grant and the
+//! caller identity they resolve to
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rmcp::model::Tool;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+use crate::limits::RateLimits;
+use crate::policy::{self, MailboxPolicy};
+
+/// Tools that send mail to other people
+const SEND_TOOLS: &[&str] = &["send_draft", "respond_to_invite"];
+
+/// Tools that remove mail from a mailbox
+const DELETE_TOOLS: &[&str] = &["trash_emails", "delete_emails", "empty_folder"];
+
+/// Prefix of the names of OAuth callers, which token names may not use so an
+/// access token's subject can never pass for a configured token
+pub const OAUTH_CALLER_PREFIX: &str = "oauth:";
+
+#[derive(Debug, Error)]
+pub enum TokenFileError {
+    #[error("Could not read token file '{0}': {1}")]
+    Io(String, std::io::Error),
+    #[error("Could not parse token file '{0}': {1}")]
+    Parse(String, serde_json::Error),
+    #[error("Invalid token '{0}': {1}")]
+    InvalidToken(String, String),
+}
+
+/// What a token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read mail, list mailboxes and save attachments or exports locally
+    Read,
+    /// Change flags, move mail, import and manage drafts
+    Write,
+    /// Send mail through SMTP
+    Send,
+    /// Trash or permanently delete mail
+    Delete,
+    /// See every caller's entries in the audit log, not only its own
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 5] = [Scope::Read, Scope::Write, Scope::Send, Scope::Delete, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Send => "send",
+            Scope::Delete => "delete",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// The scope a caller needs to use `tool`
+    pub fn required_for(tool: &Tool) -> Scope {
+        let name = tool.name.as_ref();
+        if SEND_TOOLS.contains(&name) {
+            Scope::Send
+        } else if DELETE_TOOLS.contains(&name) {
+            Scope::Delete
+        } else if tool.annotations.as_ref().and_then(|a| a.read_only_hint).unwrap_or(false)
+            || policy::LOCAL_WRITE_TOOLS.contains(&name)
+        {
+            Scope::Read
+        } else {
+            Scope::Write
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "send" => Ok(Scope::Send),
+            "delete" => Ok(Scope::Delete),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("unknown scope '{}', expected read, write, send, delete or admin", other)),
+        }
+    }
+}
+
+/// The authenticated identity behind a request
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Token name, or `oauth:` and the subject of an OAuth access token. It
+    /// owns the caller's audit entries, approvals, undo journal and rate budget.
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// When set, only these accounts may be used
+    pub accounts: Option<Vec<String>>,
+    /// Mailboxes the caller may use, on top of each account's own allow-list
+    pub mailbox_policy: MailboxPolicy,
+    /// The caller's own tool-call rates, replacing the server-wide ones
+    pub rate_limits: Option<RateLimits>,
+}
+
+impl Caller {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn permits_account(&self, account: &str) -> bool {
+        self.accounts.as_ref().is_none_or(|accounts| accounts.iter().any(|allowed| allowed == account))
+    }
+}
+
+/// One entry of the token file, or of `[[server.tokens]]` in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenEntry {
+    name: String,
+    #[serde(default)]
+    token: Option<String>,
+    /// Hex SHA-256 of the token, so the file need not hold the secret itself
+    #[serde(default)]
+    token_sha256: Option<String>,
+    scopes: Vec<Scope>,
+    #[serde(default)]
+    accounts: Option<Vec<String>>,
+    #[serde(default)]
+    mailboxes: Option<Vec<String>>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    rate_limits: Option<RateLimits>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokenFile {
+    tokens: Vec<TokenEntry>,
+}
+
+#[derive(Debug)]
+struct StoredToken {
+    digest: [u8; 32],
+    expires_at: Option<DateTime<Utc>>,
+    caller: Caller,
+}
+
+/// Why a bearer token was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthFailure {
+    Unknown,
+    /// The token matched the named entry, but it has expired
+    Expired(String),
+}
+
+/// The tokens accepted by the HTTP transport
+#[derive(Debug, Default)]
+pub struct TokenStore {
+    tokens: Vec<StoredToken>,
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+impl TokenStore {
+    /// Read a JSON token file
+    pub fn load(path: &Path) -> Result<Self, TokenFileError> {
+        let display = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|e| TokenFileError::Io(display.clone(), e))?;
+        Self::parse(&text).map_err(|e| match e {
+            TokenFileError::Parse(_, e) => TokenFileError::Parse(display, e),
+            other => other,
+        })
+    }
+
+    /// Parse the contents of a token file
+    pub fn parse(text: &str) -> Result<Self, TokenFileError> {
+        let file: TokenFile = serde_json::from_str(text).map_err(|e| TokenFileError::Parse(String::new(), e))?;
+        let mut store = TokenStore::default();
+        store.add_entries(file.tokens)?;
+        Ok(store)
+    }
+
+    /// Accept the tokens of `entries`, whose names must differ from those
+    /// already accepted
+    pub fn add_entries(&mut self, entries: Vec<TokenEntry>) -> Result<(), TokenFileError> {
+        for entry in entries {
+            let invalid = |reason: &str| TokenFileError::InvalidToken(entry.name.clone(), reason.to_string());
+            if entry.name.trim().is_empty() {
+                return Err(invalid("name must not be empty"));
+            }
+            if entry.name.starts_with(OAUTH_CALLER_PREFIX) {
+                return Err(invalid("names starting with 'oauth:' are reserved for OAuth callers"));
+            }
+            if self.tokens.iter().any(|stored| stored.caller.name == entry.name) {
+                return Err(invalid("duplicate token name"));
+            }
+            let digest = match (&entry.token, &entry.token_sha256) {
+                (Some(token), None) if !token.is_empty() => digest(token),
+                (None, Some(hex)) => parse_digest(hex).ok_or_else(|| invalid("token_sha256 must be 64 hex digits"))?,
+                _ => return Err(invalid("exactly one of token and token_sha256 must be set")),
+            };
+            if entry.scopes.is_empty() {
+                return Err(invalid("at least one scope is required"));
+            }
+            self.tokens.push(StoredToken {
+                digest,
+                expires_at: entry.expires_at,
+                caller: Caller {
+                    name: entry.name,
+                    scopes: entry.scopes,
+                    accounts: entry.accounts,
+                    mailbox_policy: MailboxPolicy { allow: entry.mailboxes },
+                    rate_limits: entry.rate_limits,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Accept `token` as a caller with every scope, for the single shared
+    /// MCP_AUTH_TOKEN
+    pub fn add_shared(&mut self, name: &str, token: &str) {
+        self.tokens.push(StoredToken {
+            digest: digest(token),
+            expires_at: None,
+            caller: Caller {
+                name: name.to_string(),
+                scopes: Scope::ALL.to_vec(),
+                accounts: None,
+                mailbox_policy: MailboxPolicy::default(),
+                rate_limits: None,
+            },
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// The caller of the token entry named `name`, unless the entry has expired
+    pub fn caller_named(&self, name: &str, now: DateTime<Utc>) -> Result<&Caller, AuthFailure> {
+        let stored = self.tokens.iter().find(|stored| stored.caller.name == name).ok_or(AuthFailure::Unknown)?;
+        if stored.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(AuthFailure::Expired(stored.caller.name.clone()));
+        }
+        Ok(&stored.caller)
+    }
+
+    /// The caller a presented bearer token belongs to. Every stored token is
+    /// compared, in constant time, against a digest of the presented one, so
+    /// neither the token's length nor which entry it is close to leaks.
+    pub fn authenticate(&self, presented: &str, now: DateTime<Utc>) -> Result<&Caller, AuthFailure> {
+        let presented = digest(presented);
+        let mut matched = None;
+        for stored in &self.tokens {
+            if bool::from(stored.digest.ct_eq(&presented)) {
+                matched = Some(stored);
+            }
+        }
+        let stored = matched.ok_or(AuthFailure::Unknown)?;
+        if stored.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(AuthFailure::Expired(stored.caller.name.clone()));
+        }
+        Ok(&stored.caller)
+    }
+}
@@ -0,0 +1,479 @@
+//! Tool calls held for a human to approve or reject before they run
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// Tools whose `dry_run` argument is true unless the call sets it
+const DRY_RUN_BY_DEFAULT: &[&str] = &["delete_emails", "empty_folder"];
+
+/// Rejected, expired and completed calls are forgotten after this long
+const RETAIN_FINISHED: chrono::TimeDelta = chrono::TimeDelta::days(7);
+
+/// How often a held call checks the store for a decision made by the
+/// `approvals` command in another process
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A tool whose calls need approval, optionally only when they take mail
+/// out of one of `from_mailboxes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalRule {
+    pub tool: String,
+    pub from_mailboxes: Option<Vec<String>>,
+}
+
+impl FromStr for ApprovalRule {
+    type Err = String;
+
+    /// Parse `tool` or `tool:Mailbox`, e.g. `send_draft` or `move_emails:INBOX`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tool, mailbox) = match s.split_once(':') {
+            Some((tool, mailbox)) => (tool.trim(), Some(mailbox.trim())),
+            None => (s.trim(), None),
+        };
+        if tool.is_empty() || mailbox.is_some_and(str::is_empty) {
+            return Err(format!("'{}' is not a tool name or tool:mailbox", s));
+        }
+        Ok(ApprovalRule {
+            tool: tool.to_string(),
+            from_mailboxes: mailbox.map(|mailbox| vec![mailbox.to_string()]),
+        })
+    }
+}
+
+impl fmt::Display for ApprovalRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from_mailboxes {
+            Some(mailboxes) => write!(f, "{}:{}", self.tool, mailboxes.join("|")),
+            None => f.write_str(&self.tool),
+        }
+    }
+}
+
+/// Which tool calls wait for approval, and where held calls are kept
+#[derive(Debug, Clone)]
+pub struct ApprovalConfig {
+    pub rules: Vec<ApprovalRule>,
+    /// JSON file of held calls, shared with the `approvals` command
+    pub store: Option<PathBuf>,
+    /// Held calls nobody decided on expire after this long
+    pub expire_after: Duration,
+    /// Bearer token for the HTTP approval endpoint
+    pub token: Option<String>,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        ApprovalConfig {
+            rules: Vec::new(),
+            store: None,
+            expire_after: Duration::from_secs(24 * 3600),
+            token: None,
+        }
+    }
+}
+
+impl ApprovalConfig {
+    /// Add rules from a comma-separated list. Rules for the same tool are
+    /// merged; a rule without a mailbox covers every source mailbox.
+    pub fn add_rules(&mut self, list: &str) -> Result<(), String> {
+        for rule in crate::policy::parse_list(list) {
+            let rule: ApprovalRule = rule.parse()?;
+            match self.rules.iter_mut().find(|existing| existing.tool == rule.tool) {
+                Some(existing) => match (&mut existing.from_mailboxes, rule.from_mailboxes) {
+                    (Some(mailboxes), Some(more)) => mailboxes.extend(more),
+                    (mailboxes, _) => *mailboxes = None,
+                },
+                None => self.rules.push(rule),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a call of `tool` with `arguments` must wait for approval.
+    /// Dry runs and attachment downloads that save nothing never do.
+    pub fn requires(&self, tool: &str, arguments: Option<&JsonMap<String, JsonValue>>) -> bool {
+        let Some(rule) = self.rules.iter().find(|rule| rule.tool == tool) else { return false };
+        let argument = |name: &str| arguments.and_then(|arguments| arguments.get(name));
+        let dry_run = argument("dry_run")
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(DRY_RUN_BY_DEFAULT.contains(&tool));
+        if dry_run {
+            return false;
+        }
+        if tool == "get_attachment" && !crate::policy::saves_attachment(arguments) {
+            return false;
+        }
+        let Some(mailboxes) = &rule.from_mailboxes else { return true };
+        let source = argument("from_mailbox")
+            .or_else(|| argument("mailbox"))
+            .and_then(JsonValue::as_str)
+            .unwrap_or("INBOX");
+        mailboxes.iter().any(|mailbox| {
+            mailbox == source || (mailbox.eq_ignore_ascii_case("inbox") && source.eq_ignore_ascii_case("inbox"))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    /// Waiting for a human
+    Pending,
+    /// Approved; runs as soon as the server holding it sees the decision
+    Approved,
+    Rejected,
+    /// Nobody decided in time
+    Expired,
+    /// Approved and running now
+    Executing,
+    /// Ran; the result or error is kept
+    Completed,
+}
+
+impl ApprovalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Rejected => "rejected",
+            ApprovalStatus::Expired => "expired",
+            ApprovalStatus::Executing => "executing",
+            ApprovalStatus::Completed => "completed",
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self, ApprovalStatus::Rejected | ApprovalStatus::Expired | ApprovalStatus::Completed)
+    }
+}
+
+impl fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A held tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Approval {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Token name, or "local" over stdio
+    pub caller: String,
+    pub account: String,
+    pub tool: String,
+    pub arguments: JsonMap<String, JsonValue>,
+    pub status: ApprovalStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<DateTime<Utc>>,
+    /// Who approved or rejected the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// What the tool returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<JsonValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ApprovalError {
+    #[error("no held call with id '{0}'")]
+    Unknown(String),
+    #[error("call '{id}' is {status}")]
+    WrongStatus { id: String, status: ApprovalStatus },
+    #[error("could not update approval store: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Held calls, shared by every MCP session of the server. With a store file
+/// every change re-reads and rewrites the file under a lock, on the blocking
+/// thread pool, so decisions made by the `approvals` command are seen by the
+/// running server.
+#[derive(Debug)]
+pub struct ApprovalQueue {
+    path: Option<PathBuf>,
+    expire_after: chrono::TimeDelta,
+    approvals: Mutex<Vec<Approval>>,
+    counter: AtomicU64,
+    decided: Notify,
+}
+
+impl ApprovalQueue {
+    /// A queue kept in memory only
+    pub fn in_memory(expire_after: Duration) -> Arc<Self> {
+        Self::with_path(None, expire_after)
+    }
+
+    /// The queue in `config.store`, or in memory when no store is configured
+    pub fn open(config: &ApprovalConfig) -> std::io::Result<Arc<Self>> {
+        let queue = Self::with_path(config.store.clone(), config.expire_after);
+        if let Some(path) = &queue.path {
+            load(path)?;
+        }
+        Ok(queue)
+    }
+
+    fn with_path(path: Option<PathBuf>, expire_after: Duration) -> Arc<Self> {
+        Arc::new(ApprovalQueue {
+            path,
+            expire_after: chrono::TimeDelta::from_std(expire_after).unwrap_or(chrono::TimeDelta::MAX),
+            approvals: Mutex::new(Vec::new()),
+            counter: AtomicU64::new(0),
+            decided: Notify::new(),
+        })
+    }
+
+    /// Hold a call, returning it with its new id
+    pub async fn submit(self: &Arc<Self>, caller: &str, account: &str, tool: &str, arguments: JsonMap<String, JsonValue>) -> std::io::Result<Approval> {
+        let now = Utc::now();
+        let approval = Approval {
+            id: format!("apr-{:x}-{}", now.timestamp_millis(), self.counter.fetch_add(1, Ordering::Relaxed)),
+            created_at: now,
+            expires_at: now + self.expire_after,
+            caller: caller.to_string(),
+            account: account.to_string(),
+            tool: tool.to_string(),
+            arguments,
+            status: ApprovalStatus::Pending,
+            decided_at: None,
+            decided_by: None,
+            note: None,
+            completed_at: None,
+            result: None,
+            error: None,
+        };
+        let held = approval.clone();
+        self.update(move |approvals| approvals.push(held)).await?;
+        Ok(approval)
+    }
+
+    pub async fn get(self: &Arc<Self>, id: &str) -> std::io::Result<Option<Approval>> {
+        let id = id.to_string();
+        self.update(move |approvals| approvals.iter().find(|approval| approval.id == id).cloned()).await
+    }
+
+    /// Held calls, oldest first, optionally only those with `status`
+    pub async fn list(self: &Arc<Self>, status: Option<ApprovalStatus>) -> std::io::Result<Vec<Approval>> {
+        self.update(move |approvals| {
+            approvals.iter()
+                .filter(|approval| status.is_none_or(|status| approval.status == status))
+                .cloned()
+                .collect()
+        }).await
+    }
+
+    /// Approve or reject a pending call, waking the server that holds it
+    pub async fn decide(self: &Arc<Self>, id: &str, approve: bool, by: &str, note: Option<String>) -> Result<Approval, ApprovalError> {
+        let by = by.to_string();
+        let approval = self.transition(id, ApprovalStatus::Pending, move |approval| {
+            approval.status = if approve { ApprovalStatus::Approved } else { ApprovalStatus::Rejected };
+            approval.decided_at = Some(Utc::now());
+            approval.decided_by = Some(by);
+            approval.note = note;
+        }).await?;
+        self.decided.notify_waiters();
+        Ok(approval)
+    }
+
+    /// Wait until a call may have been decided: at once for decisions made
+    /// through this queue, within a few seconds for those made elsewhere
+    pub async fn changed(&self) {
+        let _ = tokio::time::timeout(POLL_INTERVAL, self.decided.notified()).await;
+    }
+
+    /// Claim an approved call for running it, so it runs only once
+    pub async fn begin(self: &Arc<Self>, id: &str) -> Result<Approval, ApprovalError> {
+        self.transition(id, ApprovalStatus::Approved, |approval| approval.status = ApprovalStatus::Executing).await
+    }
+
+    /// Keep what a call claimed with `begin` returned
+    pub async fn complete(self: &Arc<Self>, id: &str, outcome: Result<JsonValue, String>) -> Result<Approval, ApprovalError> {
+        self.transition(id, ApprovalStatus::Executing, move |approval| {
+            approval.status = ApprovalStatus::Completed;
+            approval.completed_at = Some(Utc::now());
+            match outcome {
+                Ok(result) => approval.result = Some(result),
+                Err(error) => approval.error = Some(error),
+            }
+        }).await
+    }
+
+    async fn transition(
+        self: &Arc<Self>,
+        id: &str,
+        from: ApprovalStatus,
+        apply: impl FnOnce(&mut Approval) + Send + 'static,
+    ) -> Result<Approval, ApprovalError> {
+        let id = id.to_string();
+        self.update(move |approvals| {
+            let approval = approvals.iter_mut()
+                .find(|approval| approval.id == id)
+                .ok_or_else(|| ApprovalError::Unknown(id.clone()))?;
+            if approval.status != from {
+                return Err(ApprovalError::WrongStatus { id: id.clone(), status: approval.status });
+            }
+            apply(approval);
+            Ok(approval.clone())
+        }).await?
+    }
+
+    /// Apply `change` to the current held calls on the blocking thread pool,
+    /// since the store is locked, read and written with blocking file I/O
+    async fn update<T: Send + 'static>(
+        self: &Arc<Self>,
+        change: impl FnOnce(&mut Vec<Approval>) -> T + Send + 'static,
+    ) -> std::io::Result<T> {
+        let queue = Arc::clone(self);
+        tokio::task::spawn_blocking(move || queue.update_blocking(change))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Apply `change` to the current held calls, after expiring and
+    /// forgetting old ones, and save them
+    fn update_blocking<T>(&self, change: impl FnOnce(&mut Vec<Approval>) -> T) -> std::io::Result<T> {
+        let mut approvals = self.approvals.lock().unwrap_or_else(|e| e.into_inner());
+        let _lock = match &self.path {
+            Some(path) => {
+                let lock = lock_store(path)?;
+                *approvals = load(path)?;
+                Some(lock)
+            }
+            None => None,
+        };
+
+        let now = Utc::now();
+        for approval in approvals.iter_mut() {
+            if approval.status == ApprovalStatus::Pending && approval.expires_at <= now {
+                approval.status = ApprovalStatus::Expired;
+            }
+        }
+        approvals.retain(|approval| {
+            let finished_at = approval.completed_at.or(approval.decided_at).unwrap_or(approval.expires_at);
+            !approval.status.is_finished() || finished_at + RETAIN_FINISHED > now
+        });
+
+        let value = change(&mut approvals);
+        if let Some(path) = &self.path {
+            save(path, &approvals)?;
+        }
+        Ok(value)
+    }
+}
+
+/// Take an exclusive lock on `<store>.lock`, released when the file is dropped
+fn lock_store(path: &Path) -> std::io::Result<File> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(PathBuf::from(name))?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn load(path: &Path) -> std::io::Result<Vec<Approval>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(std::io::Error::other),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write the store through a temporary file, so a crash never leaves it half written
+fn save(path: &Path, approvals: &[Approval]) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let json = serde_json::to_vec_pretty(approvals).map_err(std::io::Error::other)?;
+    std::fs::write(&temporary, json)?;
+    std::fs::rename(&temporary, path)
+}
+
+/// Routes for humans to list, approve and reject held calls:
+/// `GET /approvals[?status=pending]`, `GET /approvals/{id}`,
+/// `POST /approvals/{id}/approve` and `POST /approvals/{id}/reject`, the
+/// last two with an optional `{"note": "..."}` body
+#[cfg(feature = "http")]
+pub fn http_routes(queue: Arc<ApprovalQueue>) -> axum::Router {
+    use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::{get, post};
+    use axum::{Extension, Json};
+
+    use crate::access::Caller;
+
+    #[derive(Deserialize)]
+    struct ListQuery {
+        status: Option<ApprovalStatus>,
+    }
+
+    #[derive(Deserialize)]
+    struct Decision {
+        note: Option<String>,
+    }
+
+    fn error_response(error: ApprovalError) -> Response {
+        let status = match &error {
+            ApprovalError::Unknown(_) => StatusCode::NOT_FOUND,
+            ApprovalError::WrongStatus { .. } => StatusCode::CONFLICT,
+            ApprovalError::Io(e) => {
+                log::error!("Approval store error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, Json(serde_json::json!({ "error": error.to_string() }))).into_response()
+    }
+
+    async fn decide(queue: &Arc<ApprovalQueue>, id: &str, approve: bool, caller: &Caller, decision: Option<Json<Decision>>) -> Response {
+        let note = decision.and_then(|Json(decision)| decision.note);
+        match queue.decide(id, approve, &caller.name, note).await {
+            Ok(approval) => {
+                log::info!("'{}' {} held call {} ({})", caller.name, approval.status, approval.id, approval.tool);
+                Json(approval).into_response()
+            }
+            Err(e) => error_response(e),
+        }
+    }
+
+    axum::Router::new()
+        .route("/approvals", get(|State(queue): State<Arc<ApprovalQueue>>, Query(query): Query<ListQuery>| async move {
+            match queue.list(query.status).await {
+                Ok(approvals) => Json(approvals).into_response(),
+                Err(e) => error_response(e.into()),
+            }
+        }))
+        .route("/approvals/{id}", get(|State(queue): State<Arc<ApprovalQueue>>, Path(id): Path<String>| async move {
+            match queue.get(&id).await {
+                Ok(Some(approval)) => Json(approval).into_response(),
+                Ok(None) => error_response(ApprovalError::Unknown(id)),
+                Err(e) => error_response(e.into()),
+            }
+        }))
+        .route("/approvals/{id}/approve", post(
+            |State(queue): State<Arc<ApprovalQueue>>, Path(id): Path<String>, Extension(caller): Extension<Caller>, decision: Option<Json<Decision>>| async move {
+                decide(&queue, &id, true, &caller, decision).await
+            },
+        ))
+        .route("/approvals/{id}/reject", post(
+            |State(queue): State<Arc<ApprovalQueue>>, Path(id): Path<String>, Extension(caller): Extension<Caller>, decision: Option<Json<Decision>>| async move {
+                decide(&queue, &id, false, &caller, decision).await
+            },
+        ))
+        .with_state(queue)
+}
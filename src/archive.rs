@@ -0,0 +1,393 @@
+//! Reading and writing mbox files and Maildir directories for import and export
+
+use crate::imap::Flag;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use mail_parser::MessageParser;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// A message read from an archive, ready to APPEND
+#[derive(Debug, Clone)]
+pub struct ArchivedMessage {
+    /// Where the message came from (file path, or mbox path with message index)
+    pub source: String,
+    pub content: Vec<u8>,
+    pub flags: Vec<Flag>,
+    pub internal_date: Option<DateTime<FixedOffset>>,
+}
+
+/// Archive layouts supported for bulk import
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Mbox(MboxFormat),
+    Maildir,
+}
+
+/// How an mbox file quotes body lines that look like message separators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MboxFormat {
+    /// Lines matching `^>*From ` gain one `>`, which reading removes again
+    #[default]
+    Mboxrd,
+    /// Only `From ` lines are quoted, so a quoted line cannot be told from a
+    /// real `>From ` line and is kept as it is
+    Mboxo,
+}
+
+impl ArchiveFormat {
+    /// Guess the format from the path: directories are Maildir, files are mboxrd
+    pub fn detect(path: &Path) -> io::Result<Self> {
+        if fs::metadata(path)?.is_dir() {
+            Ok(ArchiveFormat::Maildir)
+        } else {
+            Ok(ArchiveFormat::Mbox(MboxFormat::default()))
+        }
+    }
+}
+
+/// Streaming reader for mboxrd/mboxo files
+pub struct MboxReader<R: BufRead> {
+    reader: R,
+    path: String,
+    format: MboxFormat,
+    index: usize,
+    pending_from_line: Option<String>,
+}
+
+impl MboxReader<io::BufReader<fs::File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        Ok(Self::new(io::BufReader::new(file), path.display().to_string()))
+    }
+}
+
+impl<R: BufRead> MboxReader<R> {
+    pub fn new(reader: R, path: String) -> Self {
+        Self {
+            reader,
+            path,
+            format: MboxFormat::default(),
+            index: 0,
+            pending_from_line: None,
+        }
+    }
+
+    /// Read `format` instead of mboxrd
+    pub fn with_format(mut self, format: MboxFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        if self.reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+
+    fn read_message(&mut self) -> io::Result<Option<ArchivedMessage>> {
+        // Skip anything before the first separator line
+        let from_line = match self.pending_from_line.take() {
+            Some(line) => line,
+            None => loop {
+                match self.read_line()? {
+                    Some(line) if line.starts_with(b"From ") => {
+                        break String::from_utf8_lossy(&line).trim_end().to_string();
+                    }
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            },
+        };
+
+        let mut content = Vec::new();
+        let mut previous_blank = true;
+        while let Some(line) = self.read_line()? {
+            if previous_blank && line.starts_with(b"From ") {
+                self.pending_from_line = Some(String::from_utf8_lossy(&line).trim_end().to_string());
+                break;
+            }
+            previous_blank = line == b"\n" || line == b"\r\n";
+            match self.format {
+                MboxFormat::Mboxrd => content.extend_from_slice(unescape_from_line(&line)),
+                MboxFormat::Mboxo => content.extend_from_slice(&line),
+            }
+        }
+
+        // The blank line before the next separator belongs to the mbox, not the message
+        if content.ends_with(b"\r\n\r\n") || content.ends_with(b"\n\n") {
+            content.truncate(content.len() - if content.ends_with(b"\r\n") { 2 } else { 1 });
+        }
+
+        self.index += 1;
+        let flags = mbox_status_flags(&content);
+        Ok(Some(ArchivedMessage {
+            source: format!("{}#{}", self.path, self.index),
+            content: to_crlf(&content),
+            flags,
+            internal_date: parse_from_line_date(&from_line),
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for MboxReader<R> {
+    type Item = io::Result<ArchivedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_message().transpose()
+    }
+}
+
+/// mboxrd quotes body lines matching `^>*From ` with one extra `>`
+fn unescape_from_line(line: &[u8]) -> &[u8] {
+    let quotes = line.iter().take_while(|b| **b == b'>').count();
+    if quotes > 0 && line[quotes..].starts_with(b"From ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// Normalize bare LF line endings to CRLF as IMAP requires
+fn to_crlf(content: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(content.len() + content.len() / 40);
+    let mut previous = 0u8;
+    for &byte in content {
+        if byte == b'\n' && previous != b'\r' {
+            output.push(b'\r');
+        }
+        output.push(byte);
+        previous = byte;
+    }
+    output
+}
+
+/// Parse the asctime date at the end of a `From sender Sat Jan  3 01:05:34 1996` line
+fn parse_from_line_date(line: &str) -> Option<DateTime<FixedOffset>> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 7 {
+        return None;
+    }
+    let date = parts[parts.len() - 5..].join(" ");
+    NaiveDateTime::parse_from_str(&date, "%a %b %e %H:%M:%S %Y")
+        .ok()
+        .map(|naive| naive.and_utc().fixed_offset())
+}
+
+/// Read flags from the `Status:` and `X-Status:` headers written by mutt and similar clients
+fn mbox_status_flags(content: &[u8]) -> Vec<Flag> {
+    let header_end = content.windows(2)
+        .position(|w| w == b"\n\n")
+        .or_else(|| content.windows(4).position(|w| w == b"\r\n\r\n"))
+        .unwrap_or(content.len());
+    let headers = String::from_utf8_lossy(&content[..header_end]);
+
+    let mut flags = Vec::new();
+    for line in headers.lines() {
+        let (name, value) = match line.split_once(':') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let letters: &[(char, Flag)] = if name.eq_ignore_ascii_case("Status") {
+            &[('R', Flag::Seen)]
+        } else if name.eq_ignore_ascii_case("X-Status") {
+            &[('A', Flag::Answered), ('F', Flag::Flagged), ('T', Flag::Draft)]
+        } else {
+            continue;
+        };
+        for (letter, flag) in letters {
+            if value.contains(*letter) && !flags.contains(flag) {
+                flags.push(flag.clone());
+            }
+        }
+    }
+    flags
+}
+
+/// List message files in a Maildir (`cur/` and `new/`), sorted by name
+pub fn list_maildir(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for sub in ["cur", "new"] {
+        let dir = path.join(sub);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    if files.is_empty() && !path.join("cur").is_dir() && !path.join("new").is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a Maildir (no cur/ or new/)", path.display()),
+        ));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Read one Maildir message, taking flags from the `:2,` filename suffix and the date from mtime
+pub fn read_maildir_message(path: &Path) -> io::Result<ArchivedMessage> {
+    let content = fs::read(path)?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+
+    let mut flags = Vec::new();
+    if let Some((_, info)) = name.rsplit_once(":2,") {
+        for letter in info.chars() {
+            let flag = match letter {
+                'D' => Flag::Draft,
+                'F' => Flag::Flagged,
+                'R' => Flag::Answered,
+                'S' => Flag::Seen,
+                // 'T' (trashed) is skipped so imported mail is not expunged
+                _ => continue,
+            };
+            if !flags.contains(&flag) {
+                flags.push(flag);
+            }
+        }
+    }
+
+    let internal_date = fs::metadata(path)?
+        .modified()
+        .ok()
+        .map(|mtime| DateTime::<chrono::Utc>::from(mtime).fixed_offset());
+
+    Ok(ArchivedMessage {
+        source: path.display().to_string(),
+        content: to_crlf(&content),
+        flags,
+        internal_date,
+    })
+}
+
+/// Escape body lines for mboxrd: any line matching `^>*From ` gains one `>`
+fn escape_from_lines(content: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(content.len());
+    for line in content.split_inclusive(|b| *b == b'\n') {
+        let quotes = line.iter().take_while(|b| **b == b'>').count();
+        if line[quotes..].starts_with(b"From ") {
+            output.push(b'>');
+        }
+        output.extend_from_slice(line);
+    }
+    output
+}
+
+/// Normalize CRLF line endings to LF, as mbox and Maildir files conventionally use
+fn to_lf(content: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(content.len());
+    let mut iter = content.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        if byte == b'\r' && iter.peek() == Some(&&b'\n') {
+            continue;
+        }
+        output.push(byte);
+    }
+    output
+}
+
+/// Format one message as an mboxrd entry (separator line, escaped body, trailing blank line)
+pub fn mbox_entry(date: Option<DateTime<FixedOffset>>, content: &[u8]) -> Vec<u8> {
+    let date = date.unwrap_or_else(|| chrono::Utc::now().fixed_offset());
+    let sender = MessageParser::default().parse_headers(content)
+        .and_then(|message| {
+            message.from()
+                .and_then(|from| from.first())
+                .and_then(|addr| addr.address.as_ref())
+                .map(|address| address.to_string())
+        })
+        .filter(|address| !address.is_empty() && !address.contains(char::is_whitespace))
+        .unwrap_or_else(|| "MAILER-DAEMON".to_string());
+
+    let mut entry = format!("From {} {}\n", sender, date.naive_utc().format("%a %b %e %H:%M:%S %Y")).into_bytes();
+    let mut body = escape_from_lines(&to_lf(content));
+    if !body.ends_with(b"\n") {
+        body.push(b'\n');
+    }
+    entry.extend_from_slice(&body);
+    entry.push(b'\n');
+    entry
+}
+
+/// A Maildir unique name for a message that is the same on every export: a
+/// hash of its Message-ID, or of its content when it has none
+pub fn maildir_unique(content: &[u8]) -> String {
+    let message_id = MessageParser::default().parse_headers(content)
+        .and_then(|message| message.message_id().map(str::to_string));
+    let digest = match &message_id {
+        Some(message_id) => Sha256::digest(message_id.as_bytes()),
+        None => Sha256::digest(content),
+    };
+    let hex: String = digest[..12].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}.protonmail-mcp", hex)
+}
+
+/// Write a message into a Maildir (creating `cur/`, `new/` and `tmp/`) and return its path.
+/// Fails with `AlreadyExists` if a message with the same `unique` name is present, whatever
+/// its timestamp and flags, and `overwrite` is false; with `overwrite` that message is replaced.
+pub fn write_maildir_message(
+    root: &Path,
+    unique: &str,
+    flags: &[Flag],
+    date: Option<DateTime<FixedOffset>>,
+    content: &[u8],
+    overwrite: bool,
+) -> io::Result<PathBuf> {
+    for sub in ["cur", "new", "tmp"] {
+        fs::create_dir_all(root.join(sub))?;
+    }
+
+    // Maildir flag letters must appear in ASCII order
+    let mut letters: Vec<char> = flags.iter()
+        .filter_map(|flag| match flag {
+            Flag::Draft => Some('D'),
+            Flag::Flagged => Some('F'),
+            Flag::Answered => Some('R'),
+            Flag::Seen => Some('S'),
+            _ => None,
+        })
+        .collect();
+    letters.sort_unstable();
+    letters.dedup();
+
+    let timestamp = date.map(|d| d.timestamp()).unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let name = format!("{}.{}:2,{}", timestamp, unique, letters.into_iter().collect::<String>());
+
+    let existing = maildir_files_named(root, unique)?;
+    if let Some(path) = existing.first().filter(|_| !overwrite) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+
+    // Deliver through tmp/ so readers never see a partial file
+    let tmp = root.join("tmp").join(&name);
+    let destination = root.join("cur").join(&name);
+    fs::write(&tmp, to_lf(content))?;
+    fs::rename(&tmp, &destination)?;
+    for path in existing.into_iter().filter(|path| *path != destination) {
+        fs::remove_file(path)?;
+    }
+    Ok(destination)
+}
+
+/// Messages in `cur/` and `new/` whose name is `<timestamp>.<unique>` with any info suffix
+fn maildir_files_named(root: &Path, unique: &str) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for sub in ["cur", "new"] {
+        for entry in fs::read_dir(root.join(sub))? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let base = name.split_once(':').map_or(name, |(base, _)| base);
+            if base.split_once('.').is_some_and(|(_, rest)| rest == unique) {
+                found.push(path);
+            }
+        }
+    }
+    Ok(found)
+}
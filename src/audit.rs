@@ -0,0 +1,289 @@
+//! Append-only JSON-lines audit log of the actions agents take on mailboxes
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::access::{Caller, Scope};
+
+/// How many recent entries are kept in memory for `get_audit_log`
+const RECENT_ENTRIES: usize = 1000;
+
+/// Argument values longer than this are shortened in the log
+const MAX_ARGUMENT_CHARS: usize = 200;
+
+/// Argument names whose values are never written to the log
+const SECRET_ARGUMENTS: &[&str] = &["password", "secret", "token", "api_key", "private_key"];
+
+/// Arguments naming the mailboxes a call works in
+const MAILBOX_ARGUMENTS: &[&str] = &["mailbox", "from_mailbox", "to_mailbox", "mailboxes"];
+
+/// Arguments naming the emails a call works on
+const UID_ARGUMENTS: &[&str] = &["email_id", "email_ids"];
+
+/// Where and how the audit log is written
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub path: PathBuf,
+    /// Rotate the file once it would grow beyond this size
+    pub max_bytes: Option<u64>,
+    /// Rotated files to keep (`audit.jsonl.1` is the newest)
+    pub keep: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Error,
+}
+
+/// One audited tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Token name, or "local" over stdio
+    pub caller: String,
+    pub tool: String,
+    pub account: String,
+    /// Call arguments with secrets redacted and long values shortened
+    pub arguments: JsonValue,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mailboxes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message_ids: Vec<String>,
+    /// Id to pass to undo_operation, when the call can be undone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_id: Option<String>,
+    /// Id of the held call, when the call waits for approval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_id: Option<String>,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl AuditEntry {
+    /// Whether `caller` may read this entry: its own entries, or everyone's
+    /// with the admin scope, and only for accounts and mailboxes it may use
+    pub fn visible_to(&self, caller: &Caller) -> bool {
+        (self.caller == caller.name || caller.has_scope(Scope::Admin))
+            && caller.permits_account(&self.account)
+            && self.mailboxes.iter().all(|mailbox| caller.mailbox_policy.permits(mailbox))
+    }
+
+    /// An entry for a call with `arguments`, before its outcome is known.
+    /// Mailboxes and UIDs are taken from the arguments.
+    pub fn new(caller: &str, tool: &str, account: &str, arguments: Option<&JsonMap<String, JsonValue>>) -> Self {
+        let mut entry = AuditEntry {
+            timestamp: Utc::now(),
+            caller: caller.to_string(),
+            tool: tool.to_string(),
+            account: account.to_string(),
+            arguments: JsonValue::Object(arguments.map(redact).unwrap_or_default()),
+            mailboxes: Vec::new(),
+            uids: Vec::new(),
+            message_ids: Vec::new(),
+            operation_id: None,
+            approval_id: None,
+            outcome: Outcome::Ok,
+            error: None,
+            duration_ms: 0,
+        };
+        if let Some(arguments) = arguments {
+            for (name, value) in arguments {
+                if MAILBOX_ARGUMENTS.contains(&name.as_str()) {
+                    collect_strings(value, &mut entry.mailboxes);
+                } else if UID_ARGUMENTS.contains(&name.as_str()) {
+                    collect_strings(value, &mut entry.uids);
+                }
+            }
+        }
+        entry
+    }
+
+    /// Record the Message-IDs the call's result reports, e.g. of a sent
+    /// email, its undo operation id and the id it was held under
+    pub fn add_result(&mut self, result: &JsonValue) {
+        collect_message_ids(result, &mut self.message_ids);
+        if let Some(id) = result.get("operation_id").and_then(JsonValue::as_str) {
+            self.operation_id = Some(id.to_string());
+        }
+        if let Some(id) = result.get("approval_id").and_then(JsonValue::as_str) {
+            self.approval_id = Some(id.to_string());
+        }
+    }
+}
+
+fn collect_strings(value: &JsonValue, into: &mut Vec<String>) {
+    match value {
+        JsonValue::String(s) => into.push(s.clone()),
+        JsonValue::Number(n) => into.push(n.to_string()),
+        JsonValue::Array(items) => items.iter().for_each(|item| collect_strings(item, into)),
+        _ => {}
+    }
+}
+
+fn collect_message_ids(value: &JsonValue, into: &mut Vec<String>) {
+    match value {
+        JsonValue::Object(fields) => {
+            for (name, value) in fields {
+                match value {
+                    JsonValue::String(id) if name == "message_id" && !into.contains(id) => into.push(id.clone()),
+                    _ => collect_message_ids(value, into),
+                }
+            }
+        }
+        JsonValue::Array(items) => items.iter().for_each(|item| collect_message_ids(item, into)),
+        _ => {}
+    }
+}
+
+/// Copy `arguments` with secret values replaced and long strings shortened
+pub fn redact(arguments: &JsonMap<String, JsonValue>) -> JsonMap<String, JsonValue> {
+    arguments.iter()
+        .map(|(name, value)| {
+            let lower = name.to_lowercase();
+            let value = if SECRET_ARGUMENTS.iter().any(|secret| lower.contains(secret)) {
+                JsonValue::String("[redacted]".to_string())
+            } else {
+                shorten(value)
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+fn shorten(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::String(s) if s.chars().count() > MAX_ARGUMENT_CHARS => {
+            let kept: String = s.chars().take(MAX_ARGUMENT_CHARS).collect();
+            JsonValue::String(format!("{}... ({} chars)", kept, s.chars().count()))
+        }
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(shorten).collect()),
+        JsonValue::Object(fields) => JsonValue::Object(redact(fields)),
+        other => other.clone(),
+    }
+}
+
+#[derive(Debug)]
+struct LogFile {
+    config: AuditConfig,
+    file: Option<File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(config: AuditConfig) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { config, file: Some(file), size })
+    }
+
+    fn rotated(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Shift `audit.jsonl.N` to `.N+1`, dropping the oldest, and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        let path = self.config.path.clone();
+        if self.config.keep == 0 {
+            std::fs::remove_file(&path)?;
+        } else {
+            for n in (1..self.config.keep).rev() {
+                let from = Self::rotated(&path, n);
+                if from.exists() {
+                    std::fs::rename(&from, Self::rotated(&path, n + 1))?;
+                }
+            }
+            std::fs::rename(&path, Self::rotated(&path, 1))?;
+        }
+        *self = Self::open(self.config.clone())?;
+        Ok(())
+    }
+
+    fn append(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.config.max_bytes.is_some_and(|max| self.size > 0 && self.size + len > max) {
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            *self = Self::open(self.config.clone())?;
+        }
+        let file = self.file.as_mut().expect("file was just opened");
+        writeln!(file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+}
+
+/// The audit log shared by every MCP session of the server
+#[derive(Debug)]
+pub struct AuditLog {
+    file: Option<Mutex<LogFile>>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// A log that only keeps recent entries in memory
+    pub fn in_memory() -> Arc<Self> {
+        Arc::new(AuditLog { file: None, recent: Mutex::new(VecDeque::new()) })
+    }
+
+    /// A log appended to the configured file. The newest entries already in
+    /// the file are loaded so `get_audit_log` sees them after a restart.
+    pub fn open(config: AuditConfig) -> std::io::Result<Arc<Self>> {
+        let mut recent = VecDeque::new();
+        if let Ok(file) = File::open(&config.path) {
+            for line in BufReader::new(file).lines() {
+                if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) {
+                    if recent.len() == RECENT_ENTRIES {
+                        recent.pop_front();
+                    }
+                    recent.push_back(entry);
+                }
+            }
+        }
+        Ok(Arc::new(AuditLog {
+            file: Some(Mutex::new(LogFile::open(config)?)),
+            recent: Mutex::new(recent),
+        }))
+    }
+
+    /// Append an entry. A failure to write the file is logged but does not
+    /// fail the call being audited.
+    pub fn record(&self, entry: AuditEntry) {
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let written = serde_json::to_string(&entry)
+                .map_err(std::io::Error::other)
+                .and_then(|line| file.append(&line));
+            if let Err(e) = written {
+                log::error!("Could not write audit log {}: {}", file.config.path.display(), e);
+            }
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() == RECENT_ENTRIES {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    /// Up to `limit` of the newest entries matching `filter`, newest first
+    pub fn recent(&self, limit: usize, filter: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().rev().filter(|entry| filter(entry)).take(limit).cloned().collect()
+    }
+}
@@ -0,0 +1,469 @@
+//! Sender authentication: SPF, DKIM, DMARC and ARC results as recorded by the
+//! receiving server (RFC 8601, RFC 7208, RFC 8617), and heuristic spoofing signals.
+//!
+//! Results are only read from the receiving server configured as trusted, by
+//! the authserv-id it writes into its `Authentication-Results` headers (RFC
+//! 8601 §5) and the `receiver=` of its `Received-SPF` headers. Headers from
+//! any other server, or any header when no server is configured, could have
+//! been written by the sender, so the results are then reported as unknown.
+
+use mail_parser::Message;
+use serde::Serialize;
+
+/// Domains commonly impersonated in phishing, checked for lookalikes
+const IMPERSONATED_DOMAINS: &[&str] = &[
+    "proton.me",
+    "protonmail.com",
+    "paypal.com",
+    "google.com",
+    "microsoft.com",
+    "apple.com",
+    "amazon.com",
+    "docusign.com",
+    "dropbox.com",
+    "linkedin.com",
+];
+
+/// Public suffixes with two labels, so that `example.co.uk` is treated as one
+/// organization. An approximation of the Public Suffix List.
+const TWO_LABEL_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "com.au", "net.au", "org.au", "co.nz",
+    "co.jp", "ne.jp", "or.jp", "co.kr", "co.in", "co.za", "com.br", "com.cn",
+    "com.mx", "com.tr", "com.sg", "com.hk",
+];
+
+/// How the sender of an email was authenticated
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SenderAuthentication {
+    /// Domain of the From address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_domain: Option<String>,
+    /// True when DMARC passed, or SPF or DKIM passed for a domain aligned with From
+    pub authenticated: bool,
+    pub results: ResultsOrigin,
+    /// Server that recorded the results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authserv_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spf: Option<AuthResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dkim: Vec<AuthResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dmarc: Option<AuthResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arc: Option<ArcStatus>,
+    /// DKIM-Signature headers present in the message, whether or not they verified
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dkim_signatures: Vec<DkimSignature>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signals: Vec<SpoofingSignal>,
+}
+
+/// Whether the SPF, DKIM, DMARC and ARC results can be relied on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultsOrigin {
+    /// Recorded by the trusted receiving server
+    Trusted,
+    /// No trusted server is configured, or none of its headers are present
+    #[default]
+    Unknown,
+}
+
+/// Result of one authentication method
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthResult {
+    /// pass, fail, softfail, neutral, none, temperror, permerror or policy
+    pub result: String,
+    /// Domain the result applies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Whether `domain` belongs to the same organization as the From domain
+    pub aligned: bool,
+}
+
+/// ARC chain state
+#[derive(Debug, Clone, Serialize)]
+pub struct ArcStatus {
+    /// arc= result recorded by the receiving server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Number of ARC sets (hops that sealed the message)
+    pub instances: u32,
+    /// cv= of the latest ARC-Seal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_validation: Option<String>,
+    /// d= of the latest ARC-Seal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sealer: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DkimSignature {
+    pub domain: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    pub aligned: bool,
+}
+
+/// A heuristic hint that the sender may be impersonating someone
+#[derive(Debug, Clone, Serialize)]
+pub struct SpoofingSignal {
+    pub kind: SignalKind,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalKind {
+    /// The display name shows a different address or brand than the real sender
+    DisplayNameMismatch,
+    /// The From domain imitates a known or the user's own domain
+    LookalikeDomain,
+    /// Replies would go to a different organization than the sender
+    ReplyToMismatch,
+}
+
+/// Analyze the authentication headers and sender of a message. `own_address`
+/// is the mailbox owner, whose domain is protected against lookalikes too.
+/// `trusted_authserv_id` names the receiving server whose results are read.
+pub fn analyze(message: &Message<'_>, own_address: Option<&str>, trusted_authserv_id: Option<&str>) -> SenderAuthentication {
+    let from = message.from().and_then(|from| from.first());
+    let from_domain = from.and_then(|f| f.address()).and_then(domain_of);
+    let mut auth = SenderAuthentication {
+        from_domain: from_domain.clone(),
+        ..Default::default()
+    };
+    let aligned = |domain: Option<&str>| match (domain, &from_domain) {
+        (Some(domain), Some(from)) => organizational_domain(domain) == organizational_domain(from),
+        _ => false,
+    };
+    let result = |result: String, domain: Option<String>| AuthResult { aligned: aligned(domain.as_deref()), result, domain };
+
+    let trusted = |server: Option<&str>| match (server, trusted_authserv_id) {
+        (Some(server), Some(trusted)) => server.eq_ignore_ascii_case(trusted.trim()),
+        _ => false,
+    };
+
+    let mut arc_seals = Vec::new();
+    let mut results = None;
+    let mut received_spf = None;
+    for (name, value) in message.headers_raw() {
+        // Unfolding only removes the line breaks (RFC 5322 §2.2.3)
+        let value = value.replace(['\r', '\n'], "");
+        match name.to_ascii_lowercase().as_str() {
+            "authentication-results" if results.is_none() => {
+                let (authserv_id, methods) = parse_authentication_results(&value);
+                if trusted(authserv_id.as_deref()) {
+                    results = Some((authserv_id, methods));
+                }
+            }
+            "received-spf" if received_spf.is_none() => {
+                let (verdict, rest) = split_received_spf(&value);
+                if trusted(tag(&tag_list(&rest), "receiver")) {
+                    received_spf = Some((verdict, rest));
+                }
+            }
+            "dkim-signature" => {
+                let tags = tag_list(&value);
+                if let Some(domain) = tag(&tags, "d") {
+                    auth.dkim_signatures.push(DkimSignature {
+                        aligned: aligned(Some(domain)),
+                        domain: domain.to_ascii_lowercase(),
+                        selector: tag(&tags, "s").map(str::to_string),
+                    });
+                }
+            }
+            "arc-seal" => arc_seals.push(tag_list(&value)),
+            _ => {}
+        }
+    }
+
+    let mut arc_result = None;
+    if let Some((authserv_id, methods)) = results {
+        auth.authserv_id = authserv_id;
+        for method in methods {
+            match method.name.as_str() {
+                "spf" if auth.spf.is_none() => {
+                    let domain = method.property("smtp.mailfrom").or(method.property("smtp.helo")).and_then(domain_of);
+                    auth.spf = Some(result(method.result, domain));
+                }
+                "dkim" => {
+                    let domain = method.property("header.d")
+                        .map(str::to_ascii_lowercase)
+                        .or_else(|| method.property("header.i").and_then(domain_of));
+                    auth.dkim.push(result(method.result, domain));
+                }
+                "dmarc" if auth.dmarc.is_none() => {
+                    let domain = method.property("header.from").map(|d| domain_of(d).unwrap_or_else(|| d.to_ascii_lowercase()));
+                    auth.dmarc = Some(result(method.result, domain));
+                }
+                "arc" if arc_result.is_none() => arc_result = Some(method.result),
+                _ => {}
+            }
+        }
+    }
+
+    if auth.spf.is_none()
+        && let Some((verdict, rest)) = received_spf
+    {
+        let domain = tag(&tag_list(&rest), "envelope-from").and_then(domain_of);
+        auth.spf = Some(result(verdict, domain));
+        auth.authserv_id.get_or_insert_with(|| trusted_authserv_id.unwrap_or_default().trim().to_string());
+    }
+    if auth.authserv_id.is_some() {
+        auth.results = ResultsOrigin::Trusted;
+    }
+
+    let latest_seal = arc_seals.iter()
+        .max_by_key(|tags| tag(tags, "i").and_then(|i| i.parse::<u32>().ok()).unwrap_or(0));
+    if arc_result.is_some() || latest_seal.is_some() {
+        auth.arc = Some(ArcStatus {
+            result: arc_result,
+            instances: arc_seals.len() as u32,
+            chain_validation: latest_seal.and_then(|tags| tag(tags, "cv")).map(str::to_ascii_lowercase),
+            sealer: latest_seal.and_then(|tags| tag(tags, "d")).map(str::to_ascii_lowercase),
+        });
+    }
+
+    let passed = |r: &AuthResult| r.result == "pass";
+    auth.authenticated = auth.dmarc.as_ref().is_some_and(passed)
+        || auth.spf.as_ref().is_some_and(|r| passed(r) && r.aligned)
+        || auth.dkim.iter().any(|r| passed(r) && r.aligned);
+
+    if let (Some(from), Some(from_domain)) = (from, &from_domain) {
+        let own_domain = own_address.and_then(domain_of);
+        auth.signals = spoofing_signals(message, from.name(), from_domain, own_domain.as_deref());
+    }
+    auth
+}
+
+fn spoofing_signals(message: &Message<'_>, display_name: Option<&str>, from_domain: &str, own_domain: Option<&str>) -> Vec<SpoofingSignal> {
+    let mut signals = Vec::new();
+    let from_org = organizational_domain(from_domain);
+    let mut protected: Vec<&str> = IMPERSONATED_DOMAINS.to_vec();
+    protected.extend(own_domain);
+
+    if let Some(name) = display_name {
+        let lowered = name.to_lowercase();
+        let shown_address = lowered.split(|c: char| c.is_whitespace() || "<>()\"'".contains(c))
+            .find(|token| token.contains('@') && token.contains('.'));
+        if let Some(shown) = shown_address
+            && let Some(shown_domain) = domain_of(shown)
+            && organizational_domain(&shown_domain) != from_org
+        {
+            signals.push(SpoofingSignal {
+                kind: SignalKind::DisplayNameMismatch,
+                detail: format!("display name shows {} but the message is from {}", shown, from_domain),
+            });
+        } else if let Some(brand) = protected.iter().find(|domain| {
+            let label = first_label(&organizational_domain(domain));
+            let mut words = lowered.split(|c: char| !c.is_alphanumeric());
+            label.len() >= 4 && words.any(|word| word == label) && organizational_domain(domain) != from_org
+        }) {
+            signals.push(SpoofingSignal {
+                kind: SignalKind::DisplayNameMismatch,
+                detail: format!("display name mentions {} but the message is from {}", brand, from_domain),
+            });
+        }
+    }
+
+    if let Some(reason) = lookalike(from_domain, &protected) {
+        signals.push(SpoofingSignal { kind: SignalKind::LookalikeDomain, detail: reason });
+    }
+
+    let reply_domain = message.reply_to()
+        .and_then(|reply_to| reply_to.first())
+        .and_then(|addr| addr.address())
+        .and_then(domain_of);
+    if let Some(reply_domain) = reply_domain
+        && organizational_domain(&reply_domain) != from_org
+    {
+        signals.push(SpoofingSignal {
+            kind: SignalKind::ReplyToMismatch,
+            detail: format!("replies go to {} but the message is from {}", reply_domain, from_domain),
+        });
+    }
+    signals
+}
+
+/// Why `domain` looks like one of the `protected` domains without being it
+fn lookalike(domain: &str, protected: &[&str]) -> Option<String> {
+    if !domain.is_ascii() || domain.split('.').any(|label| label.starts_with("xn--")) {
+        return Some(format!("{} uses internationalized characters that can imitate other letters", domain));
+    }
+
+    let org = organizational_domain(domain);
+    let label = first_label(&org);
+    for target in protected {
+        let target_org = organizational_domain(target);
+        if org == target_org {
+            continue;
+        }
+        let target_label = first_label(&target_org);
+
+        if skeleton(&label) == skeleton(&target_label) {
+            return Some(format!("{} imitates {} with similar-looking characters", domain, target_org));
+        }
+        if target_label.len() >= 5 && edit_distance(&label, &target_label) == 1 {
+            return Some(format!("{} is one character away from {}", domain, target_org));
+        }
+        if domain.contains(&format!("{}.", target_org)) || label.split('-').any(|part| part == target_label) {
+            return Some(format!("{} embeds {} but belongs to {}", domain, target_org, org));
+        }
+    }
+    None
+}
+
+/// Fold characters that are easily confused into one representative
+fn skeleton(label: &str) -> String {
+    label.replace("rn", "m")
+        .replace("vv", "w")
+        .replace("cl", "d")
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | 'i' => 'l',
+            '5' => 's',
+            '3' => 'e',
+            _ => c,
+        })
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The registrable domain, e.g. `mail.example.co.uk` → `example.co.uk`
+pub fn organizational_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    let suffix_len = match labels.len() {
+        n if n >= 3 && TWO_LABEL_SUFFIXES.contains(&labels[n - 2..].join(".").as_str()) => 2,
+        _ => 1,
+    };
+    let keep = (suffix_len + 1).min(labels.len());
+    labels[labels.len() - keep..].join(".")
+}
+
+fn first_label(domain: &str) -> String {
+    domain.split('.').next().unwrap_or_default().to_string()
+}
+
+fn domain_of(address: &str) -> Option<String> {
+    let address = address.trim().trim_matches(['<', '>']);
+    let domain = address.rsplit_once('@').map_or(address, |(_, domain)| domain);
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    (domain.contains('.') && !domain.contains(char::is_whitespace)).then_some(domain)
+}
+
+/// Split a Received-SPF value, "pass (comment) client-ip=...; envelope-from=...;",
+/// into its lowercased verdict and its tags
+fn split_received_spf(value: &str) -> (String, String) {
+    let value = strip_comments(value);
+    let (verdict, rest) = value.trim().split_once(char::is_whitespace).unwrap_or((value.trim(), ""));
+    (verdict.to_ascii_lowercase(), rest.to_string())
+}
+
+/// One `method=result` entry of an Authentication-Results header
+struct MethodResult {
+    name: String,
+    result: String,
+    properties: Vec<(String, String)>,
+}
+
+impl MethodResult {
+    fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Split an Authentication-Results value into its authserv-id and results
+fn parse_authentication_results(value: &str) -> (Option<String>, Vec<MethodResult>) {
+    let value = strip_comments(value);
+    let mut segments = split_outside_quotes(&value, ';').into_iter();
+    let authserv_id = segments.next()
+        .and_then(|s| s.split_whitespace().next().map(str::to_string))
+        .filter(|id| !id.is_empty());
+
+    let methods = segments
+        .filter_map(|segment| {
+            let mut tokens = segment.split_whitespace();
+            let (name, result) = tokens.next()?.split_once('=')?;
+            let name = name.split('/').next().unwrap_or(name).to_ascii_lowercase();
+            let properties = tokens
+                .filter_map(|token| token.split_once('='))
+                .map(|(key, value)| (key.to_ascii_lowercase(), value.trim_matches('"').to_string()))
+                .collect();
+            Some(MethodResult { name, result: result.to_ascii_lowercase(), properties })
+        })
+        .collect();
+    (authserv_id, methods)
+}
+
+/// Parse `tag=value; tag=value` lists (DKIM-Signature, ARC-Seal, Received-SPF)
+fn tag_list(value: &str) -> Vec<(String, String)> {
+    split_outside_quotes(value, ';')
+        .into_iter()
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((key.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+        })
+        .collect()
+}
+
+fn tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+/// Remove parenthesized comments, which may nest, outside quoted strings
+fn strip_comments(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    for c in value.chars() {
+        match c {
+            '"' if depth == 0 => {
+                in_quotes = !in_quotes;
+                output.push(c);
+            }
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes && depth > 0 => {
+                depth -= 1;
+                output.push(' ');
+            }
+            _ if depth == 0 => output.push(c),
+            _ => {}
+        }
+    }
+    output
+}
+
+fn split_outside_quotes(value: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            _ if c == separator && !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts.into_iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
+}
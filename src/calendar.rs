@@ -0,0 +1,395 @@
+//! iCalendar (RFC 5545) invitations carried in iMIP (RFC 6047) messages:
+//! parsing the VEVENTs of a `text/calendar` part and building REPLY objects
+
+use chrono::{DateTime, Utc};
+use mail_parser::{Message, MimeHeaders, PartType};
+use serde::Serialize;
+
+const PRODID: &str = "-//protonmail-mcp-server//iMIP//EN";
+
+/// Properties copied verbatim from the invitation into a REPLY (RFC 5546 section 3.2.3)
+const REPLY_PROPERTIES: &[&str] = &["ORGANIZER", "RECURRENCE-ID", "DTSTART", "DTEND", "DURATION", "SUMMARY"];
+
+/// A calendar object found in an email
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CalendarInvite {
+    /// iTIP method, e.g. REQUEST, CANCEL or REPLY
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub events: Vec<CalendarEvent>,
+    /// Unfolded VTIMEZONE components, copied into replies
+    #[serde(skip)]
+    timezones: Vec<String>,
+}
+
+/// One VEVENT of an invitation
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CalendarEvent {
+    pub uid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organizer: Option<Participant>,
+    pub attendees: Vec<Participant>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<EventTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<EventTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub sequence: u32,
+    /// Set when this VEVENT overrides one occurrence of a recurring event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence_rule: Option<String>,
+    /// Unfolded content lines of the properties a reply has to repeat
+    #[serde(skip)]
+    reply_lines: Vec<String>,
+}
+
+/// An organizer or attendee
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Participant {
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Participation status, e.g. NEEDS-ACTION or ACCEPTED
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub rsvp: bool,
+}
+
+/// Start or end of an event as written in the invitation. Times with a TZID
+/// are wall-clock times in that zone; times without a timezone are floating.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventTime {
+    /// ISO 8601 date or date-time, with a `Z` suffix for UTC
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub all_day: bool,
+}
+
+/// Participation status sent in a REPLY
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyStatus {
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+impl ReplyStatus {
+    /// The PARTSTAT value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplyStatus::Accepted => "ACCEPTED",
+            ReplyStatus::Declined => "DECLINED",
+            ReplyStatus::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// Subject prefix used by common calendar clients
+    pub fn subject_prefix(&self) -> &'static str {
+        match self {
+            ReplyStatus::Accepted => "Accepted",
+            ReplyStatus::Declined => "Declined",
+            ReplyStatus::Tentative => "Tentative",
+        }
+    }
+}
+
+/// A parsed content line: `NAME;PARAM=value:VALUE`
+struct ContentLine<'a> {
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+impl ContentLine<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse the first calendar object in a message, looking at `text/calendar`
+/// parts first and then at `.ics` attachments
+pub fn find_invite(message: &Message<'_>) -> Option<CalendarInvite> {
+    let is_calendar_type = |part: &mail_parser::MessagePart<'_>| {
+        part.content_type().is_some_and(|ct| {
+            let subtype = ct.subtype().unwrap_or_default();
+            (ct.ctype().eq_ignore_ascii_case("text") && subtype.eq_ignore_ascii_case("calendar"))
+                || (ct.ctype().eq_ignore_ascii_case("application") && subtype.eq_ignore_ascii_case("ics"))
+        })
+    };
+    let is_ics_file = |part: &mail_parser::MessagePart<'_>| {
+        part.attachment_name().is_some_and(|name| name.to_ascii_lowercase().ends_with(".ics"))
+    };
+
+    let candidates = message.parts.iter().filter(|part| is_calendar_type(part))
+        .chain(message.parts.iter().filter(|part| !is_calendar_type(part) && is_ics_file(part)));
+    for part in candidates {
+        let text = match &part.body {
+            PartType::Text(text) => text.to_string(),
+            PartType::Binary(data) | PartType::InlineBinary(data) => String::from_utf8_lossy(data).into_owned(),
+            _ => continue,
+        };
+        if let Some(invite) = parse(&text) {
+            return Some(invite);
+        }
+    }
+    None
+}
+
+/// Parse a VCALENDAR object. Returns `None` when it contains no VEVENT.
+pub fn parse(text: &str) -> Option<CalendarInvite> {
+    let mut invite = CalendarInvite::default();
+    // Components entered below VCALENDAR, innermost last
+    let mut stack: Vec<String> = Vec::new();
+    let mut event: Option<CalendarEvent> = None;
+    let mut timezone = String::new();
+
+    for line in unfold(text).lines() {
+        let line = line.trim_end_matches('\r');
+        let Some(content) = parse_line(line) else {
+            continue;
+        };
+
+        match content.name.as_str() {
+            "BEGIN" => {
+                let component = content.value.trim().to_ascii_uppercase();
+                if component == "VEVENT" && stack.last().is_some_and(|c| c == "VCALENDAR") {
+                    event = Some(CalendarEvent::default());
+                }
+                stack.push(component);
+            }
+            "END" => {
+                let component = stack.pop().unwrap_or_default();
+                if component == "VEVENT" && stack.last().is_some_and(|c| c == "VCALENDAR") {
+                    invite.events.extend(event.take());
+                }
+            }
+            _ => {}
+        }
+
+        if stack.iter().any(|c| c == "VTIMEZONE") || (content.name == "END" && content.value.trim().eq_ignore_ascii_case("VTIMEZONE")) {
+            timezone.push_str(line);
+            timezone.push_str("\r\n");
+            if stack.iter().all(|c| c != "VTIMEZONE") {
+                invite.timezones.push(std::mem::take(&mut timezone));
+            }
+            continue;
+        }
+
+        match (stack.last().map(String::as_str), &mut event) {
+            (Some("VCALENDAR"), _) if content.name == "METHOD" => {
+                invite.method = Some(content.value.trim().to_ascii_uppercase());
+            }
+            (Some("VEVENT"), Some(event)) if stack.len() == 2 => apply_property(event, &content, line),
+            _ => {}
+        }
+    }
+
+    if invite.events.is_empty() {
+        None
+    } else {
+        Some(invite)
+    }
+}
+
+fn apply_property(event: &mut CalendarEvent, line: &ContentLine<'_>, raw: &str) {
+    if REPLY_PROPERTIES.contains(&line.name.as_str()) {
+        event.reply_lines.push(raw.to_string());
+    }
+
+    match line.name.as_str() {
+        "UID" => event.uid = line.value.trim().to_string(),
+        "SUMMARY" => event.summary = Some(unescape(line.value)),
+        "LOCATION" => event.location = Some(unescape(line.value)),
+        "DESCRIPTION" => event.description = Some(unescape(line.value)),
+        "STATUS" => event.status = Some(line.value.trim().to_ascii_uppercase()),
+        "SEQUENCE" => event.sequence = line.value.trim().parse().unwrap_or(0),
+        "RRULE" => event.recurrence_rule = Some(line.value.trim().to_string()),
+        "RECURRENCE-ID" => event.recurrence_id = Some(line.value.trim().to_string()),
+        "DTSTART" => event.start = Some(event_time(line)),
+        "DTEND" => event.end = Some(event_time(line)),
+        "ORGANIZER" => event.organizer = Some(participant(line)),
+        "ATTENDEE" => event.attendees.push(participant(line)),
+        _ => {}
+    }
+}
+
+fn participant(line: &ContentLine<'_>) -> Participant {
+    Participant {
+        email: calendar_address(line.value),
+        name: line.param("CN").map(str::to_string).filter(|name| !name.is_empty()),
+        role: line.param("ROLE").map(str::to_ascii_uppercase),
+        status: line.param("PARTSTAT").map(str::to_ascii_uppercase),
+        rsvp: line.param("RSVP").is_some_and(|rsvp| rsvp.eq_ignore_ascii_case("TRUE")),
+    }
+}
+
+/// Strip the `mailto:` scheme from a CAL-ADDRESS
+fn calendar_address(value: &str) -> String {
+    let value = value.trim();
+    match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => value[7..].to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn event_time(line: &ContentLine<'_>) -> EventTime {
+    let raw = line.value.trim();
+    let all_day = line.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || !raw.contains('T');
+    let utc = raw.ends_with(['Z', 'z']);
+
+    let digits = raw.trim_end_matches(['Z', 'z']);
+    let value = match (digits.get(..4), digits.get(4..6), digits.get(6..8)) {
+        (Some(year), Some(month), Some(day)) if all_day => format!("{}-{}-{}", year, month, day),
+        (Some(year), Some(month), Some(day)) => match (digits.get(9..11), digits.get(11..13), digits.get(13..15)) {
+            (Some(hour), Some(minute), Some(second)) => format!(
+                "{}-{}-{}T{}:{}:{}{}",
+                year, month, day, hour, minute, second,
+                if utc { "Z" } else { "" }
+            ),
+            _ => raw.to_string(),
+        },
+        _ => raw.to_string(),
+    };
+
+    let timezone = if utc {
+        Some("UTC".to_string())
+    } else {
+        line.param("TZID").map(str::to_string)
+    };
+    EventTime { value, timezone, all_day }
+}
+
+/// Build a METHOD:REPLY calendar object answering `event` for `attendee`
+pub fn build_reply(
+    invite: &CalendarInvite,
+    event: &CalendarEvent,
+    attendee: &Participant,
+    status: ReplyStatus,
+    comment: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "METHOD:REPLY".to_string(),
+    ];
+    lines.extend(invite.timezones.iter().flat_map(|tz| tz.lines().map(str::to_string)));
+    lines.push("BEGIN:VEVENT".to_string());
+    lines.push(format!("UID:{}", event.uid));
+    lines.push(format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+    lines.push(format!("SEQUENCE:{}", event.sequence));
+    lines.extend(event.reply_lines.iter().cloned());
+
+    let mut attendee_line = format!("ATTENDEE;PARTSTAT={}", status.as_str());
+    if let Some(name) = &attendee.name {
+        attendee_line.push_str(&format!(";CN=\"{}\"", name.replace('"', "'")));
+    }
+    attendee_line.push_str(&format!(":mailto:{}", attendee.email));
+    lines.push(attendee_line);
+
+    if let Some(comment) = comment.map(str::trim).filter(|c| !c.is_empty()) {
+        lines.push(format!("COMMENT:{}", escape(comment)));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line.trim_end_matches('\r'))).collect()
+}
+
+/// Parse one unfolded content line. Parameter values may be quoted, and
+/// quoted values may contain `:` and `;`.
+fn parse_line(line: &str) -> Option<ContentLine<'_>> {
+    let mut params = Vec::new();
+    let mut in_quotes = false;
+    let mut segment_start = 0;
+    let mut name: Option<String> = None;
+
+    let mut push_segment = |segment: &str, name: &mut Option<String>| {
+        if name.is_none() {
+            *name = Some(segment.trim().to_ascii_uppercase());
+        } else if let Some((key, value)) = segment.split_once('=') {
+            params.push((key.trim().to_ascii_uppercase(), value.trim().trim_matches('"').to_string()));
+        }
+    };
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                push_segment(&line[segment_start..index], &mut name);
+                segment_start = index + 1;
+            }
+            ':' if !in_quotes => {
+                push_segment(&line[segment_start..index], &mut name);
+                let name = name?;
+                if name.is_empty() {
+                    return None;
+                }
+                return Some(ContentLine { name, params, value: &line[index + 1..] });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Undo RFC 5545 line folding (a line break followed by a space or tab)
+pub fn unfold(text: &str) -> String {
+    text.replace("\r\n ", "").replace("\r\n\t", "").replace("\n ", "").replace("\n\t", "")
+}
+
+/// Fold a content line at 75 octets, without splitting UTF-8 sequences
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
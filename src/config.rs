@@ -1,15 +1,33 @@
+use crate::access::{Scope, TokenEntry};
+use crate::approval::ApprovalConfig;
+use crate::audit::AuditConfig;
 use crate::imap::ImapSettings;
+use crate::journal::JournalConfig;
+use crate::limits::{RateLimit, RateLimits};
+use crate::policy::{self, MailboxPolicy, ToolPolicy};
+use crate::safety::SafetyMode;
+use crate::smtp::SmtpSettings;
+use std::path::PathBuf;
+use std::time::Duration;
 use secrecy::Secret;
 use thiserror::Error;
 
+mod check;
+mod sources;
+
+pub use check::check;
+pub use sources::{ACCOUNT_SETTINGS, SERVER_SETTINGS, Setting, Sources, account_env};
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
-    #[error("Environment variable not set: {0}")]
-    EnvVar(#[from] std::env::VarError),
-    #[error("Could not parse port number: {0}")]
-    ParsePort(#[from] std::num::ParseIntError),
-    #[error("Could not parse boolean value: {0}")]
-    ParseBool(#[from] std::str::ParseBoolError),
+    #[error("Missing setting: {0}")]
+    Missing(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+    #[error("Unknown setting {0}")]
+    UnknownKey(String),
+    #[error("Could not read config file {0}: {1}")]
+    File(String, String),
 }
 
 pub type Result<T> = std::result::Result<T, ConfigError>;
@@ -23,6 +41,48 @@ pub struct ImapConfig {
     pub password: Secret<String>,
     pub use_tls: bool,
     pub skip_tls_verify: bool,
+    /// authserv-id of the receiving server whose Authentication-Results are trusted
+    pub authserv_id: Option<String>,
+}
+
+/// SMTP submission configuration (defaults target ProtonMail Bridge)
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Secret<String>,
+    pub use_tls: bool,
+    pub skip_tls_verify: bool,
+    /// From address for composed messages
+    pub from: String,
+}
+
+/// OAuth 2.1 protected-resource settings for HTTP mode
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    /// Issuer (`iss`) of accepted access tokens, advertised as the authorization server
+    pub issuer: String,
+    /// JWKS file with the issuer's public signing keys
+    pub jwks_file: PathBuf,
+    /// Public URL of the MCP endpoint, e.g. `https://mail.example.com/mcp`
+    pub resource: String,
+    /// Required `aud` claim (defaults to `resource`)
+    pub audience: String,
+}
+
+/// HTTPS settings for HTTP mode
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_file: PathBuf,
+    /// PEM private key
+    pub key_file: PathBuf,
+    /// PEM bundle of CAs whose client certificates are accepted instead of a bearer token
+    pub client_ca_file: Option<PathBuf>,
+    /// Scopes of a client certificate whose subject names no token entry;
+    /// such certificates are rejected when empty
+    pub client_scopes: Vec<Scope>,
 }
 
 /// MCP server configuration
@@ -34,42 +94,380 @@ pub struct ServerConfig {
     pub http_bind: String,
     /// Authentication token for HTTP mode (required when transport = "http")
     pub auth_token: Option<String>,
+    /// JSON file of named bearer tokens with scopes for HTTP mode
+    pub auth_tokens_file: Option<PathBuf>,
+    /// Named bearer tokens given inline as `[[server.tokens]]` in the config file
+    pub auth_tokens: Vec<TokenEntry>,
+    /// Accept JWT access tokens from an OAuth authorization server
+    pub oauth: Option<OAuthConfig>,
+    /// Serve HTTPS instead of plain HTTP
+    pub tls: Option<TlsConfig>,
     /// Enable SSE keep-alive pings (default: true)
     /// Disable if using Python MCP SDK < 1.25.0 which can't parse empty SSE data.
     /// See: https://github.com/modelcontextprotocol/python-sdk/issues/1672
     pub sse_keepalive: bool,
+    /// Maximum number of IMAP connections open at once, across all sessions and accounts
+    pub imap_max_connections: usize,
+    /// How long an unused IMAP connection stays open for reuse
+    pub imap_idle_timeout: Duration,
+    /// How long an HTTP MCP session may stay inactive before it is closed
+    pub session_idle_timeout: Duration,
+    /// Tool-call rates per caller and category
+    pub rate_limits: RateLimits,
+    /// HTTP request rate per caller, answered with 429 when exceeded
+    pub http_rate_limit: Option<RateLimit>,
+    /// Maximum number of tool calls working on one account at once
+    pub account_max_concurrent: usize,
+    /// Maximum number of emails a single trash/delete/empty call may affect
+    pub max_destructive_count: usize,
+    /// JSON-lines file recording every mutating and sending tool call
+    pub audit_log: Option<AuditConfig>,
+    /// Journal of moves and flag changes that undo_operation can revert
+    pub undo_journal: JournalConfig,
+    /// Tool calls held until a human approves them
+    pub approvals: ApprovalConfig,
+    /// Directory that import tools may read mbox/Maildir/.eml files from
+    pub import_dir: Option<PathBuf>,
+    /// Directory that export tools may write .eml/mbox/Maildir files into
+    pub export_dir: Option<PathBuf>,
+    /// Directory that get_attachment may save files into
+    pub attachment_dir: Option<PathBuf>,
+    /// Maximum total size in bytes of the files kept in the attachment directory
+    pub attachment_quota: u64,
+    /// Binary OpenPGP keyring of trusted signers, checked with gpgv
+    pub pgp_keyring: Option<PathBuf>,
+    /// PEM bundle of trusted S/MIME root certificates, checked with openssl
+    pub smime_ca_file: Option<PathBuf>,
+    /// gpgv executable used to verify PGP signatures
+    pub gpgv_path: String,
+    /// openssl executable used to verify S/MIME signatures
+    pub openssl_path: String,
+    /// Content-safety handling of email text returned to the model
+    pub content_safety: SafetyMode,
+    /// Risk score at or above which quarantine mode withholds an email body
+    pub quarantine_threshold: u8,
+    /// Read-only mode and tool allow/deny lists
+    pub tool_policy: ToolPolicy,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            transport: "stdio".to_string(),
+            http_bind: "127.0.0.1:8080".to_string(),
+            auth_token: None,
+            auth_tokens_file: None,
+            auth_tokens: Vec::new(),
+            oauth: None,
+            tls: None,
+            sse_keepalive: true,
+            imap_max_connections: 8,
+            imap_idle_timeout: Duration::from_secs(300),
+            session_idle_timeout: Duration::from_secs(1800),
+            rate_limits: RateLimits::default(),
+            http_rate_limit: None,
+            account_max_concurrent: 4,
+            max_destructive_count: 50,
+            audit_log: None,
+            undo_journal: JournalConfig { path: None, capacity: 200 },
+            approvals: ApprovalConfig::default(),
+            import_dir: None,
+            export_dir: None,
+            attachment_dir: None,
+            attachment_quota: 100 * 1024 * 1024,
+            pgp_keyring: None,
+            smime_ca_file: None,
+            gpgv_path: "gpgv".to_string(),
+            openssl_path: "openssl".to_string(),
+            content_safety: SafetyMode::Off,
+            quarantine_threshold: 70,
+            tool_policy: ToolPolicy::default(),
+        }
+    }
+}
+
+/// A named mail account with its own servers, credentials and mailbox policy
+#[derive(Debug, Clone)]
+pub struct AccountConfig {
+    pub name: String,
+    pub imap: ImapConfig,
+    pub smtp: SmtpConfig,
+    /// Mailboxes tools may work in for this account
+    pub mailbox_policy: MailboxPolicy,
 }
 
 /// Complete configuration loaded from environment
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub imap: ImapConfig,
+    /// Configured accounts; the first one is the primary account
+    pub accounts: Vec<AccountConfig>,
     pub server: ServerConfig,
 }
 
-fn parse_bool(s: &str) -> bool {
-    matches!(s.to_lowercase().as_str(), "true" | "1" | "yes")
+fn parse_bool(key: &str, s: &str) -> Result<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(ConfigError::InvalidValue(key.to_string(), "expected true, false, 1, 0, yes or no".to_string())),
+    }
+}
+
+fn parse_positive(key: &str, s: &str) -> Result<usize> {
+    match s.trim().parse::<usize>() {
+        Ok(0) => Err(ConfigError::InvalidValue(key.to_string(), "must be greater than zero".to_string())),
+        Ok(n) => Ok(n),
+        Err(e) => Err(ConfigError::InvalidValue(key.to_string(), e.to_string())),
+    }
 }
 
+/// Load the configuration from the environment and `.env`
 pub fn load_config() -> Result<Config> {
     dotenv::dotenv().ok();
+    load_config_from(&Sources::from_env())
+}
+
+/// Load the configuration from flags, environment and config file
+pub fn load_config_from(sources: &Sources) -> Result<Config> {
+    // Accounts: the primary one from IMAP_*/SMTP_*, others from ACCOUNT_<NAME>_*
+    let primary_name = sources.var("MCP_ACCOUNT_NAME").unwrap_or_else(|_| "primary".to_string());
+    let mut accounts = vec![load_account(sources, &primary_name, "", None)?];
+    if let Ok(names) = sources.var("MCP_ACCOUNTS") {
+        for name in policy::parse_list(&names) {
+            if accounts.iter().any(|account| account.name == name) {
+                return Err(ConfigError::InvalidValue(sources.key("MCP_ACCOUNTS"), format!("duplicate account name '{}'", name)));
+            }
+            let account = load_account(sources, &name, &account_prefix(&name), Some(&accounts[0]))?;
+            accounts.push(account);
+        }
+    }
+
+    // Server configuration
+    let transport = sources.var("MCP_TRANSPORT").unwrap_or_else(|_| "stdio".to_string());
+    let http_bind = sources.var("MCP_HTTP_BIND").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let auth_token = sources.var("MCP_AUTH_TOKEN").ok();
+    let auth_tokens_file = sources.var("MCP_AUTH_TOKENS_FILE").ok().map(PathBuf::from);
+    let auth_tokens = sources.tokens()?;
+    let oauth = load_oauth(sources)?;
+    let tls = match (sources.var("MCP_TLS_CERT"), sources.var("MCP_TLS_KEY")) {
+        (Ok(cert_file), Ok(key_file)) => Some(TlsConfig {
+            cert_file: PathBuf::from(cert_file),
+            key_file: PathBuf::from(key_file),
+            client_ca_file: sources.var("MCP_TLS_CLIENT_CA").ok().map(PathBuf::from),
+            client_scopes: match sources.var("MCP_TLS_CLIENT_SCOPES") {
+                Ok(s) if s.trim().eq_ignore_ascii_case("none") => Vec::new(),
+                Ok(s) => policy::parse_list(&s).iter()
+                    .map(|scope| scope.parse::<Scope>())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|e| ConfigError::InvalidValue(sources.key("MCP_TLS_CLIENT_SCOPES"), e))?,
+                Err(_) => vec![Scope::Read],
+            },
+        }),
+        (Err(_), Err(_)) if sources.var("MCP_TLS_CLIENT_CA").is_err() => None,
+        _ => return Err(ConfigError::InvalidValue(
+            sources.key("MCP_TLS_CERT"),
+            "MCP_TLS_CERT and MCP_TLS_KEY must be set together, and MCP_TLS_CLIENT_CA needs both".to_string(),
+        )),
+    };
+    let sse_keepalive = match sources.var("MCP_SSE_KEEPALIVE") {
+        Ok(s) => parse_bool(&sources.key("MCP_SSE_KEEPALIVE"), &s)?,
+        Err(_) => true, // Default enabled
+    };
+    let imap_max_connections = match sources.var("MCP_IMAP_MAX_CONNECTIONS") {
+        Ok(s) => parse_positive(&sources.key("MCP_IMAP_MAX_CONNECTIONS"), &s)?,
+        Err(_) => ServerConfig::default().imap_max_connections,
+    };
+    let imap_idle_timeout = match sources.var("MCP_IMAP_IDLE_SECS") {
+        Ok(s) => Duration::from_secs(parse_positive(&sources.key("MCP_IMAP_IDLE_SECS"), &s)? as u64),
+        Err(_) => ServerConfig::default().imap_idle_timeout,
+    };
+    let session_idle_timeout = match sources.var("MCP_SESSION_IDLE_SECS") {
+        Ok(s) => Duration::from_secs(parse_positive(&sources.key("MCP_SESSION_IDLE_SECS"), &s)? as u64),
+        Err(_) => ServerConfig::default().session_idle_timeout,
+    };
+    let rate_limit = |key: &str| match sources.var(key) {
+        Ok(s) => s.parse::<RateLimit>().map(Some).map_err(|e| ConfigError::InvalidValue(sources.key(key), e)),
+        Err(_) => Ok(None),
+    };
+    let rate_limits = RateLimits {
+        read: rate_limit("MCP_RATE_LIMIT_READ")?,
+        write: rate_limit("MCP_RATE_LIMIT_WRITE")?,
+        send: rate_limit("MCP_RATE_LIMIT_SEND")?,
+    };
+    let http_rate_limit = rate_limit("MCP_HTTP_RATE_LIMIT")?;
+    let account_max_concurrent = match sources.var("MCP_ACCOUNT_MAX_CONCURRENT") {
+        Ok(s) => parse_positive(&sources.key("MCP_ACCOUNT_MAX_CONCURRENT"), &s)?,
+        Err(_) => ServerConfig::default().account_max_concurrent,
+    };
+    let max_destructive_count = match sources.var("MCP_MAX_DESTRUCTIVE_COUNT") {
+        Ok(s) => parse_positive(&sources.key("MCP_MAX_DESTRUCTIVE_COUNT"), &s)?,
+        Err(_) => ServerConfig::default().max_destructive_count,
+    };
+    let audit_log = match sources.var("MCP_AUDIT_LOG") {
+        Ok(path) => Some(AuditConfig {
+            path: PathBuf::from(path),
+            max_bytes: match sources.var("MCP_AUDIT_LOG_MAX_BYTES") {
+                Ok(s) => Some(parse_positive(&sources.key("MCP_AUDIT_LOG_MAX_BYTES"), &s)? as u64),
+                Err(_) => None,
+            },
+            keep: match sources.var("MCP_AUDIT_LOG_KEEP") {
+                Ok(s) => s.trim().parse().map_err(|e: std::num::ParseIntError| ConfigError::InvalidValue(sources.key("MCP_AUDIT_LOG_KEEP"), e.to_string()))?,
+                Err(_) => 5,
+            },
+        }),
+        Err(_) => None,
+    };
+    let undo_journal = JournalConfig {
+        path: sources.var("MCP_UNDO_JOURNAL").ok().map(PathBuf::from),
+        capacity: match sources.var("MCP_UNDO_JOURNAL_SIZE") {
+            Ok(s) => parse_positive(&sources.key("MCP_UNDO_JOURNAL_SIZE"), &s)?,
+            Err(_) => ServerConfig::default().undo_journal.capacity,
+        },
+    };
+    let mut approvals = ApprovalConfig {
+        store: sources.var("MCP_APPROVAL_STORE").ok().map(PathBuf::from),
+        token: sources.var("MCP_APPROVAL_TOKEN").ok(),
+        ..ApprovalConfig::default()
+    };
+    if let Ok(list) = sources.var("MCP_APPROVAL_TOOLS") {
+        approvals.add_rules(&list).map_err(|e| ConfigError::InvalidValue(sources.key("MCP_APPROVAL_TOOLS"), e))?;
+    }
+    if let Ok(s) = sources.var("MCP_APPROVAL_EXPIRE_SECS") {
+        approvals.expire_after = Duration::from_secs(parse_positive(&sources.key("MCP_APPROVAL_EXPIRE_SECS"), &s)? as u64);
+    }
+
+    let import_dir = sources.var("MCP_IMPORT_DIR").ok().map(PathBuf::from);
+    let export_dir = sources.var("MCP_EXPORT_DIR").ok().map(PathBuf::from);
+    let attachment_dir = sources.var("ATTACHMENT_DIR").ok().map(PathBuf::from);
+    let attachment_quota = match sources.var("ATTACHMENT_QUOTA_BYTES") {
+        Ok(s) => parse_positive(&sources.key("ATTACHMENT_QUOTA_BYTES"), &s)? as u64,
+        Err(_) => ServerConfig::default().attachment_quota,
+    };
+    let pgp_keyring = sources.var("PGP_KEYRING").ok().map(PathBuf::from);
+    let smime_ca_file = sources.var("SMIME_CA_FILE").ok().map(PathBuf::from);
+    let gpgv_path = sources.var("GPGV_PATH").unwrap_or_else(|_| ServerConfig::default().gpgv_path);
+    let openssl_path = sources.var("OPENSSL_PATH").unwrap_or_else(|_| ServerConfig::default().openssl_path);
+    let content_safety = match sources.var("MCP_CONTENT_SAFETY") {
+        Ok(s) => s.parse().map_err(|e| ConfigError::InvalidValue(sources.key("MCP_CONTENT_SAFETY"), e))?,
+        Err(_) => ServerConfig::default().content_safety,
+    };
+    let quarantine_threshold = match sources.var("MCP_QUARANTINE_THRESHOLD") {
+        Ok(s) => match s.trim().parse::<u8>() {
+            Ok(n @ 1..=100) => n,
+            Ok(_) => return Err(ConfigError::InvalidValue(sources.key("MCP_QUARANTINE_THRESHOLD"), "must be between 1 and 100".to_string())),
+            Err(e) => return Err(ConfigError::InvalidValue(sources.key("MCP_QUARANTINE_THRESHOLD"), e.to_string())),
+        },
+        Err(_) => ServerConfig::default().quarantine_threshold,
+    };
+    let tool_policy = ToolPolicy {
+        read_only: match sources.var("MCP_READ_ONLY") {
+            Ok(s) => parse_bool(&sources.key("MCP_READ_ONLY"), &s)?,
+            Err(_) => false,
+        },
+        allow: sources.var("MCP_TOOLS_ALLOW").ok().map(|s| policy::parse_list(&s)).filter(|l| !l.is_empty()),
+        deny: sources.var("MCP_TOOLS_DENY").map(|s| policy::parse_list(&s)).unwrap_or_default(),
+    };
+
+    let server = ServerConfig {
+        transport,
+        http_bind,
+        auth_token,
+        auth_tokens_file,
+        auth_tokens,
+        oauth,
+        tls,
+        sse_keepalive,
+        imap_max_connections,
+        imap_idle_timeout,
+        session_idle_timeout,
+        rate_limits,
+        http_rate_limit,
+        account_max_concurrent,
+        max_destructive_count,
+        audit_log,
+        undo_journal,
+        approvals,
+        import_dir,
+        export_dir,
+        attachment_dir,
+        attachment_quota,
+        pgp_keyring,
+        smime_ca_file,
+        gpgv_path,
+        openssl_path,
+        content_safety,
+        quarantine_threshold,
+        tool_policy,
+    };
+
+    Ok(Config { accounts, server })
+}
+
+/// OAuth settings, when MCP_OAUTH_ISSUER is set. The JWKS file and the
+/// resource URL are then required too.
+fn load_oauth(sources: &Sources) -> Result<Option<OAuthConfig>> {
+    let Ok(issuer) = sources.var("MCP_OAUTH_ISSUER") else {
+        return Ok(None);
+    };
+    let required = |key: &str| sources.var(key)
+        .map_err(|_| ConfigError::Missing(format!("{}, required when {} is set", sources.describe(key), sources.key("MCP_OAUTH_ISSUER"))));
+    let jwks_file = PathBuf::from(required("MCP_OAUTH_JWKS_FILE")?);
+    let resource = required("MCP_OAUTH_RESOURCE")?;
+    if !resource.starts_with("https://") && !resource.starts_with("http://") {
+        return Err(ConfigError::InvalidValue(sources.key("MCP_OAUTH_RESOURCE"), "must be an http(s) URL".to_string()));
+    }
+    let audience = sources.var("MCP_OAUTH_AUDIENCE").unwrap_or_else(|_| resource.clone());
+    Ok(Some(OAuthConfig { issuer, jwks_file, resource, audience }))
+}
+
+/// Environment variable prefix of a named account, e.g. `ACCOUNT_SUPPORT_`
+pub fn account_prefix(name: &str) -> String {
+    let key: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("ACCOUNT_{}_", key)
+}
+
+/// Load an account from the variables starting with `prefix`. Hosts, ports
+/// and TLS options a named account leaves out are taken from `primary`.
+fn load_account(sources: &Sources, name: &str, prefix: &str, primary: Option<&AccountConfig>) -> Result<AccountConfig> {
+    let var = |key: &str| sources.var(&format!("{}{}", prefix, key));
+    let required = |key: &str| var(key).map_err(|_| ConfigError::Missing(sources.describe(&format!("{}{}", prefix, key))));
+    let parse_port = |key: &str, s: String| s.trim().parse::<u16>()
+        .map_err(|e| ConfigError::InvalidValue(sources.key(&format!("{}{}", prefix, key)), e.to_string()));
+    let flag = |key: &str| var(key).ok()
+        .map(|s| parse_bool(&sources.key(&format!("{}{}", prefix, key)), &s))
+        .transpose();
 
     // IMAP configuration
-    let host = std::env::var("IMAP_HOST")?;
+    let host = match primary {
+        Some(primary) => var("IMAP_HOST").unwrap_or_else(|_| primary.imap.host.clone()),
+        None => required("IMAP_HOST")?,
+    };
 
-    let use_tls_str = std::env::var("IMAP_USE_TLS").unwrap_or_else(|_| "false".to_string());
-    let use_tls = parse_bool(&use_tls_str);
+    let use_tls = flag("IMAP_USE_TLS")?
+        .unwrap_or(primary.is_some_and(|p| p.imap.use_tls));
 
-    let default_port = if use_tls { "993" } else { "1143" };
-    let port_str = std::env::var("IMAP_PORT").unwrap_or_else(|_| default_port.to_string());
-    let port: u16 = port_str.parse()?;
+    let default_port = match primary {
+        Some(primary) if primary.imap.use_tls == use_tls => primary.imap.port,
+        _ if use_tls => 993,
+        _ => 1143,
+    };
+    let port: u16 = match var("IMAP_PORT") {
+        Ok(s) => parse_port("IMAP_PORT", s)?,
+        Err(_) => default_port,
+    };
+
+    let user = var("IMAP_USERNAME").or_else(|_| var("IMAP_USER")).or_else(|_| required("IMAP_USERNAME"))?;
+    let password = Secret::new(required("IMAP_PASSWORD")?);
 
-    let user = std::env::var("IMAP_USERNAME").or_else(|_| std::env::var("IMAP_USER"))?;
-    let password = Secret::new(std::env::var("IMAP_PASSWORD")?);
+    let skip_tls_verify = flag("IMAP_SKIP_TLS_VERIFY")?
+        .unwrap_or(primary.is_none_or(|p| p.imap.skip_tls_verify));
 
-    let skip_tls_verify_str = std::env::var("IMAP_SKIP_TLS_VERIFY")
-        .unwrap_or_else(|_| "true".to_string());
-    let skip_tls_verify = parse_bool(&skip_tls_verify_str);
+    // Another host is another receiving server, with its own authserv-id
+    let authserv_id = var("IMAP_AUTHSERV_ID").ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| primary.filter(|p| p.imap.host == host).and_then(|p| p.imap.authserv_id.clone()));
 
     let imap = ImapConfig {
         host,
@@ -78,24 +476,52 @@ pub fn load_config() -> Result<Config> {
         password,
         use_tls,
         skip_tls_verify,
+        authserv_id,
     };
 
-    // Server configuration
-    let transport = std::env::var("MCP_TRANSPORT").unwrap_or_else(|_| "stdio".to_string());
-    let http_bind = std::env::var("MCP_HTTP_BIND").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let auth_token = std::env::var("MCP_AUTH_TOKEN").ok();
-    let sse_keepalive = std::env::var("MCP_SSE_KEEPALIVE")
-        .map(|s| parse_bool(&s))
-        .unwrap_or(true); // Default enabled
+    // SMTP configuration (falls back to the account's IMAP host and credentials).
+    // A named account only inherits the primary's SMTP settings when it uses the
+    // same IMAP host; another host is a regular mail provider, not a local Bridge,
+    // so it defaults to direct TLS on 465 (or STARTTLS on 587)
+    let same_server = primary.filter(|p| p.imap.host == imap.host);
+    let other_server = primary.is_some() && same_server.is_none();
+    let smtp_use_tls = flag("SMTP_USE_TLS")?
+        .unwrap_or(same_server.map_or(other_server, |p| p.smtp.use_tls));
+    let smtp_port: u16 = match var("SMTP_PORT") {
+        Ok(s) => parse_port("SMTP_PORT", s)?,
+        Err(_) => match same_server {
+            Some(primary) if primary.smtp.use_tls == smtp_use_tls => primary.smtp.port,
+            _ if smtp_use_tls => 465,
+            _ if other_server => 587,
+            _ => 1025,
+        },
+    };
+    let smtp_user = var("SMTP_USERNAME").unwrap_or_else(|_| imap.user.clone());
 
-    let server = ServerConfig {
-        transport,
-        http_bind,
-        auth_token,
-        sse_keepalive,
+    let smtp = SmtpConfig {
+        host: var("SMTP_HOST")
+            .unwrap_or_else(|_| same_server.map_or_else(|| imap.host.clone(), |p| p.smtp.host.clone())),
+        port: smtp_port,
+        from: var("SMTP_FROM").unwrap_or_else(|_| smtp_user.clone()),
+        user: smtp_user,
+        password: var("SMTP_PASSWORD")
+            .map(Secret::new)
+            .unwrap_or_else(|_| imap.password.clone()),
+        use_tls: smtp_use_tls,
+        skip_tls_verify: flag("SMTP_SKIP_TLS_VERIFY")?.unwrap_or(imap.skip_tls_verify),
+    };
+
+    let mailboxes_allow = if primary.is_some() { var("MAILBOXES_ALLOW") } else { sources.var("MCP_MAILBOXES_ALLOW") };
+    let mailbox_policy = MailboxPolicy {
+        allow: mailboxes_allow.ok().map(|s| policy::parse_list(&s)).filter(|l| !l.is_empty()),
     };
 
-    Ok(Config { imap, server })
+    Ok(AccountConfig {
+        name: name.to_string(),
+        imap,
+        smtp,
+        mailbox_policy,
+    })
 }
 
 impl ImapConfig {
@@ -107,13 +533,33 @@ impl ImapConfig {
             password: self.password.clone(),
             use_tls: self.use_tls,
             skip_tls_verify: self.skip_tls_verify,
+            authserv_id: self.authserv_id.clone(),
+        }
+    }
+}
+
+impl SmtpConfig {
+    pub fn to_smtp_settings(&self) -> SmtpSettings {
+        SmtpSettings {
+            host: self.host.clone(),
+            port: self.port,
+            user: self.user.clone(),
+            password: self.password.clone(),
+            use_tls: self.use_tls,
+            skip_tls_verify: self.skip_tls_verify,
+            from: self.from.clone(),
         }
     }
 }
 
-// Keep backward compatibility
 impl Config {
+    /// The account tools use when no `account` is given
+    pub fn primary(&self) -> &AccountConfig {
+        &self.accounts[0]
+    }
+
+    // Keep backward compatibility
     pub fn to_imap_settings(&self) -> ImapSettings {
-        self.imap.to_imap_settings()
+        self.primary().imap.to_imap_settings()
     }
 }
@@ -0,0 +1,95 @@
+//! Validation beyond parsing, for `config check`: files and directories the
+//! settings name, token lists and tool names.
+
+use std::path::Path;
+
+use super::{Config, Sources};
+use crate::access::TokenStore;
+use crate::server::ImapMailboxServer;
+
+/// Problems that would stop the server or a tool from working, each naming
+/// the setting at fault. Empty when the configuration is usable.
+pub fn check(config: &Config, sources: &Sources) -> Vec<String> {
+    let mut problems = Vec::new();
+    let server = &config.server;
+    let problem = |env: &str, reason: String| format!("{}: {}", sources.key(env), reason);
+
+    if !matches!(server.transport.as_str(), "stdio" | "http") {
+        problems.push(problem("MCP_TRANSPORT", format!("unknown transport '{}', use stdio or http", server.transport)));
+    }
+
+    let mut tokens = match &server.auth_tokens_file {
+        Some(path) => TokenStore::load(path).unwrap_or_else(|e| {
+            problems.push(problem("MCP_AUTH_TOKENS_FILE", e.to_string()));
+            TokenStore::default()
+        }),
+        None => TokenStore::default(),
+    };
+    if let Err(e) = tokens.add_entries(server.auth_tokens.clone()) {
+        problems.push(format!("server.tokens: {}", e));
+    }
+
+    let files = [
+        ("MCP_OAUTH_JWKS_FILE", server.oauth.as_ref().map(|oauth| oauth.jwks_file.as_path())),
+        ("MCP_TLS_CERT", server.tls.as_ref().map(|tls| tls.cert_file.as_path())),
+        ("MCP_TLS_KEY", server.tls.as_ref().map(|tls| tls.key_file.as_path())),
+        ("MCP_TLS_CLIENT_CA", server.tls.as_ref().and_then(|tls| tls.client_ca_file.as_deref())),
+        ("PGP_KEYRING", server.pgp_keyring.as_deref()),
+        ("SMIME_CA_FILE", server.smime_ca_file.as_deref()),
+    ];
+    for (env, path) in files {
+        if let Some(path) = path.filter(|path| !path.is_file()) {
+            problems.push(problem(env, format!("{} is not a file", path.display())));
+        }
+    }
+    let directories = [
+        ("MCP_IMPORT_DIR", server.import_dir.as_deref()),
+        ("MCP_EXPORT_DIR", server.export_dir.as_deref()),
+        ("ATTACHMENT_DIR", server.attachment_dir.as_deref()),
+    ];
+    for (env, path) in directories {
+        if let Some(path) = path.filter(|path| !path.is_dir()) {
+            problems.push(problem(env, format!("{} is not a directory", path.display())));
+        }
+    }
+    let stores = [
+        ("MCP_AUDIT_LOG", server.audit_log.as_ref().map(|audit| audit.path.as_path())),
+        ("MCP_UNDO_JOURNAL", server.undo_journal.path.as_deref()),
+        ("MCP_APPROVAL_STORE", server.approvals.store.as_deref()),
+    ];
+    for (env, path) in stores {
+        if let Some(parent) = path.and_then(Path::parent).filter(|parent| !parent.as_os_str().is_empty() && !parent.is_dir()) {
+            problems.push(problem(env, format!("directory {} does not exist", parent.display())));
+        }
+    }
+
+    #[cfg(feature = "http")]
+    {
+        if let Some(oauth) = server.oauth.as_ref().filter(|oauth| oauth.jwks_file.is_file())
+            && let Err(e) = crate::http_auth::JwtValidator::load(oauth)
+        {
+            problems.push(problem("MCP_OAUTH_JWKS_FILE", e.to_string()));
+        }
+        if let Some(tls) = server.tls.as_ref().filter(|tls| tls.cert_file.is_file() && tls.key_file.is_file())
+            && let Err(e) = crate::tls::server_config(tls)
+        {
+            problems.push(problem("MCP_TLS_CERT", e.to_string()));
+        }
+    }
+
+    let known = ImapMailboxServer::tool_names();
+    for unknown in server.tool_policy.unknown_tools(&known) {
+        let env = if server.tool_policy.deny.iter().any(|name| name == unknown) { "MCP_TOOLS_DENY" } else { "MCP_TOOLS_ALLOW" };
+        problems.push(problem(env, format!("unknown tool '{}'", unknown)));
+    }
+    let permitted = ImapMailboxServer::permitted_tool_names(&server.tool_policy);
+    for rule in &server.approvals.rules {
+        if !known.contains(&rule.tool) {
+            problems.push(problem("MCP_APPROVAL_TOOLS", format!("unknown tool '{}'", rule.tool)));
+        } else if !permitted.contains(&rule.tool) {
+            problems.push(problem("MCP_APPROVAL_TOOLS", format!("tool '{}' is disabled by the tool policy", rule.tool)));
+        }
+    }
+
+    problems
+}
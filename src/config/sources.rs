@@ -0,0 +1,319 @@
+//! Where settings come from: command-line flags, the environment and a TOML
+//! config file, in that order of precedence. Every setting has an
+//! environment variable name, under which the other sources are looked up.
+
+use std::collections::HashMap;
+use std::env::VarError;
+use std::path::Path;
+
+use super::{ConfigError, Result, account_prefix};
+use crate::access::{TokenEntry, TokenStore};
+
+/// A setting with its environment variable, config file key and flag
+#[derive(Debug, Clone, Copy)]
+pub struct Setting {
+    pub env: &'static str,
+    /// Key in the config file; account keys are relative to an `[[accounts]]` entry
+    pub key: &'static str,
+    /// Command-line flag without the dashes. Passwords and tokens have none,
+    /// since command lines are visible to other users.
+    pub flag: Option<&'static str>,
+    pub help: &'static str,
+}
+
+const fn setting(env: &'static str, key: &'static str, flag: Option<&'static str>, help: &'static str) -> Setting {
+    Setting { env, key, flag, help }
+}
+
+/// Server-wide settings
+pub const SERVER_SETTINGS: &[Setting] = &[
+    setting("MCP_TRANSPORT", "server.transport", Some("transport"), "Transport mode: stdio or http"),
+    setting("MCP_HTTP_BIND", "server.http_bind", Some("bind"), "HTTP bind address"),
+    setting("MCP_AUTH_TOKEN", "server.auth_token", None, "Shared bearer token with every scope"),
+    setting("MCP_AUTH_TOKENS_FILE", "server.auth_tokens_file", Some("auth-tokens-file"), "JSON file of named bearer tokens"),
+    setting("MCP_OAUTH_ISSUER", "server.oauth.issuer", Some("oauth-issuer"), "Issuer of accepted OAuth access tokens"),
+    setting("MCP_OAUTH_JWKS_FILE", "server.oauth.jwks_file", Some("oauth-jwks-file"), "JWKS file with the issuer's signing keys"),
+    setting("MCP_OAUTH_RESOURCE", "server.oauth.resource", Some("oauth-resource"), "Public URL of the MCP endpoint"),
+    setting("MCP_OAUTH_AUDIENCE", "server.oauth.audience", Some("oauth-audience"), "Required aud claim (defaults to the resource)"),
+    setting("MCP_TLS_CERT", "server.tls.cert_file", Some("tls-cert"), "PEM certificate chain for HTTPS"),
+    setting("MCP_TLS_KEY", "server.tls.key_file", Some("tls-key"), "PEM private key for HTTPS"),
+    setting("MCP_TLS_CLIENT_CA", "server.tls.client_ca_file", Some("tls-client-ca"), "PEM CAs whose client certificates are accepted"),
+    setting("MCP_TLS_CLIENT_SCOPES", "server.tls.client_scopes", Some("tls-client-scopes"), "Scopes of certificates without a token entry, or none"),
+    setting("MCP_SSE_KEEPALIVE", "server.sse_keepalive", Some("sse-keepalive"), "Send SSE keep-alive pings"),
+    setting("MCP_IMAP_MAX_CONNECTIONS", "server.imap_max_connections", Some("imap-max-connections"), "IMAP connections open at once"),
+    setting("MCP_IMAP_IDLE_SECS", "server.imap_idle_secs", Some("imap-idle-secs"), "Seconds an unused IMAP connection stays open"),
+    setting("MCP_SESSION_IDLE_SECS", "server.session_idle_secs", Some("session-idle-secs"), "Seconds before an idle HTTP session closes"),
+    setting("MCP_RATE_LIMIT_READ", "server.rate_limits.read", Some("rate-limit-read"), "Read tool calls per caller, e.g. 60/min"),
+    setting("MCP_RATE_LIMIT_WRITE", "server.rate_limits.write", Some("rate-limit-write"), "Write and delete tool calls per caller"),
+    setting("MCP_RATE_LIMIT_SEND", "server.rate_limits.send", Some("rate-limit-send"), "Send tool calls per caller"),
+    setting("MCP_HTTP_RATE_LIMIT", "server.http_rate_limit", Some("http-rate-limit"), "HTTP requests per caller"),
+    setting("MCP_ACCOUNT_MAX_CONCURRENT", "server.account_max_concurrent", Some("account-max-concurrent"), "Tool calls working on one account at once"),
+    setting("MCP_MAX_DESTRUCTIVE_COUNT", "server.max_destructive_count", Some("max-destructive-count"), "Emails one trash or delete call may affect"),
+    setting("MCP_AUDIT_LOG", "server.audit_log.path", Some("audit-log"), "JSON-lines audit log file"),
+    setting("MCP_AUDIT_LOG_MAX_BYTES", "server.audit_log.max_bytes", Some("audit-log-max-bytes"), "Rotate the audit log beyond this size"),
+    setting("MCP_AUDIT_LOG_KEEP", "server.audit_log.keep", Some("audit-log-keep"), "Rotated audit logs to keep"),
+    setting("MCP_UNDO_JOURNAL", "server.undo_journal.path", Some("undo-journal"), "JSON file of the undo journal"),
+    setting("MCP_UNDO_JOURNAL_SIZE", "server.undo_journal.size", Some("undo-journal-size"), "Operations the undo journal keeps"),
+    setting("MCP_APPROVAL_TOOLS", "server.approvals.tools", Some("approval-tools"), "Tools whose calls wait for approval"),
+    setting("MCP_APPROVAL_STORE", "server.approvals.store", Some("approval-store"), "JSON file of calls held for approval"),
+    setting("MCP_APPROVAL_TOKEN", "server.approvals.token", None, "Bearer token for the HTTP approval endpoint"),
+    setting("MCP_APPROVAL_EXPIRE_SECS", "server.approvals.expire_secs", Some("approval-expire-secs"), "Seconds before an undecided call expires"),
+    setting("MCP_IMPORT_DIR", "server.import_dir", Some("import-dir"), "Directory import tools may read from"),
+    setting("MCP_EXPORT_DIR", "server.export_dir", Some("export-dir"), "Directory export tools may write into"),
+    setting("ATTACHMENT_DIR", "server.attachment_dir", Some("attachment-dir"), "Directory get_attachment may save into"),
+    setting("ATTACHMENT_QUOTA_BYTES", "server.attachment_quota_bytes", Some("attachment-quota-bytes"), "Total size of saved attachments"),
+    setting("PGP_KEYRING", "server.pgp_keyring", Some("pgp-keyring"), "OpenPGP keyring of trusted signers"),
+    setting("SMIME_CA_FILE", "server.smime_ca_file", Some("smime-ca-file"), "PEM bundle of trusted S/MIME roots"),
+    setting("GPGV_PATH", "server.gpgv_path", Some("gpgv-path"), "gpgv executable"),
+    setting("OPENSSL_PATH", "server.openssl_path", Some("openssl-path"), "openssl executable"),
+    setting("MCP_CONTENT_SAFETY", "server.content_safety", Some("content-safety"), "Content-safety mode: off, annotate or quarantine"),
+    setting("MCP_QUARANTINE_THRESHOLD", "server.quarantine_threshold", Some("quarantine-threshold"), "Risk score that quarantines an email"),
+    setting("MCP_READ_ONLY", "server.tools.read_only", Some("read-only"), "Only offer tools that change nothing"),
+    setting("MCP_TOOLS_ALLOW", "server.tools.allow", Some("tools-allow"), "Only offer these tools"),
+    setting("MCP_TOOLS_DENY", "server.tools.deny", Some("tools-deny"), "Never offer these tools"),
+];
+
+/// Settings of each account. The environment variables of the primary
+/// account are unprefixed; those of named accounts start with
+/// `ACCOUNT_<NAME>_`. Flags only set the primary account.
+pub const ACCOUNT_SETTINGS: &[Setting] = &[
+    setting("IMAP_HOST", "imap.host", Some("imap-host"), "IMAP server host"),
+    setting("IMAP_PORT", "imap.port", Some("imap-port"), "IMAP server port"),
+    setting("IMAP_USERNAME", "imap.username", Some("imap-username"), "IMAP user name"),
+    setting("IMAP_PASSWORD", "imap.password", None, "IMAP password"),
+    setting("IMAP_USE_TLS", "imap.use_tls", Some("imap-use-tls"), "Connect to IMAP with TLS"),
+    setting("IMAP_SKIP_TLS_VERIFY", "imap.skip_tls_verify", Some("imap-skip-tls-verify"), "Accept any IMAP certificate"),
+    setting("IMAP_AUTHSERV_ID", "imap.authserv_id", Some("imap-authserv-id"), "Receiving server whose Authentication-Results are trusted"),
+    setting("SMTP_HOST", "smtp.host", Some("smtp-host"), "SMTP server host"),
+    setting("SMTP_PORT", "smtp.port", Some("smtp-port"), "SMTP server port"),
+    setting("SMTP_USERNAME", "smtp.username", Some("smtp-username"), "SMTP user name"),
+    setting("SMTP_PASSWORD", "smtp.password", None, "SMTP password"),
+    setting("SMTP_USE_TLS", "smtp.use_tls", Some("smtp-use-tls"), "Connect to SMTP with TLS"),
+    setting("SMTP_SKIP_TLS_VERIFY", "smtp.skip_tls_verify", Some("smtp-skip-tls-verify"), "Accept any SMTP certificate"),
+    setting("SMTP_FROM", "smtp.from", Some("smtp-from"), "From address of sent mail"),
+    setting("MAILBOXES_ALLOW", "mailboxes_allow", Some("mailboxes-allow"), "Mailboxes tools may use"),
+];
+
+/// Settings given as lists, which the config file may write as arrays
+const LIST_SETTINGS: &[&str] = &["MCP_APPROVAL_TOOLS", "MCP_TOOLS_ALLOW", "MCP_TOOLS_DENY", "MCP_TLS_CLIENT_SCOPES", "MAILBOXES_ALLOW"];
+
+/// Environment variable of an account setting. The primary account's
+/// mailbox allow-list is `MCP_MAILBOXES_ALLOW`.
+pub fn account_env(prefix: &str, setting: &Setting) -> String {
+    if prefix.is_empty() && setting.env == "MAILBOXES_ALLOW" {
+        "MCP_MAILBOXES_ALLOW".to_string()
+    } else {
+        format!("{}{}", prefix, setting.env)
+    }
+}
+
+/// A value and the flag or file key it was given under
+#[derive(Debug, Clone)]
+struct Given {
+    value: String,
+    origin: String,
+}
+
+/// Setting values by environment variable name
+#[derive(Debug, Default, Clone)]
+pub struct Sources {
+    flags: HashMap<String, Given>,
+    env: HashMap<String, String>,
+    file: HashMap<String, Given>,
+    /// `[[server.tokens]]` of the config file, with its key
+    tokens: Option<(String, toml::Value)>,
+}
+
+impl Sources {
+    /// The process environment, without flags or a config file
+    pub fn from_env() -> Self {
+        Sources { env: std::env::vars().collect(), ..Sources::default() }
+    }
+
+    /// Set an environment variable, as seen by these sources only
+    pub fn with_env(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the setting behind `env` from the command-line flag `flag`
+    pub fn with_flag(mut self, env: &str, flag: &str, value: &str) -> Self {
+        self.flags.insert(env.to_string(), Given { value: value.to_string(), origin: format!("--{}", flag) });
+        self
+    }
+
+    /// Read a TOML config file
+    pub fn with_file(self, path: &Path) -> Result<Self> {
+        let display = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|e| ConfigError::File(display.clone(), e.to_string()))?;
+        self.with_file_contents(&text, &display)
+    }
+
+    /// Read the contents of a TOML config file named `name`
+    pub fn with_file_contents(mut self, text: &str, name: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(text).map_err(|e| ConfigError::File(name.to_string(), e.to_string()))?;
+        let mut file = FileReader { name, values: HashMap::new(), tokens: None };
+        for (section, value) in table {
+            match (section.as_str(), value) {
+                ("server", toml::Value::Table(server)) => file.read_server(server)?,
+                ("accounts", toml::Value::Array(accounts)) => file.read_accounts(accounts)?,
+                ("server", _) => return Err(file.invalid("server", "must be a table")),
+                ("accounts", _) => return Err(file.invalid("accounts", "must be an array of [[accounts]] tables")),
+                (other, _) => return Err(ConfigError::UnknownKey(file.origin(other))),
+            }
+        }
+        self.file = file.values;
+        self.tokens = file.tokens;
+        Ok(self)
+    }
+
+    /// The value of a setting, like `std::env::var`
+    pub fn var(&self, env: &str) -> std::result::Result<String, VarError> {
+        if let Some(given) = self.flags.get(env) {
+            return Ok(given.value.clone());
+        }
+        if let Some(value) = self.env.get(env) {
+            return Ok(value.clone());
+        }
+        self.file.get(env).map(|given| given.value.clone()).ok_or(VarError::NotPresent)
+    }
+
+    /// Name of the flag, variable or file key a setting's value came from,
+    /// for error messages
+    pub fn key(&self, env: &str) -> String {
+        match self.flags.get(env) {
+            Some(given) => given.origin.clone(),
+            None if self.env.contains_key(env) => env.to_string(),
+            None => self.file.get(env).map_or_else(|| env.to_string(), |given| given.origin.clone()),
+        }
+    }
+
+    /// Every way to give a setting, for the error when it is missing
+    pub fn describe(&self, env: &str) -> String {
+        let alternatives = |flag: Option<&str>, key: &str| match flag {
+            Some(flag) => format!("{} (or --{}, or {} in the config file)", env, flag, key),
+            None => format!("{} (or {} in the config file)", env, key),
+        };
+        if let Some(setting) = SERVER_SETTINGS.iter().find(|setting| setting.env == env) {
+            return alternatives(setting.flag, setting.key);
+        }
+        for setting in ACCOUNT_SETTINGS {
+            if account_env("", setting) == env {
+                return alternatives(setting.flag, &format!("accounts[0].{}", setting.key));
+            }
+            if env.starts_with("ACCOUNT_") && env.ends_with(&format!("_{}", setting.env)) {
+                return alternatives(None, &format!("{} of the account's [[accounts]] entry", setting.key));
+            }
+        }
+        env.to_string()
+    }
+
+    /// Named bearer tokens from `[[server.tokens]]`, checked the way the
+    /// HTTP server accepts them so mistakes surface when the config loads
+    pub fn tokens(&self) -> Result<Vec<TokenEntry>> {
+        let Some((origin, tokens)) = &self.tokens else { return Ok(Vec::new()) };
+        let invalid = |reason: String| ConfigError::InvalidValue(origin.clone(), reason);
+        let entries: Vec<TokenEntry> = tokens.clone().try_into().map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        TokenStore::default().add_entries(entries.clone()).map_err(|e| invalid(e.to_string()))?;
+        Ok(entries)
+    }
+}
+
+/// Flattens a config file into values by environment variable name
+struct FileReader<'a> {
+    name: &'a str,
+    values: HashMap<String, Given>,
+    tokens: Option<(String, toml::Value)>,
+}
+
+impl FileReader<'_> {
+    fn origin(&self, key: &str) -> String {
+        format!("{} in {}", key, self.name)
+    }
+
+    fn invalid(&self, key: &str, reason: &str) -> ConfigError {
+        ConfigError::InvalidValue(self.origin(key), reason.to_string())
+    }
+
+    fn read_server(&mut self, server: toml::Table) -> Result<()> {
+        for (key, value) in flatten("server", server) {
+            if key == "server.tokens" {
+                self.tokens = Some((self.origin(&key), value));
+                continue;
+            }
+            let setting = SERVER_SETTINGS.iter()
+                .find(|setting| setting.key == key)
+                .ok_or_else(|| ConfigError::UnknownKey(self.origin(&key)))?;
+            self.set(setting.env.to_string(), &key, value)?;
+        }
+        Ok(())
+    }
+
+    /// The first entry is the primary account; the others are the named
+    /// accounts of `MCP_ACCOUNTS`
+    fn read_accounts(&mut self, accounts: Vec<toml::Value>) -> Result<()> {
+        let mut names = Vec::new();
+        for (index, account) in accounts.into_iter().enumerate() {
+            let base = format!("accounts[{}]", index);
+            let toml::Value::Table(mut account) = account else {
+                return Err(self.invalid(&base, "must be a table"));
+            };
+            let name = match account.remove("name") {
+                Some(toml::Value::String(name)) if !name.trim().is_empty() => name,
+                Some(_) => return Err(self.invalid(&format!("{}.name", base), "must be a non-empty string")),
+                None if index == 0 => "primary".to_string(),
+                None => return Err(self.invalid(&format!("{}.name", base), "is required for every account but the first")),
+            };
+            let prefix = if index == 0 {
+                self.values.insert("MCP_ACCOUNT_NAME".to_string(), Given { value: name.clone(), origin: self.origin(&format!("{}.name", base)) });
+                String::new()
+            } else {
+                names.push(name.clone());
+                account_prefix(&name)
+            };
+            for (key, value) in flatten("", account) {
+                let setting = ACCOUNT_SETTINGS.iter()
+                    .find(|setting| setting.key == key)
+                    .ok_or_else(|| ConfigError::UnknownKey(self.origin(&format!("{}.{}", base, key))))?;
+                self.set(account_env(&prefix, setting), &format!("{}.{}", base, key), value)?;
+            }
+        }
+        if !names.is_empty() {
+            self.values.insert("MCP_ACCOUNTS".to_string(), Given { value: names.join(","), origin: self.origin("accounts") });
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, env: String, key: &str, value: toml::Value) -> Result<()> {
+        let is_list = LIST_SETTINGS.iter().any(|list| env.ends_with(list));
+        let value = match value {
+            toml::Value::String(s) => s,
+            toml::Value::Integer(n) => n.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            toml::Value::Array(items) if is_list => items.into_iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => Ok(s),
+                    _ => Err(self.invalid(key, "must be a list of strings")),
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join(","),
+            _ if is_list => return Err(self.invalid(key, "must be a string or a list of strings")),
+            _ => return Err(self.invalid(key, "must be a string, number or boolean")),
+        };
+        self.values.insert(env, Given { value, origin: self.origin(key) });
+        Ok(())
+    }
+}
+
+/// Leaf values of a table by dotted key. Arrays of tables are leaves.
+fn flatten(prefix: &str, table: toml::Table) -> Vec<(String, toml::Value)> {
+    let mut leaves = Vec::new();
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::Table(table) => leaves.extend(flatten(&key, table)),
+            value => leaves.push((key, value)),
+        }
+    }
+    leaves
+}
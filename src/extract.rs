@@ -0,0 +1,279 @@
+//! Plain-text extraction from attachments (PDF, office documents, CSV, calendars, zip)
+//!
+//! Only compiled with the `extract` feature. Every format has an input size limit,
+//! and output is capped so a single attachment cannot flood the model context.
+
+use crate::calendar;
+use calamine::Reader;
+use quick_xml::events::Event;
+use serde::Serialize;
+use std::io::{Cursor, Read};
+use thiserror::Error;
+
+/// Maximum characters of extracted text returned for one attachment
+pub const MAX_TEXT_CHARS: usize = 100_000;
+
+const MIB: usize = 1024 * 1024;
+
+/// Most bytes the parts of a spreadsheet may inflate to. The spreadsheet reader
+/// decompresses whole parts itself, so this is checked before handing it the data.
+const MAX_SPREADSHEET_INFLATED: u64 = 50 * MIB as u64;
+
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error("Cannot extract text from {0}")]
+    Unsupported(String),
+    #[error("{kind} attachment is {size} bytes, over the {limit} byte extraction limit")]
+    TooLarge { kind: DocumentKind, size: usize, limit: usize },
+    #[error("Failed to extract text from {0}: {1}")]
+    Failed(DocumentKind, String),
+}
+
+pub type Result<T> = std::result::Result<T, ExtractError>;
+
+/// Document formats that can be turned into text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Pdf,
+    Docx,
+    Odt,
+    Xlsx,
+    Ods,
+    Csv,
+    Text,
+    Calendar,
+    Zip,
+}
+
+impl DocumentKind {
+    /// Identify a format from the MIME type, falling back to the file extension
+    /// for the generic types mailers often use
+    pub fn detect(content_type: &str, filename: Option<&str>) -> Option<Self> {
+        let content_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let by_type = match content_type.as_str() {
+            "application/pdf" => Some(DocumentKind::Pdf),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some(DocumentKind::Docx),
+            "application/vnd.oasis.opendocument.text" => Some(DocumentKind::Odt),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some(DocumentKind::Xlsx),
+            "application/vnd.oasis.opendocument.spreadsheet" => Some(DocumentKind::Ods),
+            "text/csv" => Some(DocumentKind::Csv),
+            "text/calendar" | "application/ics" => Some(DocumentKind::Calendar),
+            "application/zip" | "application/x-zip-compressed" => Some(DocumentKind::Zip),
+            t if t.starts_with("text/") => Some(DocumentKind::Text),
+            _ => None,
+        };
+
+        by_type.or_else(|| {
+            let extension = filename?.rsplit_once('.')?.1.to_ascii_lowercase();
+            match extension.as_str() {
+                "pdf" => Some(DocumentKind::Pdf),
+                "docx" => Some(DocumentKind::Docx),
+                "odt" => Some(DocumentKind::Odt),
+                "xlsx" => Some(DocumentKind::Xlsx),
+                "ods" => Some(DocumentKind::Ods),
+                "csv" => Some(DocumentKind::Csv),
+                "ics" => Some(DocumentKind::Calendar),
+                "zip" => Some(DocumentKind::Zip),
+                "txt" | "md" | "log" | "json" | "xml" | "eml" => Some(DocumentKind::Text),
+                _ => None,
+            }
+        })
+    }
+
+    /// Largest input accepted for this format
+    pub fn size_limit(&self) -> usize {
+        match self {
+            DocumentKind::Pdf | DocumentKind::Zip => 20 * MIB,
+            DocumentKind::Docx | DocumentKind::Odt | DocumentKind::Xlsx | DocumentKind::Ods => 10 * MIB,
+            DocumentKind::Csv | DocumentKind::Text | DocumentKind::Calendar => 5 * MIB,
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DocumentKind::Pdf => "PDF",
+            DocumentKind::Docx => "DOCX",
+            DocumentKind::Odt => "ODT",
+            DocumentKind::Xlsx => "XLSX",
+            DocumentKind::Ods => "ODS",
+            DocumentKind::Csv => "CSV",
+            DocumentKind::Text => "text",
+            DocumentKind::Calendar => "calendar",
+            DocumentKind::Zip => "zip",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Text extracted from an attachment
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedText {
+    pub format: DocumentKind,
+    pub text: String,
+    /// True when the text was cut at `MAX_TEXT_CHARS`
+    pub truncated: bool,
+}
+
+/// Extract text from attachment data, choosing the format from its type and name
+pub fn extract_text(content_type: &str, filename: Option<&str>, data: &[u8]) -> Result<ExtractedText> {
+    let kind = DocumentKind::detect(content_type, filename)
+        .ok_or_else(|| ExtractError::Unsupported(content_type.to_string()))?;
+    let text = extract_kind(kind, data)?;
+    let (text, truncated) = truncate(text, MAX_TEXT_CHARS);
+    Ok(ExtractedText { format: kind, text, truncated })
+}
+
+fn extract_kind(kind: DocumentKind, data: &[u8]) -> Result<String> {
+    if data.len() > kind.size_limit() {
+        return Err(ExtractError::TooLarge { kind, size: data.len(), limit: kind.size_limit() });
+    }
+
+    match kind {
+        DocumentKind::Pdf => extract_pdf(data),
+        DocumentKind::Docx => extract_zipped_xml(kind, data, "word/document.xml"),
+        DocumentKind::Odt => extract_zipped_xml(kind, data, "content.xml"),
+        DocumentKind::Xlsx | DocumentKind::Ods => extract_spreadsheet(kind, data),
+        DocumentKind::Csv | DocumentKind::Text => Ok(String::from_utf8_lossy(data).into_owned()),
+        DocumentKind::Calendar => Ok(calendar::unfold(&String::from_utf8_lossy(data))),
+        DocumentKind::Zip => extract_zip(data),
+    }
+}
+
+fn extract_pdf(data: &[u8]) -> Result<String> {
+    // The PDF parser panics on some malformed input; treat that as a failed extraction
+    std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data))
+        .map_err(|_| ExtractError::Failed(DocumentKind::Pdf, "malformed document".to_string()))?
+        .map_err(|e| ExtractError::Failed(DocumentKind::Pdf, e.to_string()))
+}
+
+/// Read the text runs of a DOCX or ODT body, turning paragraphs, breaks and tabs
+/// into whitespace. Both formats name these elements alike once prefixes are dropped.
+fn extract_zipped_xml(kind: DocumentKind, data: &[u8], entry: &str) -> Result<String> {
+    let failed = |e: String| ExtractError::Failed(kind, e);
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| failed(e.to_string()))?;
+    let file = archive.by_name(entry).map_err(|e| failed(format!("{}: {}", entry, e)))?;
+    let limit = kind.size_limit() as u64;
+    if file.size() > limit {
+        return Err(failed(format!("{} expands to {} bytes", entry, file.size())));
+    }
+    let mut xml = String::new();
+    file.take(limit).read_to_string(&mut xml).map_err(|e| failed(e.to_string()))?;
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut text = String::new();
+    loop {
+        match reader.read_event().map_err(|e| failed(e.to_string()))? {
+            Event::Text(t) => {
+                let decoded = t.decode().map_err(|e| failed(e.to_string()))?;
+                text.push_str(&quick_xml::escape::unescape(&decoded).map_err(|e| failed(e.to_string()))?);
+            }
+            Event::GeneralRef(r) => {
+                let name = r.decode().map_err(|e| failed(e.to_string()))?;
+                if let Some(resolved) = quick_xml::escape::resolve_predefined_entity(&name) {
+                    text.push_str(resolved);
+                } else if let Ok(Some(c)) = r.resolve_char_ref() {
+                    text.push(c);
+                }
+            }
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"tab" => text.push('\t'),
+                b"br" | b"line-break" | b"cr" => text.push('\n'),
+                b"s" => text.push(' '),
+                _ => {}
+            },
+            Event::End(e) if matches!(e.local_name().as_ref(), b"p" | b"h") => text.push('\n'),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text)
+}
+
+/// Render each sheet as a heading followed by tab-separated rows
+fn extract_spreadsheet(kind: DocumentKind, data: &[u8]) -> Result<String> {
+    check_inflated_size(kind, data, MAX_SPREADSHEET_INFLATED)?;
+    let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(data))
+        .map_err(|e| ExtractError::Failed(kind, e.to_string()))?;
+
+    let mut text = String::new();
+    for (name, range) in workbook.worksheets() {
+        text.push_str(&format!("## {}\n", name));
+        for row in range.rows() {
+            let cells: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+            text.push_str(cells.join("\t").trim_end());
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// Inflate every entry of a zip-based document without keeping it, failing once
+/// the total exceeds `limit`. Counts the bytes actually produced rather than the
+/// sizes the archive declares, which a crafted file can understate.
+fn check_inflated_size(kind: DocumentKind, data: &[u8], limit: u64) -> Result<()> {
+    let failed = |e: String| ExtractError::Failed(kind, e);
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| failed(e.to_string()))?;
+
+    let mut total = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(|e| failed(e.to_string()))?;
+        let name = file.name().to_string();
+        total += std::io::copy(&mut file.take(limit - total + 1), &mut std::io::sink())
+            .map_err(|e| failed(format!("{}: {}", name, e)))?;
+        if total > limit {
+            return Err(failed(format!("contents expand to more than {} bytes", limit)));
+        }
+    }
+    Ok(())
+}
+
+/// Extract every supported file in a zip archive, one section per entry.
+/// Nested archives are listed but not opened.
+fn extract_zip(data: &[u8]) -> Result<String> {
+    let failed = |e: String| ExtractError::Failed(DocumentKind::Zip, e);
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| failed(e.to_string()))?;
+
+    let mut text = String::new();
+    let mut budget = DocumentKind::Zip.size_limit() as u64;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| failed(e.to_string()))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        let kind = DocumentKind::detect("application/octet-stream", Some(&name))
+            .filter(|kind| *kind != DocumentKind::Zip);
+
+        let section = match kind {
+            None => "(not extracted: unsupported type)".to_string(),
+            // Check declared sizes before inflating to avoid zip bombs
+            Some(kind) if file.size() > kind.size_limit() as u64 || file.size() > budget => {
+                format!("(not extracted: {} bytes is over the limit)", file.size())
+            }
+            Some(kind) => {
+                let declared = file.size();
+                let mut content = Vec::new();
+                (&mut file).take(declared).read_to_end(&mut content).map_err(|e| failed(e.to_string()))?;
+                budget -= content.len() as u64;
+                extract_kind(kind, &content).unwrap_or_else(|e| format!("(not extracted: {})", e))
+            }
+        };
+        text.push_str(&format!("== {} ==\n{}\n\n", name, section.trim_end()));
+
+        if text.len() > MAX_TEXT_CHARS * 4 {
+            break;
+        }
+    }
+    Ok(text)
+}
+
+fn truncate(text: String, max_chars: usize) -> (String, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text, false),
+    }
+}
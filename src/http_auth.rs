@@ -0,0 +1,305 @@
+//! Authentication for the HTTP transport: named static tokens, OAuth 2.1 JWT
+//! access tokens with RFC 9728 protected-resource discovery, and client
+//! certificates
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::State,
+    http::{HeaderValue, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::access::{AuthFailure, Caller, OAUTH_CALLER_PREFIX, Scope, TokenStore};
+use crate::config::OAuthConfig;
+use crate::policy::MailboxPolicy;
+use crate::tls::ClientCertificate;
+
+/// Signing algorithms accepted for access tokens. Shared-secret HMAC
+/// algorithms are refused: a JWKS holds public keys only.
+const ACCEPTED_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+    Algorithm::PS256, Algorithm::PS384, Algorithm::PS512,
+    Algorithm::ES256, Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+const WELL_KNOWN: &str = "/.well-known/oauth-protected-resource";
+
+#[derive(Debug, Error)]
+pub enum JwtError {
+    #[error("Could not read JWKS file '{0}': {1}")]
+    Io(String, std::io::Error),
+    #[error("Could not parse JWKS file '{0}': {1}")]
+    Parse(String, serde_json::Error),
+    #[error("Signing algorithm {0:?} is not accepted")]
+    Algorithm(Algorithm),
+    #[error("No signing key matches key id {0:?}")]
+    NoKey(Option<String>),
+    #[error("Invalid access token: {0}")]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// Space-separated OAuth scopes
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+/// Validates JWT access tokens against an issuer's published keys
+pub struct JwtValidator {
+    keys: JwkSet,
+    issuer: String,
+    audience: String,
+}
+
+impl JwtValidator {
+    /// Load the JWKS file named in `config`
+    pub fn load(config: &OAuthConfig) -> Result<Self, JwtError> {
+        let display = config.jwks_file.display().to_string();
+        let text = std::fs::read_to_string(&config.jwks_file).map_err(|e| JwtError::Io(display.clone(), e))?;
+        Self::new(&text, &config.issuer, &config.audience).map_err(|e| JwtError::Parse(display, e))
+    }
+
+    pub fn new(jwks: &str, issuer: &str, audience: &str) -> Result<Self, serde_json::Error> {
+        Ok(JwtValidator {
+            keys: serde_json::from_str(jwks)?,
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+        })
+    }
+
+    /// Check the token's signature, issuer, audience and expiry, and map
+    /// its `sub` and `scope` claims to a caller named `oauth:<sub>`. Unknown
+    /// scopes are ignored.
+    pub fn validate(&self, token: &str) -> Result<Caller, JwtError> {
+        let jwt_header = jsonwebtoken::decode_header(token)?;
+        if !ACCEPTED_ALGORITHMS.contains(&jwt_header.alg) {
+            return Err(JwtError::Algorithm(jwt_header.alg));
+        }
+        let jwk = match &jwt_header.kid {
+            Some(kid) => self.keys.find(kid),
+            None if self.keys.keys.len() == 1 => self.keys.keys.first(),
+            None => None,
+        };
+        let jwk = jwk.ok_or_else(|| JwtError::NoKey(jwt_header.kid.clone()))?;
+
+        let mut validation = Validation::new(jwt_header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<Claims>(token, &DecodingKey::from_jwk(jwk)?, &validation)?.claims;
+
+        let mut scopes = Vec::new();
+        for scope in claims.scope.as_deref().unwrap_or_default().split_whitespace() {
+            if let Ok(scope) = scope.parse::<Scope>()
+                && !scopes.contains(&scope)
+            {
+                scopes.push(scope);
+            }
+        }
+        Ok(Caller {
+            name: format!("{}{}", OAUTH_CALLER_PREFIX, claims.sub),
+            scopes,
+            accounts: None,
+            mailbox_policy: MailboxPolicy::default(),
+            rate_limits: None,
+        })
+    }
+}
+
+/// RFC 9728 protected resource metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectedResourceMetadata {
+    pub resource: String,
+    pub authorization_servers: Vec<String>,
+    pub scopes_supported: Vec<&'static str>,
+    pub bearer_methods_supported: Vec<&'static str>,
+}
+
+/// Split a URL into its origin and path, e.g. `https://host:8443` and `/mcp`
+fn split_origin(url: &str) -> (&str, &str) {
+    let after_scheme = url.find("://").map_or(0, |i| i + 3);
+    match url[after_scheme..].find('/') {
+        Some(i) => url.split_at(after_scheme + i),
+        None => (url, ""),
+    }
+}
+
+/// Where the metadata of `resource` is published: the well-known path is
+/// inserted between the origin and the resource's path (RFC 9728 section 3.1)
+pub fn metadata_path(resource: &str) -> String {
+    let (_, path) = split_origin(resource);
+    format!("{}{}", WELL_KNOWN, path.trim_end_matches('/'))
+}
+
+/// Why a request was turned away
+#[derive(Debug)]
+pub enum Rejection {
+    Missing,
+    Invalid(String),
+}
+
+/// Bearer authentication state shared by every HTTP request
+pub struct HttpAuth {
+    tokens: TokenStore,
+    jwt: Option<JwtValidator>,
+    metadata: Option<ProtectedResourceMetadata>,
+    /// Scopes of client certificates without a token entry, when client
+    /// certificates are accepted at all
+    certificate_scopes: Option<Vec<Scope>>,
+}
+
+impl HttpAuth {
+    pub fn new(tokens: TokenStore) -> Self {
+        HttpAuth { tokens, jwt: None, metadata: None, certificate_scopes: None }
+    }
+
+    /// Also accept JWT access tokens, and publish discovery metadata that
+    /// points clients at the authorization server
+    pub fn with_oauth(mut self, config: &OAuthConfig, validator: JwtValidator) -> Self {
+        self.jwt = Some(validator);
+        self.metadata = Some(ProtectedResourceMetadata {
+            resource: config.resource.clone(),
+            authorization_servers: vec![config.issuer.clone()],
+            scopes_supported: Scope::ALL.iter().map(Scope::as_str).collect(),
+            bearer_methods_supported: vec!["header"],
+        });
+        self
+    }
+
+    /// Also accept connections that presented a verified client certificate.
+    /// Certificates whose subject names no token entry get `default_scopes`,
+    /// or are rejected when it is empty.
+    pub fn with_client_certificates(mut self, default_scopes: Vec<Scope>) -> Self {
+        self.certificate_scopes = Some(default_scopes);
+        self
+    }
+
+    /// Whether no request could ever authenticate
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty() && self.jwt.is_none() && self.certificate_scopes.is_none()
+    }
+
+    /// The caller behind a verified client certificate: the unexpired token
+    /// entry named like the certificate's subject, or a caller with the
+    /// default certificate scopes
+    pub fn certificate_caller(&self, certificate: &ClientCertificate) -> Result<Caller, Rejection> {
+        let default_scopes = self.certificate_scopes.as_deref().unwrap_or_default();
+        match self.tokens.caller_named(&certificate.subject, chrono::Utc::now()) {
+            Ok(caller) => Ok(caller.clone()),
+            Err(AuthFailure::Expired(name)) => Err(Rejection::Invalid(format!("token '{}' has expired", name))),
+            Err(AuthFailure::Unknown) if certificate.subject.starts_with(OAUTH_CALLER_PREFIX) => Err(Rejection::Invalid(
+                format!("client certificate '{}' uses the name of an OAuth caller", certificate.subject),
+            )),
+            Err(AuthFailure::Unknown) if !default_scopes.is_empty() => Ok(Caller {
+                name: certificate.subject.clone(),
+                scopes: default_scopes.to_vec(),
+                accounts: None,
+                mailbox_policy: MailboxPolicy::default(),
+                rate_limits: None,
+            }),
+            Err(AuthFailure::Unknown) => Err(Rejection::Invalid(
+                format!("no token entry is named after client certificate '{}'", certificate.subject),
+            )),
+        }
+    }
+
+    /// The caller a bearer token belongs to. Static tokens are checked first;
+    /// anything shaped like a JWT is then validated against the issuer's keys.
+    pub fn authenticate(&self, token: &str) -> Result<Caller, Rejection> {
+        match self.tokens.authenticate(token, chrono::Utc::now()) {
+            Ok(caller) => return Ok(caller.clone()),
+            Err(AuthFailure::Expired(name)) => return Err(Rejection::Invalid(format!("token '{}' has expired", name))),
+            Err(AuthFailure::Unknown) => {}
+        }
+        match &self.jwt {
+            Some(jwt) if token.split('.').count() == 3 => jwt.validate(token).map_err(|e| Rejection::Invalid(e.to_string())),
+            _ => Err(Rejection::Invalid("unknown bearer token".to_string())),
+        }
+    }
+
+    /// `WWW-Authenticate` value of a 401 response
+    fn challenge(&self, rejection: &Rejection) -> HeaderValue {
+        let mut challenge = "Bearer".to_string();
+        if let Some(metadata) = &self.metadata {
+            let (origin, _) = split_origin(&metadata.resource);
+            challenge.push_str(&format!(" resource_metadata=\"{}{}\"", origin, metadata_path(&metadata.resource)));
+        }
+        if let Rejection::Invalid(_) = rejection {
+            challenge.push_str(if self.metadata.is_some() { ", " } else { " " });
+            challenge.push_str("error=\"invalid_token\"");
+        }
+        HeaderValue::from_str(&challenge).unwrap_or_else(|_| HeaderValue::from_static("Bearer"))
+    }
+
+    /// Routes that serve the protected resource metadata without authentication
+    pub fn discovery_routes(self: &Arc<Self>) -> Router {
+        let Some(metadata) = &self.metadata else { return Router::new() };
+        let document = metadata.clone();
+        let handler = move || {
+            let document = document.clone();
+            async move { Json(document) }
+        };
+        let path = metadata_path(&metadata.resource);
+        let router = Router::new().route(&path, get(handler.clone()));
+        if path == WELL_KNOWN {
+            router
+        } else {
+            router.route(WELL_KNOWN, get(handler))
+        }
+    }
+}
+
+/// Authentication middleware: a verified client certificate, or else a bearer
+/// token. The caller is attached to the request so tool handlers know who is
+/// acting.
+pub async fn middleware(
+    State(auth): State<Arc<HttpAuth>>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    // A rejected certificate leaves the bearer token to authenticate the request
+    if auth.certificate_scopes.is_some()
+        && let Some(certificate) = request.extensions().get::<ClientCertificate>()
+    {
+        match auth.certificate_caller(certificate) {
+            Ok(caller) => {
+                request.extensions_mut().insert(caller);
+                return next.run(request).await;
+            }
+            Err(Rejection::Invalid(reason)) => log::warn!("Rejected client certificate: {}", reason),
+            Err(Rejection::Missing) => {}
+        }
+    }
+
+    let token = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let rejection = match token.map(|token| auth.authenticate(token)) {
+        Some(Ok(caller)) => {
+            request.extensions_mut().insert(caller);
+            return next.run(request).await;
+        }
+        Some(Err(rejection)) => rejection,
+        None => Rejection::Missing,
+    };
+    match &rejection {
+        Rejection::Missing => log::warn!("Missing Authorization header"),
+        Rejection::Invalid(reason) => log::warn!("Rejected bearer token: {}", reason),
+    }
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response.headers_mut().insert(header::WWW_AUTHENTICATE, auth.challenge(&rejection));
+    response
+}
@@ -1,35 +1,75 @@
+use crate::archive::ArchivedMessage;
+use crate::authentication;
+use crate::calendar;
+use crate::signature;
 use crate::imap::types::{
+    AppendResult,
     AttachmentData,
+    AttachmentInfo,
+    AttachmentSelector,
     EmailContent,
     EmailInfo,
+    Flag,
     ImapError,
     ImapSettings,
+    ImportFailure,
+    ImportProgress,
+    ImportSummary,
+    MailboxEntry,
     MoveEmailStatus,
+    NestedMessage,
+    PermanentFlags,
+    RawEmail,
     Result,
+    SpecialUse,
 };
+use crate::imap::mime;
+use crate::imap::pool::{ImapPool, Pooled};
+use async_imap::imap_proto::{Response, ResponseCode, SectionPath, Status, UidSetMember};
 use async_native_tls::TlsConnector;
+use chrono::{DateTime, FixedOffset};
 use futures::stream::StreamExt;
-use mail_parser::{MessageParser, MimeHeaders};
+use mail_parser::MessageParser;
 use secrecy::ExposeSecret;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
+
+pub(crate) type ImapSession = async_imap::Session<async_native_tls::TlsStream<async_std::net::TcpStream>>;
+
+/// Sessions a client without a shared pool keeps open
+const DEFAULT_POOL_SIZE: usize = 2;
 
 #[derive(Debug)]
 pub struct ImapClient {
     settings: ImapSettings,
     connection_timeout: Duration,
+    pool: Arc<ImapPool>,
 }
 
 impl ImapClient {
     pub fn new(settings: ImapSettings) -> Result<Self> {
+        Self::with_pool(settings, ImapPool::new(DEFAULT_POOL_SIZE, Duration::from_secs(300)))
+    }
+
+    /// A client that borrows its sessions from a pool shared with other clients
+    pub fn with_pool(settings: ImapSettings, pool: Arc<ImapPool>) -> Result<Self> {
         Ok(Self {
             settings,
             connection_timeout: Duration::from_secs(30),
+            pool,
         })
     }
 
-    async fn connect(
-        &self,
-    ) -> Result<async_imap::Session<async_native_tls::TlsStream<async_std::net::TcpStream>>> {
+    /// Borrow a logged-in session for this account, logging in if the pool
+    /// has none idle. Each operation has the session to itself until it
+    /// calls `release`.
+    async fn connect(&self) -> Result<Pooled<ImapSession>> {
+        let key = format!("{}@{}:{}", self.settings.user, self.settings.host, self.settings.port);
+        self.pool.checkout(&key, || self.open_session()).await
+    }
+
+    async fn open_session(&self) -> Result<ImapSession> {
         let connect_future = self.connect_internal();
         match async_std::future::timeout(self.connection_timeout, connect_future).await {
             Ok(result) => result,
@@ -43,9 +83,7 @@ impl ImapClient {
         }
     }
 
-    async fn connect_internal(
-        &self,
-    ) -> Result<async_imap::Session<async_native_tls::TlsStream<async_std::net::TcpStream>>> {
+    async fn connect_internal(&self) -> Result<ImapSession> {
         log::info!(
             "Connecting to {}:{} using {}...",
             self.settings.host,
@@ -117,12 +155,77 @@ impl ImapClient {
         }
         drop(stream);
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
+        session.release();
         Ok(names)
     }
 
+    /// List mailboxes with whether they can be selected and their special use
+    pub async fn list_mailbox_entries(&self) -> Result<Vec<MailboxEntry>> {
+        use async_imap::types::NameAttribute;
+
+        let mut session = self.connect().await?;
+        let mut entries = Vec::new();
+        {
+            let mailbox_stream = session.list(None, Some("*")).await?;
+            let mut stream = Box::pin(mailbox_stream);
+            while let Some(mailbox_result) = stream.next().await {
+                match mailbox_result {
+                    Ok(mailbox) => {
+                        let attributes = mailbox.attributes();
+                        let selectable = !attributes.iter().any(|attr| match attr {
+                            NameAttribute::NoSelect => true,
+                            NameAttribute::Extension(name) => name.eq_ignore_ascii_case("\\NonExistent"),
+                            _ => false,
+                        });
+                        let special_use = SpecialUse::ALL.into_iter()
+                            .find(|special_use| attributes.iter().any(|attr| special_use.matches(attr)))
+                            .or_else(|| SpecialUse::ALL.into_iter()
+                                .find(|special_use| mailbox.name().eq_ignore_ascii_case(special_use.fallback_name())));
+                        entries.push(MailboxEntry { name: mailbox.name().to_string(), selectable, special_use });
+                    }
+                    Err(err) => log::error!("IMAP mailbox listing error: {}", err),
+                }
+            }
+        }
+
+        session.release();
+        Ok(entries)
+    }
+
+    /// Run one search in several mailboxes over a single session. A mailbox
+    /// that cannot be selected or searched yields an error without stopping
+    /// the others.
+    pub async fn search_mailboxes(
+        &self,
+        mailboxes: &[String],
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<(String, Result<Vec<EmailInfo>>)>> {
+        let mut session = self.connect().await?;
+        let mut results = Vec::with_capacity(mailboxes.len());
+
+        for mailbox in mailboxes {
+            let result = match session.select(mailbox).await {
+                Err(e) => Err(ImapError::MailboxSelect(mailbox.to_string(), e.to_string())),
+                Ok(_) => match session.uid_search(query).await {
+                    Err(e) => Err(ImapError::SearchFailed(query.to_string(), e.to_string())),
+                    Ok(found) => {
+                        let mut uids: Vec<_> = found.into_iter().collect();
+                        uids.sort_by(|a, b| b.cmp(a)); // Newest first
+                        if let Some(limit) = limit {
+                            uids.truncate(limit as usize);
+                        }
+                        Ok(fetch_email_infos(&mut session, &uids).await)
+                    }
+                },
+            };
+            results.push((mailbox.clone(), result));
+        }
+
+        session.release();
+        Ok(results)
+    }
+
     pub async fn search_emails(
         &self,
         mailbox: &str,
@@ -145,38 +248,9 @@ impl ImapClient {
             uids.truncate(limit as usize);
         }
 
-        let mut results = Vec::new();
-        for uid in uids {
-            let uid_str = uid.to_string();
-            match session
-                .uid_fetch(&uid_str, "BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE)]")
-                .await
-            {
-                Ok(mut fetch_stream) => {
-                    while let Some(fetch_result) = fetch_stream.next().await {
-                        match fetch_result {
-                            Ok(fetch) => {
-                                if let Some(header) = fetch.header() {
-                                    let header_str = String::from_utf8_lossy(header).to_string();
-                                    results.push(EmailInfo {
-                                        uid: fetch.uid.unwrap_or(uid),
-                                        subject: extract_header(&header_str, "Subject:"),
-                                        from: extract_header(&header_str, "From:"),
-                                        date: extract_header(&header_str, "Date:"),
-                                    });
-                                }
-                            }
-                            Err(err) => log::error!("IMAP fetch error for {}: {}", uid, err),
-                        }
-                    }
-                }
-                Err(err) => log::error!("IMAP fetch headers failed for {}: {}", uid, err),
-            }
-        }
+        let results = fetch_email_infos(&mut session, &uids).await;
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
+        session.release();
         Ok(results)
     }
 
@@ -195,67 +269,44 @@ impl ImapClient {
             ..Default::default()
         };
 
-        match session.uid_fetch(uid, "BODY.PEEK[]").await {
+        match session.uid_fetch(uid, "(BODYSTRUCTURE BODY.PEEK[])").await {
             Ok(mut fetch_stream) => {
                 while let Some(fetch_result) = fetch_stream.next().await {
                     match fetch_result {
                         Ok(fetch) => {
+                            let parts = fetch.bodystructure().map(mime::parts).unwrap_or_default();
+                            content.attachments = mime::attachments_in(&parts, None);
+
                             if let Some(body) = fetch.body()
                                 && let Some(parsed) = MessageParser::default().parse(body)
                             {
                                 content.subject = parsed.subject()
                                     .map(|s| s.to_string())
                                     .unwrap_or_default();
-
-                                if let Some(mail_parser::Address::List(list)) = parsed.from()
-                                    && let Some(addr) = list.first()
-                                {
-                                    content.sender = addr.address
-                                        .as_ref()
-                                        .map(|a| a.to_string())
-                                        .unwrap_or_default();
-                                }
-
-                                if let Some(mail_parser::Address::List(list)) = parsed.to() {
-                                    content.recipients = list.iter()
-                                        .filter_map(|a| a.address.as_ref().map(|s| s.to_string()))
-                                        .collect();
-                                }
-
-                                if let Some(mail_parser::Address::List(list)) = parsed.cc() {
-                                    content.cc_recipients = list.iter()
-                                        .filter_map(|a| a.address.as_ref().map(|s| s.to_string()))
-                                        .collect();
-                                }
-
-                                content.body = parsed.text_bodies()
+                                content.sender = first_address(parsed.from());
+                                content.recipients = address_list(parsed.to());
+                                content.cc_recipients = address_list(parsed.cc());
+                                content.body = body_text(&parsed);
+                                content.html_body = parsed.html_bodies()
                                     .next()
-                                    .map(|p| String::from_utf8_lossy(p.contents()).to_string())
-                                    .or_else(|| parsed.html_bodies()
-                                        .next()
-                                        .map(|p| String::from_utf8_lossy(p.contents()).to_string()))
-                                    .unwrap_or_default();
-
-                                for attachment in parsed.attachments() {
-                                    if let Some(name) = attachment.attachment_name() {
-                                        content.attachments.push(name.to_string());
-                                    }
+                                    .filter(|p| p.is_text_html())
+                                    .map(|p| String::from_utf8_lossy(p.contents()).to_string());
+                                content.calendar = calendar::find_invite(&parsed);
+                                content.security = signature::inspect(&parsed);
+                                content.authentication = Some(authentication::analyze(
+                                    &parsed,
+                                    Some(&self.settings.user),
+                                    self.settings.authserv_id.as_deref(),
+                                ));
+
+                                if let Some(date) = message_date(&parsed) {
+                                    content.received_time = date;
                                 }
 
-                                if let Some(date) = parsed.date() {
-                                    use chrono::TimeZone;
-                                    content.received_time = chrono::Utc
-                                        .with_ymd_and_hms(
-                                            date.year as i32,
-                                            date.month as u32,
-                                            date.day as u32,
-                                            date.hour as u32,
-                                            date.minute as u32,
-                                            date.second as u32,
-                                        )
-                                        .single()
-                                        .unwrap_or_else(chrono::Utc::now);
-                                }
+                                content.nested_messages = mime::nested_messages(&parsed, None)
+                                    .into_iter()
+                                    .map(|(part_id, message)| nested_message(part_id, message, &parts))
+                                    .collect();
                             }
                         }
                         Err(err) => log::error!("IMAP fetch error for email {}: {}", uid, err),
@@ -265,92 +316,82 @@ impl ImapClient {
             Err(err) => log::error!("IMAP fetch failed for email {}: {}", uid, err),
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
+        session.release();
         Ok(Some(content))
     }
 
-    /// Fetch a specific attachment from an email
-    pub async fn fetch_attachment(&self, mailbox: &str, uid: &str, attachment_name: &str) -> Result<Option<AttachmentData>> {
+    /// List the attachments of an email from its BODYSTRUCTURE, without downloading it
+    pub async fn fetch_attachment_infos(&self, mailbox: &str, uid: &str) -> Result<Option<Vec<AttachmentInfo>>> {
         let mut session = self.connect().await?;
 
         session.select(mailbox).await
             .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
 
-        let mut attachment_data: Option<AttachmentData> = None;
+        let outcome = fetch_parts(&mut session, uid).await;
 
-        match session.uid_fetch(uid, "BODY.PEEK[]").await {
-            Ok(mut fetch_stream) => {
-                while let Some(fetch_result) = fetch_stream.next().await {
-                    match fetch_result {
-                        Ok(fetch) => {
-                            if let Some(body) = fetch.body()
-                                && let Some(parsed) = MessageParser::default().parse(body)
-                            {
-                                for attachment in parsed.attachments() {
-                                    if let Some(name) = attachment.attachment_name()
-                                        && name == attachment_name
-                                    {
-                                        let content_type = attachment.content_type()
-                                            .map(|ct| format!("{}/{}", ct.c_type, ct.c_subtype.as_deref().unwrap_or("octet-stream")))
-                                            .unwrap_or_else(|| "application/octet-stream".to_string());
-
-                                        attachment_data = Some(AttachmentData {
-                                            name: name.to_string(),
-                                            content_type,
-                                            data: attachment.contents().to_vec(),
-                                        });
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        Err(err) => log::error!("IMAP fetch error for attachment {} on {}: {}", attachment_name, uid, err),
-                    }
-                }
-            }
-            Err(err) => log::error!("IMAP fetch failed for attachment {} on {}: {}", attachment_name, uid, err),
-        }
+        session.release();
+        Ok(outcome?.map(|parts| {
+            parts.into_iter()
+                .filter(|part| part.is_attachment)
+                .map(|part| part.info)
+                .collect()
+        }))
+    }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
-        Ok(attachment_data)
+    /// Fetch a specific attachment from an email, downloading only its MIME part
+    pub async fn fetch_attachment(&self, mailbox: &str, uid: &str, selector: &AttachmentSelector) -> Result<Option<AttachmentData>> {
+        let mut session = self.connect().await?;
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let outcome = fetch_part(&mut session, uid, selector).await;
+
+        session.release();
+        outcome
+    }
+
+    /// Get a mailbox's UIDVALIDITY without selecting it for writing
+    pub async fn get_uid_validity(&self, mailbox: &str) -> Result<Option<u32>> {
+        let mut session = self.connect().await?;
+
+        let mailbox_info = session.examine(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        session.release();
+        Ok(mailbox_info.uid_validity)
     }
 
     /// Get permanent flags available in a mailbox
-    pub async fn get_permanent_flags(&self, mailbox: &str) -> Result<Vec<String>> {
+    pub async fn get_permanent_flags(&self, mailbox: &str) -> Result<PermanentFlags> {
         let mut session = self.connect().await?;
 
         let mailbox_info = session.select(mailbox).await
             .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
 
-        // Standard IMAP flags that are always available
-        let mut flags = vec![
-            "\\Seen".to_string(),
-            "\\Answered".to_string(),
-            "\\Flagged".to_string(),
-            "\\Deleted".to_string(),
-            "\\Draft".to_string(),
-        ];
-
-        // Check for custom flags support
-        for flag in mailbox_info.permanent_flags.iter() {
-            let flag_str = format!("{:?}", flag);
-            if !flags.contains(&flag_str) && !flag_str.contains("MayCreate") {
-                flags.push(flag_str);
+        // Without a PERMANENTFLAGS response every flag in FLAGS is permanent (RFC 3501 7.1)
+        let advertised = if mailbox_info.permanent_flags.is_empty() {
+            &mailbox_info.flags
+        } else {
+            &mailbox_info.permanent_flags
+        };
+
+        let mut flags: Vec<Flag> = Vec::new();
+        let mut may_create_keywords = false;
+        for flag in advertised.iter().map(Flag::from) {
+            match flag {
+                Flag::MayCreate => may_create_keywords = true,
+                flag if !flag.is_storable() || flags.contains(&flag) => {}
+                flag => flags.push(flag),
             }
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
-        Ok(flags)
+        session.release();
+        Ok(PermanentFlags { flags, may_create_keywords })
     }
 
     /// Get flags currently set on an email
-    pub async fn fetch_flags(&self, mailbox: &str, uid: &str) -> Result<Vec<String>> {
+    pub async fn fetch_flags(&self, mailbox: &str, uid: &str) -> Result<Vec<Flag>> {
         let mut session = self.connect().await?;
 
         session.select(mailbox).await
@@ -363,22 +404,11 @@ impl ImapClient {
                 while let Some(fetch_result) = fetch_stream.next().await {
                     match fetch_result {
                         Ok(fetch) => {
-                            for flag in fetch.flags() {
-                                let flag_str = match flag {
-                                    async_imap::types::Flag::Seen => "\\Seen",
-                                    async_imap::types::Flag::Answered => "\\Answered",
-                                    async_imap::types::Flag::Flagged => "\\Flagged",
-                                    async_imap::types::Flag::Deleted => "\\Deleted",
-                                    async_imap::types::Flag::Draft => "\\Draft",
-                                    async_imap::types::Flag::Recent => "\\Recent",
-                                    async_imap::types::Flag::MayCreate => continue,
-                                    async_imap::types::Flag::Custom(c) => {
-                                        flags.push(c.to_string());
-                                        continue;
-                                    }
-                                };
-                                flags.push(flag_str.to_string());
-                            }
+                            flags.extend(
+                                fetch.flags()
+                                    .map(|flag| Flag::from(&flag))
+                                    .filter(|flag| *flag != Flag::MayCreate),
+                            );
                         }
                         Err(err) => log::error!("IMAP fetch flags error for {}: {}", uid, err),
                     }
@@ -387,14 +417,16 @@ impl ImapClient {
             Err(err) => log::error!("IMAP fetch flags failed for {}: {}", uid, err),
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
+        session.release();
         Ok(flags)
     }
 
     /// Add or remove a flag from an email
-    pub async fn store_flag(&self, mailbox: &str, uid: &str, flag: &str, add: bool) -> Result<()> {
+    pub async fn store_flag(&self, mailbox: &str, uid: &str, flag: &Flag, add: bool) -> Result<()> {
+        if !flag.is_storable() {
+            return Err(ImapError::InvalidFlag(flag.to_string(), "flag cannot be set by clients".to_string()));
+        }
+
         let mut session = self.connect().await?;
 
         session.select(mailbox).await
@@ -411,14 +443,14 @@ impl ImapClient {
             while store_stream.next().await.is_some() {}
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
+        session.release();
         Ok(())
     }
 
-    /// Move an email to another mailbox (COPY + DELETE + EXPUNGE)
-    pub async fn move_email(&self, uid: &str, from_mailbox: &str, to_mailbox: &str) -> Result<()> {
+    /// Move an email to another mailbox (COPY + DELETE + EXPUNGE), returning
+    /// the destination's UIDVALIDITY and the email's UID there when the
+    /// server reports COPYUID
+    pub async fn move_email(&self, uid: &str, from_mailbox: &str, to_mailbox: &str) -> Result<(Option<u32>, Option<u32>)> {
         let mut session = self.connect().await?;
 
         session.select(from_mailbox).await
@@ -444,22 +476,18 @@ impl ImapClient {
 
         match exists {
             Ok(false) => {
-                if let Err(err) = session.logout().await {
-                    log::warn!("IMAP logout failed: {}", err);
-                }
+                session.release();
                 return Err(ImapError::MessageNotFound);
             }
             Err(e) => {
-                if let Err(err) = session.logout().await {
-                    log::warn!("IMAP logout failed: {}", err);
-                }
+                session.release();
                 return Err(e);
             }
             Ok(true) => {}
         }
 
         // Copy to destination
-        session.uid_copy(uid, to_mailbox).await
+        let (uid_validity, copied) = copy_with_uids(&mut session, "UID COPY", uid, to_mailbox).await
             .map_err(|e| ImapError::FlagOperation(format!("Copy failed: {}", e)))?;
 
         // Mark original as deleted
@@ -478,10 +506,8 @@ impl ImapClient {
             while pinned.next().await.is_some() {}
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
-        }
-        Ok(())
+        session.release();
+        Ok((uid_validity, copied.first().map(|&(_, new_uid)| new_uid)))
     }
 
     /// Move multiple emails to another mailbox (COPY + DELETE + EXPUNGE)
@@ -503,6 +529,8 @@ impl ImapClient {
             let mut status = MoveEmailStatus {
                 email_id: uid.clone(),
                 success: true,
+                new_email_id: None,
+                uid_validity: None,
                 error: None,
             };
 
@@ -534,12 +562,18 @@ impl ImapClient {
                 continue;
             }
 
-            if let Err(e) = session.uid_copy(uid, to_mailbox).await {
-                log::error!("IMAP copy failed for {}: {}", uid, e);
-                status.success = false;
-                status.error = Some(format!("Copy failed: {}", e));
-                results.push(status);
-                continue;
+            match copy_with_uids(&mut session, "UID COPY", uid, to_mailbox).await {
+                Ok((uid_validity, copied)) => {
+                    status.new_email_id = copied.first().map(|(_, new_uid)| new_uid.to_string());
+                    status.uid_validity = uid_validity;
+                }
+                Err(e) => {
+                    log::error!("IMAP copy failed for {}: {}", uid, e);
+                    status.success = false;
+                    status.error = Some(format!("Copy failed: {}", e));
+                    results.push(status);
+                    continue;
+                }
             }
 
             let delete_result = match session.uid_store(uid, "+FLAGS (\\Deleted)").await {
@@ -582,11 +616,652 @@ impl ImapClient {
             }
         }
 
-        if let Err(err) = session.logout().await {
-            log::warn!("IMAP logout failed: {}", err);
+        session.release();
+        Ok(results)
+    }
+
+    /// Find the mailbox carrying a special-use attribute, falling back to its conventional name
+    pub async fn find_special_use_mailbox(&self, special_use: SpecialUse) -> Result<Option<String>> {
+        let mut session = self.connect().await?;
+
+        let mut by_attribute = None;
+        let mut by_name = None;
+        {
+            let mailbox_stream = session.list(None, Some("*")).await?;
+            let mut stream = Box::pin(mailbox_stream);
+            while let Some(mailbox_result) = stream.next().await {
+                match mailbox_result {
+                    Ok(mailbox) => {
+                        if mailbox.attributes().iter().any(|attr| special_use.matches(attr)) {
+                            by_attribute.get_or_insert_with(|| mailbox.name().to_string());
+                        } else if mailbox.name().eq_ignore_ascii_case(special_use.fallback_name()) {
+                            by_name.get_or_insert_with(|| mailbox.name().to_string());
+                        }
+                    }
+                    Err(err) => log::error!("IMAP mailbox listing error: {}", err),
+                }
+            }
         }
+
+        session.release();
+        Ok(by_attribute.or(by_name))
+    }
+
+    /// Return UIDs matching a search query, newest first
+    pub async fn search_uids(&self, mailbox: &str, query: &str) -> Result<Vec<u32>> {
+        let mut session = self.connect().await?;
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let search_result = session.uid_search(query).await
+            .map_err(|e| ImapError::SearchFailed(query.to_string(), e.to_string()))?;
+        let mut uids: Vec<u32> = search_result.into_iter().collect();
+        uids.sort_by(|a, b| b.cmp(a));
+
+        session.release();
+        Ok(uids)
+    }
+
+    /// Fetch header summaries for specific UIDs; UIDs not in the mailbox are omitted
+    pub async fn fetch_email_infos(&self, mailbox: &str, uids: &[u32]) -> Result<Vec<EmailInfo>> {
+        let mut session = self.connect().await?;
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let existing = existing_uids(&mut session, uids).await?;
+        let results = fetch_email_infos(&mut session, &existing).await;
+
+        session.release();
         Ok(results)
     }
+
+    /// Move emails using UID MOVE, or COPY plus UID EXPUNGE, so no other
+    /// message flagged \Deleted in the source mailbox is expunged
+    pub async fn move_uids(
+        &self,
+        uids: &[u32],
+        from_mailbox: &str,
+        to_mailbox: &str,
+    ) -> Result<Vec<MoveEmailStatus>> {
+        let mut session = self.connect().await?;
+
+        let capabilities = session.capabilities().await?;
+        let has_move = capabilities.has_str("MOVE");
+        if !has_move && !capabilities.has_str("UIDPLUS") {
+            session.release();
+            return Err(ImapError::CapabilityMissing("MOVE or UIDPLUS".to_string()));
+        }
+
+        session.select(from_mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(from_mailbox.to_string(), e.to_string()))?;
+
+        let existing = existing_uids(&mut session, uids).await?;
+        let outcome = if existing.is_empty() {
+            Ok((None, Vec::new()))
+        } else if has_move {
+            copy_with_uids(&mut session, "UID MOVE", &uid_set(&existing), to_mailbox).await
+                .map_err(|e| ImapError::DeleteOperation(format!("Move to '{}' failed: {}", to_mailbox, e)))
+        } else {
+            match copy_with_uids(&mut session, "UID COPY", &uid_set(&existing), to_mailbox).await {
+                Ok(copied) => expunge_uids(&mut session, &existing).await.map(|()| copied),
+                Err(e) => Err(ImapError::DeleteOperation(format!("Copy to '{}' failed: {}", to_mailbox, e))),
+            }
+        };
+
+        session.release();
+
+        let ((uid_validity, copied), error) = match outcome {
+            Ok(copied) => (copied, None),
+            Err(e) => {
+                log::error!("Move from {} to {} failed: {}", from_mailbox, to_mailbox, e);
+                ((None, Vec::new()), Some(e.to_string()))
+            }
+        };
+        let mut statuses = status_for(uids, &existing, from_mailbox, error.as_deref());
+        for status in &mut statuses {
+            status.new_email_id = copied.iter()
+                .find(|(uid, _)| uid.to_string() == status.email_id)
+                .map(|(_, new_uid)| new_uid.to_string());
+            status.uid_validity = status.new_email_id.as_ref().and(uid_validity);
+        }
+        Ok(statuses)
+    }
+
+    /// Permanently delete emails: flag \Deleted and UID EXPUNGE only those UIDs
+    pub async fn delete_emails(&self, uids: &[u32], mailbox: &str) -> Result<Vec<MoveEmailStatus>> {
+        let mut session = self.connect().await?;
+
+        // Plain EXPUNGE would also remove anything other clients flagged \Deleted
+        if !session.capabilities().await?.has_str("UIDPLUS") {
+            session.release();
+            return Err(ImapError::CapabilityMissing("UIDPLUS (UID EXPUNGE)".to_string()));
+        }
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let existing = existing_uids(&mut session, uids).await?;
+        let outcome = if existing.is_empty() {
+            Ok(())
+        } else {
+            expunge_uids(&mut session, &existing).await
+        };
+
+        session.release();
+
+        let error = outcome.err().map(|e| {
+            log::error!("Delete failed in {}: {}", mailbox, e);
+            e.to_string()
+        });
+        Ok(status_for(uids, &existing, mailbox, error.as_deref()))
+    }
+
+    /// Fetch the complete RFC 822 source of an email
+    pub async fn fetch_raw(&self, mailbox: &str, uid: &str) -> Result<Option<Vec<u8>>> {
+        let mut session = self.connect().await?;
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let mut raw = None;
+        match session.uid_fetch(uid, "BODY.PEEK[]").await {
+            Ok(mut fetch_stream) => {
+                while let Some(fetch_result) = fetch_stream.next().await {
+                    match fetch_result {
+                        Ok(fetch) => {
+                            if let Some(body) = fetch.body() {
+                                raw = Some(body.to_vec());
+                            }
+                        }
+                        Err(err) => log::error!("IMAP fetch error for email {}: {}", uid, err),
+                    }
+                }
+            }
+            Err(err) => log::error!("IMAP fetch failed for email {}: {}", uid, err),
+        }
+
+        session.release();
+        Ok(raw)
+    }
+
+    /// Fetch the source, flags and internal date of several emails over one session
+    pub async fn fetch_raw_many(&self, mailbox: &str, uids: &[u32]) -> Result<Vec<RawEmail>> {
+        let mut session = self.connect().await?;
+
+        session.select(mailbox).await
+            .map_err(|e| ImapError::MailboxSelect(mailbox.to_string(), e.to_string()))?;
+
+        let mut emails = Vec::with_capacity(uids.len());
+        if !uids.is_empty() {
+            match session.uid_fetch(uid_set(uids), "(UID FLAGS INTERNALDATE BODY.PEEK[])").await {
+                Ok(mut fetch_stream) => {
+                    while let Some(fetch_result) = fetch_stream.next().await {
+                        match fetch_result {
+                            Ok(fetch) => {
+                                if let (Some(uid), Some(body)) = (fetch.uid, fetch.body()) {
+                                    emails.push(RawEmail {
+                                        uid,
+                                        content: body.to_vec(),
+                                        flags: fetch.flags().map(|flag| Flag::from(&flag)).collect(),
+                                        internal_date: fetch.internal_date(),
+                                    });
+                                }
+                            }
+                            Err(err) => log::error!("IMAP fetch error in {}: {}", mailbox, err),
+                        }
+                    }
+                }
+                Err(err) => log::error!("IMAP fetch failed in {}: {}", mailbox, err),
+            }
+        }
+
+        session.release();
+
+        // Keep the caller's order regardless of how the server streamed results
+        emails.sort_by_key(|email| uids.iter().position(|uid| *uid == email.uid));
+        Ok(emails)
+    }
+
+    /// Append a raw message to a mailbox, returning the UID from APPENDUID when available
+    pub async fn append(
+        &self,
+        mailbox: &str,
+        flags: &[Flag],
+        internal_date: Option<DateTime<FixedOffset>>,
+        content: &[u8],
+    ) -> Result<AppendResult> {
+        let mut session = self.connect().await?;
+
+        let outcome = append_on_session(&mut session, mailbox, flags, internal_date, content).await;
+
+        session.release();
+
+        let (uid_validity, uid) = outcome?;
+        Ok(AppendResult {
+            mailbox: mailbox.to_string(),
+            uid,
+            uid_validity,
+        })
+    }
+
+    /// Append many messages over a single session, reporting progress after each one
+    pub async fn append_many<F>(
+        &self,
+        mailbox: &str,
+        mut messages: mpsc::Receiver<std::io::Result<ArchivedMessage>>,
+        mut progress: F,
+    ) -> Result<ImportSummary>
+    where
+        F: FnMut(&ImportProgress),
+    {
+        let mut session = self.connect().await?;
+
+        let mut summary = ImportSummary {
+            mailbox: mailbox.to_string(),
+            imported: 0,
+            failed: 0,
+            uids: Vec::new(),
+            failures: Vec::new(),
+        };
+
+        let mut index = 0;
+        while let Some(message) = messages.recv().await {
+            index += 1;
+            let (source, outcome) = match message {
+                Ok(message) => {
+                    let outcome = append_on_session(
+                        &mut session,
+                        mailbox,
+                        &message.flags,
+                        message.internal_date,
+                        &message.content,
+                    ).await;
+                    (message.source, outcome)
+                }
+                Err(e) => (format!("message #{}", index), Err(ImapError::Io(e))),
+            };
+
+            match outcome {
+                Ok((_, uid)) => {
+                    summary.imported += 1;
+                    summary.uids.extend(uid);
+                }
+                Err(e) => {
+                    log::error!("Import of {} into {} failed: {}", source, mailbox, e);
+                    summary.failed += 1;
+                    summary.failures.push(ImportFailure { source: source.clone(), error: e.to_string() });
+                }
+            }
+
+            progress(&ImportProgress {
+                processed: index,
+                imported: summary.imported,
+                failed: summary.failed,
+                source,
+            });
+        }
+
+        session.release();
+        Ok(summary)
+    }
+}
+
+/// APPEND on an open session, returning (UIDVALIDITY, UID) when the server reports APPENDUID
+async fn append_on_session(
+    session: &mut ImapSession,
+    mailbox: &str,
+    flags: &[Flag],
+    internal_date: Option<DateTime<FixedOffset>>,
+    content: &[u8],
+) -> Result<(Option<u32>, Option<u32>)> {
+    let flag_list = format!(
+        "({})",
+        flags.iter().filter(|f| f.is_storable()).map(Flag::as_str).collect::<Vec<_>>().join(" ")
+    );
+    let date = internal_date.map(|d| format!("\"{}\"", d.format("%d-%b-%Y %H:%M:%S %z")));
+
+    match std::str::from_utf8(content) {
+        Ok(text) => append_with_uid(session, mailbox, &flag_list, date.as_deref(), text).await,
+        Err(_) => {
+            // 8-bit content cannot go through run_command; the stock APPEND drops APPENDUID
+            log::debug!("Message is not valid UTF-8; appending without APPENDUID");
+            session.append(mailbox, Some(&flag_list), date.as_deref(), content).await
+                .map(|_| (None, None))
+                .map_err(|e| ImapError::AppendFailed(mailbox.to_string(), e.to_string()))
+        }
+    }
+}
+
+/// Run APPEND by hand so the tagged APPENDUID response code (RFC 4315) is not discarded
+async fn append_with_uid(
+    session: &mut ImapSession,
+    mailbox: &str,
+    flag_list: &str,
+    date: Option<&str>,
+    content: &str,
+) -> Result<(Option<u32>, Option<u32>)> {
+    let fail = |reason: String| ImapError::AppendFailed(mailbox.to_string(), reason);
+
+    let command = format!(
+        "APPEND {} {}{} {{{}}}",
+        quote_mailbox(mailbox)?,
+        flag_list,
+        date.map(|d| format!(" {}", d)).unwrap_or_default(),
+        content.len()
+    );
+    let tag = session.run_command(&command).await.map_err(|e| fail(e.to_string()))?;
+
+    loop {
+        let response = session.read_response().await
+            .ok_or_else(|| fail("connection closed".to_string()))?
+            .map_err(|e| fail(e.to_string()))?;
+        match response.parsed() {
+            Response::Continue { .. } => break,
+            Response::Done { status, information, .. } => {
+                return Err(fail(format!("{:?}: {}", status, information.as_deref().unwrap_or(""))));
+            }
+            _ => {}
+        }
+    }
+
+    // The literal is followed by the CRLF that ends the APPEND command
+    session.run_command_untagged(content).await.map_err(|e| fail(e.to_string()))?;
+
+    loop {
+        let response = session.read_response().await
+            .ok_or_else(|| fail("connection closed".to_string()))?
+            .map_err(|e| fail(e.to_string()))?;
+        if let Response::Done { tag: done_tag, status, code, information } = response.parsed()
+            && *done_tag == tag
+        {
+            if *status != Status::Ok {
+                return Err(fail(format!("{:?}: {}", status, information.as_deref().unwrap_or(""))));
+            }
+            return Ok(match code {
+                Some(ResponseCode::AppendUid(validity, uids)) => {
+                    let uid = uids.first().map(|member| match member {
+                        UidSetMember::Uid(uid) => *uid,
+                        UidSetMember::UidRange(range) => *range.start(),
+                    });
+                    (Some(*validity), uid)
+                }
+                _ => (None, None),
+            });
+        }
+    }
+}
+
+/// Run UID COPY or UID MOVE by hand so the COPYUID response code (RFC 4315)
+/// is not discarded, returning the destination's UIDVALIDITY and (source UID,
+/// destination UID) pairs. MOVE reports COPYUID in an untagged OK, COPY in
+/// the tagged one.
+async fn copy_with_uids(session: &mut ImapSession, command: &str, uids: &str, mailbox: &str) -> std::result::Result<(Option<u32>, Vec<(u32, u32)>), String> {
+    let mailbox = quote_mailbox(mailbox).map_err(|e| e.to_string())?;
+    let tag = session.run_command(format!("{} {} {}", command, uids, mailbox)).await
+        .map_err(|e| e.to_string())?;
+
+    let mut copied = (None, Vec::new());
+    loop {
+        let response = session.read_response().await
+            .ok_or_else(|| "connection closed".to_string())?
+            .map_err(|e| e.to_string())?;
+        let code = match response.parsed() {
+            Response::Done { tag: done_tag, status, code, information } if *done_tag == tag => {
+                if *status != Status::Ok {
+                    return Err(format!("{:?}: {}", status, information.as_deref().unwrap_or("")));
+                }
+                if let Some(ResponseCode::CopyUid(uid_validity, source, destination)) = code {
+                    copied = (Some(*uid_validity), expand_uid_set(source).into_iter().zip(expand_uid_set(destination)).collect());
+                }
+                return Ok(copied);
+            }
+            Response::Data { status: Status::Ok, code, .. } => code,
+            _ => continue,
+        };
+        if let Some(ResponseCode::CopyUid(uid_validity, source, destination)) = code {
+            copied = (Some(*uid_validity), expand_uid_set(source).into_iter().zip(expand_uid_set(destination)).collect());
+        }
+    }
+}
+
+/// The UIDs of a COPYUID set, in the order the server listed them
+fn expand_uid_set(set: &[UidSetMember]) -> Vec<u32> {
+    set.iter()
+        .flat_map(|member| match member {
+            UidSetMember::Uid(uid) => *uid..=*uid,
+            UidSetMember::UidRange(range) => range.clone(),
+        })
+        .collect()
+}
+
+/// Quote a mailbox name as an IMAP quoted string. Line breaks cannot be
+/// quoted and would end the command, so names containing them are refused.
+fn quote_mailbox(mailbox: &str) -> Result<String> {
+    if mailbox.contains(['\r', '\n']) {
+        return Err(ImapError::InvalidMailbox(mailbox.to_string(), "line breaks are not allowed".to_string()));
+    }
+    Ok(format!("\"{}\"", mailbox.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+/// Fetch subject/from/date headers for each UID in the selected mailbox
+async fn fetch_email_infos(session: &mut ImapSession, uids: &[u32]) -> Vec<EmailInfo> {
+    let mut results = Vec::new();
+    for &uid in uids {
+        let uid_str = uid.to_string();
+        match session
+            .uid_fetch(&uid_str, "BODY.PEEK[HEADER.FIELDS (SUBJECT FROM DATE)]")
+            .await
+        {
+            Ok(mut fetch_stream) => {
+                while let Some(fetch_result) = fetch_stream.next().await {
+                    match fetch_result {
+                        Ok(fetch) => {
+                            if let Some(header) = fetch.header() {
+                                let header_str = String::from_utf8_lossy(header).to_string();
+                                results.push(EmailInfo {
+                                    uid: fetch.uid.unwrap_or(uid),
+                                    subject: extract_header(&header_str, "Subject:"),
+                                    from: extract_header(&header_str, "From:"),
+                                    date: extract_header(&header_str, "Date:"),
+                                });
+                            }
+                        }
+                        Err(err) => log::error!("IMAP fetch error for {}: {}", uid, err),
+                    }
+                }
+            }
+            Err(err) => log::error!("IMAP fetch headers failed for {}: {}", uid, err),
+        }
+    }
+    results
+}
+
+/// Address of the first mailbox in a From-style header
+fn first_address(address: Option<&mail_parser::Address>) -> String {
+    address_list(address).into_iter().next().unwrap_or_default()
+}
+
+/// Addresses of every mailbox in an address header
+fn address_list(address: Option<&mail_parser::Address>) -> Vec<String> {
+    match address {
+        Some(mail_parser::Address::List(list)) => list.iter()
+            .filter_map(|a| a.address.as_ref().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The first text body, falling back to the first HTML body
+fn body_text(message: &mail_parser::Message) -> String {
+    message.text_bodies()
+        .next()
+        .map(|p| String::from_utf8_lossy(p.contents()).to_string())
+        .or_else(|| message.html_bodies()
+            .next()
+            .map(|p| String::from_utf8_lossy(p.contents()).to_string()))
+        .unwrap_or_default()
+}
+
+/// The Date header in UTC; `None` when it is missing or not a valid time
+fn message_date(message: &mail_parser::Message) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+    let date = message.date()?;
+    chrono::Utc
+        .with_ymd_and_hms(
+            date.year as i32,
+            date.month as u32,
+            date.day as u32,
+            date.hour as u32,
+            date.minute as u32,
+            date.second as u32,
+        )
+        .single()
+}
+
+/// Describe an encapsulated message and, recursively, the messages inside it
+fn nested_message(part_id: String, message: &mail_parser::Message, parts: &[mime::MimePart]) -> NestedMessage {
+    NestedMessage {
+        sender: first_address(message.from()),
+        recipients: address_list(message.to()),
+        cc_recipients: address_list(message.cc()),
+        subject: message.subject().map(|s| s.to_string()).unwrap_or_default(),
+        date: message_date(message),
+        body: body_text(message),
+        attachments: mime::attachments_in(parts, Some(&part_id)),
+        nested_messages: mime::nested_messages(message, Some(&part_id))
+            .into_iter()
+            .map(|(inner_id, inner)| nested_message(inner_id, inner, parts))
+            .collect(),
+        part_id,
+    }
+}
+
+/// Fetch the BODYSTRUCTURE of a message in the selected mailbox, flattened into its leaf parts
+async fn fetch_parts(session: &mut ImapSession, uid: &str) -> Result<Option<Vec<mime::MimePart>>> {
+    let mut parts = None;
+    let mut fetch_stream = session.uid_fetch(uid, "BODYSTRUCTURE").await
+        .map_err(|e| ImapError::InvalidMessage(format!("BODYSTRUCTURE fetch failed for {}: {}", uid, e)))?;
+    while let Some(fetch_result) = fetch_stream.next().await {
+        match fetch_result {
+            Ok(fetch) => {
+                if let Some(structure) = fetch.bodystructure() {
+                    parts = Some(mime::parts(structure));
+                }
+            }
+            Err(err) => log::error!("IMAP fetch error for BODYSTRUCTURE of {}: {}", uid, err),
+        }
+    }
+    Ok(parts)
+}
+
+/// Locate an attachment through BODYSTRUCTURE and fetch just that section
+async fn fetch_part(session: &mut ImapSession, uid: &str, selector: &AttachmentSelector) -> Result<Option<AttachmentData>> {
+    let Some(parts) = fetch_parts(session, uid).await? else {
+        return Ok(None);
+    };
+
+    // By part id any leaf can be fetched; by name only listed attachments match
+    let info = match selector {
+        AttachmentSelector::PartId(part_id) => {
+            parts.into_iter().map(|part| part.info).find(|info| &info.part_id == part_id)
+        }
+        AttachmentSelector::Name(name) => {
+            let matches: Vec<AttachmentInfo> = parts.into_iter()
+                .filter(|part| part.is_attachment && part.info.filename.as_deref() == Some(name.as_str()))
+                .map(|part| part.info)
+                .collect();
+            if matches.len() > 1 {
+                let part_ids: Vec<&str> = matches.iter().map(|info| info.part_id.as_str()).collect();
+                return Err(ImapError::AmbiguousAttachment(name.clone(), part_ids.join(", ")));
+            }
+            matches.into_iter().next()
+        }
+    };
+    let Some(info) = info else {
+        return Ok(None);
+    };
+    let Some(path) = mime::parse_part_id(&info.part_id) else {
+        return Ok(None);
+    };
+
+    let mut data = None;
+    let mut fetch_stream = session.uid_fetch(uid, format!("BODY.PEEK[{}]", info.part_id)).await
+        .map_err(|e| ImapError::InvalidMessage(format!("fetch of part {} failed for {}: {}", info.part_id, uid, e)))?;
+    let section = SectionPath::Part(path, None);
+    while let Some(fetch_result) = fetch_stream.next().await {
+        match fetch_result {
+            Ok(fetch) => {
+                if let Some(raw) = fetch.section(&section) {
+                    data = Some(mime::decode_transfer_encoding(&info.encoding, raw));
+                }
+            }
+            Err(err) => log::error!("IMAP fetch error for part {} of {}: {}", info.part_id, uid, err),
+        }
+    }
+
+    Ok(data.map(|data| AttachmentData {
+        part_id: info.part_id.clone(),
+        name: info.filename.clone().unwrap_or_else(|| format!("part-{}", info.part_id)),
+        content_type: info.content_type.clone(),
+        data,
+    }))
+}
+
+/// Format UIDs as an IMAP sequence set ("1,5,7")
+fn uid_set(uids: &[u32]) -> String {
+    uids.iter().map(|uid| uid.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Of the requested UIDs, return those present in the selected mailbox
+async fn existing_uids(session: &mut ImapSession, uids: &[u32]) -> Result<Vec<u32>> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let query = format!("UID {}", uid_set(uids));
+    let found = session.uid_search(&query).await
+        .map_err(|e| ImapError::SearchFailed(query.clone(), e.to_string()))?;
+    Ok(uids.iter().copied().filter(|uid| found.contains(uid)).collect())
+}
+
+/// Flag the UIDs \Deleted and expunge exactly those, leaving other \Deleted messages alone
+async fn expunge_uids(session: &mut ImapSession, uids: &[u32]) -> Result<()> {
+    let set = uid_set(uids);
+    {
+        let mut store_stream = session.uid_store(&set, "+FLAGS.SILENT (\\Deleted)")
+            .await
+            .map_err(|e| ImapError::DeleteOperation(format!("Delete flag failed: {}", e)))?;
+        while store_stream.next().await.is_some() {}
+    }
+    {
+        let expunge_stream = session.uid_expunge(&set).await
+            .map_err(|e| ImapError::DeleteOperation(format!("UID EXPUNGE failed: {}", e)))?;
+        let mut pinned = Box::pin(expunge_stream);
+        while pinned.next().await.is_some() {}
+    }
+    Ok(())
+}
+
+/// Per-UID results of a batch operation on `existing`: UIDs missing from the
+/// mailbox fail as not found, the others with `error` if the batch failed
+fn status_for(uids: &[u32], existing: &[u32], mailbox: &str, error: Option<&str>) -> Vec<MoveEmailStatus> {
+    uids.iter()
+        .map(|uid| {
+            let error = if !existing.contains(uid) {
+                Some(format!("Email not found in mailbox '{}'", mailbox))
+            } else {
+                error.map(|e| e.to_string())
+            };
+            MoveEmailStatus {
+                email_id: uid.to_string(),
+                success: error.is_none(),
+                new_email_id: None,
+                uid_validity: None,
+                error,
+            }
+        })
+        .collect()
 }
 
 fn extract_header(data: &str, field: &str) -> Option<String> {
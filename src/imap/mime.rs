@@ -0,0 +1,182 @@
+//! Locating message parts through IMAP BODYSTRUCTURE, so attachments can be
+//! listed and fetched without downloading the whole message, and numbering
+//! encapsulated (forwarded) messages the same way
+
+use crate::imap::types::AttachmentInfo;
+use async_imap::imap_proto::{BodyContentCommon, BodyContentSinglePart, BodyStructure, ContentEncoding};
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+
+/// A leaf MIME part and whether it should be presented as an attachment
+#[derive(Debug, Clone)]
+pub struct MimePart {
+    pub info: AttachmentInfo,
+    pub is_attachment: bool,
+    /// Part id of the innermost message/rfc822 part containing this one, if any
+    pub enclosing_message: Option<String>,
+}
+
+/// Flatten a message into its leaf parts, in MIME order. Encapsulated
+/// message/rfc822 parts are listed themselves and then descended into.
+pub fn parts(structure: &BodyStructure<'_>) -> Vec<MimePart> {
+    let mut parts = Vec::new();
+    collect_leaves(structure, None, None, &mut parts);
+    parts
+}
+
+/// List the attachments of the top-level message, in MIME order
+pub fn attachments(structure: &BodyStructure<'_>) -> Vec<AttachmentInfo> {
+    attachments_in(&parts(structure), None)
+}
+
+/// Attachments that belong directly to the message at `message_part`
+/// (`None` for the top-level message), excluding those of deeper messages
+pub fn attachments_in(parts: &[MimePart], message_part: Option<&str>) -> Vec<AttachmentInfo> {
+    parts.iter()
+        .filter(|part| part.is_attachment && part.enclosing_message.as_deref() == message_part)
+        .map(|part| part.info.clone())
+        .collect()
+}
+
+/// Messages encapsulated directly in `message`, paired with their IMAP part ids.
+/// `message_part` is the part id of `message` itself (`None` at the top level);
+/// numbering matches BODYSTRUCTURE so the ids can be passed to `BODY.PEEK[..]`.
+pub fn nested_messages<'a, 'x>(message: &'a Message<'x>, message_part: Option<&str>) -> Vec<(String, &'a Message<'x>)> {
+    fn walk<'a, 'x>(message: &'a Message<'x>, children: &[usize], parent: Option<&str>, found: &mut Vec<(String, &'a Message<'x>)>) {
+        for (index, child) in children.iter().enumerate() {
+            let part_id = child_part_id(parent, index);
+            match message.part(*child).map(|part| &part.body) {
+                Some(PartType::Multipart(grandchildren)) => walk(message, grandchildren, Some(&part_id), found),
+                Some(PartType::Message(inner)) => found.push((part_id, inner)),
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    match &message.root_part().body {
+        PartType::Multipart(children) => walk(message, children, message_part, &mut found),
+        PartType::Message(inner) => found.push((child_part_id(message_part, 0), inner)),
+        _ => {}
+    }
+    found
+}
+
+fn child_part_id(parent: Option<&str>, index: usize) -> String {
+    match parent {
+        Some(parent) => format!("{}.{}", parent, index + 1),
+        None => (index + 1).to_string(),
+    }
+}
+
+/// Parse a section number such as "1.2" into the path used by FETCH responses
+pub fn parse_part_id(part_id: &str) -> Option<Vec<u32>> {
+    part_id.split('.')
+        .map(|segment| segment.parse::<u32>().ok().filter(|n| *n > 0))
+        .collect()
+}
+
+/// Undo the Content-Transfer-Encoding of a fetched section
+pub fn decode_transfer_encoding(encoding: &str, data: &[u8]) -> Vec<u8> {
+    let decoded = match encoding.to_ascii_lowercase().as_str() {
+        "base64" => mail_parser::decoders::base64::base64_decode(data),
+        "quoted-printable" => mail_parser::decoders::quoted_printable::quoted_printable_decode(data),
+        _ => None,
+    };
+    decoded.unwrap_or_else(|| data.to_vec())
+}
+
+/// Walk the structure assigning RFC 3501 section numbers. A non-multipart
+/// message has a single part "1"; multipart children are numbered from 1, and
+/// the body of an encapsulated message "n" is "n.1" unless it is multipart.
+fn collect_leaves(structure: &BodyStructure<'_>, part_id: Option<&str>, enclosing: Option<&str>, leaves: &mut Vec<MimePart>) {
+    match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (index, body) in bodies.iter().enumerate() {
+                let child = child_part_id(part_id, index);
+                collect_leaves(body, Some(&child), enclosing, leaves);
+            }
+        }
+        BodyStructure::Basic { common, other, .. } | BodyStructure::Text { common, other, .. } => {
+            leaves.push(leaf(common, other, part_id.unwrap_or("1"), enclosing));
+        }
+        BodyStructure::Message { common, other, body, .. } => {
+            let message_part = part_id.unwrap_or("1").to_string();
+            leaves.push(leaf(common, other, &message_part, enclosing));
+            match body.as_ref() {
+                BodyStructure::Multipart { .. } => collect_leaves(body, Some(&message_part), Some(&message_part), leaves),
+                _ => collect_leaves(body, Some(&format!("{}.1", message_part)), Some(&message_part), leaves),
+            }
+        }
+    }
+}
+
+fn leaf(common: &BodyContentCommon<'_>, other: &BodyContentSinglePart<'_>, part_id: &str, enclosing: Option<&str>) -> MimePart {
+    let content_type = format!("{}/{}", common.ty.ty, common.ty.subtype).to_ascii_lowercase();
+    let disposition = common.disposition.as_ref().map(|d| d.ty.to_ascii_lowercase());
+    let filename = decoded_filename(common);
+
+    // Plain and HTML text are message bodies unless explicitly attached or named;
+    // every other leaf (images, calendars, forwarded messages) is an attachment
+    let is_attachment = match content_type.as_str() {
+        "text/plain" | "text/html" => disposition.as_deref() == Some("attachment") || filename.is_some(),
+        _ => true,
+    };
+
+    let info = AttachmentInfo {
+        part_id: part_id.to_string(),
+        filename,
+        content_type,
+        size: other.octets,
+        disposition,
+        content_id: other.id.as_ref()
+            .map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string()),
+        encoding: encoding_name(&other.transfer_encoding),
+    };
+    MimePart {
+        info,
+        is_attachment,
+        enclosing_message: enclosing.map(str::to_string),
+    }
+}
+
+fn encoding_name(encoding: &ContentEncoding<'_>) -> String {
+    match encoding {
+        ContentEncoding::SevenBit => "7bit".to_string(),
+        ContentEncoding::EightBit => "8bit".to_string(),
+        ContentEncoding::Binary => "binary".to_string(),
+        ContentEncoding::Base64 => "base64".to_string(),
+        ContentEncoding::QuotedPrintable => "quoted-printable".to_string(),
+        ContentEncoding::Other(other) => other.to_ascii_lowercase(),
+    }
+}
+
+/// Decode the filename from the Content-Disposition or Content-Type parameters.
+///
+/// Servers return parameters still encoded (RFC 2047 words, RFC 2231 `filename*`
+/// continuations), so they are rendered back into headers for mail-parser to decode.
+fn decoded_filename(common: &BodyContentCommon<'_>) -> Option<String> {
+    fn render(params: &Option<Vec<(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)>>) -> String {
+        params.iter()
+            .flatten()
+            .map(|(key, value)| {
+                let value = value.replace(['\r', '\n'], "");
+                if key.ends_with('*') {
+                    format!("; {}={}", key, value)
+                } else {
+                    format!("; {}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\""))
+                }
+            })
+            .collect()
+    }
+
+    let mut headers = format!("Content-Type: {}/{}{}\r\n", common.ty.ty, common.ty.subtype, render(&common.ty.params));
+    if let Some(disposition) = &common.disposition {
+        headers.push_str(&format!("Content-Disposition: {}{}\r\n", disposition.ty, render(&disposition.params)));
+    }
+    headers.push_str("\r\n");
+
+    MessageParser::default()
+        .parse_headers(headers.as_bytes())
+        .and_then(|message| message.attachment_name().map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+}
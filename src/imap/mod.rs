@@ -1,25 +1,43 @@
 pub mod imap_client;
+pub mod mime;
+pub mod pool;
 pub mod types;
 
 pub use self::imap_client::ImapClient;
+pub use self::pool::ImapPool;
 pub use self::types::{
+    AppendResult,
     AttachmentData,
+    AttachmentInfo,
+    AttachmentSelector,
     EmailContent,
     EmailInfo,
     EmailMetadata,
+    Flag,
     ImapError,
     ImapSettings,
+    ImportFailure,
+    ImportProgress,
+    ImportSummary,
+    MailboxEntry,
+    MailboxFailure,
+    MailboxSearch,
     MoveEmailStatus,
+    NestedMessage,
+    PermanentFlags,
+    RawEmail,
     Result,
+    SpecialUse,
 };
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 
 #[derive(Debug)]
 pub struct ImapConnection {
     pub settings: ImapSettings,
     client: Option<ImapClient>,
     connected: bool,
+    pool: Option<std::sync::Arc<ImapPool>>,
 }
 
 impl ImapConnection {
@@ -28,13 +46,26 @@ impl ImapConnection {
             settings,
             client: None,
             connected: false,
+            pool: None,
+        }
+    }
+
+    /// A connection whose IMAP sessions come from a pool shared with other connections
+    pub fn with_pool(settings: ImapSettings, pool: std::sync::Arc<ImapPool>) -> Self {
+        ImapConnection {
+            pool: Some(pool),
+            ..Self::new(settings)
         }
     }
 
     pub async fn connect(&mut self) -> Result<()> {
         log::info!("ImapConnection: Attempting connect...");
 
-        match ImapClient::new(self.settings.clone()) {
+        let client = match &self.pool {
+            Some(pool) => ImapClient::with_pool(self.settings.clone(), pool.clone()),
+            None => ImapClient::new(self.settings.clone()),
+        };
+        match client {
             Ok(client) => match client.list_mailboxes().await {
                 Ok(_) => {
                     log::info!("ImapConnection: Connect successful.");
@@ -87,23 +118,7 @@ impl ImapConnection {
 
             let results = client.search_emails(mailbox, &query, limit.map(|l| l as u32)).await?;
 
-            Ok(results
-                .into_iter()
-                .map(|info| {
-                    let received_time = info.date
-                        .as_ref()
-                        .and_then(|d| chrono::DateTime::parse_from_rfc2822(d).ok())
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(Utc::now);
-
-                    EmailMetadata {
-                        email_id: info.uid.to_string(),
-                        sender: info.from.unwrap_or_default(),
-                        subject: info.subject.unwrap_or_default(),
-                        received_time,
-                    }
-                })
-                .collect())
+            Ok(results.into_iter().map(EmailMetadata::from).collect())
         } else {
             Err(ImapError::Login("Not connected".to_string()))
         }
@@ -121,65 +136,56 @@ impl ImapConnection {
         log::debug!("ImapConnection: Searching emails by keyword '{}' in '{}'...", query, mailbox);
 
         if let Some(client) = &self.client {
-            // Build field-specific query parts
-            let field_queries: Vec<String> = match fields {
-                Some(f) if !f.is_empty() => {
-                    f.iter()
-                        .filter_map(|field| {
-                            let field_lower = field.to_lowercase();
-                            match field_lower.as_str() {
-                                "text" => Some(format!("TEXT \"{}\"", query)),
-                                "subject" => Some(format!("SUBJECT \"{}\"", query)),
-                                "from" => Some(format!("FROM \"{}\"", query)),
-                                "to" => Some(format!("TO \"{}\"", query)),
-                                "body" => Some(format!("BODY \"{}\"", query)),
-                                _ => {
-                                    log::warn!("Unknown search field: {}", field);
-                                    None
-                                }
-                            }
-                        })
-                        .collect()
-                }
-                _ => vec![format!("TEXT \"{}\"", query)], // Default to TEXT search
-            };
+            let imap_query = keyword_query(query, fields, since_date);
+            log::debug!("IMAP search query: {}", imap_query);
 
-            // Combine field queries with OR if multiple
-            let search_part = if field_queries.len() == 1 {
-                field_queries.into_iter().next().unwrap()
-            } else {
-                // IMAP OR syntax: OR <search1> <search2>
-                // For multiple: OR (OR a b) c
-                field_queries.into_iter().reduce(|acc, q| format!("OR {} {}", acc, q)).unwrap()
-            };
+            let results = client.search_emails(mailbox, &imap_query, limit.map(|l| l as u32)).await?;
 
-            // Add date filter if provided
-            let imap_query = match since_date {
-                Some(date) => format!("{} SINCE {}", search_part, date.format("%d-%b-%Y")),
-                None => search_part,
-            };
+            Ok(results.into_iter().map(EmailMetadata::from).collect())
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
 
-            log::debug!("IMAP search query: {}", imap_query);
+    /// Search several mailboxes by keyword, merging the matches newest first
+    pub async fn search_mailboxes_by_keyword(
+        &self,
+        mailboxes: &[String],
+        query: &str,
+        fields: Option<&[String]>,
+        since_date: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<MailboxSearch> {
+        log::debug!("ImapConnection: Searching emails by keyword '{}' in {} mailboxes...", query, mailboxes.len());
 
-            let results = client.search_emails(mailbox, &imap_query, limit.map(|l| l as u32)).await?;
+        if let Some(client) = &self.client {
+            let imap_query = keyword_query(query, fields, since_date);
+            log::debug!("IMAP search query: {}", imap_query);
 
-            Ok(results
-                .into_iter()
-                .map(|info| {
-                    let received_time = info.date
-                        .as_ref()
-                        .and_then(|d| chrono::DateTime::parse_from_rfc2822(d).ok())
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(Utc::now);
-
-                    EmailMetadata {
-                        email_id: info.uid.to_string(),
-                        sender: info.from.unwrap_or_default(),
-                        subject: info.subject.unwrap_or_default(),
-                        received_time,
+            let mut search = MailboxSearch::default();
+            for (mailbox, result) in client.search_mailboxes(mailboxes, &imap_query, Some(limit as u32)).await? {
+                match result {
+                    Ok(infos) => search.emails.extend(infos.into_iter().map(|info| EmailMetadata {
+                        mailbox: Some(mailbox.clone()),
+                        ..EmailMetadata::from(info)
+                    })),
+                    Err(e) => {
+                        log::warn!("Search in '{}' failed: {}", mailbox, e);
+                        search.failed.push(MailboxFailure { mailbox, error: e.to_string() });
                     }
-                })
-                .collect())
+                }
+            }
+            search.emails.sort_by_key(|email| std::cmp::Reverse(email.received_time));
+            search.emails.truncate(limit);
+            Ok(search)
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    pub async fn list_mailbox_entries(&self) -> Result<Vec<MailboxEntry>> {
+        if let Some(client) = &self.client {
+            client.list_mailbox_entries().await
         } else {
             Err(ImapError::Login("Not connected".to_string()))
         }
@@ -199,7 +205,7 @@ impl ImapConnection {
     }
 
     /// Get available tags/flags for a mailbox
-    pub async fn get_available_tags(&self, mailbox: &str) -> Result<Vec<String>> {
+    pub async fn get_available_tags(&self, mailbox: &str) -> Result<PermanentFlags> {
         log::debug!("ImapConnection: Getting available tags for '{}'...", mailbox);
 
         if let Some(client) = &self.client {
@@ -209,8 +215,19 @@ impl ImapConnection {
         }
     }
 
+    /// Get a mailbox's UIDVALIDITY, which changes when its UIDs are reassigned
+    pub async fn get_uid_validity(&self, mailbox: &str) -> Result<Option<u32>> {
+        log::debug!("ImapConnection: Getting UIDVALIDITY of '{}'...", mailbox);
+
+        if let Some(client) = &self.client {
+            client.get_uid_validity(mailbox).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
     /// Get tags currently on an email
-    pub async fn get_email_tags(&self, mailbox: &str, email_id: &str) -> Result<Vec<String>> {
+    pub async fn get_email_tags(&self, mailbox: &str, email_id: &str) -> Result<Vec<Flag>> {
         log::debug!("ImapConnection: Getting tags for email {}...", email_id);
 
         if let Some(client) = &self.client {
@@ -221,7 +238,7 @@ impl ImapConnection {
     }
 
     /// Apply a tag to an email
-    pub async fn apply_tag(&self, mailbox: &str, email_id: &str, tag: &str) -> Result<()> {
+    pub async fn apply_tag(&self, mailbox: &str, email_id: &str, tag: &Flag) -> Result<()> {
         log::debug!("ImapConnection: Applying tag '{}' to email {}...", tag, email_id);
 
         if let Some(client) = &self.client {
@@ -232,7 +249,7 @@ impl ImapConnection {
     }
 
     /// Remove a tag from an email
-    pub async fn remove_tag(&self, mailbox: &str, email_id: &str, tag: &str) -> Result<()> {
+    pub async fn remove_tag(&self, mailbox: &str, email_id: &str, tag: &Flag) -> Result<()> {
         log::debug!("ImapConnection: Removing tag '{}' from email {}...", tag, email_id);
 
         if let Some(client) = &self.client {
@@ -243,7 +260,7 @@ impl ImapConnection {
     }
 
     /// Move an email to another mailbox
-    pub async fn move_email(&self, email_id: &str, from_mailbox: &str, to_mailbox: &str) -> Result<()> {
+    pub async fn move_email(&self, email_id: &str, from_mailbox: &str, to_mailbox: &str) -> Result<(Option<u32>, Option<u32>)> {
         log::debug!("ImapConnection: Moving email {} from '{}' to '{}'...", email_id, from_mailbox, to_mailbox);
 
         if let Some(client) = &self.client {
@@ -274,14 +291,214 @@ impl ImapConnection {
         }
     }
 
+    /// List the attachments of an email without downloading its content
+    pub async fn list_attachments(&self, mailbox: &str, email_id: &str) -> Result<Vec<AttachmentInfo>> {
+        log::debug!("ImapConnection: Listing attachments of email {} in '{}'...", email_id, mailbox);
+
+        if let Some(client) = &self.client {
+            client.fetch_attachment_infos(mailbox, email_id).await?
+                .ok_or(ImapError::MessageNotFound)
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
     /// Fetch an attachment from an email
-    pub async fn get_attachment(&self, mailbox: &str, email_id: &str, attachment_name: &str) -> Result<Option<AttachmentData>> {
-        log::debug!("ImapConnection: Getting attachment '{}' from email {} in '{}'...", attachment_name, email_id, mailbox);
+    pub async fn get_attachment(&self, mailbox: &str, email_id: &str, selector: &AttachmentSelector) -> Result<Option<AttachmentData>> {
+        log::debug!("ImapConnection: Getting attachment {} from email {} in '{}'...", selector, email_id, mailbox);
 
         if let Some(client) = &self.client {
-            client.fetch_attachment(mailbox, email_id, attachment_name).await
+            client.fetch_attachment(mailbox, email_id, selector).await
         } else {
             Err(ImapError::Login("Not connected".to_string()))
         }
     }
+
+    /// Resolve a special-use mailbox such as \Trash or \Drafts
+    pub async fn find_special_use_mailbox(&self, special_use: SpecialUse) -> Result<String> {
+        log::debug!("ImapConnection: Looking up {} mailbox...", special_use);
+
+        if let Some(client) = &self.client {
+            client.find_special_use_mailbox(special_use).await?
+                .ok_or(ImapError::SpecialUseNotFound(special_use))
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// List UIDs of every email in a mailbox, newest first
+    pub async fn list_uids(&self, mailbox: &str) -> Result<Vec<u32>> {
+        log::debug!("ImapConnection: Listing UIDs in '{}'...", mailbox);
+
+        if let Some(client) = &self.client {
+            client.search_uids(mailbox, "ALL").await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Fetch metadata for specific emails (used to preview destructive operations)
+    pub async fn get_emails_metadata(&self, mailbox: &str, uids: &[u32]) -> Result<Vec<EmailMetadata>> {
+        log::debug!("ImapConnection: Fetching metadata for {} emails in '{}'...", uids.len(), mailbox);
+
+        if let Some(client) = &self.client {
+            let infos = client.fetch_email_infos(mailbox, uids).await?;
+            Ok(infos.into_iter().map(EmailMetadata::from).collect())
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Move emails to the trash mailbox
+    pub async fn trash_emails(&self, uids: &[u32], from_mailbox: &str, trash_mailbox: &str) -> Result<Vec<MoveEmailStatus>> {
+        log::debug!("ImapConnection: Trashing {} emails from '{}' to '{}'...", uids.len(), from_mailbox, trash_mailbox);
+
+        if let Some(client) = &self.client {
+            client.move_uids(uids, from_mailbox, trash_mailbox).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Move emails without expunging anything else flagged \Deleted in the source mailbox
+    pub async fn move_uids(&self, uids: &[u32], from_mailbox: &str, to_mailbox: &str) -> Result<Vec<MoveEmailStatus>> {
+        log::debug!("ImapConnection: Moving {} emails from '{}' to '{}' by UID...", uids.len(), from_mailbox, to_mailbox);
+
+        if let Some(client) = &self.client {
+            client.move_uids(uids, from_mailbox, to_mailbox).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Permanently delete emails from a mailbox
+    pub async fn delete_emails(&self, uids: &[u32], mailbox: &str) -> Result<Vec<MoveEmailStatus>> {
+        log::debug!("ImapConnection: Deleting {} emails from '{}'...", uids.len(), mailbox);
+
+        if let Some(client) = &self.client {
+            client.delete_emails(uids, mailbox).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Fetch the raw RFC 822 source of an email
+    pub async fn get_raw_email(&self, mailbox: &str, email_id: &str) -> Result<Vec<u8>> {
+        log::debug!("ImapConnection: Getting raw source for email {} in '{}'...", email_id, mailbox);
+
+        if let Some(client) = &self.client {
+            client.fetch_raw(mailbox, email_id).await?
+                .ok_or(ImapError::MessageNotFound)
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Get the complete source of several emails, in the order requested
+    pub async fn get_raw_emails(&self, mailbox: &str, uids: &[u32]) -> Result<Vec<RawEmail>> {
+        log::debug!("ImapConnection: Getting raw source for {} email(s) in '{}'...", uids.len(), mailbox);
+
+        if let Some(client) = &self.client {
+            client.fetch_raw_many(mailbox, uids).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Append a raw RFC 822 message to a mailbox
+    pub async fn append(
+        &self,
+        mailbox: &str,
+        flags: &[Flag],
+        internal_date: Option<DateTime<FixedOffset>>,
+        content: &[u8],
+    ) -> Result<AppendResult> {
+        log::debug!("ImapConnection: Appending {} bytes to '{}'...", content.len(), mailbox);
+
+        if let Some(client) = &self.client {
+            client.append(mailbox, flags, internal_date, content).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+
+    /// Import a raw RFC 822 message into a mailbox, preserving flags and internal date
+    pub async fn import_message(
+        &self,
+        mailbox: &str,
+        content: &[u8],
+        flags: &[Flag],
+        internal_date: Option<DateTime<FixedOffset>>,
+    ) -> Result<AppendResult> {
+        log::debug!("ImapConnection: Importing {} byte message into '{}'...", content.len(), mailbox);
+
+        let has_headers = mail_parser::MessageParser::default()
+            .parse_headers(content)
+            .is_some_and(|message| !message.headers().is_empty());
+        if !has_headers {
+            return Err(ImapError::InvalidMessage("no RFC 822 headers found".to_string()));
+        }
+
+        self.append(mailbox, flags, internal_date, content).await
+    }
+
+    /// Import a sequence of archived messages (e.g. from an mbox or Maildir) over one session
+    pub async fn import_messages<F>(
+        &self,
+        mailbox: &str,
+        messages: tokio::sync::mpsc::Receiver<std::io::Result<crate::archive::ArchivedMessage>>,
+        progress: F,
+    ) -> Result<ImportSummary>
+    where
+        F: FnMut(&ImportProgress),
+    {
+        log::debug!("ImapConnection: Bulk importing into '{}'...", mailbox);
+
+        if let Some(client) = &self.client {
+            client.append_many(mailbox, messages, progress).await
+        } else {
+            Err(ImapError::Login("Not connected".to_string()))
+        }
+    }
+}
+
+/// Build an IMAP SEARCH query for `query` in the given fields
+fn keyword_query(query: &str, fields: Option<&[String]>, since_date: Option<DateTime<Utc>>) -> String {
+    // Build field-specific query parts
+    let field_queries: Vec<String> = match fields {
+        Some(f) if !f.is_empty() => {
+            f.iter()
+                .filter_map(|field| {
+                    let field_lower = field.to_lowercase();
+                    match field_lower.as_str() {
+                        "text" => Some(format!("TEXT \"{}\"", query)),
+                        "subject" => Some(format!("SUBJECT \"{}\"", query)),
+                        "from" => Some(format!("FROM \"{}\"", query)),
+                        "to" => Some(format!("TO \"{}\"", query)),
+                        "body" => Some(format!("BODY \"{}\"", query)),
+                        _ => {
+                            log::warn!("Unknown search field: {}", field);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        }
+        _ => vec![format!("TEXT \"{}\"", query)], // Default to TEXT search
+    };
+
+    // Combine field queries with OR if multiple
+    let search_part = if field_queries.len() == 1 {
+        field_queries.into_iter().next().unwrap()
+    } else {
+        // IMAP OR syntax: OR <search1> <search2>
+        // For multiple: OR (OR a b) c
+        field_queries.into_iter().reduce(|acc, q| format!("OR {} {}", acc, q)).unwrap()
+    };
+
+    // Add date filter if provided
+    match since_date {
+        Some(date) => format!("{} SINCE {}", search_part, date.format("%d-%b-%Y")),
+        None => search_part,
+    }
 }
@@ -0,0 +1,253 @@
+//! A pool of logged-in IMAP sessions shared by every MCP session of the
+//! server, keyed by account and capped in total size
+
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::imap::imap_client::ImapSession;
+
+/// A connection the pool can keep open between uses
+pub trait PoolConnection: Send + 'static {
+    /// Whether an idle connection still works (e.g. answers NOOP)
+    fn is_usable(&mut self) -> impl Future<Output = bool> + Send;
+    /// Close the connection politely
+    fn close(self) -> impl Future<Output = ()> + Send;
+}
+
+impl PoolConnection for ImapSession {
+    async fn is_usable(&mut self) -> bool {
+        self.noop().await.is_ok()
+    }
+
+    async fn close(mut self) {
+        if let Err(err) = self.logout().await {
+            log::debug!("IMAP logout failed: {}", err);
+        }
+    }
+}
+
+struct IdleConnection<C> {
+    key: String,
+    connection: C,
+    since: Instant,
+}
+
+struct PoolState<C> {
+    /// Connections that are open, whether idle or checked out
+    open: usize,
+    /// Idle connections, most recently returned last
+    idle: Vec<IdleConnection<C>>,
+}
+
+/// Open connections, handed out to one operation at a time
+pub struct Pool<C: PoolConnection> {
+    max_connections: usize,
+    idle_timeout: Duration,
+    state: Mutex<PoolState<C>>,
+    available: Notify,
+}
+
+/// The pool of IMAP sessions
+pub type ImapPool = Pool<ImapSession>;
+
+impl<C: PoolConnection> fmt::Debug for Pool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("max_connections", &self.max_connections)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("open", &self.open_connections())
+            .finish()
+    }
+}
+
+enum Next<C> {
+    Reuse(C),
+    Connect,
+    Replace(C),
+    Wait,
+}
+
+impl<C: PoolConnection> Pool<C> {
+    /// A pool of at most `max_connections` connections that closes those
+    /// left idle for `idle_timeout`. Inside a Tokio runtime a background
+    /// task closes idle connections even while no operation runs.
+    pub fn new(max_connections: usize, idle_timeout: Duration) -> Arc<Self> {
+        let pool = Arc::new(Pool {
+            max_connections: max_connections.max(1),
+            idle_timeout,
+            state: Mutex::new(PoolState { open: 0, idle: Vec::new() }),
+            available: Notify::new(),
+        });
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(reap_idle(Arc::downgrade(&pool)));
+        }
+        pool
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<C>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Connections currently open, idle or in use
+    pub fn open_connections(&self) -> usize {
+        self.lock().open
+    }
+
+    pub fn idle_connections(&self) -> usize {
+        self.lock().idle.len()
+    }
+
+    /// Borrow an idle connection for `key`, or open one with `connect`. When
+    /// the pool is full, an idle connection of another key is closed to make
+    /// room; if every connection is in use this waits for one to come back.
+    pub async fn checkout<F, Fut, E>(self: &Arc<Self>, key: &str, connect: F) -> Result<Pooled<C>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<C, E>>,
+    {
+        self.evict_idle().await;
+        let mut connect = Some(connect);
+        loop {
+            let available = self.available.notified();
+            let next = {
+                let mut state = self.lock();
+                if let Some(index) = state.idle.iter().rposition(|idle| idle.key == key) {
+                    Next::Reuse(state.idle.remove(index).connection)
+                } else if state.open < self.max_connections {
+                    state.open += 1;
+                    Next::Connect
+                } else if !state.idle.is_empty() {
+                    // The oldest idle connection of another account gives up its slot
+                    Next::Replace(state.idle.remove(0).connection)
+                } else {
+                    Next::Wait
+                }
+            };
+
+            match next {
+                Next::Reuse(mut connection) => {
+                    if connection.is_usable().await {
+                        return Ok(Pooled::new(self.clone(), key, connection));
+                    }
+                    log::debug!("Dropping broken pooled connection for {}", key);
+                    drop(connection);
+                    self.free_slot();
+                    continue;
+                }
+                Next::Replace(connection) => connection.close().await,
+                Next::Connect => {}
+                Next::Wait => {
+                    available.await;
+                    continue;
+                }
+            }
+
+            let connect = connect.take().expect("connect is only called once");
+            return match connect().await {
+                Ok(connection) => Ok(Pooled::new(self.clone(), key, connection)),
+                Err(e) => {
+                    self.free_slot();
+                    Err(e)
+                }
+            };
+        }
+    }
+
+    /// Close connections idle for longer than the idle timeout, returning
+    /// how many were closed
+    pub async fn evict_idle(&self) -> usize {
+        let stale: Vec<C> = {
+            let mut state = self.lock();
+            let (stale, fresh) = std::mem::take(&mut state.idle).into_iter()
+                .partition(|idle| idle.since.elapsed() >= self.idle_timeout);
+            state.idle = fresh;
+            state.open -= stale.len();
+            stale.into_iter().map(|idle: IdleConnection<C>| idle.connection).collect()
+        };
+        let count = stale.len();
+        if count > 0 {
+            self.available.notify_waiters();
+            log::debug!("Closing {} idle IMAP connection(s)", count);
+        }
+        for connection in stale {
+            connection.close().await;
+        }
+        count
+    }
+
+    fn put_back(&self, key: String, connection: C) {
+        self.lock().idle.push(IdleConnection { key, connection, since: Instant::now() });
+        self.available.notify_one();
+    }
+
+    fn free_slot(&self) {
+        self.lock().open -= 1;
+        self.available.notify_one();
+    }
+}
+
+/// Close idle connections periodically until the pool is dropped
+async fn reap_idle<C: PoolConnection>(pool: Weak<Pool<C>>) {
+    let period = match pool.upgrade() {
+        Some(pool) => (pool.idle_timeout / 2).max(Duration::from_secs(1)),
+        None => return,
+    };
+    loop {
+        tokio::time::sleep(period).await;
+        let Some(pool) = pool.upgrade() else { return };
+        pool.evict_idle().await;
+    }
+}
+
+/// A connection borrowed from the pool. `release` returns it for reuse;
+/// dropping it instead (e.g. when an operation fails halfway) discards it,
+/// since its state is unknown.
+pub struct Pooled<C: PoolConnection> {
+    pool: Arc<Pool<C>>,
+    key: String,
+    connection: Option<C>,
+}
+
+impl<C: PoolConnection> Pooled<C> {
+    fn new(pool: Arc<Pool<C>>, key: &str, connection: C) -> Self {
+        Pooled { pool, key: key.to_string(), connection: Some(connection) }
+    }
+
+    /// Hand the connection back to the pool for the next operation
+    pub fn release(mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.put_back(std::mem::take(&mut self.key), connection);
+        }
+    }
+}
+
+impl<C: PoolConnection> Deref for Pooled<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.connection.as_ref().expect("connection is present until released")
+    }
+}
+
+impl<C: PoolConnection> DerefMut for Pooled<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.connection.as_mut().expect("connection is present until released")
+    }
+}
+
+impl<C: PoolConnection> Drop for Pooled<C> {
+    fn drop(&mut self) {
+        if self.connection.take().is_some() {
+            self.pool.free_slot();
+        }
+    }
+}
@@ -1,6 +1,12 @@
-use chrono::{DateTime, Utc};
+use crate::authentication::SenderAuthentication;
+use crate::calendar::CalendarInvite;
+use crate::safety::ContentSafety;
+use crate::signature::MessageSecurity;
+use chrono::{DateTime, FixedOffset, Utc};
 use secrecy::Secret;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 use thiserror::Error;
 
 #[derive(Debug, Clone)]
@@ -11,6 +17,8 @@ pub struct ImapSettings {
     pub password: Secret<String>,
     pub use_tls: bool,
     pub skip_tls_verify: bool,
+    /// authserv-id of the receiving server whose Authentication-Results are trusted
+    pub authserv_id: Option<String>,
 }
 
 impl ImapSettings {
@@ -33,6 +41,7 @@ impl ImapSettings {
             password: Secret::new(password),
             use_tls,
             skip_tls_verify,
+            authserv_id: None,
         }
     }
 }
@@ -46,6 +55,7 @@ impl Default for ImapSettings {
             password: Secret::new(String::new()),
             use_tls: false,
             skip_tls_verify: true,
+            authserv_id: None,
         }
     }
 }
@@ -74,6 +84,22 @@ pub enum ImapError {
     SearchFailed(String, String),
     #[error("Flag operation failed: {0}")]
     FlagOperation(String),
+    #[error("Invalid flag '{0}': {1}")]
+    InvalidFlag(String, String),
+    #[error("Invalid mailbox name {0:?}: {1}")]
+    InvalidMailbox(String, String),
+    #[error("No mailbox with special use {0} found")]
+    SpecialUseNotFound(SpecialUse),
+    #[error("Server does not support {0}")]
+    CapabilityMissing(String),
+    #[error("Delete operation failed: {0}")]
+    DeleteOperation(String),
+    #[error("Append to '{0}' failed: {1}")]
+    AppendFailed(String, String),
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+    #[error("Attachment name '{0}' matches several parts ({1}); select one by part_id")]
+    AmbiguousAttachment(String, String),
 }
 
 pub type Result<T> = std::result::Result<T, ImapError>;
@@ -91,11 +117,32 @@ pub struct EmailInfo {
 #[derive(Debug, Clone, Serialize)]
 pub struct EmailMetadata {
     pub email_id: String,
+    /// Mailbox the email ID belongs to, set by cross-mailbox search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailbox: Option<String>,
     pub sender: String,
     pub subject: String,
     pub received_time: DateTime<Utc>,
 }
 
+impl From<EmailInfo> for EmailMetadata {
+    fn from(info: EmailInfo) -> Self {
+        let received_time = info.date
+            .as_ref()
+            .and_then(|d| chrono::DateTime::parse_from_rfc2822(d).ok())
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now);
+
+        EmailMetadata {
+            email_id: info.uid.to_string(),
+            mailbox: None,
+            sender: info.from.unwrap_or_default(),
+            subject: info.subject.unwrap_or_default(),
+            received_time,
+        }
+    }
+}
+
 /// Full email content (matches existing server mod.rs expectations)
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct EmailContent {
@@ -105,22 +152,343 @@ pub struct EmailContent {
     pub cc_recipients: Vec<String>,
     pub subject: String,
     pub body: String,
-    pub attachments: Vec<String>,
+    pub attachments: Vec<AttachmentInfo>,
     pub received_time: DateTime<Utc>,
+    /// Forwarded messages attached as message/rfc822 parts
+    pub nested_messages: Vec<NestedMessage>,
+    /// Meeting invitation or update carried in a text/calendar part
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<CalendarInvite>,
+    /// PGP or S/MIME signing and encryption, when present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<MessageSecurity>,
+    /// SPF/DKIM/DMARC/ARC results and spoofing signals for the sender
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<SenderAuthentication>,
+    /// Risk assessment of the content, when the content-safety layer is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_safety: Option<ContentSafety>,
+    /// HTML alternative of the body, kept for the content-safety checks
+    #[serde(skip)]
+    pub html_body: Option<String>,
+}
+
+/// An encapsulated (message/rfc822) message, e.g. an email forwarded as an attachment.
+/// Its attachments carry part ids that `get_attachment` accepts directly.
+#[derive(Debug, Clone, Serialize)]
+pub struct NestedMessage {
+    /// IMAP part id of the message/rfc822 part
+    pub part_id: String,
+    pub sender: String,
+    pub recipients: Vec<String>,
+    pub cc_recipients: Vec<String>,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+    pub body: String,
+    pub attachments: Vec<AttachmentInfo>,
+    pub nested_messages: Vec<NestedMessage>,
+}
+
+/// Complete source of a message with the metadata needed to archive it
+#[derive(Debug, Clone)]
+pub struct RawEmail {
+    pub uid: u32,
+    pub content: Vec<u8>,
+    pub flags: Vec<Flag>,
+    pub internal_date: Option<DateTime<FixedOffset>>,
+}
+
+/// Result of an IMAP APPEND
+#[derive(Debug, Clone, Serialize)]
+pub struct AppendResult {
+    pub mailbox: String,
+    /// UID assigned by the server (from APPENDUID), if reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid_validity: Option<u32>,
+}
+
+/// Progress of a bulk import, reported after each message
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub processed: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub source: String,
+}
+
+/// A message that could not be imported
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportFailure {
+    pub source: String,
+    pub error: String,
+}
+
+/// Outcome of a bulk import
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub mailbox: String,
+    pub imported: usize,
+    pub failed: usize,
+    /// UIDs assigned to imported messages, where the server reported them
+    pub uids: Vec<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<ImportFailure>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct MoveEmailStatus {
     pub email_id: String,
     pub success: bool,
+    /// UID in the destination mailbox, when the server reports COPYUID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_email_id: Option<String>,
+    /// UIDVALIDITY of the destination mailbox `new_email_id` belongs to
+    #[serde(skip)]
+    pub uid_validity: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
+/// An attachment located through BODYSTRUCTURE, addressable by its MIME part id
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttachmentInfo {
+    /// IMAP section number, e.g. "2" or "1.3"
+    pub part_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub content_type: String,
+    /// Size of the part as stored on the server (before transfer decoding)
+    pub size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disposition: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
+    /// Content-Transfer-Encoding of the part, needed to decode a fetched section
+    #[serde(skip)]
+    pub encoding: String,
+}
+
+/// How a caller identifies the attachment to fetch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentSelector {
+    PartId(String),
+    Name(String),
+}
+
+impl fmt::Display for AttachmentSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachmentSelector::PartId(part_id) => write!(f, "part {}", part_id),
+            AttachmentSelector::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
 /// Attachment data with content
 #[derive(Debug, Clone, Serialize)]
 pub struct AttachmentData {
+    pub part_id: String,
     pub name: String,
     pub content_type: String,
     pub data: Vec<u8>,
 }
+
+/// IMAP message flag (RFC 3501 system flags plus keywords)
+///
+/// Parses from and serializes to the exact wire form, so `"\\Seen"` and
+/// `"$label1"` round-trip unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Seen,
+    Answered,
+    Flagged,
+    Deleted,
+    Draft,
+    Recent,
+    /// `\*` in PERMANENTFLAGS: new keywords may be created
+    MayCreate,
+    /// Keyword or non-standard system flag, stored verbatim
+    Keyword(String),
+}
+
+impl Flag {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Flag::Seen => "\\Seen",
+            Flag::Answered => "\\Answered",
+            Flag::Flagged => "\\Flagged",
+            Flag::Deleted => "\\Deleted",
+            Flag::Draft => "\\Draft",
+            Flag::Recent => "\\Recent",
+            Flag::MayCreate => "\\*",
+            Flag::Keyword(k) => k,
+        }
+    }
+
+    /// Whether a client may set this flag with STORE
+    pub fn is_storable(&self) -> bool {
+        !matches!(self, Flag::Recent | Flag::MayCreate)
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Flag {
+    type Err = ImapError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| ImapError::InvalidFlag(s.to_string(), reason.to_string());
+
+        if s.is_empty() {
+            return Err(invalid("flag cannot be empty"));
+        }
+
+        let system = match s.to_ascii_lowercase().as_str() {
+            "\\seen" => Some(Flag::Seen),
+            "\\answered" => Some(Flag::Answered),
+            "\\flagged" => Some(Flag::Flagged),
+            "\\deleted" => Some(Flag::Deleted),
+            "\\draft" => Some(Flag::Draft),
+            "\\recent" => Some(Flag::Recent),
+            "\\*" => Some(Flag::MayCreate),
+            _ => None,
+        };
+        if let Some(flag) = system {
+            return Ok(flag);
+        }
+
+        // flag-keyword = atom, flag-extension = "\" atom (RFC 3501 section 9)
+        let atom = s.strip_prefix('\\').unwrap_or(s);
+        if atom.is_empty() {
+            return Err(invalid("missing flag name after backslash"));
+        }
+        if let Some(c) = atom.chars().find(|c| !is_atom_char(*c)) {
+            return Err(invalid(&format!("character {:?} is not allowed in a flag", c)));
+        }
+
+        Ok(Flag::Keyword(s.to_string()))
+    }
+}
+
+fn is_atom_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']')
+}
+
+impl From<&async_imap::types::Flag<'_>> for Flag {
+    fn from(flag: &async_imap::types::Flag<'_>) -> Self {
+        use async_imap::types::Flag as ImapFlag;
+        match flag {
+            ImapFlag::Seen => Flag::Seen,
+            ImapFlag::Answered => Flag::Answered,
+            ImapFlag::Flagged => Flag::Flagged,
+            ImapFlag::Deleted => Flag::Deleted,
+            ImapFlag::Draft => Flag::Draft,
+            ImapFlag::Recent => Flag::Recent,
+            ImapFlag::MayCreate => Flag::MayCreate,
+            ImapFlag::Custom(c) => Flag::Keyword(c.to_string()),
+        }
+    }
+}
+
+impl Serialize for Flag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Flags a client may permanently set in a mailbox
+#[derive(Debug, Clone, Serialize)]
+pub struct PermanentFlags {
+    pub flags: Vec<Flag>,
+    /// Server advertised `\*`, so new keywords may be created
+    pub may_create_keywords: bool,
+}
+
+/// A mailbox from LIST
+#[derive(Debug, Clone)]
+pub struct MailboxEntry {
+    pub name: String,
+    /// False for `\Noselect` and `\NonExistent` mailboxes, which only hold children
+    pub selectable: bool,
+    /// Special use from the LIST attributes, or from the conventional name
+    pub special_use: Option<SpecialUse>,
+}
+
+/// Result of searching several mailboxes
+#[derive(Debug, Clone, Default)]
+pub struct MailboxSearch {
+    /// Matches from all mailboxes, newest first
+    pub emails: Vec<EmailMetadata>,
+    /// Mailboxes that could not be searched
+    pub failed: Vec<MailboxFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MailboxFailure {
+    pub mailbox: String,
+    pub error: String,
+}
+
+/// Special-use mailbox attribute (RFC 6154)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SpecialUse {
+    All,
+    Archive,
+    Drafts,
+    Flagged,
+    Junk,
+    Sent,
+    Trash,
+}
+
+impl SpecialUse {
+    pub const ALL: [SpecialUse; 7] = [
+        SpecialUse::All,
+        SpecialUse::Archive,
+        SpecialUse::Drafts,
+        SpecialUse::Flagged,
+        SpecialUse::Junk,
+        SpecialUse::Sent,
+        SpecialUse::Trash,
+    ];
+
+    /// Conventional folder name used when the server does not advertise the attribute
+    pub fn fallback_name(&self) -> &'static str {
+        match self {
+            SpecialUse::All => "All Mail",
+            SpecialUse::Archive => "Archive",
+            SpecialUse::Drafts => "Drafts",
+            SpecialUse::Flagged => "Starred",
+            SpecialUse::Junk => "Spam",
+            SpecialUse::Sent => "Sent",
+            SpecialUse::Trash => "Trash",
+        }
+    }
+
+    pub(crate) fn matches(&self, attribute: &async_imap::types::NameAttribute<'_>) -> bool {
+        use async_imap::types::NameAttribute;
+        matches!(
+            (self, attribute),
+            (SpecialUse::All, NameAttribute::All)
+                | (SpecialUse::Archive, NameAttribute::Archive)
+                | (SpecialUse::Drafts, NameAttribute::Drafts)
+                | (SpecialUse::Flagged, NameAttribute::Flagged)
+                | (SpecialUse::Junk, NameAttribute::Junk)
+                | (SpecialUse::Sent, NameAttribute::Sent)
+                | (SpecialUse::Trash, NameAttribute::Trash)
+        )
+    }
+}
+
+impl fmt::Display for SpecialUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\\{:?}", self)
+    }
+}
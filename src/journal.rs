@@ -0,0 +1,166 @@
+//! Bounded journal of reversible changes, so `undo_operation` can put moved
+//! mail back and restore flags
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where the journal is kept and how many operations it remembers
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    /// JSON file the journal is saved to, so undo survives a restart
+    pub path: Option<PathBuf>,
+    /// Operations kept; older ones can no longer be undone
+    pub capacity: usize,
+}
+
+/// One email a move put somewhere else
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedEmail {
+    /// UID in the source mailbox before the move
+    pub email_id: String,
+    /// UID in the destination mailbox, from COPYUID
+    pub new_email_id: String,
+    /// UIDVALIDITY of the destination mailbox, from COPYUID. Undo refuses to
+    /// move the email back when the mailbox no longer has it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid_validity: Option<u32>,
+}
+
+/// What an operation changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Move {
+        from_mailbox: String,
+        to_mailbox: String,
+        emails: Vec<MovedEmail>,
+    },
+    Flag {
+        mailbox: String,
+        email_id: String,
+        flag: String,
+        /// Whether the flag was added (true) or removed (false)
+        added: bool,
+        /// Whether the email carried the flag before the change
+        was_set: bool,
+    },
+}
+
+/// A journaled tool call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Token name, or "local" over stdio
+    pub caller: String,
+    pub account: String,
+    pub tool: String,
+    pub change: Change,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+/// The journal shared by every MCP session of the server
+#[derive(Debug)]
+pub struct Journal {
+    config: JournalConfig,
+    operations: Mutex<VecDeque<Operation>>,
+    counter: AtomicU64,
+}
+
+impl Journal {
+    /// A journal kept in memory only
+    pub fn in_memory(capacity: usize) -> Arc<Self> {
+        Self::with_operations(JournalConfig { path: None, capacity }, VecDeque::new())
+    }
+
+    /// A journal saved to `config.path`, loading the operations already there
+    pub fn open(config: JournalConfig) -> std::io::Result<Arc<Self>> {
+        let operations = match &config.path {
+            Some(path) if path.exists() => {
+                let text = std::fs::read_to_string(path)?;
+                serde_json::from_str(&text).map_err(std::io::Error::other)?
+            }
+            _ => VecDeque::new(),
+        };
+        Ok(Self::with_operations(config, operations))
+    }
+
+    fn with_operations(config: JournalConfig, mut operations: VecDeque<Operation>) -> Arc<Self> {
+        let capacity = config.capacity.max(1);
+        while operations.len() > capacity {
+            operations.pop_front();
+        }
+        Arc::new(Journal {
+            config: JournalConfig { capacity, ..config },
+            operations: Mutex::new(operations),
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Remember a change and return the id to undo it with
+    pub fn record(&self, caller: &str, account: &str, tool: &str, change: Change) -> String {
+        let now = Utc::now();
+        let id = format!("op-{:x}-{}", now.timestamp_millis(), self.counter.fetch_add(1, Ordering::Relaxed));
+        let mut operations = self.lock();
+        if operations.len() == self.config.capacity {
+            operations.pop_front();
+        }
+        operations.push_back(Operation {
+            id: id.clone(),
+            timestamp: now,
+            caller: caller.to_string(),
+            account: account.to_string(),
+            tool: tool.to_string(),
+            change,
+            undone_at: None,
+        });
+        self.save(&operations);
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<Operation> {
+        self.lock().iter().find(|operation| operation.id == id).cloned()
+    }
+
+    /// Replace what is left to undo of a partly undone operation
+    pub fn update(&self, id: &str, change: Change) {
+        self.modify(id, |operation| operation.change = change);
+    }
+
+    pub fn mark_undone(&self, id: &str) {
+        self.modify(id, |operation| operation.undone_at = Some(Utc::now()));
+    }
+
+    fn modify(&self, id: &str, apply: impl FnOnce(&mut Operation)) {
+        let mut operations = self.lock();
+        if let Some(operation) = operations.iter_mut().find(|operation| operation.id == id) {
+            apply(operation);
+            self.save(&operations);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Operation>> {
+        self.operations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write the journal through a temporary file, so a crash never leaves
+    /// it half written. Failures are logged; the in-memory journal still works.
+    fn save(&self, operations: &VecDeque<Operation>) {
+        let Some(path) = &self.config.path else { return };
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let written = serde_json::to_vec_pretty(operations)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&temporary, json))
+            .and_then(|()| std::fs::rename(&temporary, path));
+        if let Err(e) = written {
+            log::error!("Could not save undo journal {}: {}", path.display(), e);
+        }
+    }
+}
@@ -1,6 +1,25 @@
+pub mod access;
+pub mod approval;
+pub mod archive;
+pub mod audit;
+pub mod authentication;
+pub mod calendar;
 pub mod config;
+#[cfg(feature = "extract")]
+pub mod extract;
+#[cfg(feature = "http")]
+pub mod http_auth;
 pub mod imap;
+pub mod journal;
+pub mod limits;
+pub mod policy;
+pub mod safety;
+pub mod sandbox;
 pub mod server;
+pub mod signature;
+pub mod smtp;
+#[cfg(feature = "http")]
+pub mod tls;
 
 pub use config::{Config, load_config};
 pub use imap::{EmailContent, EmailMetadata, ImapConnection, ImapSettings};
@@ -0,0 +1,245 @@
+//! Rate limits per caller and tool category, and a cap on concurrent
+//! operations per account, so a runaway agent cannot flood the IMAP server
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::access::Scope;
+
+/// Caller key used when the transport does not authenticate callers (stdio)
+pub const LOCAL_CALLER: &str = "local";
+
+/// A token-bucket rate: up to `requests` calls at once, refilled evenly
+/// over `per`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Time for one call's worth of tokens to refill
+    fn interval(&self) -> Duration {
+        self.per / self.requests
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    /// Parse `<count>/<unit>`, e.g. `60/min`, `5/s` or `1000/hour`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, unit) = s.trim().split_once('/')
+            .ok_or_else(|| format!("'{}' is not a rate such as 60/min", s))?;
+        let requests = match count.trim().parse::<u32>() {
+            Ok(0) | Err(_) => return Err(format!("'{}' must start with a positive count", s)),
+            Ok(n) => n,
+        };
+        let per = match unit.trim().to_lowercase().as_str() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => return Err(format!("unknown rate unit '{}', expected s, min or hour", other)),
+        };
+        Ok(RateLimit { requests, per })
+    }
+}
+
+impl<'de> Deserialize<'de> for RateLimit {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.per.as_secs() {
+            1 => "s",
+            60 => "min",
+            _ => "hour",
+        };
+        write!(f, "{}/{}", self.requests, unit)
+    }
+}
+
+/// Tool-call rates per category. Delete tools count as writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimits {
+    #[serde(default)]
+    pub read: Option<RateLimit>,
+    #[serde(default)]
+    pub write: Option<RateLimit>,
+    #[serde(default)]
+    pub send: Option<RateLimit>,
+}
+
+impl RateLimits {
+    /// The rate for tools that need `scope`
+    pub fn for_scope(&self, scope: Scope) -> Option<RateLimit> {
+        match scope {
+            Scope::Read | Scope::Admin => self.read,
+            Scope::Write | Scope::Delete => self.write,
+            Scope::Send => self.send,
+        }
+    }
+
+    /// Category name used in bucket keys and error data
+    fn category(scope: Scope) -> &'static str {
+        match scope {
+            Scope::Read | Scope::Admin => "read",
+            Scope::Write | Scope::Delete => "write",
+            Scope::Send => "send",
+        }
+    }
+}
+
+/// Why a call was turned away
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Throttled {
+    /// What ran out: a tool category, "http" or "account"
+    pub limit: String,
+    /// How long until the call would be accepted
+    pub retry_after: Duration,
+}
+
+impl Throttled {
+    /// Whole seconds to wait, rounded up so clients never retry too early
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// When the bucket is full again; calls spend `interval` each
+    full_at: Instant,
+}
+
+/// How often buckets that have refilled are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    swept_at: Option<Instant>,
+}
+
+/// Token buckets keyed by caller and category
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Spend one call from the bucket `key`, or say how long until one is
+    /// available
+    pub fn check(&self, key: &str, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        // A full bucket is the same as a new one, so dropping it loses nothing
+        // and keeps callers that went idle from piling up
+        if buckets.swept_at.is_none_or(|swept_at| now >= swept_at + SWEEP_INTERVAL) {
+            buckets.by_key.retain(|_, bucket| bucket.full_at > now);
+            buckets.swept_at = Some(now);
+        }
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert(Bucket { full_at: now });
+        let interval = limit.interval();
+        // The bucket holds `requests` calls: it is empty once full_at is
+        // `per` in the future
+        let full_at = bucket.full_at.max(now) + interval;
+        let empty_at = full_at.checked_sub(limit.per).unwrap_or(now);
+        if empty_at > now {
+            return Err(empty_at - now);
+        }
+        bucket.full_at = full_at;
+        Ok(())
+    }
+
+    /// Number of buckets currently kept
+    pub fn tracked(&self) -> usize {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).by_key.len()
+    }
+}
+
+/// Limits shared by every MCP session of the server
+#[derive(Debug)]
+pub struct Limits {
+    rates: RateLimits,
+    http_rate: Option<RateLimit>,
+    account_max_concurrent: usize,
+    buckets: RateLimiter,
+    accounts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Limits {
+    pub fn new(rates: RateLimits, http_rate: Option<RateLimit>, account_max_concurrent: usize) -> Arc<Self> {
+        Arc::new(Limits {
+            rates,
+            http_rate,
+            account_max_concurrent: account_max_concurrent.max(1),
+            buckets: RateLimiter::default(),
+            accounts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Count a call to a tool needing `scope` against the caller's rate for
+    /// that category. `overrides` are the caller's own rates, if any.
+    pub fn check_tool(&self, caller: &str, overrides: Option<&RateLimits>, scope: Scope) -> Result<(), Throttled> {
+        let limit = overrides.and_then(|rates| rates.for_scope(scope)).or_else(|| self.rates.for_scope(scope));
+        let Some(limit) = limit else { return Ok(()) };
+        let category = RateLimits::category(scope);
+        self.buckets.check(&format!("{}/{}", caller, category), limit, Instant::now())
+            .map_err(|retry_after| Throttled { limit: category.to_string(), retry_after })
+    }
+
+    /// Count an HTTP request against the caller's request rate
+    pub fn check_http(&self, caller: &str) -> Result<(), Throttled> {
+        let Some(limit) = self.http_rate else { return Ok(()) };
+        self.buckets.check(&format!("{}/http", caller), limit, Instant::now())
+            .map_err(|retry_after| Throttled { limit: "http".to_string(), retry_after })
+    }
+
+    /// Reserve one of the account's concurrent operation slots for as long
+    /// as the permit is held
+    pub fn try_acquire_account(&self, account: &str) -> Result<OwnedSemaphorePermit, Throttled> {
+        let semaphore = self.accounts.lock().unwrap_or_else(|e| e.into_inner())
+            .entry(account.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.account_max_concurrent)))
+            .clone();
+        semaphore.try_acquire_owned().map_err(|_| Throttled {
+            limit: "account".to_string(),
+            retry_after: Duration::from_secs(1),
+        })
+    }
+}
+
+/// Middleware that answers 429 Too Many Requests once a caller exceeds the
+/// HTTP request rate. It runs after authentication, so callers are told
+/// apart by the name their token resolved to.
+#[cfg(feature = "http")]
+pub async fn http_middleware(
+    axum::extract::State(limits): axum::extract::State<Arc<Limits>>,
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use axum::http::{HeaderValue, StatusCode, header};
+    use axum::response::IntoResponse;
+
+    let caller = request.extensions().get::<crate::access::Caller>()
+        .map_or(LOCAL_CALLER, |caller| caller.name.as_str());
+    match limits.check_http(caller) {
+        Ok(()) => next.run(request).await,
+        Err(throttled) => {
+            log::warn!("Rate limited HTTP requests from '{}' for {}s", caller, throttled.retry_after_secs());
+            let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(throttled.retry_after_secs()));
+            response
+        }
+    }
+}
@@ -1,19 +1,64 @@
-use clap::Parser;
+use clap::{Arg, CommandFactory, FromArgMatches, Parser, Subcommand};
 use log::LevelFilter;
-use protonmail_mcp_server::config::{load_config, Config};
+use protonmail_mcp_server::approval::{ApprovalQueue, ApprovalStatus};
+use protonmail_mcp_server::audit::AuditLog;
+use protonmail_mcp_server::config::{self, ACCOUNT_SETTINGS, Config, SERVER_SETTINGS, Setting, Sources, account_env, load_config_from};
+use protonmail_mcp_server::journal::Journal;
+use std::path::PathBuf;
+use std::sync::Arc;
 use protonmail_mcp_server::server::ImapMailboxServer;
 
 #[derive(Parser)]
 #[command(name = "protonmail-mcp-server")]
 #[command(about = "IMAP Mailbox MCP Server for ProtonMail Bridge")]
 struct Args {
-    /// Transport mode: stdio or http (overrides MCP_TRANSPORT env var)
-    #[arg(long)]
-    transport: Option<String>,
+    /// TOML config file; environment variables and flags override its settings
+    #[arg(long, env = "MCP_CONFIG", global = true)]
+    config: Option<PathBuf>,
 
-    /// HTTP bind address (overrides MCP_HTTP_BIND env var)
-    #[arg(long)]
-    bind: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Review tool calls held for approval (needs MCP_APPROVAL_STORE)
+    Approvals {
+        #[command(subcommand)]
+        action: ApprovalAction,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Validate the configuration and the files it names without starting the server
+    Check,
+}
+
+#[derive(Subcommand)]
+enum ApprovalAction {
+    /// List held calls, pending ones unless --all is given
+    List {
+        #[arg(long)]
+        all: bool,
+    },
+    /// Approve a held call; it runs when the agent next checks on it
+    Approve {
+        id: String,
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Reject a held call so it never runs
+    Reject {
+        id: String,
+        #[arg(long)]
+        note: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -25,36 +70,91 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .format_target(false)
         .init();
 
-    // Load configuration from .env file
-    let config = match load_config() {
+    // Flags override the environment (including the .env file), which
+    // overrides the config file
+    dotenv::dotenv().ok();
+    let settings = setting_flags();
+    let mut command = Args::command();
+    for (env, setting) in &settings {
+        let flag = setting.flag.unwrap_or_default();
+        command = command.arg(
+            Arg::new(flag)
+                .long(flag)
+                .value_name("VALUE")
+                .help(format!("{} [env: {}]", setting.help, env))
+                .help_heading("Settings")
+                .global(true),
+        );
+    }
+    let matches = command.get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut sources = Sources::from_env();
+    if let Some(path) = &args.config {
+        sources = sources.with_file(path).inspect_err(|e| log::error!("{}", e))?;
+    }
+    for (env, setting) in &settings {
+        let flag = setting.flag.unwrap_or_default();
+        if let Some(value) = matches.get_one::<String>(flag) {
+            sources = sources.with_flag(env, flag, value);
+        }
+    }
+    let config = match load_config_from(&sources) {
         Ok(config) => config,
         Err(e) => {
-            log::error!("Could not load config from environment: {}", e);
-            log::error!("Required: IMAP_HOST, IMAP_USERNAME, IMAP_PASSWORD");
+            log::error!("Could not load config: {}", e);
             return Err(e.into());
         }
     };
 
-    // CLI args override env vars
-    let args = Args::parse();
-    let transport = args.transport.unwrap_or(config.server.transport.clone());
-    let bind = args.bind.unwrap_or(config.server.http_bind.clone());
-    #[cfg(not(feature = "http"))]
-    let _ = &bind;
+    match args.command {
+        Some(Command::Approvals { action }) => return review_approvals(&config, action).await,
+        Some(Command::Config { action: ConfigAction::Check }) => return check_config(&config, &sources),
+        None => {}
+    }
+    let transport = config.server.transport.clone();
 
     log::info!("Starting ProtonMail MCP server (transport: {})...", transport);
-    log::info!(
-        "IMAP: {}:{} (TLS: {}, skip_verify: {})",
-        config.imap.host,
-        config.imap.port,
-        config.imap.use_tls,
-        config.imap.skip_tls_verify
-    );
+    for account in &config.accounts {
+        log::info!(
+            "IMAP ({}): {}:{} (TLS: {}, skip_verify: {})",
+            account.name,
+            account.imap.host,
+            account.imap.port,
+            account.imap.use_tls,
+            account.imap.skip_tls_verify
+        );
+    }
+
+    // Audit log of mutating and sending calls, shared by every session
+    let audit = match &config.server.audit_log {
+        Some(audit_config) => {
+            log::info!("Writing audit log to {}", audit_config.path.display());
+            AuditLog::open(audit_config.clone())
+                .inspect_err(|e| log::error!("Could not open audit log {}: {}", audit_config.path.display(), e))?
+        }
+        None => AuditLog::in_memory(),
+    };
+    // Undo journal of moves and flag changes, shared by every session
+    let journal = Journal::open(config.server.undo_journal.clone())
+        .inspect_err(|e| log::error!("Could not open undo journal: {}", e))?;
+    // Calls held for human approval, shared by every session
+    let approvals = ApprovalQueue::open(&config.server.approvals)
+        .inspect_err(|e| log::error!("Could not open approval store: {}", e))?;
+    if !config.server.approvals.rules.is_empty() {
+        let rules: Vec<String> = config.server.approvals.rules.iter().map(ToString::to_string).collect();
+        log::info!("Holding calls for approval: {}", rules.join(", "));
+        if config.server.approvals.store.is_none() && config.server.approvals.token.is_none() {
+            log::warn!("Held calls can only be approved through elicitation; set MCP_APPROVAL_STORE or MCP_APPROVAL_TOKEN");
+        }
+    }
+    let shared = Shared { audit, journal, approvals };
+    #[cfg(not(feature = "http"))]
+    let _ = &shared;
 
     match transport.as_str() {
-        "stdio" => run_stdio_server(config).await,
+        "stdio" => run_stdio_server(config, shared).await,
         #[cfg(feature = "http")]
-        "http" => run_http_server(config, &bind).await,
+        "http" => run_http_server(config, shared).await,
         #[cfg(not(feature = "http"))]
         "http" => {
             log::error!("HTTP transport not available. Rebuild with --features http");
@@ -67,18 +167,107 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Settings that have a flag, with the environment variable each sets.
+/// Account flags set the primary account.
+fn setting_flags() -> Vec<(String, Setting)> {
+    SERVER_SETTINGS.iter()
+        .map(|setting| (setting.env.to_string(), *setting))
+        .chain(ACCOUNT_SETTINGS.iter().map(|setting| (account_env("", setting), *setting)))
+        .filter(|(_, setting)| setting.flag.is_some())
+        .collect()
+}
+
+/// Audit log, undo journal and approval queue, shared by every session
+#[derive(Clone)]
+#[cfg_attr(not(any(feature = "stdio", feature = "http")), allow(dead_code))]
+struct Shared {
+    audit: Arc<AuditLog>,
+    journal: Arc<Journal>,
+    approvals: Arc<ApprovalQueue>,
+}
+
+impl Shared {
+    #[cfg_attr(not(any(feature = "stdio", feature = "http")), allow(dead_code))]
+    fn attach(&self, server: ImapMailboxServer) -> ImapMailboxServer {
+        server
+            .with_audit_log(self.audit.clone())
+            .with_journal(self.journal.clone())
+            .with_approvals(self.approvals.clone())
+    }
+}
+
+/// List, approve or reject held calls in the approval store
+async fn review_approvals(config: &Config, action: ApprovalAction) -> Result<(), Box<dyn std::error::Error>> {
+    if config.server.approvals.store.is_none() {
+        return Err("MCP_APPROVAL_STORE must be set so the server and this command share held calls".into());
+    }
+    let queue = ApprovalQueue::open(&config.server.approvals)?;
+    let by = std::env::var("USER").map_or_else(|_| "cli".to_string(), |user| format!("cli:{}", user));
+    let approval = match action {
+        ApprovalAction::List { all } => {
+            let status = if all { None } else { Some(ApprovalStatus::Pending) };
+            for approval in queue.list(status).await? {
+                println!(
+                    "{}  {:<9}  {}  {} -> {} on '{}'  {}",
+                    approval.id,
+                    approval.status.as_str(),
+                    approval.created_at.format("%Y-%m-%d %H:%M"),
+                    approval.caller,
+                    approval.tool,
+                    approval.account,
+                    serde_json::Value::Object(protonmail_mcp_server::audit::redact(&approval.arguments)),
+                );
+            }
+            return Ok(());
+        }
+        ApprovalAction::Approve { id, note } => queue.decide(&id, true, &by, note).await?,
+        ApprovalAction::Reject { id, note } => queue.decide(&id, false, &by, note).await?,
+    };
+    println!("{} {} ({} on '{}')", approval.id, approval.status, approval.tool, approval.account);
+    Ok(())
+}
+
+/// Report every problem with the configuration, or summarize it when there are none
+fn check_config(config: &Config, sources: &Sources) -> Result<(), Box<dyn std::error::Error>> {
+    let problems = config::check(config, sources);
+    if !problems.is_empty() {
+        for problem in &problems {
+            log::error!("{}", problem);
+        }
+        return Err(format!("{} problem(s) in the configuration", problems.len()).into());
+    }
+    println!("Configuration is valid");
+    println!("  transport: {}", config.server.transport);
+    for account in &config.accounts {
+        println!(
+            "  account '{}': IMAP {}@{}:{}, SMTP {}:{}",
+            account.name, account.imap.user, account.imap.host, account.imap.port, account.smtp.host, account.smtp.port
+        );
+    }
+    let tokens = config.server.auth_tokens.len();
+    if tokens > 0 {
+        println!("  {} bearer token(s) in the config file", tokens);
+    }
+    if !config.server.approvals.rules.is_empty() {
+        let rules: Vec<String> = config.server.approvals.rules.iter().map(ToString::to_string).collect();
+        println!("  held for approval: {}", rules.join(", "));
+    }
+    Ok(())
+}
+
 /// Create server from config
-fn create_server(config: &Config) -> ImapMailboxServer {
-    ImapMailboxServer::with_config(config.clone())
+#[cfg(feature = "stdio")]
+fn create_server(config: &Config, shared: &Shared) -> ImapMailboxServer {
+    shared.attach(ImapMailboxServer::with_config(config.clone()))
 }
 
 /// Run the MCP server over stdio transport
 #[cfg(feature = "stdio")]
-async fn run_stdio_server(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_stdio_server(config: Config, shared: Shared) -> Result<(), Box<dyn std::error::Error>> {
     use rmcp::ServiceExt;
     use tokio::io::{stdin, stdout};
 
-    let server = create_server(&config);
+    let server = create_server(&config, &shared);
 
     // Auto-connect for stdio mode (eager connection)
     if server.is_auto_connect() {
@@ -106,28 +295,52 @@ async fn run_stdio_server(config: Config) -> Result<(), Box<dyn std::error::Erro
 }
 
 #[cfg(not(feature = "stdio"))]
-async fn run_stdio_server(_config: Config) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_stdio_server(_config: Config, _shared: Shared) -> Result<(), Box<dyn std::error::Error>> {
     log::error!("Stdio transport not available. Rebuild with default features or --features stdio");
     Err("Stdio transport not compiled in".into())
 }
 
 /// Run the MCP server over HTTP+SSE transport
 #[cfg(feature = "http")]
-async fn run_http_server(config: Config, bind: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_http_server(config: Config, shared: Shared) -> Result<(), Box<dyn std::error::Error>> {
     use axum::{Router, middleware};
+    use protonmail_mcp_server::access::TokenStore;
+    use protonmail_mcp_server::approval;
+    use protonmail_mcp_server::http_auth::{self, HttpAuth, JwtValidator};
+    use protonmail_mcp_server::imap::ImapPool;
+    use protonmail_mcp_server::limits::{self, Limits};
     use rmcp::transport::streamable_http_server::{
         StreamableHttpService, StreamableHttpServerConfig,
-        session::local::LocalSessionManager,
+        session::local::{LocalSessionManager, SessionConfig},
     };
-    use std::sync::Arc;
     use std::time::Duration;
     use tokio_util::sync::CancellationToken;
 
-    // Get auth token (required for HTTP mode)
-    let auth_token = config.server.auth_token.clone().ok_or_else(|| {
-        log::error!("MCP_AUTH_TOKEN is required for HTTP transport");
-        "MCP_AUTH_TOKEN not set"
-    })?;
+    // Bearer tokens (required for HTTP mode): named ones from the token file
+    // and the config file, plus the shared MCP_AUTH_TOKEN with every scope
+    let mut tokens = match &config.server.auth_tokens_file {
+        Some(path) => TokenStore::load(path).inspect_err(|e| log::error!("{}", e))?,
+        None => TokenStore::default(),
+    };
+    tokens.add_entries(config.server.auth_tokens.clone()).inspect_err(|e| log::error!("{}", e))?;
+    if let Some(token) = &config.server.auth_token {
+        tokens.add_shared("default", token);
+    }
+    log::info!("Accepting {} bearer token(s)", tokens.len());
+    let mut auth = HttpAuth::new(tokens);
+    if let Some(oauth) = &config.server.oauth {
+        let validator = JwtValidator::load(oauth).inspect_err(|e| log::error!("{}", e))?;
+        log::info!("Accepting OAuth access tokens from {} for {}", oauth.issuer, oauth.audience);
+        auth = auth.with_oauth(oauth, validator);
+    }
+    if let Some(tls) = config.server.tls.as_ref().filter(|tls| tls.client_ca_file.is_some()) {
+        log::info!("Accepting client certificates instead of bearer tokens");
+        auth = auth.with_client_certificates(tls.client_scopes.clone());
+    }
+    if auth.is_empty() {
+        log::error!("MCP_AUTH_TOKEN, MCP_AUTH_TOKENS_FILE, MCP_OAUTH_ISSUER or MCP_TLS_CLIENT_CA is required for HTTP transport");
+        return Err("No bearer tokens configured".into());
+    }
 
     let ct = CancellationToken::new();
 
@@ -141,15 +354,34 @@ async fn run_http_server(config: Config, bind: &str) -> Result<(), Box<dyn std::
         None
     };
 
-    // Factory creates fresh server per session with its own IMAP connection
+    // Factory creates a fresh server per session; all of them borrow IMAP
+    // sessions from one pool, so the number of logins stays bounded
+    let pool = ImapPool::new(config.server.imap_max_connections, config.server.imap_idle_timeout);
+    log::info!(
+        "Sharing up to {} IMAP connection(s) across MCP sessions; idle MCP sessions close after {}s",
+        pool.max_connections(),
+        config.server.session_idle_timeout.as_secs()
+    );
+    let limits = Limits::new(config.server.rate_limits, config.server.http_rate_limit, config.server.account_max_concurrent);
     let config_for_factory = config.clone();
+    let limits_for_factory = limits.clone();
+    let shared_for_factory = shared.clone();
     let mcp_service = StreamableHttpService::new(
         move || {
-            let srv = create_server(&config_for_factory);
+            let srv = shared_for_factory.attach(
+                ImapMailboxServer::with_shared_pool(config_for_factory.clone(), pool.clone())
+                    .with_limits(limits_for_factory.clone()),
+            );
             // Connection happens lazily on first tool call via ensure_connected()
             Ok(srv)
         },
-        LocalSessionManager::default().into(),
+        LocalSessionManager {
+            session_config: SessionConfig {
+                keep_alive: Some(config.server.session_idle_timeout),
+                ..Default::default()
+            },
+            ..Default::default()
+        }.into(),
         StreamableHttpServerConfig {
             cancellation_token: ct.child_token(),
             sse_keep_alive,
@@ -157,52 +389,52 @@ async fn run_http_server(config: Config, bind: &str) -> Result<(), Box<dyn std::
         },
     );
 
-    // Build router with auth middleware
-    let auth_state = Arc::new(auth_token);
-    let router = Router::new()
+    // Build router with auth middleware, which runs before the rate limit
+    // so requests are counted per caller
+    if let Some(rate) = config.server.http_rate_limit {
+        log::info!("HTTP requests limited to {} per caller", rate);
+    }
+    let auth = Arc::new(auth);
+    let mut router = Router::new()
         .nest_service("/mcp", mcp_service)
-        .layer(middleware::from_fn_with_state(auth_state, auth_middleware));
+        .layer(middleware::from_fn_with_state(limits, limits::http_middleware))
+        .layer(middleware::from_fn_with_state(auth.clone(), http_auth::middleware))
+        .merge(auth.discovery_routes());
 
-    let listener = tokio::net::TcpListener::bind(bind).await?;
-    log::info!("MCP HTTP server listening on http://{}", bind);
-    log::info!("Endpoint: POST http://{}/mcp", bind);
+    // Humans approve held calls with their own token, which agents do not have
+    if let Some(token) = &config.server.approvals.token {
+        let mut approvers = TokenStore::default();
+        approvers.add_shared("approver", token);
+        let approver_auth = Arc::new(HttpAuth::new(approvers));
+        router = router.merge(
+            approval::http_routes(shared.approvals.clone())
+                .layer(middleware::from_fn_with_state(approver_auth, http_auth::middleware)),
+        );
+        log::info!("Serving the approval endpoint at /approvals");
+    }
+
+    let bind = config.server.http_bind.clone();
+    let listener = tokio::net::TcpListener::bind(&bind).await?;
+    let scheme = if config.server.tls.is_some() { "https" } else { "http" };
+    log::info!("MCP HTTP server listening on {}://{}", scheme, bind);
+    log::info!("Endpoint: POST {}://{}/mcp", scheme, bind);
 
     // Handle graceful shutdown on Ctrl+C
     let ct_for_shutdown = ct.clone();
-    axum::serve(listener, router)
-        .with_graceful_shutdown(async move {
-            tokio::signal::ctrl_c().await.ok();
-            log::info!("Received shutdown signal");
-            ct_for_shutdown.cancel();
-        })
-        .await?;
+    let shutdown = async move {
+        tokio::signal::ctrl_c().await.ok();
+        log::info!("Received shutdown signal");
+        ct_for_shutdown.cancel();
+    };
+    match config.server.tls {
+        Some(tls) => {
+            log::info!("TLS certificate: {} (reloaded on SIGHUP)", tls.cert_file.display());
+            tokio::spawn(shutdown);
+            protonmail_mcp_server::tls::serve(listener, router, tls, ct.clone()).await?;
+        }
+        None => axum::serve(listener, router).with_graceful_shutdown(shutdown).await?,
+    }
 
     log::info!("MCP HTTP server shut down");
     Ok(())
 }
-
-/// Bearer token authentication middleware
-#[cfg(feature = "http")]
-async fn auth_middleware(
-    axum::extract::State(expected_token): axum::extract::State<std::sync::Arc<String>>,
-    headers: axum::http::HeaderMap,
-    request: axum::http::Request<axum::body::Body>,
-    next: axum::middleware::Next,
-) -> Result<axum::response::Response, axum::http::StatusCode> {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "));
-
-    match auth_header {
-        Some(token) if token == expected_token.as_str() => Ok(next.run(request).await),
-        Some(_) => {
-            log::warn!("Invalid bearer token provided");
-            Err(axum::http::StatusCode::UNAUTHORIZED)
-        }
-        None => {
-            log::warn!("Missing Authorization header");
-            Err(axum::http::StatusCode::UNAUTHORIZED)
-        }
-    }
-}
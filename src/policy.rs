@@ -0,0 +1,116 @@
+//! Deployment restrictions on which tools are offered and which mailboxes
+//! they may work in
+
+use crate::imap::{MailboxEntry, SpecialUse};
+use rmcp::model::Tool;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// Tools that never change the mailbox but may write into a local directory
+/// the deployment configured for them, so they need only the read scope
+pub(crate) const LOCAL_WRITE_TOOLS: &[&str] = &["get_attachment", "export_emails"];
+
+/// Local-write tools that read-only mode keeps, because they refuse to write
+/// files in that mode
+const READ_ONLY_LOCAL_TOOLS: &[&str] = &["get_attachment"];
+
+/// Whether `get_attachment` called with `arguments` writes a file: `save` is
+/// set or a `save_path` is given, the same test the handler makes
+pub(crate) fn saves_attachment(arguments: Option<&JsonMap<String, JsonValue>>) -> bool {
+    let argument = |name: &str| arguments.and_then(|arguments| arguments.get(name));
+    argument("save").and_then(JsonValue::as_bool).unwrap_or(false)
+        || argument("save_path").is_some_and(|path| !path.is_null())
+}
+
+/// Which tools are advertised and callable
+#[derive(Debug, Clone, Default)]
+pub struct ToolPolicy {
+    /// Only offer tools that do not change the mailbox, send mail or write
+    /// local files
+    pub read_only: bool,
+    /// When set, only these tools are offered
+    pub allow: Option<Vec<String>>,
+    /// Tools that are never offered, even if allowed
+    pub deny: Vec<String>,
+}
+
+impl ToolPolicy {
+    pub fn permits(&self, tool: &Tool) -> bool {
+        let name = tool.name.as_ref();
+        if self.deny.iter().any(|denied| denied == name) {
+            return false;
+        }
+        if let Some(allow) = &self.allow
+            && !allow.iter().any(|allowed| allowed == name)
+        {
+            return false;
+        }
+        if self.read_only {
+            let read_only_hint = tool.annotations.as_ref().and_then(|a| a.read_only_hint).unwrap_or(false);
+            return read_only_hint || READ_ONLY_LOCAL_TOOLS.contains(&name);
+        }
+        true
+    }
+
+    /// Names in the allow and deny lists that are not in `known`
+    pub fn unknown_tools<'a>(&'a self, known: &[String]) -> Vec<&'a str> {
+        self.allow.iter().flatten()
+            .chain(self.deny.iter())
+            .filter(|name| !known.contains(name))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Which mailboxes tools may read from and write to
+#[derive(Debug, Clone, Default)]
+pub struct MailboxPolicy {
+    /// When set, only these mailboxes and their subfolders are accessible
+    pub allow: Option<Vec<String>>,
+}
+
+impl MailboxPolicy {
+    pub fn permits(&self, mailbox: &str) -> bool {
+        let Some(allow) = &self.allow else { return true };
+        allow.iter().any(|allowed| {
+            let prefix_len = allowed.len();
+            let matches_prefix = mailbox.get(..prefix_len).is_some_and(|prefix| {
+                // INBOX is case-insensitive (RFC 3501 section 5.1)
+                prefix == allowed || (allowed.eq_ignore_ascii_case("inbox") && prefix.eq_ignore_ascii_case("inbox"))
+            });
+            matches_prefix && matches!(mailbox[prefix_len..].chars().next(), None | Some('/'))
+        })
+    }
+
+    /// Mailboxes a search of every mailbox covers. The `\All` mailbox alone
+    /// is used when it is permitted and Trash and Spam are excluded, since
+    /// RFC 6154 lets it omit them; otherwise every other selectable,
+    /// permitted mailbox except the virtual `\Flagged` one.
+    pub fn search_targets(&self, entries: &[MailboxEntry], include_trash: bool) -> Vec<String> {
+        let all_mail = entries.iter()
+            .find(|entry| entry.selectable && entry.special_use == Some(SpecialUse::All) && self.permits(&entry.name));
+        if let Some(all_mail) = all_mail
+            && !include_trash
+        {
+            return vec![all_mail.name.clone()];
+        }
+
+        entries.iter()
+            .filter(|entry| entry.selectable && self.permits(&entry.name))
+            .filter(|entry| match entry.special_use {
+                Some(SpecialUse::All | SpecialUse::Flagged) => false,
+                Some(SpecialUse::Trash | SpecialUse::Junk) => include_trash,
+                _ => true,
+            })
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+}
+
+/// Split a comma-separated setting, dropping empty entries
+pub fn parse_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,131 @@
+//! Confining model-supplied file paths to configured root directories
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    #[error("{0} is not configured")]
+    NotConfigured(&'static str),
+    #[error("Path '{0}' escapes the configured directory")]
+    Escape(String),
+    #[error("Path '{0}' is not allowed: {1}")]
+    InvalidPath(String, String),
+    #[error("I/O error for '{0}': {1}")]
+    Io(String, io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, SandboxError>;
+
+/// Reject absolute paths and `..` components before touching the filesystem
+fn check_relative(requested: &str) -> Result<&Path> {
+    let path = Path::new(requested);
+    for component in path.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(SandboxError::Escape(requested.to_string()));
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(SandboxError::InvalidPath(
+                    requested.to_string(),
+                    "must be relative to the configured directory".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Resolve an existing file or directory under `root`, following symlinks and
+/// rejecting anything that ends up outside it.
+pub fn resolve_existing(root: &Path, requested: &str) -> Result<PathBuf> {
+    let relative = check_relative(requested)?;
+    let root = root.canonicalize()
+        .map_err(|e| SandboxError::Io(root.display().to_string(), e))?;
+    let resolved = root.join(relative).canonicalize()
+        .map_err(|e| SandboxError::Io(requested.to_string(), e))?;
+
+    if !resolved.starts_with(&root) {
+        return Err(SandboxError::Escape(requested.to_string()));
+    }
+    Ok(resolved)
+}
+
+/// Resolve a path under `root` that may not exist yet. The deepest existing
+/// ancestor (and the target itself, if present) must stay inside `root` once
+/// symlinks are followed.
+pub fn resolve_for_write(root: &Path, requested: &str) -> Result<PathBuf> {
+    let relative = check_relative(requested)?;
+    let root = root.canonicalize()
+        .map_err(|e| SandboxError::Io(root.display().to_string(), e))?;
+    let target = root.join(relative);
+
+    let mut existing = target.as_path();
+    while !existing.exists() {
+        existing = match existing.parent() {
+            Some(parent) => parent,
+            None => break,
+        };
+    }
+    let resolved_ancestor = existing.canonicalize()
+        .map_err(|e| SandboxError::Io(requested.to_string(), e))?;
+    if !resolved_ancestor.starts_with(&root) {
+        return Err(SandboxError::Escape(requested.to_string()));
+    }
+
+    // A dangling symlink does not "exist" but would still be followed on write
+    if target.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+        let link_target = target.canonicalize()
+            .map_err(|_| SandboxError::Escape(requested.to_string()))?;
+        if !link_target.starts_with(&root) {
+            return Err(SandboxError::Escape(requested.to_string()));
+        }
+    }
+
+    Ok(target)
+}
+
+/// Turn an untrusted name (e.g. an attachment filename) into a single safe path
+/// component: no separators, control or reserved characters, no leading dots.
+pub fn sanitize_filename(name: &str) -> String {
+    // Some mailers send a full client path as the filename; keep only the last part
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim_end_matches(['.', ' ']);
+
+    // Stay well within common 255-byte filename limits
+    let mut end = cleaned.len().min(200);
+    while !cleaned.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cleaned = &cleaned[..end];
+
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Total size in bytes of the regular files under `root`, without following symlinks
+pub fn disk_usage(root: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok(total)
+}
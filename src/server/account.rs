@@ -0,0 +1,93 @@
+//! A configured mail account: its IMAP connection, SMTP settings and the
+//! mailboxes tools may use in it
+
+use std::sync::Arc;
+use rmcp::ErrorData as McpError;
+use serde_json::Value as JsonValue;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::imap::{ImapConnection, ImapPool, ImapSettings};
+use crate::policy::MailboxPolicy;
+use crate::smtp::SmtpSettings;
+
+use super::{invalid_input, not_connected_error};
+
+pub(super) struct Account {
+    pub(super) name: String,
+    /// IMAP username
+    pub(super) user: String,
+    pub(super) smtp_settings: Option<SmtpSettings>,
+    pub(super) mailbox_policy: MailboxPolicy,
+    connection: Arc<Mutex<ImapConnection>>,
+    auto_connect: bool,
+}
+
+impl Account {
+    pub(super) fn new(
+        name: String,
+        settings: ImapSettings,
+        smtp_settings: Option<SmtpSettings>,
+        mailbox_policy: MailboxPolicy,
+        pool: Option<Arc<ImapPool>>,
+        auto_connect: bool,
+    ) -> Self {
+        let user = settings.user.clone();
+        let connection = match pool {
+            Some(pool) => ImapConnection::with_pool(settings, pool),
+            None => ImapConnection::new(settings),
+        };
+        Account {
+            name,
+            user,
+            smtp_settings,
+            mailbox_policy,
+            connection: Arc::new(Mutex::new(connection)),
+            auto_connect,
+        }
+    }
+
+    pub(super) async fn connect_now(&self) -> Result<(), crate::imap::ImapError> {
+        let mut conn = self.connection.lock().await;
+        conn.connect().await
+    }
+
+    pub(super) async fn is_connected(&self) -> bool {
+        self.connection.lock().await.is_connected().await
+    }
+
+    /// Lock the account's connection, auto-connecting first if configured
+    pub(super) async fn connect(&self) -> Result<MutexGuard<'_, ImapConnection>, McpError> {
+        let mut conn = self.connection.lock().await;
+        if conn.is_connected().await {
+            return Ok(conn);
+        }
+
+        // Not connected - try auto-connect if enabled
+        if self.auto_connect {
+            conn.connect().await
+                .map_err(|e| {
+                    log::error!("Auto-connect of account '{}' failed: {}", self.name, e);
+                    McpError::internal_error(format!("Auto-connect failed: {}", e), None)
+                })?;
+            Ok(conn)
+        } else {
+            Err(not_connected_error())
+        }
+    }
+
+    /// Reject mailboxes outside the account's allow-list
+    pub(super) fn check_mailbox(&self, field: &str, mailbox: &str) -> Result<(), McpError> {
+        if self.mailbox_policy.permits(mailbox) {
+            return Ok(());
+        }
+        let allowed = self.mailbox_policy.allow.as_deref().unwrap_or_default().join(", ");
+        Err(invalid_input(
+            field,
+            "not_allowed",
+            &format!("mailbox is not accessible in account '{}'", self.name),
+            Some(&allowed),
+            Some("Use list_mailboxes to see the accessible mailboxes."),
+            Some(JsonValue::String(mailbox.to_string())),
+        ))
+    }
+}
@@ -1,21 +1,45 @@
 use std::sync::Arc;
-use chrono::{DateTime, Utc};
+use std::time::Instant;
+use chrono::{DateTime, FixedOffset, Utc};
+use mail_parser::MessageParser;
 use rmcp::{
     ErrorData as McpError,
+    Peer,
+    RoleServer,
     ServerHandler,
     handler::server::{
         router::tool::ToolRouter,
+        tool::ToolCallContext,
         wrapper::Parameters,
     },
     model::*,
     schemars::{self, JsonSchema},
-    tool, tool_handler, tool_router,
+    service::{ElicitationError, RequestContext},
+    tool, tool_router,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{Map as JsonMap, Value as JsonValue};
-use tokio::sync::Mutex;
-
-use crate::imap::{EmailMetadata, ImapConnection, ImapSettings, MoveEmailStatus};
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::access::{Caller, Scope};
+use crate::approval::{Approval, ApprovalError, ApprovalQueue, ApprovalStatus};
+use crate::archive::{self, ArchiveFormat, MboxFormat, MboxReader};
+use crate::audit::{AuditEntry, AuditLog, Outcome};
+use crate::calendar::{self, ReplyStatus};
+use crate::config::ServerConfig;
+use crate::imap::mime;
+use crate::journal::{Change, Journal, MovedEmail, Operation};
+use crate::limits::{LOCAL_CALLER, Limits, Throttled};
+use crate::imap::{AttachmentData, AttachmentInfo, AttachmentSelector, EmailMetadata, Flag, ImapConnection, ImapPool, ImapSettings, ImportProgress, MailboxFailure, MoveEmailStatus, RawEmail, SpecialUse};
+use crate::policy::{self, MailboxPolicy, ToolPolicy};
+use crate::safety::{ContentGuard, ContentSafety};
+use crate::sandbox;
+use crate::signature;
+use crate::smtp::{CalendarPart, OutgoingMessage, SendReport, SmtpClient};
+
+mod account;
+
+use self::account::Account;
 
 fn not_connected_error() -> McpError {
     let message = "Not connected to IMAP server. Use connect() first.";
@@ -106,14 +130,399 @@ fn validate_limit(limit: usize) -> Result<(), McpError> {
     Ok(())
 }
 
+fn parse_flag(field: &str, value: &str) -> Result<Flag, McpError> {
+    value.trim().parse::<Flag>().map_err(|e| {
+        invalid_input(
+            field,
+            "invalid_format",
+            &e.to_string(),
+            Some("IMAP system flag (e.g., \\Seen) or keyword atom (e.g., $label1)"),
+            Some("Use list_tags to see the flags this mailbox accepts."),
+            Some(JsonValue::String(value.to_string())),
+        )
+    })
+}
+
+fn parse_uids(field: &str, values: &[String]) -> Result<Vec<u32>, McpError> {
+    let mut uids = Vec::with_capacity(values.len());
+    for (index, value) in values.iter().enumerate() {
+        let uid = value.trim().parse::<u32>().ok().filter(|uid| *uid > 0).ok_or_else(|| {
+            invalid_input(
+                &format!("{}[{}]", field, index),
+                "invalid_format",
+                "must be a numeric email ID",
+                Some("positive integer UID as returned by search tools"),
+                Some("Use get_inbox_items or search_emails to find email IDs."),
+                Some(JsonValue::String(value.clone())),
+            )
+        })?;
+        if !uids.contains(&uid) {
+            uids.push(uid);
+        }
+    }
+    Ok(uids)
+}
+
+fn validate_max_count(field: &str, count: usize, max_count: usize) -> Result<(), McpError> {
+    if count > max_count {
+        return Err(invalid_input(
+            field,
+            "too_many",
+            &format!("would affect {} emails, more than the per-call maximum of {}", count, max_count),
+            Some(&format!("at most {} emails", max_count)),
+            Some("Split the operation into smaller batches, or use dry_run to review first."),
+            Some(JsonValue::Number(count.into())),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_addresses(field: &str, values: &[String]) -> Result<(), McpError> {
+    for (index, value) in values.iter().enumerate() {
+        let trimmed = value.trim();
+        if trimmed.is_empty() || !trimmed.contains('@') || trimmed.contains(['\r', '\n']) {
+            return Err(invalid_input(
+                &format!("{}[{}]", field, index),
+                "invalid_format",
+                "must be an email address",
+                Some("address like alice@example.com or \"Alice <alice@example.com>\""),
+                None,
+                Some(JsonValue::String(value.clone())),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_single_line(field: &str, value: &str) -> Result<(), McpError> {
+    if value.contains(['\r', '\n']) {
+        return Err(invalid_input(
+            field,
+            "invalid_format",
+            "cannot contain line breaks",
+            Some("single-line string"),
+            None,
+            None,
+        ));
+    }
+    Ok(())
+}
+
+fn parse_iso_date(field: &str, value: &str) -> Result<DateTime<FixedOffset>, McpError> {
+    DateTime::parse_from_rfc3339(value.trim()).map_err(|e| {
+        invalid_input(
+            field,
+            "invalid_format",
+            &format!("invalid date: {}", e),
+            Some("ISO 8601 timestamp"),
+            Some("Example: 2025-01-31T10:15:00Z"),
+            Some(JsonValue::String(value.to_string())),
+        )
+    })
+}
+
+fn parse_flags(field: &str, values: &[String]) -> Result<Vec<Flag>, McpError> {
+    values.iter()
+        .enumerate()
+        .map(|(index, value)| parse_flag(&format!("{}[{}]", field, index), value))
+        .collect()
+}
+
+/// The address part of `Name <address>`, or the input itself
+fn bare_address(value: &str) -> &str {
+    let value = value.trim();
+    match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    }
+}
+
+fn sandbox_error(field: &str, value: &str, error: sandbox::SandboxError) -> McpError {
+    let (reason, hint) = match &error {
+        sandbox::SandboxError::NotConfigured(_) => ("not_configured", "Ask the operator to configure the directory."),
+        sandbox::SandboxError::Io(..) => ("not_found", "Check that the path exists."),
+        _ => ("outside_sandbox", "Use a path relative to the configured directory."),
+    };
+    invalid_input(
+        field,
+        reason,
+        &error.to_string(),
+        Some("relative path inside the configured directory"),
+        Some(hint),
+        Some(JsonValue::String(value.to_string())),
+    )
+}
+
+fn already_exists(field: &str, path: &str) -> McpError {
+    invalid_input(
+        field,
+        "already_exists",
+        &format!("'{}' already exists", path),
+        None,
+        Some("Choose another path, or set overwrite to true to replace it."),
+        Some(JsonValue::String(path.to_string())),
+    )
+}
+
+fn write_io_error(path: &std::path::Path, error: std::io::Error) -> McpError {
+    log::error!("Failed to write {}: {}", path.display(), error);
+    McpError::internal_error(format!("Failed to write file: {}", error), None)
+}
+
+/// Output layouts supported by export_emails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Eml,
+    Mbox,
+    Maildir,
+}
+
+impl ExportFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Eml => "eml",
+            ExportFormat::Mbox => "mbox",
+            ExportFormat::Maildir => "maildir",
+        }
+    }
+}
+
+/// Save an attachment inside `root` at `requested`, or under its sanitized
+/// name when that names a directory, keeping the directory within `quota` bytes
+fn write_attachment(
+    root: &std::path::Path,
+    quota: u64,
+    name: &str,
+    data: &[u8],
+    requested: &str,
+    overwrite: bool,
+) -> Result<String, McpError> {
+    // A directory (or no path at all) gets a file named after the attachment
+    let mut relative = std::path::PathBuf::from(requested);
+    let names_directory = requested.is_empty()
+        || requested.ends_with('/')
+        || sandbox::resolve_existing(root, requested).is_ok_and(|path| path.is_dir());
+    if names_directory {
+        relative.push(sandbox::sanitize_filename(name));
+    } else if let Some(file_name) = relative.file_name() {
+        let sanitized = sandbox::sanitize_filename(&file_name.to_string_lossy());
+        relative.set_file_name(sanitized);
+    }
+    let relative = relative.to_string_lossy().into_owned();
+
+    let destination = sandbox::resolve_for_write(root, &relative)
+        .map_err(|e| sandbox_error("save_path", &relative, e))?;
+    let existing_size = match destination.symlink_metadata() {
+        Ok(_) if !overwrite => return Err(already_exists("save_path", &relative)),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    let used = sandbox::disk_usage(root).map_err(|e| write_io_error(root, e))?;
+    let needed = used.saturating_sub(existing_size) + data.len() as u64;
+    if needed > quota {
+        return Err(invalid_input(
+            "save_path",
+            "quota_exceeded",
+            &format!(
+                "saving {} bytes would bring the attachment directory to {} bytes, over its quota of {}",
+                data.len(),
+                needed,
+                quota
+            ),
+            None,
+            Some("Remove old files from the attachment directory, or omit save to receive base64 instead."),
+            None,
+        ));
+    }
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| write_io_error(parent, e))?;
+    }
+    // create_new makes the no-overwrite check atomic with the write
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    let mut file = options.open(&destination).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => already_exists("save_path", &relative),
+        _ => write_io_error(&destination, e),
+    })?;
+    std::io::Write::write_all(&mut file, data).map_err(|e| write_io_error(&destination, e))?;
+
+    Ok(relative)
+}
+
+/// Write fetched emails below `target` in `format`, returning the files written.
+/// A conflicting eml or mbox destination fails before anything is written.
+fn write_export(
+    format: ExportFormat,
+    root: &std::path::Path,
+    target: &std::path::Path,
+    path: &str,
+    overwrite: bool,
+    emails: &[RawEmail],
+) -> Result<Vec<ExportedFile>, McpError> {
+    let relative = std::path::Path::new(path.trim_end_matches('/'));
+    let mut files = Vec::new();
+    match format {
+        ExportFormat::Eml => {
+            // Check every destination before writing anything so a conflict leaves no partial export
+            let mut destinations = Vec::with_capacity(emails.len());
+            for email in emails {
+                let name = relative.join(format!("{}.eml", email.uid)).to_string_lossy().into_owned();
+                let destination = sandbox::resolve_for_write(root, &name)
+                    .map_err(|e| sandbox_error("path", &name, e))?;
+                if !overwrite && destination.symlink_metadata().is_ok() {
+                    return Err(already_exists("path", &name));
+                }
+                destinations.push((name, destination));
+            }
+            if !emails.is_empty() {
+                std::fs::create_dir_all(target).map_err(|e| write_io_error(target, e))?;
+            }
+            for (email, (name, destination)) in emails.iter().zip(destinations) {
+                std::fs::write(&destination, &email.content).map_err(|e| write_io_error(&destination, e))?;
+                files.push(ExportedFile { path: name, bytes: email.content.len(), email_ids: vec![email.uid.to_string()] });
+            }
+        }
+        ExportFormat::Mbox => {
+            if target.is_dir() {
+                return Err(invalid_input(
+                    "path",
+                    "invalid_value",
+                    "mbox export needs a file path, not a directory",
+                    Some("file path such as 'exports/inbox.mbox'"),
+                    None,
+                    Some(JsonValue::String(path.to_string())),
+                ));
+            }
+            if !overwrite && target.symlink_metadata().is_ok() {
+                return Err(already_exists("path", path));
+            }
+            let mut mbox = Vec::new();
+            for email in emails {
+                mbox.extend_from_slice(&archive::mbox_entry(email.internal_date, &email.content));
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| write_io_error(parent, e))?;
+            }
+            std::fs::write(target, &mbox).map_err(|e| write_io_error(target, e))?;
+            files.push(ExportedFile {
+                path: relative.to_string_lossy().into_owned(),
+                bytes: mbox.len(),
+                email_ids: emails.iter().map(|email| email.uid.to_string()).collect(),
+            });
+        }
+        ExportFormat::Maildir => {
+            let mut written: Vec<String> = Vec::new();
+            for email in emails {
+                let unique = archive::maildir_unique(&email.content);
+                // Copies of one message share its Message-ID and so its file
+                if let Some(index) = written.iter().position(|name| *name == unique) {
+                    files[index].email_ids.push(email.uid.to_string());
+                    continue;
+                }
+                let destination = archive::write_maildir_message(
+                    target,
+                    &unique,
+                    &email.flags,
+                    email.internal_date,
+                    &email.content,
+                    overwrite,
+                )
+                .map_err(|e| match e.kind() {
+                    std::io::ErrorKind::AlreadyExists => already_exists("path", path),
+                    _ => write_io_error(target, e),
+                })?;
+                let name = destination.strip_prefix(target)
+                    .map(|inner| relative.join(inner))
+                    .unwrap_or(destination.clone());
+                let bytes = std::fs::metadata(&destination).map(|m| m.len() as usize).unwrap_or_default();
+                files.push(ExportedFile {
+                    path: name.to_string_lossy().into_owned(),
+                    bytes,
+                    email_ids: vec![email.uid.to_string()],
+                });
+                written.push(unique);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Convert an attachment to text off the async runtime, mapping failures to tool errors
+#[cfg(feature = "extract")]
+async fn extract_attachment_text(attachment: &AttachmentData) -> Result<AttachmentText, McpError> {
+    use crate::extract::{self, ExtractError};
+
+    let (content_type, name, data) = (attachment.content_type.clone(), attachment.name.clone(), attachment.data.clone());
+    let extracted = tokio::task::spawn_blocking(move || extract::extract_text(&content_type, Some(&name), &data))
+        .await
+        .map_err(|e| McpError::internal_error(format!("Text extraction task failed: {}", e), None))?;
+
+    match extracted {
+        Ok(extracted) => Ok(AttachmentText {
+            format: extracted.format.to_string(),
+            text: extracted.text,
+            truncated: extracted.truncated,
+        }),
+        Err(ExtractError::Unsupported(content_type)) => Err(invalid_input(
+            "extract_text",
+            "unsupported_type",
+            &format!("cannot extract text from {} attachments", content_type),
+            Some("PDF, DOCX, XLSX, ODT, ODS, CSV, plain text, calendar or zip"),
+            Some("Omit extract_text to receive the attachment as base64."),
+            Some(JsonValue::String(content_type)),
+        )),
+        Err(e @ ExtractError::TooLarge { .. }) => Err(invalid_input(
+            "extract_text",
+            "too_large",
+            &e.to_string(),
+            None,
+            Some("Omit extract_text and save the attachment instead."),
+            None,
+        )),
+        Err(e) => {
+            log::error!("{}", e);
+            Err(McpError::internal_error(e.to_string(), None))
+        }
+    }
+}
+
+#[cfg(not(feature = "extract"))]
+async fn extract_attachment_text(_attachment: &AttachmentData) -> Result<AttachmentText, McpError> {
+    Err(invalid_input(
+        "extract_text",
+        "not_available",
+        "this server was built without attachment text extraction",
+        None,
+        Some("Rebuild with --features extract, or omit extract_text to receive base64."),
+        None,
+    ))
+}
+
 /// IMAP Mailbox MCP Server
 #[derive(Clone)]
 pub struct ImapMailboxServer {
-    connection: Arc<Mutex<ImapConnection>>,
-    #[allow(dead_code)] // Reserved for potential future use (connection pooling, reconnection)
-    settings: Arc<Mutex<ImapSettings>>,
+    /// Configured accounts; the first one is the primary account
+    accounts: Arc<Vec<Account>>,
+    server_config: Arc<ServerConfig>,
     auto_connect: bool,
     tool_router: ToolRouter<ImapMailboxServer>,
+    /// Who made the current tool call, when the transport authenticates callers
+    caller: Option<Arc<Caller>>,
+    /// Rate and concurrency limits, shared with the servers of other sessions
+    limits: Arc<Limits>,
+    /// Record of mutating and sending calls, shared with other sessions
+    audit: Arc<AuditLog>,
+    /// Reversible changes for undo_operation, shared with other sessions
+    journal: Arc<Journal>,
+    /// Calls held for human approval, shared with other sessions
+    approvals: Arc<ApprovalQueue>,
 }
 
 impl Default for ImapMailboxServer {
@@ -122,6 +531,14 @@ impl Default for ImapMailboxServer {
     }
 }
 
+/// Request to list mailboxes
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListMailboxesRequest {
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
 /// Request to connect to an IMAP server
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct ConnectRequest {
@@ -164,6 +581,10 @@ pub struct GetInboxItemsRequest {
     #[schemars(description = "Maximum number of emails to return")]
     #[serde(default = "default_limit")]
     pub limit: usize,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to search emails by keyword
@@ -173,6 +594,14 @@ pub struct SearchEmailsRequest {
     #[serde(default = "default_inbox")]
     pub mailbox: String,
 
+    #[schemars(description = "Search several mailboxes instead of 'mailbox': a list of names, or [\"*\"] for every mailbox. Results carry the mailbox each email ID belongs to.")]
+    #[serde(default)]
+    pub mailboxes: Option<Vec<String>>,
+
+    #[schemars(description = "Include Trash and Spam when searching all mailboxes with [\"*\"]. Defaults to false.")]
+    #[serde(default)]
+    pub include_trash: bool,
+
     #[schemars(description = "Search keywords")]
     pub query: String,
 
@@ -187,6 +616,10 @@ pub struct SearchEmailsRequest {
     #[schemars(description = "Maximum number of emails to return")]
     #[serde(default = "default_limit")]
     pub limit: usize,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 fn default_inbox() -> String {
@@ -197,6 +630,33 @@ fn default_limit() -> usize {
     30
 }
 
+/// Request to check a call held for approval
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetApprovalRequest {
+    #[schemars(description = "approval_id returned when the call was held for approval")]
+    pub approval_id: String,
+}
+
+/// Request to review the audit log
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetAuditLogRequest {
+    #[schemars(description = "Maximum number of entries to return, newest first")]
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+
+    #[schemars(description = "Only entries for this tool, e.g. 'move_emails'")]
+    #[serde(default)]
+    pub tool: Option<String>,
+
+    #[schemars(description = "Only entries made by this caller (token name)")]
+    #[serde(default)]
+    pub caller: Option<String>,
+
+    #[schemars(description = "Only entries for this account")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
 /// Request to fetch email content
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct GetEmailRequest {
@@ -206,6 +666,10 @@ pub struct GetEmailRequest {
     #[schemars(description = "Mailbox containing the email")]
     #[serde(default = "default_inbox")]
     pub mailbox: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to send a reply
@@ -224,6 +688,10 @@ pub struct ListTagsRequest {
     #[schemars(description = "Mailbox to get available tags from")]
     #[serde(default = "default_inbox")]
     pub mailbox: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to get tags on an email
@@ -235,6 +703,10 @@ pub struct GetEmailTagsRequest {
     #[schemars(description = "Mailbox containing the email")]
     #[serde(default = "default_inbox")]
     pub mailbox: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to apply or remove a tag
@@ -247,8 +719,12 @@ pub struct ModifyTagRequest {
     #[serde(default = "default_inbox")]
     pub mailbox: String,
 
-    #[schemars(description = "Tag to apply or remove (e.g., \\\\Seen, \\\\Flagged, \\\\Answered)")]
+    #[schemars(description = "Tag to apply or remove: a system flag (e.g., \\\\Seen, \\\\Flagged, \\\\Answered) or a keyword exactly as returned by list_tags (e.g., $label1)")]
     pub tag: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to move an email
@@ -263,6 +739,10 @@ pub struct MoveEmailRequest {
 
     #[schemars(description = "Destination mailbox/folder to move to (e.g., 'Archive', 'Folders/Work')")]
     pub to_mailbox: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
 /// Request to move multiple emails
@@ -277,6 +757,17 @@ pub struct MoveEmailsRequest {
 
     #[schemars(description = "Destination mailbox/folder to move to (e.g., 'Archive', 'Folders/Work')")]
     pub to_mailbox: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to undo an earlier change
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UndoOperationRequest {
+    #[schemars(description = "operation_id returned by move_email, move_emails, trash_emails, apply_tag or remove_tag")]
+    pub operation_id: String,
 }
 
 /// Request to get an attachment
@@ -289,501 +780,2695 @@ pub struct GetAttachmentRequest {
     #[serde(default = "default_inbox")]
     pub mailbox: String,
 
-    #[schemars(description = "Name of the attachment to retrieve")]
-    pub attachment_name: String,
+    #[schemars(description = "MIME part id of the attachment (e.g., '2' or '1.3'), as returned by get_email or list_attachments. Provide either part_id or attachment_name.")]
+    #[serde(default)]
+    pub part_id: Option<String>,
+
+    #[schemars(description = "Filename of the attachment to retrieve. Provide either part_id or attachment_name; use part_id when several attachments share a name.")]
+    #[serde(default)]
+    pub attachment_name: Option<String>,
+
+    #[schemars(description = "Save the attachment to the configured attachment directory instead of returning base64. Implied when save_path is given.")]
+    #[serde(default)]
+    pub save: bool,
 
-    #[schemars(description = "Path to save the attachment (optional, returns base64 if not provided)")]
+    #[schemars(description = "File or directory to save to, relative to the configured attachment directory. Defaults to the sanitized attachment name.")]
+    #[serde(default)]
     pub save_path: Option<String>,
-}
 
-#[derive(Serialize)]
-struct ListMailboxesResponse {
-    mailboxes: Vec<String>,
-}
+    #[schemars(description = "Replace an existing file at the destination. Defaults to false.")]
+    #[serde(default)]
+    pub overwrite: bool,
 
-#[derive(Serialize)]
-struct GetInboxItemsResponse {
-    count: usize,
-    emails: Vec<EmailMetadata>,
-}
+    #[schemars(description = "Return the attachment as plain text instead of base64 (PDF, DOCX, XLSX, ODT, ODS, CSV, text, calendar and zip). Requires a server built with the 'extract' feature.")]
+    #[serde(default)]
+    pub extract_text: bool,
 
-#[derive(Serialize)]
-struct SearchEmailsResponse {
-    count: usize,
-    emails: Vec<EmailMetadata>,
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
-#[derive(Serialize)]
-struct CurrentDateResponse {
-    timestamp: String,
-    iso8601: String,
-}
+/// Request to move emails to the trash
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TrashEmailsRequest {
+    #[schemars(description = "Email IDs to move to the trash")]
+    pub email_ids: Vec<String>,
 
-#[derive(Serialize)]
-struct ListTagsResponse {
-    mailbox: String,
-    tags: Vec<String>,
-}
+    #[schemars(description = "Mailbox containing the emails")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
 
-#[derive(Serialize)]
-struct EmailTagsResponse {
-    email_id: String,
-    tags: Vec<String>,
-}
+    #[schemars(description = "List the emails that would be trashed without changing anything (default: false)")]
+    #[serde(default)]
+    pub dry_run: bool,
 
-#[derive(Serialize)]
-struct TagOperationResponse {
-    success: bool,
-    email_id: String,
-    tag: String,
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
-#[derive(Serialize)]
-struct MoveEmailResponse {
-    email_id: String,
-    from_mailbox: String,
-    to_mailbox: String,
-    message: String,
-}
+/// Request to permanently delete emails
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DeleteEmailsRequest {
+    #[schemars(description = "Email IDs to permanently delete")]
+    pub email_ids: Vec<String>,
 
-#[derive(Serialize)]
-struct MoveEmailsResponse {
-    moved: usize,
-    failed: usize,
-    from_mailbox: String,
-    to_mailbox: String,
-    message: String,
-    results: Vec<MoveEmailStatus>,
-}
+    #[schemars(description = "Mailbox containing the emails")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
 
-#[derive(Serialize)]
-struct AttachmentResponse {
-    name: String,
-    content_type: String,
-    size: usize,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    saved_path: Option<String>,
+    #[schemars(description = "List the emails that would be deleted without changing anything. Defaults to true; set to false to actually delete.")]
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
 }
 
-#[tool_router]
-impl ImapMailboxServer {
-    pub fn new() -> Self {
-        let settings = ImapSettings::default();
-        Self {
-            connection: Arc::new(Mutex::new(ImapConnection::new(settings.clone()))),
-            settings: Arc::new(Mutex::new(settings)),
-            auto_connect: false,
-            tool_router: Self::tool_router(),
-        }
-    }
+/// Request to permanently delete every email in a mailbox
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EmptyFolderRequest {
+    #[schemars(description = "Mailbox to empty (e.g., 'Trash', 'Spam')")]
+    pub mailbox: String,
 
-    pub fn with_config(config: crate::config::Config) -> Self {
-        let settings = config.to_imap_settings();
-        Self {
-            connection: Arc::new(Mutex::new(ImapConnection::new(settings.clone()))),
-            settings: Arc::new(Mutex::new(settings)),
-            auto_connect: true,
-            tool_router: Self::tool_router(),
-        }
-    }
+    #[schemars(description = "List the emails that would be deleted without changing anything. Defaults to true; set to false to actually empty the folder.")]
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
 
-    pub fn is_auto_connect(&self) -> bool {
-        self.auto_connect
-    }
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
 
-    pub async fn auto_connect(&self) -> Result<(), crate::imap::ImapError> {
-        let mut conn = self.connection.lock().await;
-        conn.connect().await
-    }
+/// Request to import a single RFC 822 message
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ImportMessageRequest {
+    #[schemars(description = "Mailbox to import into")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
 
-    /// Ensures connection is established, auto-connecting if configured
-    async fn ensure_connected(&self) -> Result<(), McpError> {
-        {
-            let conn = self.connection.lock().await;
-            if conn.is_connected().await {
-                return Ok(());
-            }
-        }
+    #[schemars(description = "Path of an .eml file, relative to the configured import directory. Provide either path or content_base64.")]
+    #[serde(default)]
+    pub path: Option<String>,
 
-        // Not connected - try auto-connect if enabled
-        if self.auto_connect {
-            let mut conn = self.connection.lock().await;
-            conn.connect().await
-                .map_err(|e| {
-                    log::error!("Auto-connect failed: {}", e);
-                    McpError::internal_error(format!("Auto-connect failed: {}", e), None)
-                })?;
-            Ok(())
-        } else {
-            Err(not_connected_error())
-        }
-    }
+    #[schemars(description = "Base64-encoded RFC 822 message. Provide either path or content_base64.")]
+    #[serde(default)]
+    pub content_base64: Option<String>,
 
-    #[tool(description = "List available mailboxes", annotations(read_only_hint = true))]
-    async fn list_mailboxes(&self) -> Result<CallToolResult, McpError> {
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+    #[schemars(description = "Initial flags, e.g. [\"\\\\Seen\"]")]
+    #[serde(default)]
+    pub flags: Vec<String>,
 
-        let mailboxes = connection.list_mailboxes().await
-            .map_err(|e| {
-                log::error!("Failed to list mailboxes: {}", e);
-                McpError::internal_error(e.to_string(), None)
-            })?;
+    #[schemars(description = "Internal (received) date to record, ISO 8601 (e.g., 2025-01-31T10:15:00Z). Defaults to now.")]
+    #[serde(default)]
+    pub internal_date: Option<String>,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to bulk import an mbox file or Maildir directory
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ImportArchiveRequest {
+    #[schemars(description = "Mailbox to import into")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
+
+    #[schemars(description = "Path of an mbox file or Maildir directory, relative to the configured import directory")]
+    pub path: String,
+
+    #[schemars(description = "Archive format: 'mbox' (mboxrd), 'mboxo' or 'maildir'. Detected from the path if omitted.")]
+    #[serde(default)]
+    pub format: Option<String>,
+
+    #[schemars(description = "Flags to add to every imported message, in addition to those stored in the archive")]
+    #[serde(default)]
+    pub flags: Vec<String>,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to export emails to files
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExportEmailsRequest {
+    #[schemars(description = "Mailbox to export from")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
+
+    #[schemars(description = "Email IDs to export. If omitted, the most recent emails matching query/since_date are exported.")]
+    #[serde(default)]
+    pub email_ids: Vec<String>,
+
+    #[schemars(description = "Search keywords selecting the emails to export (ignored when email_ids is given)")]
+    #[serde(default)]
+    pub query: Option<String>,
+
+    #[schemars(description = "Fields to search in: 'text' (anywhere), 'subject', 'from', 'to', 'body'. Defaults to 'text' if not specified.")]
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+
+    #[schemars(description = "Only export emails after this date (ISO 8601 format, e.g., 2025-01-31T10:15:00Z)")]
+    #[serde(default)]
+    pub since_date: Option<String>,
+
+    #[schemars(description = "Maximum number of emails to export when selecting by search")]
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+
+    #[schemars(description = "Output format: 'eml' (one file per email in a directory), 'mbox' (single file) or 'maildir' (directory tree). Defaults to 'eml'.")]
+    #[serde(default = "default_export_format")]
+    pub format: String,
+
+    #[schemars(description = "Output directory (eml, maildir) or file (mbox), relative to the configured export directory")]
+    pub path: String,
+
+    #[schemars(description = "Replace files that already exist. Defaults to false.")]
+    #[serde(default)]
+    pub overwrite: bool,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+fn default_export_format() -> String {
+    "eml".to_string()
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+/// Content of a draft message
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DraftContent {
+    #[schemars(description = "Recipient addresses")]
+    #[serde(default)]
+    pub to: Vec<String>,
+
+    #[schemars(description = "Cc addresses")]
+    #[serde(default)]
+    pub cc: Vec<String>,
+
+    #[schemars(description = "Bcc addresses (removed from the message when it is sent)")]
+    #[serde(default)]
+    pub bcc: Vec<String>,
+
+    #[schemars(description = "Subject line")]
+    #[serde(default)]
+    pub subject: String,
+
+    #[schemars(description = "Plain text body")]
+    #[serde(default)]
+    pub body: String,
+
+    #[schemars(description = "Optional HTML body, sent alongside the plain text body")]
+    #[serde(default)]
+    pub html_body: Option<String>,
+
+    #[schemars(description = "Message-ID of the email being replied to, for threading")]
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+}
+
+/// Request to save a new draft
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SaveDraftRequest {
+    #[serde(flatten)]
+    pub draft: DraftContent,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to list drafts
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListDraftsRequest {
+    #[schemars(description = "Maximum number of drafts to return")]
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to replace an existing draft
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateDraftRequest {
+    #[schemars(description = "Email ID of the draft to replace (from list_drafts)")]
+    pub email_id: String,
+
+    #[serde(flatten)]
+    pub draft: DraftContent,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to send a saved draft
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SendDraftRequest {
+    #[schemars(description = "Email ID of the draft to send (from list_drafts)")]
+    pub email_id: String,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Request to answer a meeting invitation
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RespondToInviteRequest {
+    #[schemars(description = "Email ID of the invitation")]
+    pub email_id: String,
+
+    #[schemars(description = "Mailbox containing the invitation")]
+    #[serde(default = "default_inbox")]
+    pub mailbox: String,
+
+    #[schemars(description = "Response: accepted, declined or tentative")]
+    pub response: String,
+
+    #[schemars(description = "Optional note for the organizer")]
+    #[serde(default)]
+    pub comment: Option<String>,
+
+    #[schemars(description = "UID of the event to answer when the invitation contains several (from get_email)")]
+    #[serde(default)]
+    pub event_uid: Option<String>,
+
+    #[schemars(description = "Attendee address to respond as. Defaults to the SMTP sender address.")]
+    #[serde(default)]
+    pub attendee: Option<String>,
+
+    #[schemars(description = "Account to use (from list_accounts). Defaults to the primary account.")]
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AccountSummary {
+    name: String,
+    primary: bool,
+    /// IMAP username
+    user: String,
+    /// Sender address for mail sent from this account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    connected: bool,
+    /// Mailboxes tools may use, when the account is restricted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mailboxes: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct ListAccountsResponse {
+    count: usize,
+    accounts: Vec<AccountSummary>,
+}
+
+#[derive(Serialize)]
+struct ListMailboxesResponse {
+    mailboxes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GetInboxItemsResponse {
+    count: usize,
+    emails: Vec<EmailMetadata>,
+    /// How to read the envelopes around senders and subjects, in quarantine mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    safety_notice: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SearchEmailsResponse {
+    count: usize,
+    emails: Vec<EmailMetadata>,
+    /// Mailboxes searched, for cross-mailbox searches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mailboxes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<MailboxFailure>,
+    /// How to read the envelopes around senders and subjects, in quarantine mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    safety_notice: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AuditLogResponse {
+    count: usize,
+    entries: Vec<AuditEntry>,
+}
+
+#[derive(Serialize)]
+struct CurrentDateResponse {
+    timestamp: String,
+    iso8601: String,
+}
+
+#[derive(Serialize)]
+struct ListTagsResponse {
+    mailbox: String,
+    tags: Vec<Flag>,
+    may_create_keywords: bool,
+}
+
+#[derive(Serialize)]
+struct EmailTagsResponse {
+    email_id: String,
+    tags: Vec<Flag>,
+}
+
+#[derive(Serialize)]
+struct TagOperationResponse {
+    success: bool,
+    email_id: String,
+    tag: Flag,
+    operation_id: String,
+}
+
+#[derive(Serialize)]
+struct MoveEmailResponse {
+    email_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_email_id: Option<String>,
+    from_mailbox: String,
+    to_mailbox: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MoveEmailsResponse {
+    moved: usize,
+    failed: usize,
+    from_mailbox: String,
+    to_mailbox: String,
+    message: String,
+    results: Vec<MoveEmailStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ApprovalResponse {
+    approval_id: String,
+    tool: String,
+    account: String,
+    status: ApprovalStatus,
+    expires_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decided_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// What the tool returned once it ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    message: String,
+}
+
+impl From<Approval> for ApprovalResponse {
+    fn from(approval: Approval) -> Self {
+        let message = match approval.status {
+            ApprovalStatus::Pending => format!(
+                "{} is waiting for a human to approve it. It runs once approved; check back with get_approval for its result.",
+                approval.tool,
+            ),
+            ApprovalStatus::Approved => format!("{} was approved and is about to run. Check back with get_approval for its result.", approval.tool),
+            ApprovalStatus::Rejected => format!("{} was rejected and did not run.", approval.tool),
+            ApprovalStatus::Expired => format!("Nobody approved {} in time; it did not run.", approval.tool),
+            ApprovalStatus::Executing => format!("{} was approved and is running.", approval.tool),
+            ApprovalStatus::Completed if approval.error.is_some() => format!("{} was approved but failed.", approval.tool),
+            ApprovalStatus::Completed => format!("{} was approved and ran.", approval.tool),
+        };
+        ApprovalResponse {
+            approval_id: approval.id,
+            tool: approval.tool,
+            account: approval.account,
+            status: approval.status,
+            expires_at: approval.expires_at.to_rfc3339(),
+            decided_by: approval.decided_by,
+            note: approval.note,
+            result: approval.result,
+            error: approval.error,
+            message,
+        }
+    }
+}
+
+/// The user's answer when asked to approve a call through elicitation
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ApprovalDecision {
+    #[schemars(description = "Allow the call to run")]
+    approve: bool,
+}
+
+rmcp::elicit_safe!(ApprovalDecision);
+
+#[derive(Serialize)]
+struct UndoResponse {
+    operation_id: String,
+    tool: String,
+    account: String,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    results: Vec<MoveEmailStatus>,
+}
+
+#[derive(Serialize)]
+struct DestructivePreviewResponse {
+    dry_run: bool,
+    action: &'static str,
+    mailbox: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
+    /// Emails named by the call, or in the mailbox for empty_folder
+    requested: usize,
+    /// Emails that would be affected, of the first `max_count` requested
+    count: usize,
+    max_count: usize,
+    emails: Vec<EmailMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    not_found: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DestructiveOperationResponse {
+    action: &'static str,
+    mailbox: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
+    succeeded: usize,
+    failed: usize,
+    message: String,
+    results: Vec<MoveEmailStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DraftResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_id: Option<String>,
+    mailbox: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ListDraftsResponse {
+    mailbox: String,
+    count: usize,
+    drafts: Vec<EmailMetadata>,
+}
+
+#[derive(Serialize)]
+struct SendDraftResponse {
+    email_id: String,
+    sent: SendReport,
+    draft_deleted: bool,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct RespondToInviteResponse {
+    email_id: String,
+    event_uid: String,
+    response: String,
+    organizer: String,
+    sent: SendReport,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ImportMessageResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email_id: Option<String>,
+    mailbox: String,
+    size: usize,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ExportedFile {
+    path: String,
+    bytes: usize,
+    email_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ExportEmailsResponse {
+    format: String,
+    mailbox: String,
+    count: usize,
+    total_bytes: usize,
+    files: Vec<ExportedFile>,
+}
+
+#[derive(Serialize)]
+struct RawEmailResponse {
+    email_id: String,
+    mailbox: String,
+    size: usize,
+    content_base64: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_safety: Option<ContentSafety>,
+}
+
+#[derive(Serialize)]
+struct ListAttachmentsResponse {
+    email_id: String,
+    count: usize,
+    attachments: Vec<AttachmentInfo>,
+}
+
+#[derive(Serialize)]
+struct AttachmentResponse {
+    part_id: String,
+    name: String,
+    content_type: String,
+    size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saved_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<AttachmentText>,
+    /// Assessment of the extracted text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_safety: Option<ContentSafety>,
+}
+
+#[derive(Serialize)]
+struct AttachmentText {
+    format: String,
+    text: String,
+    truncated: bool,
+}
+
+#[tool_router]
+impl ImapMailboxServer {
+    pub fn new() -> Self {
+        let account = Account::new("primary".to_string(), ImapSettings::default(), None, MailboxPolicy::default(), None, false);
+        Self {
+            accounts: Arc::new(vec![account]),
+            server_config: Arc::new(ServerConfig::default()),
+            auto_connect: false,
+            tool_router: Self::tool_router(),
+            caller: None,
+            limits: Limits::new(Default::default(), None, ServerConfig::default().account_max_concurrent),
+            audit: AuditLog::in_memory(),
+            journal: Journal::in_memory(ServerConfig::default().undo_journal.capacity),
+            approvals: ApprovalQueue::in_memory(ServerConfig::default().approvals.expire_after),
+        }
+    }
+
+    pub fn with_config(config: crate::config::Config) -> Self {
+        let pool = ImapPool::new(config.server.imap_max_connections, config.server.imap_idle_timeout);
+        Self::with_shared_pool(config, pool)
+    }
+
+    /// A server whose accounts borrow IMAP sessions from `pool`, so servers
+    /// built for different MCP sessions share logged-in connections
+    pub fn with_shared_pool(config: crate::config::Config, pool: Arc<ImapPool>) -> Self {
+        let accounts = config.accounts.into_iter()
+            .map(|account| Account::new(
+                account.name,
+                account.imap.to_imap_settings(),
+                Some(account.smtp.to_smtp_settings()),
+                account.mailbox_policy,
+                Some(pool.clone()),
+                true,
+            ))
+            .collect();
+        let tool_router = Self::permitted_tools(&config.server.tool_policy);
+        for rule in &config.server.approvals.rules {
+            if !tool_router.map.contains_key(rule.tool.as_str()) {
+                log::warn!("Approval rule names unknown or disabled tool '{}'", rule.tool);
+            }
+        }
+        Self {
+            accounts: Arc::new(accounts),
+            tool_router,
+            limits: Limits::new(config.server.rate_limits, config.server.http_rate_limit, config.server.account_max_concurrent),
+            journal: Journal::in_memory(config.server.undo_journal.capacity),
+            approvals: ApprovalQueue::in_memory(config.server.approvals.expire_after),
+            server_config: Arc::new(config.server),
+            auto_connect: true,
+            caller: None,
+            audit: AuditLog::in_memory(),
+        }
+    }
+
+    /// Share rate and concurrency limits with the servers of other sessions
+    pub fn with_limits(mut self, limits: Arc<Limits>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Record audited calls in `audit` instead of an in-memory log
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Journal reversible changes in `journal`, shared with other sessions
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Hold calls that need approval in `approvals`, shared with other
+    /// sessions and the HTTP approval endpoint
+    pub fn with_approvals(mut self, approvals: Arc<ApprovalQueue>) -> Self {
+        self.approvals = approvals;
+        self
+    }
+
+    /// Name of the caller, for the audit log and undo journal
+    fn caller_name(&self) -> &str {
+        self.caller.as_ref().map_or(LOCAL_CALLER, |caller| caller.name.as_str())
+    }
+
+    /// Journal the emails a move placed in `to_mailbox`, returning the
+    /// operation id when any of them can be moved back
+    fn journal_move(&self, account: &Account, tool: &str, from_mailbox: &str, to_mailbox: &str, results: &[MoveEmailStatus]) -> Option<String> {
+        let emails: Vec<MovedEmail> = results.iter()
+            .filter(|status| status.success)
+            .filter_map(|status| Some(MovedEmail {
+                email_id: status.email_id.clone(),
+                new_email_id: status.new_email_id.clone()?,
+                uid_validity: status.uid_validity,
+            }))
+            .collect();
+        if emails.is_empty() {
+            return None;
+        }
+        let change = Change::Move {
+            from_mailbox: from_mailbox.to_string(),
+            to_mailbox: to_mailbox.to_string(),
+            emails,
+        };
+        Some(self.journal.record(self.caller_name(), &account.name, tool, change))
+    }
+
+    /// Set or clear a flag, journaling whether the email carried it before
+    async fn change_flag(&self, account: &Account, tool: &str, req: &ModifyTagRequest, tag: &Flag, add: bool) -> Result<String, McpError> {
+        let connection = account.connect().await?;
+        let was_set = connection.get_email_tags(&req.mailbox, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to read flags of email {} in {}: {}", req.email_id, req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?
+            .contains(tag);
+
+        let changed = if add {
+            connection.apply_tag(&req.mailbox, &req.email_id, tag).await
+        } else {
+            connection.remove_tag(&req.mailbox, &req.email_id, tag).await
+        };
+        changed.map_err(|e| {
+            let action = if add { "apply tag" } else { "remove tag" };
+            log::error!("Failed to {} {} on email {} in {}: {}", action, req.tag, req.email_id, req.mailbox, e);
+            McpError::internal_error(e.to_string(), None)
+        })?;
+
+        let change = Change::Flag {
+            mailbox: req.mailbox.clone(),
+            email_id: req.email_id.clone(),
+            flag: tag.to_string(),
+            added: add,
+            was_set,
+        };
+        Ok(self.journal.record(self.caller_name(), &account.name, tool, change))
+    }
+
+    pub fn is_auto_connect(&self) -> bool {
+        self.auto_connect
+    }
+
+    /// Connect every account, returning the first failure after trying all of them
+    pub async fn auto_connect(&self) -> Result<(), crate::imap::ImapError> {
+        let mut result = Ok(());
+        for account in self.accounts.iter() {
+            if let Err(e) = account.connect_now().await {
+                log::warn!("Could not connect account '{}': {}", account.name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// The named account, or the primary one when no name is given
+    fn account(&self, name: Option<&str>) -> Result<&Account, McpError> {
+        let account = match name.map(str::trim).filter(|name| !name.is_empty()) {
+            None => &self.accounts[0],
+            Some(name) => self.accounts.iter()
+                .find(|account| account.name == name)
+                .ok_or_else(|| {
+                    invalid_input(
+                        "account",
+                        "unknown_account",
+                        "no account with this name is configured",
+                        Some(&self.visible_accounts().map(|account| account.name.as_str()).collect::<Vec<_>>().join(", ")),
+                        Some("Use list_accounts to see the configured accounts."),
+                        Some(JsonValue::String(name.to_string())),
+                    )
+                })?,
+        };
+        if let Some(caller) = &self.caller
+            && !caller.permits_account(&account.name)
+        {
+            return Err(invalid_input(
+                "account",
+                "not_allowed",
+                &format!("account is not accessible with token '{}'", caller.name),
+                Some(&self.visible_accounts().map(|account| account.name.as_str()).collect::<Vec<_>>().join(", ")),
+                Some("Use list_accounts to see the accessible accounts."),
+                Some(JsonValue::String(account.name.clone())),
+            ));
+        }
+        Ok(account)
+    }
+
+    /// Accounts the caller may use
+    fn visible_accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter()
+            .filter(|account| self.caller.as_ref().is_none_or(|caller| caller.permits_account(&account.name)))
+    }
+
+    /// Whether both the account and the caller allow `mailbox`
+    fn permits_mailbox(&self, account: &Account, mailbox: &str) -> bool {
+        account.mailbox_policy.permits(mailbox)
+            && self.caller.as_ref().is_none_or(|caller| caller.mailbox_policy.permits(mailbox))
+    }
+
+    /// Reject mailboxes outside the account's or the caller's allow-list
+    fn check_mailbox(&self, account: &Account, field: &str, mailbox: &str) -> Result<(), McpError> {
+        account.check_mailbox(field, mailbox)?;
+        let Some(caller) = &self.caller else { return Ok(()) };
+        if caller.mailbox_policy.permits(mailbox) {
+            return Ok(());
+        }
+        let allowed = caller.mailbox_policy.allow.as_deref().unwrap_or_default().join(", ");
+        Err(invalid_input(
+            field,
+            "not_allowed",
+            &format!("mailbox is not accessible with token '{}'", caller.name),
+            Some(&allowed),
+            Some("Use list_mailboxes to see the accessible mailboxes."),
+            Some(JsonValue::String(mailbox.to_string())),
+        ))
+    }
+
+    /// Reject calls to tools that need a scope the caller's token lacks
+    fn authorize(&self, caller: &Caller, tool: &str) -> Result<(), McpError> {
+        let Some(route) = self.tool_router.map.get(tool) else { return Ok(()) };
+        let scope = Scope::required_for(&route.attr);
+        if caller.has_scope(scope) {
+            return Ok(());
+        }
+        log::warn!("Token '{}' may not call {} (needs scope '{}')", caller.name, tool, scope);
+        Err(McpError::invalid_request(
+            format!("token '{}' lacks the '{}' scope that {} requires", caller.name, scope, tool),
+            Some(serde_json::json!({ "tool": tool, "required_scope": scope.as_str() })),
+        ))
+    }
+
+    /// Reserve a slot on the account the call works on, then apply the
+    /// caller's rate limit for the tool's category. The slot comes first so a
+    /// call turned away for a busy account does not use up the caller's rate;
+    /// it is held until the returned permit is dropped.
+    fn throttle(&self, caller: Option<&Caller>, request: &CallToolRequestParam) -> Result<Option<OwnedSemaphorePermit>, McpError> {
+        let name = caller.map_or(LOCAL_CALLER, |caller| caller.name.as_str());
+        let account = request.arguments.as_ref()
+            .and_then(|arguments| arguments.get("account"))
+            .and_then(JsonValue::as_str);
+        // Unknown accounts are reported by the tool itself
+        let permit = match self.account(account) {
+            Ok(account) if !ACCOUNTLESS_TOOLS.contains(&request.name.as_ref()) => Some(
+                self.limits.try_acquire_account(&account.name)
+                    .map_err(|throttled| rate_limited(name, &request.name, throttled))?,
+            ),
+            _ => None,
+        };
+        if let Some(route) = self.tool_router.map.get(request.name.as_ref()) {
+            let scope = Scope::required_for(&route.attr);
+            self.limits.check_tool(name, caller.and_then(|caller| caller.rate_limits.as_ref()), scope)
+                .map_err(|throttled| rate_limited(name, &request.name, throttled))?;
+        }
+        Ok(permit)
+    }
+
+    /// Whether a call changes mail, sends it or writes files: everything that
+    /// needs more than the read scope, exports, and attachment saves
+    fn is_audited(&self, request: &CallToolRequestParam) -> bool {
+        let Some(route) = self.tool_router.map.get(request.name.as_ref()) else { return false };
+        match request.name.as_ref() {
+            "get_attachment" => policy::saves_attachment(request.arguments.as_ref()),
+            name => Scope::required_for(&route.attr) != Scope::Read || policy::LOCAL_WRITE_TOOLS.contains(&name),
+        }
+    }
+
+    /// Complete an audit entry with the call's outcome and append it
+    fn record_audit(&self, mut entry: AuditEntry, result: &Result<CallToolResult, McpError>, started: Instant) {
+        entry.duration_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(result) => {
+                let texts = result.content.iter().filter_map(|content| content.as_text()).map(|text| text.text.as_str());
+                if result.is_error == Some(true) {
+                    entry.outcome = Outcome::Error;
+                    entry.error = Some(texts.collect::<Vec<_>>().join("\n"));
+                } else {
+                    for value in texts.filter_map(|text| serde_json::from_str::<JsonValue>(text).ok()) {
+                        entry.add_result(&value);
+                    }
+                }
+            }
+            Err(e) => {
+                entry.outcome = Outcome::Error;
+                entry.error = Some(e.message.to_string());
+            }
+        }
+        self.audit.record(entry);
+    }
+
+    #[tool(description = "List the configured email accounts. Pass an account's name as 'account' to other tools; the primary account is used by default.", annotations(read_only_hint = true))]
+    async fn list_accounts(&self) -> Result<CallToolResult, McpError> {
+        let mut accounts = Vec::with_capacity(self.accounts.len());
+        for (index, account) in self.accounts.iter().enumerate() {
+            if self.caller.as_ref().is_some_and(|caller| !caller.permits_account(&account.name)) {
+                continue;
+            }
+            accounts.push(AccountSummary {
+                name: account.name.clone(),
+                primary: index == 0,
+                user: account.user.clone(),
+                from: account.smtp_settings.as_ref().map(|smtp| smtp.from.clone()),
+                connected: account.is_connected().await,
+                mailboxes: account.mailbox_policy.allow.clone(),
+            });
+        }
+
+        let response = ListAccountsResponse { count: accounts.len(), accounts };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "List available mailboxes", annotations(read_only_hint = true))]
+    async fn list_mailboxes(&self, Parameters(req): Parameters<ListMailboxesRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        let connection = account.connect().await?;
+
+        let mailboxes = connection.list_mailboxes().await
+            .map_err(|e| {
+                log::error!("Failed to list mailboxes: {}", e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let mailboxes = mailboxes.into_iter().filter(|mailbox| self.permits_mailbox(account, mailbox)).collect();
+
+        let response = ListMailboxesResponse { mailboxes };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Get emails from a mailbox with optional date filtering", annotations(read_only_hint = true))]
+    async fn get_inbox_items(&self, Parameters(req): Parameters<GetInboxItemsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        validate_limit(req.limit)?;
+        if let Some(date_str) = &req.since_date {
+            validate_non_empty("since_date", date_str)?;
+        }
+
+        let connection = account.connect().await?;
+
+        let since_date = if let Some(date_str) = req.since_date {
+            Some(DateTime::parse_from_rfc3339(&date_str)
+                .map_err(|e| {
+                    let message = format!("Invalid date format: {}. Use ISO 8601 format.", e);
+                    log::error!("{}", message);
+                    McpError::invalid_params(
+                        message,
+                        Some(JsonValue::Object({
+                            let mut data = JsonMap::new();
+                            data.insert("field".to_string(), JsonValue::String("since_date".to_string()));
+                            data.insert("reason".to_string(), JsonValue::String("invalid_format".to_string()));
+                            data.insert("expected".to_string(), JsonValue::String("ISO 8601 timestamp".to_string()));
+                            data.insert("hint".to_string(), JsonValue::String("Example: 2025-01-31T10:15:00Z".to_string()));
+                            data.insert("value".to_string(), JsonValue::String(date_str));
+                            data
+                        })),
+                    )
+                })?
+                .with_timezone(&Utc))
+        } else {
+            None
+        };
+
+        let mut emails = connection.search_emails(&req.mailbox, since_date, Some(req.limit)).await
+            .map_err(|e| {
+                log::error!("Failed to get emails from {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let safety_notice = self.content_guard().protect_listing(&mut emails);
+
+        let response = GetInboxItemsResponse { count: emails.len(), emails, safety_notice };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Search for emails by keyword in specified fields", annotations(read_only_hint = true))]
+    async fn search_emails(&self, Parameters(req): Parameters<SearchEmailsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        // mailbox defaults to INBOX and is ignored when mailboxes is given
+        if req.mailboxes.as_deref().is_none_or(|mailboxes| mailboxes.is_empty()) {
+            validate_non_empty("mailbox", &req.mailbox)?;
+            self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        }
+        validate_non_empty("query", &req.query)?;
+        validate_limit(req.limit)?;
+        if let Some(date_str) = &req.since_date {
+            validate_non_empty("since_date", date_str)?;
+        }
+
+        let connection = account.connect().await?;
+
+        let since_date = if let Some(date_str) = &req.since_date {
+            Some(DateTime::parse_from_rfc3339(date_str)
+                .map_err(|e| {
+                    let message = format!("Invalid date format: {}. Use ISO 8601 format.", e);
+                    log::error!("{}", message);
+                    McpError::invalid_params(
+                        message,
+                        Some(JsonValue::Object({
+                            let mut data = JsonMap::new();
+                            data.insert("field".to_string(), JsonValue::String("since_date".to_string()));
+                            data.insert("reason".to_string(), JsonValue::String("invalid_format".to_string()));
+                            data.insert("expected".to_string(), JsonValue::String("ISO 8601 timestamp".to_string()));
+                            data.insert("hint".to_string(), JsonValue::String("Example: 2025-01-31T10:15:00Z".to_string()));
+                            data.insert("value".to_string(), JsonValue::String(date_str.clone()));
+                            data
+                        })),
+                    )
+                })?
+                .with_timezone(&Utc))
+        } else {
+            None
+        };
+
+        if let Some(mailboxes) = req.mailboxes.as_deref().filter(|mailboxes| !mailboxes.is_empty()) {
+            let targets = if mailboxes.iter().any(|mailbox| mailbox.trim() == "*") {
+                let mut entries = connection.list_mailbox_entries().await
+                    .map_err(|e| {
+                        log::error!("Failed to list mailboxes: {}", e);
+                        McpError::internal_error(e.to_string(), None)
+                    })?;
+                if let Some(caller) = &self.caller {
+                    entries.retain(|entry| caller.mailbox_policy.permits(&entry.name));
+                }
+                account.mailbox_policy.search_targets(&entries, req.include_trash)
+            } else {
+                for mailbox in mailboxes {
+                    validate_non_empty("mailboxes", mailbox)?;
+                    self.check_mailbox(account, "mailboxes", mailbox)?;
+                }
+                let mut targets: Vec<String> = Vec::with_capacity(mailboxes.len());
+                for mailbox in mailboxes {
+                    if !targets.contains(mailbox) {
+                        targets.push(mailbox.clone());
+                    }
+                }
+                targets
+            };
+
+            let mut search = connection.search_mailboxes_by_keyword(
+                &targets,
+                &req.query,
+                req.fields.as_deref(),
+                since_date,
+                req.limit,
+            ).await
+                .map_err(|e| {
+                    log::error!("Failed to search emails in {} mailboxes: {}", targets.len(), e);
+                    McpError::internal_error(e.to_string(), None)
+                })?;
+
+            let safety_notice = self.content_guard().protect_listing(&mut search.emails);
+
+            let response = SearchEmailsResponse {
+                count: search.emails.len(),
+                emails: search.emails,
+                mailboxes: Some(targets),
+                failed: search.failed,
+                safety_notice,
+            };
+            return Ok(CallToolResult::success(vec![Content::json(response)?]));
+        }
+
+        let mut emails = connection.search_emails_by_keyword(
+            &req.mailbox,
+            &req.query,
+            req.fields.as_deref(),
+            since_date,
+            Some(req.limit),
+        ).await
+            .map_err(|e| {
+                log::error!("Failed to search emails in {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let safety_notice = self.content_guard().protect_listing(&mut emails);
+
+        let response = SearchEmailsResponse { count: emails.len(), emails, mailboxes: None, failed: Vec::new(), safety_notice };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Get email content by ID", annotations(read_only_hint = true))]
+    async fn get_email(&self, Parameters(req): Parameters<GetEmailRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        let mut email = connection.get_email_content(&req.mailbox, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to fetch email {} from {}: {}", req.email_id, req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+        drop(connection);
+
+        if let Some(mut security) = email.security.take() {
+            // Verification runs gpgv/openssl, so keep it off the async workers
+            let trust = self.trust_store();
+            email.security = Some(
+                tokio::task::spawn_blocking(move || {
+                    security.verify(&trust);
+                    security
+                })
+                .await
+                .map_err(|e| McpError::internal_error(e.to_string(), None))?,
+            );
+        }
+        self.content_guard().protect(&mut email);
+
+        Ok(CallToolResult::success(vec![Content::json(email)?]))
+    }
+
+    #[tool(description = "Get the complete raw RFC 822 source of an email, base64-encoded", annotations(read_only_hint = true))]
+    async fn get_raw_email(&self, Parameters(req): Parameters<GetEmailRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        let raw = connection.get_raw_email(&req.mailbox, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to fetch source of email {} from {}: {}", req.email_id, req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        // The source cannot be enveloped, so quarantined mail is refused outright
+        let content_safety = self.content_guard().assess_raw(&raw);
+        if let Some(safety) = content_safety.as_ref().filter(|safety| safety.withheld) {
+            log::warn!("Withheld source of email {} in {} (risk score {})", req.email_id, req.mailbox, safety.risk_score);
+            return Err(invalid_input(
+                "email_id",
+                "quarantined",
+                &format!(
+                    "the email's risk score of {} reached the quarantine threshold of {}",
+                    safety.risk_score, self.server_config.quarantine_threshold
+                ),
+                None,
+                Some("Use get_email to read the email with its risky content withheld."),
+                Some(JsonValue::String(req.email_id.clone())),
+            ));
+        }
+
+        use base64::Engine;
+        let response = RawEmailResponse {
+            email_id: req.email_id,
+            mailbox: req.mailbox,
+            size: raw.len(),
+            content_base64: base64::engine::general_purpose::STANDARD.encode(&raw),
+            content_safety,
+        };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Review recent mutating and sending actions from the audit log, newest first: who called which tool with what arguments, the mailboxes, UIDs and Message-IDs affected, the outcome and how long it took. Tokens see only their own entries unless they have the admin scope.", annotations(read_only_hint = true))]
+    async fn get_audit_log(&self, Parameters(req): Parameters<GetAuditLogRequest>) -> Result<CallToolResult, McpError> {
+        validate_limit(req.limit)?;
+        // Entries about mailboxes an account's allow-list hides stay hidden
+        let account_permits = |entry: &AuditEntry| {
+            self.accounts.iter()
+                .find(|account| account.name == entry.account)
+                .is_some_and(|account| entry.mailboxes.iter().all(|mailbox| account.mailbox_policy.permits(mailbox)))
+        };
+        let entries = self.audit.recent(req.limit, |entry| {
+            self.caller.as_ref().is_none_or(|caller| entry.visible_to(caller))
+                && account_permits(entry)
+                && req.tool.as_ref().is_none_or(|tool| &entry.tool == tool)
+                && req.caller.as_ref().is_none_or(|caller| &entry.caller == caller)
+                && req.account.as_ref().is_none_or(|account| &entry.account == account)
+        });
+        let response = AuditLogResponse { count: entries.len(), entries };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Check a call that was held for human approval, by the approval_id it returned. An approved call runs as soon as it is approved; this returns its status and, once it ran, its result.", annotations(read_only_hint = true))]
+    async fn get_approval(&self, Parameters(req): Parameters<GetApprovalRequest>) -> Result<CallToolResult, McpError> {
+        validate_non_empty("approval_id", &req.approval_id)?;
+        // Callers only see their own held calls
+        let approval = self.approvals.get(req.approval_id.trim()).await
+            .map_err(|e| approval_error(e.into()))?
+            .filter(|approval| approval.caller == self.caller_name())
+            .ok_or_else(|| approval_error(ApprovalError::Unknown(req.approval_id.clone())))?;
+        Ok(CallToolResult::success(vec![Content::json(ApprovalResponse::from(approval))?]))
+    }
+
+    #[tool(description = "Get current date and time", annotations(read_only_hint = true))]
+    async fn get_current_date(&self) -> Result<CallToolResult, McpError> {
+        let now = Utc::now();
+        let response = CurrentDateResponse {
+            timestamp: now.format("%Y-%m-%d %H:%M:%S %Z").to_string(),
+            iso8601: now.to_rfc3339(),
+        };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "List tags/flags that can be permanently set in a mailbox, and whether new keywords may be created", annotations(read_only_hint = true))]
+    async fn list_tags(&self, Parameters(req): Parameters<ListTagsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        let available = connection.get_available_tags(&req.mailbox).await
+            .map_err(|e| {
+                log::error!("Failed to list tags for {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let response = ListTagsResponse {
+            mailbox: req.mailbox,
+            tags: available.flags,
+            may_create_keywords: available.may_create_keywords,
+        };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Get tags/flags currently set on an email", annotations(read_only_hint = true))]
+    async fn get_email_tags(&self, Parameters(req): Parameters<GetEmailTagsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        let tags = connection.get_email_tags(&req.mailbox, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to get tags for email {} in {}: {}", req.email_id, req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let response = EmailTagsResponse { email_id: req.email_id, tags };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Apply a tag/flag to an email", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = true))]
+    async fn apply_tag(&self, Parameters(req): Parameters<ModifyTagRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        validate_non_empty("tag", &req.tag)?;
+        let tag = parse_flag("tag", &req.tag)?;
+        let operation_id = self.change_flag(account, "apply_tag", &req, &tag, true).await?;
+
+        let response = TagOperationResponse { success: true, email_id: req.email_id, tag, operation_id };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Remove a tag/flag from an email", annotations(read_only_hint = false, destructive_hint = true))]
+    async fn remove_tag(&self, Parameters(req): Parameters<ModifyTagRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        validate_non_empty("tag", &req.tag)?;
+        let tag = parse_flag("tag", &req.tag)?;
+        let operation_id = self.change_flag(account, "remove_tag", &req, &tag, false).await?;
+
+        let response = TagOperationResponse { success: true, email_id: req.email_id, tag, operation_id };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Move an email to another mailbox/folder", annotations(read_only_hint = false, destructive_hint = true))]
+    async fn move_email(&self, Parameters(req): Parameters<MoveEmailRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("from_mailbox", &req.from_mailbox)?;
+        self.check_mailbox(account, "from_mailbox", &req.from_mailbox)?;
+        validate_non_empty("to_mailbox", &req.to_mailbox)?;
+        self.check_mailbox(account, "to_mailbox", &req.to_mailbox)?;
+        let connection = account.connect().await?;
+
+        let (uid_validity, new_uid) = connection.move_email(&req.email_id, &req.from_mailbox, &req.to_mailbox).await
+            .map_err(|e| {
+                log::error!(
+                    "Failed to move email {} from {} to {}: {}",
+                    req.email_id,
+                    req.from_mailbox,
+                    req.to_mailbox,
+                    e
+                );
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let status = MoveEmailStatus {
+            email_id: req.email_id.clone(),
+            success: true,
+            new_email_id: new_uid.map(|uid| uid.to_string()),
+            uid_validity,
+            error: None,
+        };
+        let operation_id = self.journal_move(account, "move_email", &req.from_mailbox, &req.to_mailbox, std::slice::from_ref(&status));
+        let response = MoveEmailResponse {
+            email_id: req.email_id,
+            new_email_id: status.new_email_id,
+            from_mailbox: req.from_mailbox.clone(),
+            to_mailbox: req.to_mailbox.clone(),
+            message: format!("Email moved from '{}' to '{}'", req.from_mailbox, req.to_mailbox),
+            operation_id,
+        };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+
+    #[tool(description = "Move multiple emails to another mailbox/folder", annotations(read_only_hint = false, destructive_hint = true))]
+    async fn move_emails(&self, Parameters(req): Parameters<MoveEmailsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty_list("email_ids", &req.email_ids)?;
+        validate_non_empty("from_mailbox", &req.from_mailbox)?;
+        self.check_mailbox(account, "from_mailbox", &req.from_mailbox)?;
+        validate_non_empty("to_mailbox", &req.to_mailbox)?;
+        self.check_mailbox(account, "to_mailbox", &req.to_mailbox)?;
+        let connection = account.connect().await?;
+
+        let results = connection
+            .move_emails(&req.email_ids, &req.from_mailbox, &req.to_mailbox)
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "Failed to move emails from {} to {}: {}",
+                    req.from_mailbox,
+                    req.to_mailbox,
+                    e
+                );
+                McpError::internal_error(e.to_string(), None)
+            })?;
 
-        let response = ListMailboxesResponse { mailboxes };
+        let moved = results.iter().filter(|status| status.success).count();
+        let failed = results.len().saturating_sub(moved);
+        let total = results.len();
+        let operation_id = self.journal_move(account, "move_emails", &req.from_mailbox, &req.to_mailbox, &results);
+
+        let message = if failed == 0 {
+            format!(
+                "Successfully moved {} email(s) from '{}' to '{}'",
+                moved, req.from_mailbox, req.to_mailbox
+            )
+        } else if moved == 0 {
+            format!(
+                "Failed to move any emails from '{}' to '{}'. {} error(s) occurred.",
+                req.from_mailbox, req.to_mailbox, failed
+            )
+        } else {
+            format!(
+                "Partial failure: moved {} of {} email(s) from '{}' to '{}'. {} failed.",
+                moved, total, req.from_mailbox, req.to_mailbox, failed
+            )
+        };
+
+        let response = MoveEmailsResponse {
+            moved,
+            failed,
+            from_mailbox: req.from_mailbox,
+            to_mailbox: req.to_mailbox,
+            message,
+            results,
+            operation_id,
+        };
+
+        if failed > 0 {
+            Err(McpError::internal_error(
+                response.message.clone(),
+                Some(serde_json::to_value(&response).unwrap_or_default()),
+            ))
+        } else {
+            Ok(CallToolResult::success(vec![Content::json(response)?]))
+        }
+    }
+
+    #[tool(description = "List the attachments of an email with their MIME part ids, filenames, content types and sizes, without downloading them", annotations(read_only_hint = true))]
+    async fn list_attachments(&self, Parameters(req): Parameters<GetEmailRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        let attachments = connection.list_attachments(&req.mailbox, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to list attachments of email {} in {}: {}", req.email_id, req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let response = ListAttachmentsResponse {
+            email_id: req.email_id,
+            count: attachments.len(),
+            attachments,
+        };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "Get emails from a mailbox with optional date filtering", annotations(read_only_hint = true))]
-    async fn get_inbox_items(&self, Parameters(req): Parameters<GetInboxItemsRequest>) -> Result<CallToolResult, McpError> {
+    /// Write attachment data inside the attachment directory, returning the path relative to it
+    async fn save_attachment(&self, name: &str, data: &[u8], save_path: Option<&str>, overwrite: bool) -> Result<String, McpError> {
+        let requested = save_path.unwrap_or_default();
+        let root = self.server_config.attachment_dir.as_deref()
+            .ok_or(sandbox::SandboxError::NotConfigured("ATTACHMENT_DIR"))
+            .map_err(|e| sandbox_error("save_path", requested, e))?;
+
+        // The quota check walks the whole directory, so it and the write run on
+        // the blocking thread pool
+        let (root, quota) = (root.to_path_buf(), self.server_config.attachment_quota);
+        let (name, data, requested) = (name.to_string(), data.to_vec(), requested.to_string());
+        tokio::task::spawn_blocking(move || write_attachment(&root, quota, &name, &data, &requested, overwrite))
+            .await
+            .map_err(|e| McpError::internal_error(format!("Attachment save task failed: {}", e), None))?
+    }
+
+    #[tool(description = "Get an attachment from an email. Returns base64-encoded content, extracted plain text when extract_text is set, or saves it inside the configured attachment directory when save or save_path is given.", annotations(read_only_hint = false, destructive_hint = false, open_world_hint = true))]
+    async fn get_attachment(&self, Parameters(req): Parameters<GetAttachmentRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("email_id", &req.email_id)?;
         validate_non_empty("mailbox", &req.mailbox)?;
-        validate_limit(req.limit)?;
-        if let Some(date_str) = &req.since_date {
-            validate_non_empty("since_date", date_str)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let selector = match (&req.part_id, &req.attachment_name) {
+            (Some(part_id), None) => {
+                if mime::parse_part_id(part_id.trim()).is_none() {
+                    return Err(invalid_input(
+                        "part_id",
+                        "invalid_format",
+                        "must be a MIME part number such as '2' or '1.3'",
+                        Some("dot-separated positive integers"),
+                        Some("Use list_attachments to find part ids."),
+                        Some(JsonValue::String(part_id.clone())),
+                    ));
+                }
+                AttachmentSelector::PartId(part_id.trim().to_string())
+            }
+            (None, Some(name)) => {
+                validate_non_empty("attachment_name", name)?;
+                AttachmentSelector::Name(name.clone())
+            }
+            _ => {
+                return Err(invalid_input(
+                    "part_id",
+                    "conflict",
+                    "provide exactly one of part_id or attachment_name",
+                    None,
+                    Some("Use list_attachments to find part ids."),
+                    None,
+                ));
+            }
+        };
+        if let Some(path) = &req.save_path {
+            validate_non_empty("save_path", path)?;
+        }
+        if (req.save || req.save_path.is_some()) && self.server_config.tool_policy.read_only {
+            return Err(invalid_input(
+                if req.save_path.is_some() { "save_path" } else { "save" },
+                "read_only",
+                "saving attachments is disabled in read-only mode",
+                None,
+                Some("Omit save and save_path to receive the content instead."),
+                None,
+            ));
         }
+        let connection = account.connect().await?;
+
+        let attachment = connection.get_attachment(&req.mailbox, &req.email_id, &selector).await
+            .map_err(|e| {
+                log::error!(
+                    "Failed to fetch attachment {} for email {} in {}: {}",
+                    selector,
+                    req.email_id,
+                    req.mailbox,
+                    e
+                );
+                McpError::internal_error(e.to_string(), None)
+            })?;
 
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        drop(connection);
 
-        let since_date = if let Some(date_str) = req.since_date {
-            Some(DateTime::parse_from_rfc3339(&date_str)
-                .map_err(|e| {
-                    let message = format!("Invalid date format: {}. Use ISO 8601 format.", e);
+        match attachment {
+            Some(data) => {
+                // Extract first, so a failed extraction leaves nothing behind on disk
+                let mut text = if req.extract_text {
+                    Some(extract_attachment_text(&data).await?)
+                } else {
+                    None
+                };
+                let saved_path = if req.save || req.save_path.is_some() {
+                    Some(self.save_attachment(&data.name, &data.data, req.save_path.as_deref(), req.overwrite).await?)
+                } else {
+                    None
+                };
+                let content_safety = text.as_mut()
+                    .and_then(|text| self.content_guard().protect_text("attachment_text", &mut text.text));
+
+                // Base64 only when the content is not delivered some other way
+                let encoded = (saved_path.is_none() && text.is_none()).then(|| {
+                    use base64::Engine;
+                    base64::engine::general_purpose::STANDARD.encode(&data.data)
+                });
+
+                let response = AttachmentResponse {
+                    part_id: data.part_id,
+                    name: data.name,
+                    content_type: data.content_type,
+                    size: data.data.len(),
+                    data: encoded,
+                    saved_path,
+                    text,
+                    content_safety,
+                };
+                Ok(CallToolResult::success(vec![Content::json(response)?]))
+            }
+            None => Err(McpError::internal_error(
+                {
+                    let message = format!("Attachment {} not found in email {}", selector, req.email_id);
                     log::error!("{}", message);
-                    McpError::invalid_params(
-                        message,
-                        Some(JsonValue::Object({
-                            let mut data = JsonMap::new();
-                            data.insert("field".to_string(), JsonValue::String("since_date".to_string()));
-                            data.insert("reason".to_string(), JsonValue::String("invalid_format".to_string()));
-                            data.insert("expected".to_string(), JsonValue::String("ISO 8601 timestamp".to_string()));
-                            data.insert("hint".to_string(), JsonValue::String("Example: 2025-01-31T10:15:00Z".to_string()));
-                            data.insert("value".to_string(), JsonValue::String(date_str));
-                            data
-                        })),
-                    )
-                })?
-                .with_timezone(&Utc))
+                    message
+                },
+                None
+            )),
+        }
+    }
+
+    /// Build a dry-run preview of the emails a destructive tool would affect
+    async fn preview_destructive(
+        &self,
+        connection: &ImapConnection,
+        action: &'static str,
+        mailbox: &str,
+        destination: Option<String>,
+        uids: &[u32],
+    ) -> Result<DestructivePreviewResponse, McpError> {
+        let max_count = self.server_config.max_destructive_count;
+        let previewed = &uids[..uids.len().min(max_count)];
+        let emails = connection.get_emails_metadata(mailbox, previewed).await
+            .map_err(|e| {
+                log::error!("Failed to preview {} in {}: {}", action, mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let not_found = previewed.iter()
+            .filter(|uid| !emails.iter().any(|email| email.email_id == uid.to_string()))
+            .map(|uid| uid.to_string())
+            .collect::<Vec<_>>();
+
+        Ok(DestructivePreviewResponse {
+            dry_run: true,
+            action,
+            mailbox: mailbox.to_string(),
+            destination,
+            requested: uids.len(),
+            count: previewed.len() - not_found.len(),
+            max_count,
+            emails,
+            not_found,
+        })
+    }
+
+    fn destructive_result(
+        action: &'static str,
+        verb: &str,
+        mailbox: String,
+        destination: Option<String>,
+        results: Vec<MoveEmailStatus>,
+        operation_id: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let succeeded = results.iter().filter(|status| status.success).count();
+        let failed = results.len() - succeeded;
+
+        let message = if failed == 0 {
+            format!("Successfully {} {} email(s) in '{}'", verb, succeeded, mailbox)
         } else {
-            None
+            format!(
+                "Partial failure: {} {} of {} email(s) in '{}'. {} failed.",
+                verb, succeeded, results.len(), mailbox, failed
+            )
+        };
+
+        let response = DestructiveOperationResponse {
+            action,
+            mailbox,
+            destination,
+            succeeded,
+            failed,
+            message,
+            results,
+            operation_id,
         };
 
-        let emails = connection.search_emails(&req.mailbox, since_date, Some(req.limit)).await
+        if failed > 0 {
+            Err(McpError::internal_error(
+                response.message.clone(),
+                Some(serde_json::to_value(&response).unwrap_or_default()),
+            ))
+        } else {
+            Ok(CallToolResult::success(vec![Content::json(response)?]))
+        }
+    }
+
+    #[tool(description = "Undo an earlier move_email, move_emails, trash_emails, apply_tag or remove_tag call by the operation_id it returned. Moved emails go back to their original mailbox (with new email IDs) and a changed flag is restored to what it was before. Only recent operations are kept.", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = true))]
+    async fn undo_operation(&self, Parameters(req): Parameters<UndoOperationRequest>) -> Result<CallToolResult, McpError> {
+        validate_non_empty("operation_id", &req.operation_id)?;
+        // Callers only see their own operations
+        let operation = self.journal.get(req.operation_id.trim())
+            .filter(|operation| operation.caller == self.caller_name())
+            .ok_or_else(|| invalid_input(
+                "operation_id",
+                "unknown_operation",
+                "no operation with this id is in the undo journal",
+                None,
+                Some("Use the operation_id a move, trash or tag tool returned; only recent operations can be undone."),
+                Some(JsonValue::String(req.operation_id.clone())),
+            ))?;
+        if let Some(undone_at) = operation.undone_at {
+            return Err(invalid_input(
+                "operation_id",
+                "already_undone",
+                &format!("operation was already undone at {}", undone_at.to_rfc3339()),
+                None,
+                None,
+                Some(JsonValue::String(req.operation_id)),
+            ));
+        }
+        let account = self.account(Some(&operation.account))?;
+
+        match &operation.change {
+            Change::Move { from_mailbox, to_mailbox, emails } => {
+                self.undo_move(account, &operation, from_mailbox, to_mailbox, emails).await
+            }
+            Change::Flag { mailbox, email_id, flag, added, was_set } => {
+                self.check_mailbox(account, "operation_id", mailbox)?;
+                let tag = parse_flag("operation_id", flag)?;
+                let message = if added == was_set {
+                    format!("Nothing to revert: email {} already had {} {} before", email_id, tag, if *was_set { "set" } else { "cleared" })
+                } else {
+                    let connection = account.connect().await?;
+                    let restored = if *was_set {
+                        connection.apply_tag(mailbox, email_id, &tag).await
+                    } else {
+                        connection.remove_tag(mailbox, email_id, &tag).await
+                    };
+                    restored.map_err(|e| {
+                        log::error!("Failed to restore {} on email {} in {}: {}", tag, email_id, mailbox, e);
+                        McpError::internal_error(e.to_string(), None)
+                    })?;
+                    format!("Restored {} to {} on email {} in '{}'", tag, if *was_set { "set" } else { "cleared" }, email_id, mailbox)
+                };
+                self.journal.mark_undone(&operation.id);
+                let response = UndoResponse {
+                    operation_id: operation.id,
+                    tool: operation.tool,
+                    account: operation.account,
+                    message,
+                    results: Vec::new(),
+                };
+                Ok(CallToolResult::success(vec![Content::json(response)?]))
+            }
+        }
+    }
+
+    /// Move the emails of a journaled move back to where they came from.
+    /// Emails that could not be moved back stay in the journal for a retry.
+    async fn undo_move(
+        &self,
+        account: &Account,
+        operation: &Operation,
+        from_mailbox: &str,
+        to_mailbox: &str,
+        emails: &[MovedEmail],
+    ) -> Result<CallToolResult, McpError> {
+        self.check_mailbox(account, "operation_id", from_mailbox)?;
+        self.check_mailbox(account, "operation_id", to_mailbox)?;
+        let connection = account.connect().await?;
+
+        // New UIDs only name the same emails while UIDVALIDITY is unchanged
+        if emails.iter().any(|email| email.uid_validity.is_some()) {
+            let uid_validity = connection.get_uid_validity(to_mailbox).await
+                .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            if let Some(moved) = emails.iter().find_map(|email| email.uid_validity.filter(|moved| Some(*moved) != uid_validity)) {
+                return Err(invalid_input(
+                    "operation_id",
+                    "uid_validity_changed",
+                    &format!(
+                        "'{}' was recreated or renumbered since the move (UIDVALIDITY {} is now {}), so its UIDs no longer name the moved emails",
+                        to_mailbox,
+                        moved,
+                        uid_validity.map_or_else(|| "unknown".to_string(), |value| value.to_string()),
+                    ),
+                    None,
+                    Some("Find the emails in the mailbox by subject or Message-ID and move them back with move_emails."),
+                    Some(JsonValue::String(operation.id.clone())),
+                ));
+            }
+        }
+
+        // UID MOVE or UID EXPUNGE, so mail others flagged \Deleted stays put
+        let moved_uids: Vec<u32> = emails.iter().filter_map(|email| email.new_email_id.parse().ok()).collect();
+        let results = connection.move_uids(&moved_uids, to_mailbox, from_mailbox).await
             .map_err(|e| {
-                log::error!("Failed to get emails from {}: {}", req.mailbox, e);
+                log::error!("Failed to move emails back from {} to {}: {}", to_mailbox, from_mailbox, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let response = GetInboxItemsResponse { count: emails.len(), emails };
-        Ok(CallToolResult::success(vec![Content::json(response)?]))
+        let remaining: Vec<MovedEmail> = emails.iter()
+            .filter(|email| !results.iter().any(|status| status.success && status.email_id == email.new_email_id))
+            .cloned()
+            .collect();
+        let failed = remaining.len();
+        let message = if remaining.is_empty() {
+            self.journal.mark_undone(&operation.id);
+            format!("Moved {} email(s) back from '{}' to '{}'", emails.len(), to_mailbox, from_mailbox)
+        } else {
+            self.journal.update(&operation.id, Change::Move {
+                from_mailbox: from_mailbox.to_string(),
+                to_mailbox: to_mailbox.to_string(),
+                emails: remaining,
+            });
+            format!(
+                "Partial failure: moved {} of {} email(s) back to '{}'. Retry to move the rest.",
+                emails.len() - failed, emails.len(), from_mailbox
+            )
+        };
+
+        let response = UndoResponse {
+            operation_id: operation.id.clone(),
+            tool: operation.tool.clone(),
+            account: operation.account.clone(),
+            message,
+            results,
+        };
+        if failed > 0 {
+            Err(McpError::internal_error(
+                response.message.clone(),
+                Some(serde_json::to_value(&response).unwrap_or_default()),
+            ))
+        } else {
+            Ok(CallToolResult::success(vec![Content::json(response)?]))
+        }
     }
 
-    #[tool(description = "Search for emails by keyword in specified fields", annotations(read_only_hint = true))]
-    async fn search_emails(&self, Parameters(req): Parameters<SearchEmailsRequest>) -> Result<CallToolResult, McpError> {
+    #[tool(description = "Move emails to the \\Trash special-use folder. Use dry_run to list what would be trashed.", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = false))]
+    async fn trash_emails(&self, Parameters(req): Parameters<TrashEmailsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty_list("email_ids", &req.email_ids)?;
         validate_non_empty("mailbox", &req.mailbox)?;
-        validate_non_empty("query", &req.query)?;
-        validate_limit(req.limit)?;
-        if let Some(date_str) = &req.since_date {
-            validate_non_empty("since_date", date_str)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let uids = parse_uids("email_ids", &req.email_ids)?;
+        if !req.dry_run {
+            validate_max_count("email_ids", uids.len(), self.server_config.max_destructive_count)?;
         }
+        let connection = account.connect().await?;
 
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        let trash = connection.find_special_use_mailbox(SpecialUse::Trash).await
+            .map_err(|e| {
+                log::error!("Failed to find trash mailbox: {}", e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+        self.check_mailbox(account, "mailbox", &trash)?;
 
-        let since_date = if let Some(date_str) = &req.since_date {
-            Some(DateTime::parse_from_rfc3339(date_str)
-                .map_err(|e| {
-                    let message = format!("Invalid date format: {}. Use ISO 8601 format.", e);
-                    log::error!("{}", message);
-                    McpError::invalid_params(
-                        message,
-                        Some(JsonValue::Object({
-                            let mut data = JsonMap::new();
-                            data.insert("field".to_string(), JsonValue::String("since_date".to_string()));
-                            data.insert("reason".to_string(), JsonValue::String("invalid_format".to_string()));
-                            data.insert("expected".to_string(), JsonValue::String("ISO 8601 timestamp".to_string()));
-                            data.insert("hint".to_string(), JsonValue::String("Example: 2025-01-31T10:15:00Z".to_string()));
-                            data.insert("value".to_string(), JsonValue::String(date_str.clone()));
-                            data
-                        })),
-                    )
-                })?
-                .with_timezone(&Utc))
-        } else {
-            None
+        if trash == req.mailbox {
+            return Err(invalid_input(
+                "mailbox",
+                "already_trash",
+                &format!("emails are already in '{}'", trash),
+                None,
+                Some("Use delete_emails to remove emails from the trash permanently."),
+                Some(JsonValue::String(req.mailbox)),
+            ));
+        }
+
+        if req.dry_run {
+            let preview = self.preview_destructive(&connection, "trash", &req.mailbox, Some(trash), &uids).await?;
+            return Ok(CallToolResult::success(vec![Content::json(preview)?]));
+        }
+
+        let results = connection.trash_emails(&uids, &req.mailbox, &trash).await
+            .map_err(|e| {
+                log::error!("Failed to trash emails in {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        let operation_id = self.journal_move(account, "trash_emails", &req.mailbox, &trash, &results);
+        Self::destructive_result("trash", "trashed", req.mailbox, Some(trash), results, operation_id)
+    }
+
+    #[tool(description = "Permanently delete emails (flag \\Deleted and UID EXPUNGE only these IDs). Dry run by default; set dry_run=false to delete.", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = true))]
+    async fn delete_emails(&self, Parameters(req): Parameters<DeleteEmailsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty_list("email_ids", &req.email_ids)?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let uids = parse_uids("email_ids", &req.email_ids)?;
+        if !req.dry_run {
+            validate_max_count("email_ids", uids.len(), self.server_config.max_destructive_count)?;
+        }
+        let connection = account.connect().await?;
+
+        if req.dry_run {
+            let preview = self.preview_destructive(&connection, "delete", &req.mailbox, None, &uids).await?;
+            return Ok(CallToolResult::success(vec![Content::json(preview)?]));
+        }
+
+        let results = connection.delete_emails(&uids, &req.mailbox).await
+            .map_err(|e| {
+                log::error!("Failed to delete emails in {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        Self::destructive_result("delete", "deleted", req.mailbox, None, results, None)
+    }
+
+    #[tool(description = "Permanently delete every email in a mailbox. Dry run by default; set dry_run=false to empty the folder.", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = true))]
+    async fn empty_folder(&self, Parameters(req): Parameters<EmptyFolderRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let connection = account.connect().await?;
+
+        // Snapshot the UIDs so mail arriving mid-operation is never removed
+        let uids = connection.list_uids(&req.mailbox).await
+            .map_err(|e| {
+                log::error!("Failed to list emails in {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        if req.dry_run {
+            let preview = self.preview_destructive(&connection, "empty_folder", &req.mailbox, None, &uids).await?;
+            return Ok(CallToolResult::success(vec![Content::json(preview)?]));
+        }
+
+        validate_max_count("mailbox", uids.len(), self.server_config.max_destructive_count)?;
+
+        let results = connection.delete_emails(&uids, &req.mailbox).await
+            .map_err(|e| {
+                log::error!("Failed to empty {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+
+        Self::destructive_result("empty_folder", "deleted", req.mailbox, None, results, None)
+    }
+
+    async fn compose_draft(&self, account: &Account, draft: DraftContent) -> Result<Vec<u8>, McpError> {
+        validate_addresses("to", &draft.to)?;
+        validate_addresses("cc", &draft.cc)?;
+        validate_addresses("bcc", &draft.bcc)?;
+        validate_single_line("subject", &draft.subject)?;
+        if let Some(parent) = &draft.in_reply_to {
+            validate_single_line("in_reply_to", parent)?;
+        }
+
+        let from = match &account.smtp_settings {
+            Some(smtp) => smtp.from.clone(),
+            None => account.user.clone(),
+        };
+
+        let message = OutgoingMessage {
+            from,
+            to: draft.to,
+            cc: draft.cc,
+            bcc: draft.bcc,
+            subject: draft.subject,
+            text_body: draft.body,
+            html_body: draft.html_body,
+            in_reply_to: draft.in_reply_to,
+            calendar: None,
         };
+        message.to_rfc822().map_err(|e| {
+            log::error!("Failed to compose draft: {}", e);
+            McpError::internal_error(e.to_string(), None)
+        })
+    }
 
-        let emails = connection.search_emails_by_keyword(
-            &req.mailbox,
-            &req.query,
-            req.fields.as_deref(),
-            since_date,
-            Some(req.limit),
-        ).await
+    /// The account's \Drafts mailbox, if the mailbox policy permits it
+    async fn drafts_mailbox(&self, account: &Account, connection: &ImapConnection) -> Result<String, McpError> {
+        let drafts = connection.find_special_use_mailbox(SpecialUse::Drafts).await
             .map_err(|e| {
-                log::error!("Failed to search emails in {}: {}", req.mailbox, e);
+                log::error!("Failed to find drafts mailbox: {}", e);
                 McpError::internal_error(e.to_string(), None)
             })?;
-
-        let response = SearchEmailsResponse { count: emails.len(), emails };
-        Ok(CallToolResult::success(vec![Content::json(response)?]))
+        self.check_mailbox(account, "mailbox", &drafts)?;
+        Ok(drafts)
     }
 
-    #[tool(description = "Get email content by ID", annotations(read_only_hint = true))]
-    async fn get_email(&self, Parameters(req): Parameters<GetEmailRequest>) -> Result<CallToolResult, McpError> {
-        validate_non_empty("email_id", &req.email_id)?;
-        validate_non_empty("mailbox", &req.mailbox)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+    #[tool(description = "Save a message as a draft in the \\Drafts folder for human review instead of sending it", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = false))]
+    async fn save_draft(&self, Parameters(req): Parameters<SaveDraftRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        let raw = self.compose_draft(account, req.draft).await?;
+        let connection = account.connect().await?;
 
-        let email = connection.get_email_content(&req.mailbox, &req.email_id).await
+        let drafts = self.drafts_mailbox(account, &connection).await?;
+        let appended = connection.append(&drafts, &[Flag::Draft, Flag::Seen], None, &raw).await
             .map_err(|e| {
-                log::error!("Failed to fetch email {} from {}: {}", req.email_id, req.mailbox, e);
+                log::error!("Failed to save draft to {}: {}", drafts, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        Ok(CallToolResult::success(vec![Content::json(email)?]))
-    }
-
-    #[tool(description = "Get current date and time", annotations(read_only_hint = true))]
-    async fn get_current_date(&self) -> Result<CallToolResult, McpError> {
-        let now = Utc::now();
-        let response = CurrentDateResponse {
-            timestamp: now.format("%Y-%m-%d %H:%M:%S %Z").to_string(),
-            iso8601: now.to_rfc3339(),
+        let response = DraftResponse {
+            email_id: appended.uid.map(|uid| uid.to_string()),
+            mailbox: drafts.clone(),
+            message: format!("Draft saved to '{}'", drafts),
         };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "List available tags/flags for a mailbox", annotations(read_only_hint = true))]
-    async fn list_tags(&self, Parameters(req): Parameters<ListTagsRequest>) -> Result<CallToolResult, McpError> {
-        validate_non_empty("mailbox", &req.mailbox)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+    #[tool(description = "List drafts in the \\Drafts folder", annotations(read_only_hint = true))]
+    async fn list_drafts(&self, Parameters(req): Parameters<ListDraftsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_limit(req.limit)?;
+        let connection = account.connect().await?;
 
-        let tags = connection.get_available_tags(&req.mailbox).await
+        let drafts = self.drafts_mailbox(account, &connection).await?;
+        let emails = connection.search_emails(&drafts, None, Some(req.limit)).await
             .map_err(|e| {
-                log::error!("Failed to list tags for {}: {}", req.mailbox, e);
+                log::error!("Failed to list drafts in {}: {}", drafts, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let response = ListTagsResponse { mailbox: req.mailbox, tags };
+        let response = ListDraftsResponse { mailbox: drafts, count: emails.len(), drafts: emails };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "Get tags/flags currently set on an email", annotations(read_only_hint = true))]
-    async fn get_email_tags(&self, Parameters(req): Parameters<GetEmailTagsRequest>) -> Result<CallToolResult, McpError> {
+    #[tool(description = "Replace an existing draft with new content. The draft gets a new email ID.", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = false))]
+    async fn update_draft(&self, Parameters(req): Parameters<UpdateDraftRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
         validate_non_empty("email_id", &req.email_id)?;
-        validate_non_empty("mailbox", &req.mailbox)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        let uids = parse_uids("email_id", std::slice::from_ref(&req.email_id))?;
+        let raw = self.compose_draft(account, req.draft).await?;
+        let connection = account.connect().await?;
+
+        let drafts = self.drafts_mailbox(account, &connection).await?;
+        if connection.get_emails_metadata(&drafts, &uids).await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?
+            .is_empty()
+        {
+            return Err(invalid_input(
+                "email_id",
+                "not_found",
+                &format!("no draft with this ID in '{}'", drafts),
+                None,
+                Some("Use list_drafts to find draft IDs."),
+                Some(JsonValue::String(req.email_id)),
+            ));
+        }
 
-        let tags = connection.get_email_tags(&req.mailbox, &req.email_id).await
+        // Append first so a failure never loses the existing draft
+        let appended = connection.append(&drafts, &[Flag::Draft, Flag::Seen], None, &raw).await
             .map_err(|e| {
-                log::error!("Failed to get tags for email {} in {}: {}", req.email_id, req.mailbox, e);
+                log::error!("Failed to save updated draft to {}: {}", drafts, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let response = EmailTagsResponse { email_id: req.email_id, tags };
+        let removed = connection.delete_emails(&uids, &drafts).await
+            .map(|results| results.iter().all(|status| status.success))
+            .unwrap_or_else(|e| {
+                log::error!("Failed to remove old draft {}: {}", req.email_id, e);
+                false
+            });
+
+        let message = if removed {
+            format!("Draft {} replaced in '{}'", req.email_id, drafts)
+        } else {
+            format!("Updated draft saved, but old draft {} could not be removed from '{}'", req.email_id, drafts)
+        };
+        let response = DraftResponse {
+            email_id: appended.uid.map(|uid| uid.to_string()),
+            mailbox: drafts,
+            message,
+        };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "Apply a tag/flag to an email", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = true))]
-    async fn apply_tag(&self, Parameters(req): Parameters<ModifyTagRequest>) -> Result<CallToolResult, McpError> {
+    #[tool(description = "Send a saved draft via SMTP, then remove it from the \\Drafts folder", annotations(read_only_hint = false, destructive_hint = true, idempotent_hint = false, open_world_hint = true))]
+    async fn send_draft(&self, Parameters(req): Parameters<SendDraftRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
         validate_non_empty("email_id", &req.email_id)?;
-        validate_non_empty("mailbox", &req.mailbox)?;
-        validate_non_empty("tag", &req.tag)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        let uids = parse_uids("email_id", std::slice::from_ref(&req.email_id))?;
+        let smtp = account.smtp_settings.clone().ok_or_else(|| {
+            let message = "SMTP is not configured. Set SMTP_HOST and related settings.";
+            log::error!("{}", message);
+            McpError::internal_error(message, None)
+        })?;
+        let connection = account.connect().await?;
+
+        let drafts = self.drafts_mailbox(account, &connection).await?;
+        let raw = connection.get_raw_email(&drafts, &req.email_id).await
+            .map_err(|e| {
+                log::error!("Failed to fetch draft {} from {}: {}", req.email_id, drafts, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
 
-        connection.apply_tag(&req.mailbox, &req.email_id, &req.tag).await
+        let sent = SmtpClient::new(smtp).send_raw(&raw).await
             .map_err(|e| {
-                log::error!("Failed to apply tag {} to email {} in {}: {}", req.tag, req.email_id, req.mailbox, e);
+                log::error!("Failed to send draft {}: {}", req.email_id, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let response = TagOperationResponse { success: true, email_id: req.email_id, tag: req.tag };
+        // The message is already sent; a cleanup failure must not look like a send failure
+        let draft_deleted = connection.delete_emails(&uids, &drafts).await
+            .map(|results| results.iter().all(|status| status.success))
+            .unwrap_or_else(|e| {
+                log::error!("Sent draft {} but could not remove it: {}", req.email_id, e);
+                false
+            });
+
+        let message = if draft_deleted {
+            format!("Draft {} sent to {} recipient(s)", req.email_id, sent.recipients.len())
+        } else {
+            format!(
+                "Draft {} sent to {} recipient(s), but it could not be removed from '{}'",
+                req.email_id,
+                sent.recipients.len(),
+                drafts
+            )
+        };
+        let response = SendDraftResponse { email_id: req.email_id, sent, draft_deleted, message };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "Remove a tag/flag from an email", annotations(read_only_hint = false, destructive_hint = true))]
-    async fn remove_tag(&self, Parameters(req): Parameters<ModifyTagRequest>) -> Result<CallToolResult, McpError> {
+    #[tool(description = "Answer a meeting invitation (accepted, declined or tentative) by sending an iCalendar REPLY to the organizer via SMTP", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = true))]
+    async fn respond_to_invite(&self, Parameters(req): Parameters<RespondToInviteRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
         validate_non_empty("email_id", &req.email_id)?;
         validate_non_empty("mailbox", &req.mailbox)?;
-        validate_non_empty("tag", &req.tag)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
-
-        connection.remove_tag(&req.mailbox, &req.email_id, &req.tag).await
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let status = match req.response.trim().to_lowercase().as_str() {
+            "accepted" | "accept" => ReplyStatus::Accepted,
+            "declined" | "decline" => ReplyStatus::Declined,
+            "tentative" => ReplyStatus::Tentative,
+            _ => {
+                return Err(invalid_input(
+                    "response",
+                    "invalid_value",
+                    "unknown response",
+                    Some("'accepted', 'declined' or 'tentative'"),
+                    None,
+                    Some(JsonValue::String(req.response.clone())),
+                ));
+            }
+        };
+        if let Some(attendee) = &req.attendee {
+            validate_addresses("attendee", std::slice::from_ref(attendee))?;
+        }
+        let smtp = account.smtp_settings.clone().ok_or_else(|| {
+            let message = "SMTP is not configured. Set SMTP_HOST and related settings.";
+            log::error!("{}", message);
+            McpError::internal_error(message, None)
+        })?;
+        let connection = account.connect().await?;
+
+        let raw = connection.get_raw_email(&req.mailbox, &req.email_id).await
             .map_err(|e| {
-                log::error!("Failed to remove tag {} from email {} in {}: {}", req.tag, req.email_id, req.mailbox, e);
+                log::error!("Failed to fetch invitation {} from {}: {}", req.email_id, req.mailbox, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
+        drop(connection);
+
+        let original = MessageParser::default().parse(&raw);
+        let invite = original.as_ref().and_then(calendar::find_invite).ok_or_else(|| {
+            invalid_input(
+                "email_id",
+                "no_invitation",
+                "email does not contain a calendar invitation",
+                None,
+                Some("Use get_email and check its calendar field."),
+                Some(JsonValue::String(req.email_id.clone())),
+            )
+        })?;
+        if invite.method.as_deref().is_some_and(|method| method != "REQUEST") {
+            return Err(invalid_input(
+                "email_id",
+                "not_a_request",
+                &format!("calendar method is {}, only REQUEST invitations can be answered", invite.method.as_deref().unwrap_or_default()),
+                None,
+                None,
+                Some(JsonValue::String(req.email_id.clone())),
+            ));
+        }
 
-        let response = TagOperationResponse { success: true, email_id: req.email_id, tag: req.tag };
-        Ok(CallToolResult::success(vec![Content::json(response)?]))
-    }
+        let event = match &req.event_uid {
+            Some(uid) => invite.events.iter().find(|event| &event.uid == uid),
+            None => invite.events.iter().find(|event| event.recurrence_id.is_none()).or(invite.events.first()),
+        }
+        .ok_or_else(|| {
+            let uids: Vec<JsonValue> = invite.events.iter().map(|event| JsonValue::String(event.uid.clone())).collect();
+            invalid_input(
+                "event_uid",
+                "not_found",
+                "no event with this UID in the invitation",
+                Some(&format!("one of {}", JsonValue::Array(uids))),
+                None,
+                req.event_uid.clone().map(JsonValue::String),
+            )
+        })?;
+        let organizer = event.organizer.clone().ok_or_else(|| {
+            invalid_input("email_id", "no_organizer", "the invitation has no organizer to reply to", None, None, None)
+        })?;
+
+        let address = bare_address(req.attendee.as_deref().unwrap_or(&smtp.from));
+        let attendee = event.attendees.iter()
+            .find(|attendee| attendee.email.eq_ignore_ascii_case(address))
+            .cloned()
+            .ok_or_else(|| {
+                let invited: Vec<JsonValue> = event.attendees.iter().map(|a| JsonValue::String(a.email.clone())).collect();
+                invalid_input(
+                    "attendee",
+                    "not_invited",
+                    &format!("{} is not an attendee of this event", address),
+                    Some(&format!("one of {}", JsonValue::Array(invited))),
+                    Some("Pass attendee to respond as a different invited address."),
+                    Some(JsonValue::String(address.to_string())),
+                )
+            })?;
 
-    #[tool(description = "Move an email to another mailbox/folder", annotations(read_only_hint = false, destructive_hint = true))]
-    async fn move_email(&self, Parameters(req): Parameters<MoveEmailRequest>) -> Result<CallToolResult, McpError> {
-        validate_non_empty("email_id", &req.email_id)?;
-        validate_non_empty("from_mailbox", &req.from_mailbox)?;
-        validate_non_empty("to_mailbox", &req.to_mailbox)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        let comment = req.comment.as_deref();
+        let ics = calendar::build_reply(&invite, event, &attendee, status, comment, Utc::now());
+        let summary = event.summary.clone().unwrap_or_else(|| "(no title)".to_string());
+        let who = attendee.name.clone().unwrap_or_else(|| attendee.email.clone());
+        let mut text_body = format!("{} has {} this invitation: {}", who, status.as_str().to_lowercase(), summary);
+        if let Some(comment) = comment.map(str::trim).filter(|c| !c.is_empty()) {
+            text_body.push_str(&format!("\n\n{}", comment));
+        }
+        let message = OutgoingMessage {
+            from: smtp.from.clone(),
+            to: vec![organizer.email.clone()],
+            subject: format!("{}: {}", status.subject_prefix(), summary.replace(['\r', '\n'], " ")),
+            text_body,
+            in_reply_to: original.as_ref().and_then(|m| m.message_id()).map(str::to_string),
+            calendar: Some(CalendarPart { method: "REPLY".to_string(), content: ics }),
+            ..Default::default()
+        };
+        let reply = message.to_rfc822().map_err(|e| {
+            log::error!("Failed to compose invitation reply: {}", e);
+            McpError::internal_error(e.to_string(), None)
+        })?;
 
-        connection.move_email(&req.email_id, &req.from_mailbox, &req.to_mailbox).await
+        let sent = SmtpClient::new(smtp).send_raw(&reply).await
             .map_err(|e| {
-                log::error!(
-                    "Failed to move email {} from {} to {}: {}",
-                    req.email_id,
-                    req.from_mailbox,
-                    req.to_mailbox,
-                    e
-                );
+                log::error!("Failed to send reply to invitation {}: {}", req.email_id, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let response = MoveEmailResponse {
+        let response = RespondToInviteResponse {
             email_id: req.email_id,
-            from_mailbox: req.from_mailbox.clone(),
-            to_mailbox: req.to_mailbox.clone(),
-            message: format!("Email moved from '{}' to '{}'", req.from_mailbox, req.to_mailbox),
+            event_uid: event.uid.clone(),
+            response: status.as_str().to_string(),
+            message: format!("Sent {} reply for '{}' to {}", status.as_str(), summary, organizer.email),
+            organizer: organizer.email,
+            sent,
         };
         Ok(CallToolResult::success(vec![Content::json(response)?]))
     }
 
-    #[tool(description = "Move multiple emails to another mailbox/folder", annotations(read_only_hint = false, destructive_hint = true))]
-    async fn move_emails(&self, Parameters(req): Parameters<MoveEmailsRequest>) -> Result<CallToolResult, McpError> {
-        validate_non_empty_list("email_ids", &req.email_ids)?;
-        validate_non_empty("from_mailbox", &req.from_mailbox)?;
-        validate_non_empty("to_mailbox", &req.to_mailbox)?;
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+    /// The tool router with the tools the policy disables removed, so they
+    /// are neither listed nor callable
+    fn permitted_tools(policy: &ToolPolicy) -> ToolRouter<Self> {
+        let mut router = Self::tool_router();
+        let tools = router.list_all();
+        let names: Vec<String> = tools.iter().map(|tool| tool.name.to_string()).collect();
+        for unknown in policy.unknown_tools(&names) {
+            log::warn!("Tool policy names unknown tool '{}'", unknown);
+        }
+        for tool in tools.iter().filter(|tool| !policy.permits(tool)) {
+            router.remove_route(&tool.name);
+        }
+        router
+    }
 
-        let results = connection
-            .move_emails(&req.email_ids, &req.from_mailbox, &req.to_mailbox)
-            .await
+    /// Names of every tool the server has
+    pub fn tool_names() -> Vec<String> {
+        Self::tool_router().list_all().into_iter().map(|tool| tool.name.to_string()).collect()
+    }
+
+    /// Names of the tools `policy` permits
+    pub fn permitted_tool_names(policy: &ToolPolicy) -> Vec<String> {
+        Self::tool_router().list_all().into_iter()
+            .filter(|tool| policy.permits(tool))
+            .map(|tool| tool.name.to_string())
+            .collect()
+    }
+
+    fn content_guard(&self) -> ContentGuard {
+        ContentGuard::new(self.server_config.content_safety, self.server_config.quarantine_threshold)
+    }
+
+    fn trust_store(&self) -> signature::TrustStore {
+        signature::TrustStore {
+            pgp_keyring: self.server_config.pgp_keyring.clone(),
+            smime_ca_file: self.server_config.smime_ca_file.clone(),
+            gpgv_program: self.server_config.gpgv_path.clone(),
+            openssl_program: self.server_config.openssl_path.clone(),
+        }
+    }
+
+    fn import_root(&self) -> Result<&std::path::Path, sandbox::SandboxError> {
+        self.server_config.import_dir.as_deref()
+            .ok_or(sandbox::SandboxError::NotConfigured("MCP_IMPORT_DIR"))
+    }
+
+    #[tool(description = "Import a raw RFC 822 message (.eml file path or base64) into a mailbox with optional flags and internal date. Returns the assigned email ID.", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = false))]
+    async fn import_message(&self, Parameters(req): Parameters<ImportMessageRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        let flags = parse_flags("flags", &req.flags)?;
+        let internal_date = req.internal_date.as_deref()
+            .map(|date| parse_iso_date("internal_date", date))
+            .transpose()?;
+
+        let content = match (&req.path, &req.content_base64) {
+            (Some(path), None) => {
+                validate_non_empty("path", path)?;
+                let resolved = self.import_root()
+                    .and_then(|root| sandbox::resolve_existing(root, path))
+                    .map_err(|e| sandbox_error("path", path, e))?;
+                std::fs::read(&resolved).map_err(|e| {
+                    log::error!("Failed to read {}: {}", resolved.display(), e);
+                    McpError::internal_error(format!("Failed to read message file: {}", e), None)
+                })?
+            }
+            (None, Some(encoded)) => {
+                use base64::Engine;
+                base64::engine::general_purpose::STANDARD.decode(encoded.trim()).map_err(|e| {
+                    invalid_input(
+                        "content_base64",
+                        "invalid_format",
+                        &format!("invalid base64: {}", e),
+                        Some("standard base64 encoding of the message"),
+                        None,
+                        None,
+                    )
+                })?
+            }
+            _ => {
+                return Err(invalid_input(
+                    "path",
+                    "conflict",
+                    "provide exactly one of path or content_base64",
+                    None,
+                    None,
+                    None,
+                ));
+            }
+        };
+
+        let connection = account.connect().await?;
+
+        let appended = connection.import_message(&req.mailbox, &content, &flags, internal_date).await
             .map_err(|e| {
-                log::error!(
-                    "Failed to move emails from {} to {}: {}",
-                    req.from_mailbox,
-                    req.to_mailbox,
-                    e
-                );
+                log::error!("Failed to import message into {}: {}", req.mailbox, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
 
-        let moved = results.iter().filter(|status| status.success).count();
-        let failed = results.len().saturating_sub(moved);
-        let total = results.len();
-
-        let message = if failed == 0 {
-            format!(
-                "Successfully moved {} email(s) from '{}' to '{}'",
-                moved, req.from_mailbox, req.to_mailbox
-            )
-        } else if moved == 0 {
-            format!(
-                "Failed to move any emails from '{}' to '{}'. {} error(s) occurred.",
-                req.from_mailbox, req.to_mailbox, failed
-            )
-        } else {
-            format!(
-                "Partial failure: moved {} of {} email(s) from '{}' to '{}'. {} failed.",
-                moved, total, req.from_mailbox, req.to_mailbox, failed
-            )
+        let response = ImportMessageResponse {
+            email_id: appended.uid.map(|uid| uid.to_string()),
+            mailbox: req.mailbox.clone(),
+            size: content.len(),
+            message: format!("Message imported into '{}'", req.mailbox),
         };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
 
-        let response = MoveEmailsResponse {
-            moved,
-            failed,
-            from_mailbox: req.from_mailbox,
-            to_mailbox: req.to_mailbox,
-            message,
-            results,
+    #[tool(description = "Bulk import every message from an mbox file or Maildir directory into a mailbox. Sends progress notifications when the client provides a progress token.", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = false))]
+    async fn import_archive(
+        &self,
+        Parameters(req): Parameters<ImportArchiveRequest>,
+        meta: Meta,
+        peer: Peer<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
+        validate_non_empty("mailbox", &req.mailbox)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        validate_non_empty("path", &req.path)?;
+        let extra_flags = parse_flags("flags", &req.flags)?;
+        let resolved = self.import_root()
+            .and_then(|root| sandbox::resolve_existing(root, &req.path))
+            .map_err(|e| sandbox_error("path", &req.path, e))?;
+
+        let format = match req.format.as_deref().map(str::to_lowercase).as_deref() {
+            Some("mbox" | "mboxrd") => ArchiveFormat::Mbox(MboxFormat::Mboxrd),
+            Some("mboxo") => ArchiveFormat::Mbox(MboxFormat::Mboxo),
+            Some("maildir") => ArchiveFormat::Maildir,
+            Some(other) => {
+                return Err(invalid_input(
+                    "format",
+                    "invalid_value",
+                    &format!("unknown archive format '{}'", other),
+                    Some("'mbox', 'mboxrd', 'mboxo' or 'maildir'"),
+                    None,
+                    Some(JsonValue::String(other.to_string())),
+                ));
+            }
+            None => ArchiveFormat::detect(&resolved)
+                .map_err(|e| McpError::internal_error(format!("Failed to inspect {}: {}", req.path, e), None))?,
         };
 
-        if failed > 0 {
-            Err(McpError::internal_error(
-                response.message.clone(),
-                Some(serde_json::to_value(&response).unwrap_or_default()),
-            ))
-        } else {
-            Ok(CallToolResult::success(vec![Content::json(response)?]))
+        // Archives are read with blocking file I/O, so on the blocking thread
+        // pool, handing messages to the upload through a channel
+        let path = resolved.clone();
+        let opened = tokio::task::spawn_blocking(move || {
+            let (messages, total): (Box<dyn Iterator<Item = std::io::Result<archive::ArchivedMessage>> + Send>, Option<usize>) =
+                match format {
+                    ArchiveFormat::Mbox(format) => (Box::new(MboxReader::open(&path)?.with_format(format)), None),
+                    ArchiveFormat::Maildir => {
+                        let files = archive::list_maildir(&path)?;
+                        let total = files.len();
+                        (Box::new(files.into_iter().map(|file| archive::read_maildir_message(&file))), Some(total))
+                    }
+                };
+            std::io::Result::Ok((messages, total))
+        }).await.map_err(std::io::Error::other).and_then(|opened| opened);
+        let (messages, total) = opened.map_err(|e| {
+            log::error!("Failed to open archive {}: {}", resolved.display(), e);
+            McpError::internal_error(format!("Failed to open archive: {}", e), None)
+        })?;
+        let (message_tx, message_rx) = tokio::sync::mpsc::channel(IMPORT_READ_AHEAD);
+        let reader = tokio::task::spawn_blocking(move || {
+            for message in messages {
+                let message = message.map(|mut message| {
+                    for flag in &extra_flags {
+                        if !message.flags.contains(flag) {
+                            message.flags.push(flag.clone());
+                        }
+                    }
+                    message
+                });
+                // The upload stopped, e.g. because the IMAP session failed
+                if message_tx.blocking_send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Forward progress in order through a single task
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel::<ImportProgress>();
+        let forwarder = meta.get_progress_token().map(|token| {
+            tokio::spawn(async move {
+                while let Some(progress) = progress_rx.recv().await {
+                    let param = ProgressNotificationParam {
+                        progress_token: token.clone(),
+                        progress: progress.processed as f64,
+                        total: total.map(|t| t as f64),
+                        message: Some(format!(
+                            "Imported {} ({} failed): {}",
+                            progress.imported, progress.failed, progress.source
+                        )),
+                    };
+                    if let Err(e) = peer.notify_progress(param).await {
+                        log::warn!("Failed to send import progress: {}", e);
+                        break;
+                    }
+                }
+            })
+        });
+
+        let connection = account.connect().await?;
+
+        let summary = connection.import_messages(&req.mailbox, message_rx, |progress| {
+            let _ = progress_tx.send(progress.clone());
+        }).await;
+        drop(progress_tx);
+        reader.await.ok();
+        if let Some(forwarder) = forwarder {
+            forwarder.await.ok();
         }
+
+        let summary = summary.map_err(|e| {
+            log::error!("Failed to import {} into {}: {}", req.path, req.mailbox, e);
+            McpError::internal_error(e.to_string(), None)
+        })?;
+        Ok(CallToolResult::success(vec![Content::json(summary)?]))
     }
 
-    #[tool(description = "Get an attachment from an email. Optionally save to a file path, otherwise returns base64-encoded content.", annotations(read_only_hint = false, destructive_hint = true, open_world_hint = true))]
-    async fn get_attachment(&self, Parameters(req): Parameters<GetAttachmentRequest>) -> Result<CallToolResult, McpError> {
-        validate_non_empty("email_id", &req.email_id)?;
+    fn export_root(&self) -> Result<&std::path::Path, sandbox::SandboxError> {
+        self.server_config.export_dir.as_deref()
+            .ok_or(sandbox::SandboxError::NotConfigured("MCP_EXPORT_DIR"))
+    }
+
+    #[tool(description = "Export emails, selected by ID or by search, as .eml files, a single mbox file or a Maildir directory inside the configured export directory. Returns the written paths and byte counts.", annotations(read_only_hint = false, destructive_hint = false, idempotent_hint = false))]
+    async fn export_emails(&self, Parameters(req): Parameters<ExportEmailsRequest>) -> Result<CallToolResult, McpError> {
+        let account = self.account(req.account.as_deref())?;
         validate_non_empty("mailbox", &req.mailbox)?;
-        validate_non_empty("attachment_name", &req.attachment_name)?;
-        if let Some(path) = &req.save_path {
-            validate_non_empty("save_path", path)?;
+        self.check_mailbox(account, "mailbox", &req.mailbox)?;
+        validate_non_empty("path", &req.path)?;
+        validate_limit(req.limit)?;
+        let format = match req.format.to_lowercase().as_str() {
+            "eml" => ExportFormat::Eml,
+            "mbox" => ExportFormat::Mbox,
+            "maildir" => ExportFormat::Maildir,
+            other => {
+                return Err(invalid_input(
+                    "format",
+                    "invalid_value",
+                    &format!("unknown export format '{}'", other),
+                    Some("'eml', 'mbox' or 'maildir'"),
+                    None,
+                    Some(JsonValue::String(req.format.clone())),
+                ));
+            }
+        };
+        let mut uids = parse_uids("email_ids", &req.email_ids)?;
+        if let Some(query) = &req.query {
+            validate_non_empty("query", query)?;
         }
-        self.ensure_connected().await?;
-        let connection = self.connection.lock().await;
+        let since_date = req.since_date.as_deref()
+            .map(|date| parse_iso_date("since_date", date))
+            .transpose()?
+            .map(|date| date.with_timezone(&Utc));
+
+        let root = self.export_root().map_err(|e| sandbox_error("path", &req.path, e))?;
+        let target = sandbox::resolve_for_write(root, &req.path)
+            .map_err(|e| sandbox_error("path", &req.path, e))?;
 
-        let attachment = connection.get_attachment(&req.mailbox, &req.email_id, &req.attachment_name).await
+        let connection = account.connect().await?;
+
+        if uids.is_empty() {
+            let found = match &req.query {
+                Some(query) => {
+                    connection.search_emails_by_keyword(&req.mailbox, query, req.fields.as_deref(), since_date, Some(req.limit)).await
+                }
+                None => connection.search_emails(&req.mailbox, since_date, Some(req.limit)).await,
+            }
             .map_err(|e| {
-                log::error!(
-                    "Failed to fetch attachment {} for email {} in {}: {}",
-                    req.attachment_name,
-                    req.email_id,
-                    req.mailbox,
-                    e
-                );
+                log::error!("Failed to search emails in {}: {}", req.mailbox, e);
                 McpError::internal_error(e.to_string(), None)
             })?;
+            uids = found.iter().filter_map(|email| email.email_id.parse().ok()).collect();
+        }
 
-        match attachment {
-            Some(data) => {
-                if let Some(path) = req.save_path {
-                    std::fs::write(&path, &data.data)
-                        .map_err(|e| {
-                            log::error!("Failed to save attachment to {}: {}", path, e);
-                            McpError::internal_error(format!("Failed to save attachment: {}", e), None)
-                        })?;
-
-                    let response = AttachmentResponse {
-                        name: data.name,
-                        content_type: data.content_type,
-                        size: data.data.len(),
-                        data: None,
-                        saved_path: Some(path),
-                    };
-                    Ok(CallToolResult::success(vec![Content::json(response)?]))
-                } else {
-                    use base64::Engine;
-                    let encoded = base64::engine::general_purpose::STANDARD.encode(&data.data);
-
-                    let response = AttachmentResponse {
-                        name: data.name,
-                        content_type: data.content_type,
-                        size: data.data.len(),
-                        data: Some(encoded),
-                        saved_path: None,
-                    };
-                    Ok(CallToolResult::success(vec![Content::json(response)?]))
+        let emails = connection.get_raw_emails(&req.mailbox, &uids).await
+            .map_err(|e| {
+                log::error!("Failed to fetch emails for export from {}: {}", req.mailbox, e);
+                McpError::internal_error(e.to_string(), None)
+            })?;
+        drop(connection);
+
+        // Exports are written with blocking file I/O, so on the blocking thread pool
+        let count = emails.len();
+        let (root, path, overwrite) = (root.to_path_buf(), req.path.clone(), req.overwrite);
+        let files = tokio::task::spawn_blocking(move || write_export(format, &root, &target, &path, overwrite, &emails))
+            .await
+            .map_err(|e| McpError::internal_error(format!("Export task failed: {}", e), None))??;
+
+        let response = ExportEmailsResponse {
+            format: format.as_str().to_string(),
+            mailbox: req.mailbox,
+            count,
+            total_bytes: files.iter().map(|file| file.bytes).sum(),
+            files,
+        };
+        Ok(CallToolResult::success(vec![Content::json(response)?]))
+    }
+}
+
+/// Tools that never touch an account's IMAP server
+const ACCOUNTLESS_TOOLS: &[&str] = &["get_current_date", "list_accounts", "get_audit_log", "get_approval"];
+
+/// Messages read ahead of the IMAP upload during a bulk import
+const IMPORT_READ_AHEAD: usize = 16;
+
+/// How long the user may take to answer an approval elicitation
+const ELICITATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// The error returned when a call exceeds a rate or concurrency limit
+fn rate_limited(caller: &str, tool: &str, throttled: Throttled) -> McpError {
+    log::warn!("Throttled {} for '{}' ({} limit, retry in {}s)", tool, caller, throttled.limit, throttled.retry_after_secs());
+    let message = if throttled.limit == "account" {
+        "too many operations are running on this account; retry shortly".to_string()
+    } else {
+        format!("rate limit for {} calls exceeded; retry in {}s", throttled.limit, throttled.retry_after_secs())
+    };
+    McpError::invalid_request(
+        message,
+        Some(serde_json::json!({
+            "reason": "rate_limited",
+            "limit": throttled.limit,
+            "tool": tool,
+            "retry_after_secs": throttled.retry_after_secs(),
+        })),
+    )
+}
+
+/// The error for an approval_id that is unknown, in the wrong state or
+/// could not be read from the store
+fn approval_error(error: ApprovalError) -> McpError {
+    match error {
+        ApprovalError::Unknown(id) => invalid_input(
+            "approval_id",
+            "unknown_approval",
+            "no held call with this id",
+            None,
+            Some("Use the approval_id a held call returned; decided calls are kept for 7 days."),
+            Some(JsonValue::String(id)),
+        ),
+        ApprovalError::WrongStatus { id, status } => McpError::invalid_request(
+            format!("held call '{}' is {}", id, status),
+            Some(serde_json::json!({ "reason": "wrong_status", "approval_id": id, "status": status })),
+        ),
+        ApprovalError::Io(e) => {
+            log::error!("Approval store error: {}", e);
+            McpError::internal_error(format!("could not update approval store: {}", e), None)
+        }
+    }
+}
+
+/// What a tool returned: its JSON, or its text when that is not JSON
+fn result_value(result: &CallToolResult) -> JsonValue {
+    let text = result.content.iter()
+        .filter_map(|content| content.as_text())
+        .map(|text| text.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    serde_json::from_str(&text).unwrap_or(JsonValue::String(text))
+}
+
+/// The caller the HTTP auth middleware attached to the request
+#[cfg(feature = "http")]
+fn request_caller(context: &RequestContext<RoleServer>) -> Option<Arc<Caller>> {
+    context.extensions.get::<axum::http::request::Parts>()?
+        .extensions.get::<Caller>()
+        .cloned()
+        .map(Arc::new)
+}
+
+#[cfg(not(feature = "http"))]
+fn request_caller(_context: &RequestContext<RoleServer>) -> Option<Arc<Caller>> {
+    None
+}
+
+impl ImapMailboxServer {
+    /// Check the caller's scopes and limits, then run the tool
+    async fn dispatch(
+        &self,
+        caller: Option<Arc<Caller>>,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(caller) = &caller {
+            self.authorize(caller, &request.name)?;
+            log::info!("Token '{}' called {}", caller.name, request.name);
+        }
+        let permit = self.throttle(caller.as_deref(), &request)?;
+        // Handlers see the caller through a per-call copy of the server
+        let server = Self { caller, ..self.clone() };
+        if server.server_config.approvals.requires(&request.name, request.arguments.as_ref()) {
+            drop(permit);
+            return server.hold_for_approval(request, context).await;
+        }
+        let tcc = ToolCallContext::new(&server, request, context);
+        server.tool_router.call(tcc).await
+    }
+
+    /// Hold a call until a human approves it. Clients that support
+    /// elicitation ask their user right away, and an approved call runs at once.
+    /// Otherwise the call runs in the background once it is approved over
+    /// HTTP or with the `approvals` command.
+    async fn hold_for_approval(&self, request: CallToolRequestParam, context: RequestContext<RoleServer>) -> Result<CallToolResult, McpError> {
+        let arguments = request.arguments.unwrap_or_default();
+        let account = self.account(arguments.get("account").and_then(JsonValue::as_str))?.name.clone();
+        let approval = self.approvals.submit(self.caller_name(), &account, &request.name, arguments).await
+            .map_err(|e| approval_error(e.into()))?;
+        log::info!("Holding {} for '{}' until approved ({})", approval.tool, approval.caller, approval.id);
+
+        let approval = match self.elicit_decision(&approval, &context.peer).await {
+            Some(approve) => self.approvals.decide(&approval.id, approve, "elicitation", None).await.map_err(approval_error)?,
+            None => approval,
+        };
+        if approval.status == ApprovalStatus::Approved {
+            return self.execute_approved(&approval, context).await;
+        }
+        if approval.status == ApprovalStatus::Pending {
+            // The request is answered now, so the call gets its own context
+            let context = RequestContext {
+                ct: Default::default(),
+                id: context.id,
+                meta: Meta::default(),
+                extensions: context.extensions,
+                peer: context.peer,
+            };
+            tokio::spawn(self.clone().run_when_approved(approval.id.clone(), context));
+        }
+        Ok(CallToolResult::success(vec![Content::json(ApprovalResponse::from(approval))?]))
+    }
+
+    /// Wait for a human to decide on a held call and run it once approved,
+    /// recording it in the audit log with its real outcome
+    async fn run_when_approved(self, id: String, context: RequestContext<RoleServer>) {
+        loop {
+            self.approvals.changed().await;
+            let approval = match self.approvals.get(&id).await {
+                Ok(Some(approval)) => approval,
+                Ok(None) => return,
+                Err(e) => {
+                    log::warn!("Could not check held call {}: {}", id, e);
+                    continue;
                 }
+            };
+            match approval.status {
+                ApprovalStatus::Pending => continue,
+                ApprovalStatus::Approved => {}
+                _ => return,
             }
-            None => Err(McpError::internal_error(
-                {
-                    let message = format!("Attachment '{}' not found in email {}", req.attachment_name, req.email_id);
-                    log::error!("{}", message);
-                    message
-                },
+
+            let entry = AuditEntry::new(&approval.caller, &approval.tool, &approval.account, Some(&approval.arguments));
+            let started = Instant::now();
+            let result = self.execute_approved(&approval, context.clone()).await;
+            let ran = self.approvals.get(&id).await.ok().flatten();
+            if ran.is_some_and(|ran| ran.status == ApprovalStatus::Approved) {
+                // The call could not start, e.g. because the account is busy
+                continue;
+            }
+            self.record_audit(entry, &result, started);
+            return;
+        }
+    }
+
+    /// Ask the client's user to approve a held call. `None` when the client
+    /// cannot ask or the user dismissed the question, leaving the call pending.
+    async fn elicit_decision(&self, approval: &Approval, peer: &Peer<RoleServer>) -> Option<bool> {
+        if !peer.supports_elicitation() {
+            return None;
+        }
+        let message = format!(
+            "'{}' wants to call {} on account '{}' with {}. Allow it?",
+            approval.caller,
+            approval.tool,
+            approval.account,
+            JsonValue::Object(crate::audit::redact(&approval.arguments)),
+        );
+        match peer.elicit_with_timeout::<ApprovalDecision>(message, Some(ELICITATION_TIMEOUT)).await {
+            Ok(Some(decision)) => Some(decision.approve),
+            Err(ElicitationError::UserDeclined) => Some(false),
+            Ok(None) | Err(ElicitationError::UserCancelled) => None,
+            Err(e) => {
+                log::warn!("Could not ask for approval of {}: {}", approval.id, e);
                 None
-            )),
+            }
         }
     }
+
+    /// Run an approved call once, keeping its result for get_approval
+    async fn execute_approved(&self, approval: &Approval, context: RequestContext<RoleServer>) -> Result<CallToolResult, McpError> {
+        let _permit = self.limits.try_acquire_account(&approval.account)
+            .map_err(|throttled| rate_limited(self.caller_name(), &approval.tool, throttled))?;
+        self.approvals.begin(&approval.id).await.map_err(approval_error)?;
+        log::info!("Running approved {} for '{}' ({})", approval.tool, approval.caller, approval.id);
+
+        let request = CallToolRequestParam {
+            name: approval.tool.clone().into(),
+            arguments: Some(approval.arguments.clone()),
+            task: None,
+        };
+        let result = self.tool_router.call(ToolCallContext::new(self, request, context)).await;
+        let outcome = match &result {
+            Ok(result) if result.is_error != Some(true) => Ok(result_value(result)),
+            Ok(result) => Err(match result_value(result) {
+                JsonValue::String(text) => text,
+                value => value.to_string(),
+            }),
+            Err(e) => Err(e.message.to_string()),
+        };
+        self.approvals.complete(&approval.id, outcome).await.map_err(approval_error)?;
+        result
+    }
 }
 
-#[tool_handler]
 impl ServerHandler for ImapMailboxServer {
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let caller = request_caller(&context);
+        let audit = self.is_audited(&request).then(|| {
+            let account = request.arguments.as_ref()
+                .and_then(|arguments| arguments.get("account"))
+                .and_then(JsonValue::as_str)
+                .unwrap_or(&self.accounts[0].name);
+            let name = caller.as_ref().map_or(LOCAL_CALLER, |caller| caller.name.as_str());
+            AuditEntry::new(name, &request.name, account, request.arguments.as_ref())
+        });
+        let started = Instant::now();
+        let result = self.dispatch(caller, request, context).await;
+        if let Some(entry) = audit {
+            self.record_audit(entry, &result, started);
+        }
+        result
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let caller = request_caller(&context);
+        let tools = self.tool_router.list_all().into_iter()
+            .filter(|tool| caller.as_ref().is_none_or(|caller| caller.has_scope(Scope::required_for(tool))))
+            .collect();
+        Ok(ListToolsResult { tools, meta: None, next_cursor: None })
+    }
+
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2024_11_05,
@@ -0,0 +1,367 @@
+//! Signed and encrypted mail: PGP/MIME (RFC 3156), inline PGP and S/MIME (RFC 8551).
+//!
+//! The structure and the signer are read from the message itself. Verification
+//! runs `gpgv` against a local keyring and `openssl cms` against a local CA
+//! bundle, and only when those are configured.
+
+mod pgp;
+mod smime;
+
+use chrono::{DateTime, Utc};
+use mail_parser::{Message, MessagePart, MimeHeaders, PartType};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How a message is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityFormat {
+    /// multipart/signed or multipart/encrypted with OpenPGP
+    PgpMime,
+    /// ASCII-armored OpenPGP blocks in a text body
+    PgpInline,
+    /// multipart/signed or application/pkcs7-mime with CMS
+    Smime,
+}
+
+/// Outcome of checking a signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    /// The signature matches the content and the signer is trusted locally
+    Verified,
+    /// As `Verified`, but the signature covers only `signed_text`, not the
+    /// whole body: text around the signed block could have been added by anyone
+    VerifiedPart,
+    /// The content was changed after signing, or the signature is corrupt
+    BadSignature,
+    /// The signature may be intact, but the key or certificate is not trusted locally
+    UnknownSigner,
+    KeyExpired,
+    KeyRevoked,
+    /// No keyring or CA bundle is configured for this format
+    NotChecked,
+    /// The verifier could not be run
+    Error,
+}
+
+/// Signed/encrypted status of an email
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageSecurity {
+    pub format: SecurityFormat,
+    pub signed: bool,
+    pub encrypted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureInfo>,
+    /// OpenPGP key ids the message is encrypted to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub encrypted_to: Vec<String>,
+    /// The text an inline signature covers, when the body holds more than the
+    /// signed block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_text: Option<String>,
+    #[serde(skip)]
+    signed_data: Option<SignedData>,
+}
+
+/// Who signed a message and whether the signature holds
+#[derive(Debug, Clone, Serialize)]
+pub struct SignatureInfo {
+    pub verification: Verification,
+    /// OpenPGP long key id of the signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// OpenPGP fingerprint of the signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// OpenPGP user id or S/MIME certificate subject
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    /// Email address of the S/MIME certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl SignatureInfo {
+    fn unchecked() -> Self {
+        SignatureInfo {
+            verification: Verification::NotChecked,
+            key_id: None,
+            fingerprint: None,
+            signer: None,
+            email: None,
+            signed_at: None,
+            detail: None,
+        }
+    }
+}
+
+/// What the verifier needs to check a signature
+#[derive(Debug, Clone)]
+enum SignedData {
+    PgpDetached { content: Vec<u8>, signature: Vec<u8> },
+    PgpCleartext(Vec<u8>),
+    SmimeDetached { content: Vec<u8>, signature: Vec<u8> },
+    SmimeOpaque(Vec<u8>),
+}
+
+/// Local trust anchors and the programs used to check signatures against them
+#[derive(Debug, Clone)]
+pub struct TrustStore {
+    /// Binary OpenPGP keyring of trusted signers, e.g. from `gpg --export`
+    pub pgp_keyring: Option<PathBuf>,
+    /// PEM bundle of trusted S/MIME root certificates
+    pub smime_ca_file: Option<PathBuf>,
+    pub gpgv_program: String,
+    pub openssl_program: String,
+}
+
+impl Default for TrustStore {
+    fn default() -> Self {
+        TrustStore {
+            pgp_keyring: None,
+            smime_ca_file: None,
+            gpgv_program: "gpgv".to_string(),
+            openssl_program: "openssl".to_string(),
+        }
+    }
+}
+
+/// Detect signing and encryption on the top-level message. Returns `None` for
+/// ordinary mail.
+pub fn inspect(message: &Message<'_>) -> Option<MessageSecurity> {
+    let root = message.root_part();
+    let content_type = root.content_type();
+    let (ctype, subtype) = content_type
+        .map(|ct| (ct.ctype().to_ascii_lowercase(), ct.subtype().unwrap_or_default().to_ascii_lowercase()))
+        .unwrap_or_default();
+    let protocol = content_type
+        .and_then(|ct| ct.attribute("protocol"))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match (ctype.as_str(), subtype.as_str()) {
+        ("multipart", "signed") => {
+            let boundary = content_type.and_then(|ct| ct.attribute("boundary"))?;
+            let body = message.raw_message().get(root.raw_body_offset()..)?;
+            let content = canonical_crlf(first_body_part(body, boundary)?);
+            let signature = child(message, root, 1)?.contents().to_vec();
+
+            if protocol.contains("pgp-signature") {
+                let info = pgp::signature_info(&signature);
+                Some(signed(SecurityFormat::PgpMime, info, SignedData::PgpDetached { content, signature }))
+            } else if protocol.contains("pkcs7-signature") {
+                let info = smime::signature_info(&signature);
+                Some(signed(SecurityFormat::Smime, info, SignedData::SmimeDetached { content, signature }))
+            } else {
+                None
+            }
+        }
+        ("multipart", "encrypted") if protocol.contains("pgp-encrypted") => {
+            let encrypted_to = child(message, root, 1)
+                .map(|part| pgp::recipients(part.contents()))
+                .unwrap_or_default();
+            Some(encrypted(SecurityFormat::PgpMime, encrypted_to))
+        }
+        ("application", "pkcs7-mime" | "x-pkcs7-mime") => {
+            let data = root.contents();
+            if smime::is_signed_data(data) {
+                let info = smime::signature_info(data);
+                Some(signed(SecurityFormat::Smime, info, SignedData::SmimeOpaque(data.to_vec())))
+            } else {
+                Some(encrypted(SecurityFormat::Smime, Vec::new()))
+            }
+        }
+        _ => inspect_inline(message),
+    }
+}
+
+/// Look for ASCII-armored OpenPGP blocks in the first text body
+fn inspect_inline(message: &Message<'_>) -> Option<MessageSecurity> {
+    let text = message.body_text(0)?;
+
+    if let Some(block) = armored_block(&text, "-----BEGIN PGP SIGNED MESSAGE-----", "-----END PGP SIGNATURE-----") {
+        let info = armored_block(block, "-----BEGIN PGP SIGNATURE-----", "-----END PGP SIGNATURE-----")
+            .map(|signature| pgp::signature_info(signature.as_bytes()))
+            .unwrap_or_else(SignatureInfo::unchecked);
+        let data = SignedData::PgpCleartext(block.as_bytes().to_vec());
+        let mut security = signed(SecurityFormat::PgpInline, info, data);
+        if text.trim() != block {
+            security.signed_text = Some(cleartext(block));
+        }
+        return Some(security);
+    }
+
+    armored_block(&text, "-----BEGIN PGP MESSAGE-----", "-----END PGP MESSAGE-----")
+        .map(|block| encrypted(SecurityFormat::PgpInline, pgp::recipients(block.as_bytes())))
+}
+
+impl MessageSecurity {
+    /// Check the signature against the trust store, filling in the verification
+    /// result and the signer details the verifier reports. Runs external programs.
+    pub fn verify(&mut self, trust: &TrustStore) {
+        let (Some(data), Some(info)) = (&self.signed_data, &mut self.signature) else {
+            return;
+        };
+
+        let outcome = match data {
+            SignedData::PgpDetached { .. } | SignedData::PgpCleartext(_) => match &trust.pgp_keyring {
+                Some(keyring) => pgp::verify(data, keyring, &trust.gpgv_program, info),
+                None => return,
+            },
+            SignedData::SmimeDetached { .. } | SignedData::SmimeOpaque(_) => match &trust.smime_ca_file {
+                Some(ca_file) => smime::verify(data, ca_file, &trust.openssl_program, info),
+                None => return,
+            },
+        };
+
+        if let Err(error) = outcome {
+            log::warn!("Signature verification failed to run: {}", error);
+            info.verification = Verification::Error;
+            info.detail = Some(error.to_string());
+        }
+        if self.signed_text.is_some() && info.verification == Verification::Verified {
+            info.verification = Verification::VerifiedPart;
+        }
+    }
+}
+
+fn signed(format: SecurityFormat, info: SignatureInfo, data: SignedData) -> MessageSecurity {
+    MessageSecurity {
+        format,
+        signed: true,
+        encrypted: false,
+        signature: Some(info),
+        encrypted_to: Vec::new(),
+        signed_text: None,
+        signed_data: Some(data),
+    }
+}
+
+fn encrypted(format: SecurityFormat, encrypted_to: Vec<String>) -> MessageSecurity {
+    MessageSecurity {
+        format,
+        signed: false,
+        encrypted: true,
+        signature: None,
+        encrypted_to,
+        signed_text: None,
+        signed_data: None,
+    }
+}
+
+fn child<'a, 'x>(message: &'a Message<'x>, part: &MessagePart<'x>, index: usize) -> Option<&'a MessagePart<'x>> {
+    match &part.body {
+        PartType::Multipart(children) => message.part(*children.get(index)?),
+        _ => None,
+    }
+}
+
+/// The text from `begin` through the end of the line holding `end`
+fn armored_block<'a>(text: &'a str, begin: &str, end: &str) -> Option<&'a str> {
+    let start = text.find(begin)?;
+    let stop = start + text[start..].find(end)? + end.len();
+    Some(&text[start..stop])
+}
+
+/// The text of a cleartext-signed block, without its armor headers and with
+/// dash-escaping undone (RFC 4880 section 7.1)
+fn cleartext(block: &str) -> String {
+    let body = block.split_once("\n\n").or_else(|| block.split_once("\r\n\r\n")).map_or("", |(_, body)| body);
+    let body = body.split("-----BEGIN PGP SIGNATURE-----").next().unwrap_or_default();
+    body.lines()
+        .map(|line| line.strip_prefix("- ").unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The first body part of a multipart body, exactly as transmitted. The line
+/// break before each delimiter belongs to the delimiter (RFC 2046 section 5.1.1).
+fn first_body_part<'a>(body: &'a [u8], boundary: &str) -> Option<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+
+    let mut line_start = 0;
+    let mut part_start = None;
+    while line_start < body.len() {
+        let line_end = body[line_start..].iter().position(|&b| b == b'\n').map_or(body.len(), |i| line_start + i + 1);
+        if body[line_start..].starts_with(delimiter) {
+            match part_start {
+                None => part_start = Some(line_end),
+                Some(start) => {
+                    let mut end = line_start;
+                    if end > start && body[end - 1] == b'\n' {
+                        end -= 1;
+                        if end > start && body[end - 1] == b'\r' {
+                            end -= 1;
+                        }
+                    }
+                    return body.get(start..end);
+                }
+            }
+        }
+        line_start = line_end;
+    }
+    None
+}
+
+/// Signatures are computed over CRLF line endings
+fn canonical_crlf(data: &[u8]) -> Vec<u8> {
+    let mut canonical = Vec::with_capacity(data.len() + data.len() / 40);
+    for (index, &byte) in data.iter().enumerate() {
+        if byte == b'\n' && (index == 0 || data[index - 1] != b'\r') {
+            canonical.push(b'\r');
+        }
+        canonical.push(byte);
+    }
+    canonical
+}
+
+/// A scratch directory for handing data to an external verifier, removed on drop
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> std::io::Result<Self> {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let name = format!(
+            "protonmail-mcp-verify-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::create_dir(&path)?;
+        Ok(ScratchDir(path))
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+        let path = self.0.join(name);
+        std::fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run a verifier, failing only when it cannot be started
+fn run(program: &str, args: &[&std::ffi::OsStr]) -> std::io::Result<std::process::Output> {
+    Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("could not run {}: {}", program, e)))
+}
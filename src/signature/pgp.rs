@@ -0,0 +1,237 @@
+//! OpenPGP (RFC 9580) packet reading for signer and recipient key ids, and
+//! verification with `gpgv`
+
+use super::{ScratchDir, SignatureInfo, SignedData, Verification};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use std::ffi::OsStr;
+use std::path::Path;
+
+const TAG_PKESK: u8 = 1;
+const TAG_SIGNATURE: u8 = 2;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_SIGNERS_USER_ID: u8 = 28;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+/// Read the signer key id, fingerprint and signing time from a signature,
+/// armored or binary. Verification is left as not checked.
+pub(super) fn signature_info(data: &[u8]) -> SignatureInfo {
+    let mut info = SignatureInfo::unchecked();
+    let data = dearmor(data);
+    let Some(body) = packets(&data).into_iter().find(|(tag, _)| *tag == TAG_SIGNATURE).map(|(_, body)| body) else {
+        info.detail = Some("signature packet could not be read".to_string());
+        return info;
+    };
+
+    match body.first() {
+        Some(3) if body.len() >= 15 => {
+            info.signed_at = timestamp(&body[3..7]);
+            info.key_id = Some(hex(&body[7..15]));
+        }
+        Some(version @ 4..=6) => {
+            let count_len = if *version == 6 { 4 } else { 2 };
+            let mut rest = body.get(4..).unwrap_or_default();
+            for _ in 0..2 {
+                let Some(count) = rest.get(..count_len).map(be_number) else { break };
+                let Some(area) = rest.get(count_len..count_len + count) else { break };
+                read_subpackets(area, &mut info);
+                rest = &rest[count_len + count..];
+            }
+        }
+        _ => info.detail = Some("unsupported signature version".to_string()),
+    }
+    info
+}
+
+fn read_subpackets(mut area: &[u8], info: &mut SignatureInfo) {
+    while let Some(&first) = area.first() {
+        let (length, header) = match first {
+            0..=191 => (first as usize, 1),
+            192..=254 => match area.get(1) {
+                Some(&second) => ((((first as usize) - 192) << 8) + second as usize + 192, 2),
+                None => return,
+            },
+            255 => match area.get(1..5) {
+                Some(bytes) => (be_number(bytes), 5),
+                None => return,
+            },
+        };
+        let Some(subpacket) = area.get(header..header + length) else { return };
+        area = &area[header + length..];
+        let Some((&kind, value)) = subpacket.split_first() else { continue };
+
+        match kind & 0x7f {
+            SUBPACKET_CREATION_TIME if value.len() == 4 => info.signed_at = timestamp(value),
+            SUBPACKET_ISSUER if value.len() == 8 && info.key_id.is_none() => info.key_id = Some(hex(value)),
+            SUBPACKET_ISSUER_FINGERPRINT if value.len() > 1 => {
+                let fingerprint = &value[1..];
+                info.fingerprint = Some(hex(fingerprint));
+                // v4 key ids are the low 64 bits of the fingerprint, v6 key ids the high 64
+                let key_id = if value[0] == 4 { fingerprint.get(fingerprint.len().saturating_sub(8)..) } else { fingerprint.get(..8) };
+                info.key_id = key_id.map(hex).or(info.key_id.take());
+            }
+            SUBPACKET_SIGNERS_USER_ID => info.signer = Some(String::from_utf8_lossy(value).into_owned()),
+            _ => {}
+        }
+    }
+}
+
+/// Key ids from the public-key encrypted session key packets of a message
+pub(super) fn recipients(data: &[u8]) -> Vec<String> {
+    let data = dearmor(data);
+    packets(&data).into_iter()
+        .filter(|(tag, body)| *tag == TAG_PKESK && body.first() == Some(&3))
+        .filter_map(|(_, body)| body.get(1..9).map(hex))
+        .collect()
+}
+
+/// Check a signature with `gpgv` against `keyring`, recording the result in `info`
+pub(super) fn verify(data: &SignedData, keyring: &Path, gpgv: &str, info: &mut SignatureInfo) -> std::io::Result<()> {
+    // gpgv looks up keyrings without a slash in its home directory
+    let keyring = std::fs::canonicalize(keyring)?;
+    let scratch = ScratchDir::new()?;
+
+    let files = match data {
+        SignedData::PgpDetached { content, signature } => {
+            vec![scratch.write("signature.asc", signature)?, scratch.write("content", content)?]
+        }
+        SignedData::PgpCleartext(text) => vec![scratch.write("message.asc", text)?],
+        _ => return Ok(()),
+    };
+
+    let mut args: Vec<&OsStr> = vec![
+        OsStr::new("--homedir"),
+        scratch.path().as_os_str(),
+        OsStr::new("--status-fd"),
+        OsStr::new("1"),
+        OsStr::new("--keyring"),
+        keyring.as_os_str(),
+    ];
+    args.extend(files.iter().map(|file| file.as_os_str()));
+    let output = super::run(gpgv, &args)?;
+
+    apply_status(&String::from_utf8_lossy(&output.stdout), info);
+    if info.verification == Verification::NotChecked {
+        info.verification = Verification::Error;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        info.detail = Some(stderr.lines().last().unwrap_or("gpgv reported no result").to_string());
+    }
+    Ok(())
+}
+
+/// Interpret gpgv `--status-fd` output
+fn apply_status(status: &str, info: &mut SignatureInfo) {
+    for line in status.lines() {
+        let mut fields = line.strip_prefix("[GNUPG:] ").unwrap_or_default().split(' ');
+        let keyword = fields.next().unwrap_or_default();
+        let first = fields.next().map(str::to_string);
+        let rest = fields.collect::<Vec<_>>();
+
+        let (verification, detail) = match keyword {
+            "GOODSIG" => (Verification::Verified, None),
+            "BADSIG" => (Verification::BadSignature, Some("signature does not match the content")),
+            "EXPSIG" => (Verification::BadSignature, Some("signature has expired")),
+            "EXPKEYSIG" => (Verification::KeyExpired, Some("signing key has expired")),
+            "REVKEYSIG" => (Verification::KeyRevoked, Some("signing key has been revoked")),
+            "ERRSIG" if rest.get(4) == Some(&"9") => (Verification::UnknownSigner, Some("signing key is not in the keyring")),
+            "ERRSIG" => (Verification::Error, Some("signature could not be checked")),
+            "VALIDSIG" => {
+                info.fingerprint = first;
+                if let Some(seconds) = rest.get(1).and_then(|s| s.parse::<i64>().ok()) {
+                    info.signed_at = DateTime::from_timestamp(seconds, 0);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if let Some(key_id) = first {
+            info.key_id = Some(key_id);
+        }
+        if matches!(keyword, "GOODSIG" | "BADSIG" | "EXPKEYSIG" | "REVKEYSIG") && !rest.is_empty() {
+            info.signer = Some(rest.join(" "));
+        }
+        info.verification = verification;
+        info.detail = detail.map(str::to_string);
+    }
+}
+
+/// Decode ASCII armor, or return binary input unchanged
+fn dearmor(data: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(data);
+    let Some(start) = text.find("-----BEGIN PGP ") else {
+        return data.to_vec();
+    };
+
+    let mut lines = text[start..].lines().skip(1);
+    // Armor headers end at the first blank line
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    // The body ends at the footer or at the optional "=XXXX" checksum line
+    let is_trailer = |line: &str| line.starts_with("-----END") || (line.starts_with('=') && line.trim().len() == 5);
+    let encoded: String = lines
+        .take_while(|line| !is_trailer(line))
+        .map(str::trim)
+        .collect();
+    base64::engine::general_purpose::STANDARD.decode(encoded).unwrap_or_default()
+}
+
+/// Split a packet stream into (tag, body) pairs, stopping at anything unreadable
+fn packets(mut data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut packets = Vec::new();
+    while let Some(&header) = data.first() {
+        if header & 0x80 == 0 {
+            break;
+        }
+        let (tag, length, offset) = if header & 0x40 != 0 {
+            let tag = header & 0x3f;
+            match data.get(1) {
+                Some(&first @ 0..=191) => (tag, first as usize, 2),
+                Some(&first @ 192..=223) => match data.get(2) {
+                    Some(&second) => (tag, (((first as usize) - 192) << 8) + second as usize + 192, 3),
+                    None => break,
+                },
+                Some(255) => match data.get(2..6) {
+                    Some(bytes) => (tag, be_number(bytes), 6),
+                    None => break,
+                },
+                // Partial body lengths only occur in data packets
+                _ => break,
+            }
+        } else {
+            let tag = (header >> 2) & 0x0f;
+            let size = match header & 0x03 {
+                0 => 1,
+                1 => 2,
+                2 => 4,
+                _ => break,
+            };
+            match data.get(1..1 + size) {
+                Some(bytes) => (tag, be_number(bytes), 1 + size),
+                None => break,
+            }
+        };
+
+        let Some(body) = data.get(offset..offset + length) else { break };
+        packets.push((tag, body));
+        data = &data[offset + length..];
+    }
+    packets
+}
+
+fn be_number(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as usize)
+}
+
+fn timestamp(bytes: &[u8]) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(be_number(bytes) as i64, 0)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
@@ -0,0 +1,329 @@
+//! CMS SignedData (RFC 5652) reading for the signer certificate, and
+//! verification with `openssl cms`
+
+use super::{ScratchDir, SignatureInfo, SignedData, Verification};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::ffi::OsStr;
+use std::path::Path;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_CONTEXT_0: u8 = 0xa0;
+const TAG_CONTEXT_3: u8 = 0xa3;
+const TAG_SUBJECT_KEY_ID: u8 = 0x80;
+const TAG_RFC822_NAME: u8 = 0x81;
+
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_SIGNING_TIME: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x05];
+const OID_EMAIL_ADDRESS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01];
+const OID_SUBJECT_KEY_ID: &[u8] = &[0x55, 0x1d, 0x0e];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// Indefinite-length elements nested deeper than this are rejected, so a
+/// crafted signature cannot exhaust the stack
+const MAX_INDEFINITE_DEPTH: usize = 32;
+
+/// Short names for the distinguished name attributes worth showing
+const NAME_ATTRIBUTES: &[(&[u8], &str)] = &[
+    (&[0x55, 0x04, 0x03], "CN"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (OID_EMAIL_ADDRESS, "emailAddress"),
+];
+
+/// One BER/DER element
+#[derive(Clone, Copy)]
+struct Element<'a> {
+    tag: u8,
+    content: &'a [u8],
+    /// The whole encoding, header included
+    raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    fn children(&self) -> Vec<Element<'a>> {
+        let mut children = Vec::new();
+        let mut rest = self.content;
+        while let Some((element, next)) = read(rest) {
+            children.push(element);
+            rest = next;
+        }
+        children
+    }
+}
+
+/// Read one element, supporting the indefinite lengths some mailers emit
+fn read(data: &[u8]) -> Option<(Element<'_>, &[u8])> {
+    read_nested(data, 0)
+}
+
+/// `read` inside `depth` enclosing indefinite-length elements
+fn read_nested(data: &[u8], depth: usize) -> Option<(Element<'_>, &[u8])> {
+    let tag = *data.first()?;
+    if tag == 0 {
+        return None;
+    }
+    let mut offset = 1;
+    if tag & 0x1f == 0x1f {
+        while data.get(offset)? & 0x80 != 0 {
+            offset += 1;
+        }
+        offset += 1;
+    }
+
+    let first = *data.get(offset)?;
+    offset += 1;
+    if first == 0x80 {
+        // Indefinite length: children up to an end-of-contents marker
+        if depth >= MAX_INDEFINITE_DEPTH {
+            return None;
+        }
+        let mut rest = data.get(offset..)?;
+        let mut length = 0;
+        while !rest.starts_with(&[0, 0]) {
+            let (element, next) = read_nested(rest, depth + 1)?;
+            length += element.raw.len();
+            rest = next;
+        }
+        let element = Element { tag, content: &data[offset..offset + length], raw: &data[..offset + length + 2] };
+        return Some((element, &data[offset + length + 2..]));
+    }
+
+    let length = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let size = (first & 0x7f) as usize;
+        if size > 4 {
+            return None;
+        }
+        let length = data.get(offset..offset + size)?.iter().fold(0usize, |n, &b| (n << 8) | b as usize);
+        offset += size;
+        length
+    };
+    let end = offset.checked_add(length)?;
+    let element = Element { tag, content: data.get(offset..end)?, raw: &data[..end] };
+    Some((element, &data[end..]))
+}
+
+/// The SignedData inside a ContentInfo, if that is what the data holds
+fn signed_data(data: &[u8]) -> Option<Element<'_>> {
+    let (content_info, _) = read(data)?;
+    let fields = content_info.children();
+    let oid = fields.first().filter(|f| f.tag == TAG_OID)?;
+    if oid.content != OID_SIGNED_DATA {
+        return None;
+    }
+    let explicit = fields.get(1).filter(|f| f.tag == TAG_CONTEXT_0)?;
+    explicit.children().into_iter().next().filter(|e| e.tag == TAG_SEQUENCE)
+}
+
+/// True for application/pkcs7-mime signed-data; false for enveloped data
+pub(super) fn is_signed_data(data: &[u8]) -> bool {
+    signed_data(data).is_some()
+}
+
+/// Read the signer certificate's subject and email and the signing time.
+/// Verification is left as not checked.
+pub(super) fn signature_info(data: &[u8]) -> SignatureInfo {
+    let mut info = SignatureInfo::unchecked();
+    let Some(signed) = signed_data(data) else {
+        info.detail = Some("CMS signature could not be read".to_string());
+        return info;
+    };
+
+    let fields = signed.children();
+    let certificates: Vec<Element<'_>> = fields.iter()
+        .find(|f| f.tag == TAG_CONTEXT_0)
+        .map(|certs| certs.children().into_iter().filter(|c| c.tag == TAG_SEQUENCE).collect())
+        .unwrap_or_default();
+    let Some(signer_info) = fields.iter()
+        .rev()
+        .find(|f| f.tag == TAG_SET)
+        .and_then(|set| set.children().into_iter().next())
+    else {
+        return info;
+    };
+
+    let signer_fields = signer_info.children();
+    if let Some(attributes) = signer_fields.iter().skip(3).find(|f| f.tag == TAG_CONTEXT_0) {
+        info.signed_at = signing_time(attributes);
+    }
+
+    let certificate = signer_fields.get(1)
+        .and_then(|sid| certificates.iter().find(|cert| issued_to(cert, sid)));
+    if let Some(tbs) = certificate.and_then(|cert| cert.children().into_iter().next()) {
+        let tbs_fields = tbs.children();
+        // Skip the optional explicit version
+        let skip = usize::from(tbs_fields.first().is_some_and(|f| f.tag == TAG_CONTEXT_0));
+        if let Some(subject) = tbs_fields.get(skip + 4) {
+            info.signer = Some(render_name(subject)).filter(|name| !name.is_empty());
+            info.email = name_attribute(subject, OID_EMAIL_ADDRESS);
+        }
+        if info.email.is_none() {
+            info.email = extension(&tbs_fields, OID_SUBJECT_ALT_NAME)
+                .and_then(|names| read(names).map(|(seq, _)| seq.children()))
+                .and_then(|names| names.into_iter().find(|n| n.tag == TAG_RFC822_NAME))
+                .map(|name| String::from_utf8_lossy(name.content).into_owned());
+        }
+    } else {
+        info.detail = Some("signer certificate is not included in the signature".to_string());
+    }
+    info
+}
+
+/// Whether a certificate matches a SignerIdentifier (issuer and serial, or subject key id)
+fn issued_to(certificate: &Element<'_>, sid: &Element<'_>) -> bool {
+    let Some(tbs) = certificate.children().into_iter().next() else {
+        return false;
+    };
+    let fields = tbs.children();
+    let skip = usize::from(fields.first().is_some_and(|f| f.tag == TAG_CONTEXT_0));
+
+    match sid.tag {
+        TAG_SEQUENCE => {
+            let sid_fields = sid.children();
+            match (sid_fields.first(), sid_fields.get(1), fields.get(skip), fields.get(skip + 2)) {
+                (Some(issuer), Some(serial), Some(cert_serial), Some(cert_issuer)) => {
+                    serial.tag == TAG_INTEGER && serial.content == cert_serial.content && issuer.raw == cert_issuer.raw
+                }
+                _ => false,
+            }
+        }
+        TAG_SUBJECT_KEY_ID => extension(&fields, OID_SUBJECT_KEY_ID)
+            .and_then(|value| read(value))
+            .is_some_and(|(key_id, _)| key_id.tag == TAG_OCTET_STRING && key_id.content == sid.content),
+        _ => false,
+    }
+}
+
+/// The value of a certificate extension, still DER-encoded
+fn extension<'a>(tbs_fields: &[Element<'a>], oid: &[u8]) -> Option<&'a [u8]> {
+    let extensions = tbs_fields.iter().find(|f| f.tag == TAG_CONTEXT_3)?;
+    let list = extensions.children().into_iter().next()?;
+    list.children().into_iter().find_map(|extension| {
+        let parts = extension.children();
+        if parts.first()?.content != oid {
+            return None;
+        }
+        parts.iter().skip(1).find(|p| p.tag == TAG_OCTET_STRING).map(|p| p.content)
+    })
+}
+
+fn signing_time(attributes: &Element<'_>) -> Option<DateTime<Utc>> {
+    attributes.children().into_iter().find_map(|attribute| {
+        let parts = attribute.children();
+        if parts.first()?.content != OID_SIGNING_TIME {
+            return None;
+        }
+        let value = parts.get(1)?.children().into_iter().next()?;
+        let text = std::str::from_utf8(value.content).ok()?.trim_end_matches('Z');
+        let format = match value.tag {
+            TAG_UTC_TIME => "%y%m%d%H%M%S",
+            TAG_GENERALIZED_TIME => "%Y%m%d%H%M%S",
+            _ => return None,
+        };
+        NaiveDateTime::parse_from_str(text, format).ok().map(|time| time.and_utc())
+    })
+}
+
+/// Name attributes in RFC 4514 order (most specific first), e.g. `CN=Alice, O=Example`
+fn render_name(name: &Element<'_>) -> String {
+    let mut attributes: Vec<String> = name_attributes(name)
+        .into_iter()
+        .filter_map(|(oid, value)| {
+            let label = NAME_ATTRIBUTES.iter().find(|(known, _)| *known == oid)?.1;
+            Some(format!("{}={}", label, value))
+        })
+        .collect();
+    attributes.reverse();
+    attributes.join(", ")
+}
+
+fn name_attribute(name: &Element<'_>, oid: &[u8]) -> Option<String> {
+    name_attributes(name).into_iter().find(|(key, _)| *key == oid).map(|(_, value)| value)
+}
+
+fn name_attributes<'a>(name: &Element<'a>) -> Vec<(&'a [u8], String)> {
+    name.children()
+        .into_iter()
+        .flat_map(|rdn| rdn.children())
+        .filter_map(|pair| {
+            let parts = pair.children();
+            let oid = parts.first().filter(|p| p.tag == TAG_OID)?.content;
+            Some((oid, directory_string(parts.get(1)?)))
+        })
+        .collect()
+}
+
+fn directory_string(value: &Element<'_>) -> String {
+    match value.tag {
+        // BMPString
+        0x1e => {
+            let units: Vec<u16> = value.content.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(value.content).into_owned(),
+    }
+}
+
+/// Check a signature with `openssl cms` against the CA bundle, recording the result in `info`
+pub(super) fn verify(data: &SignedData, ca_file: &Path, openssl: &str, info: &mut SignatureInfo) -> std::io::Result<()> {
+    let scratch = ScratchDir::new()?;
+    let output = scratch.path().join("content.out");
+
+    let mut args: Vec<&OsStr> = ["cms", "-verify", "-binary", "-inform", "DER"].map(OsStr::new).to_vec();
+    let (signature, content);
+    match data {
+        SignedData::SmimeDetached { content: signed_content, signature: detached } => {
+            signature = scratch.write("signature.p7s", detached)?;
+            content = scratch.write("content", signed_content)?;
+            args.extend([OsStr::new("-in"), signature.as_os_str(), OsStr::new("-content"), content.as_os_str()]);
+        }
+        SignedData::SmimeOpaque(message) => {
+            signature = scratch.write("message.p7m", message)?;
+            args.extend([OsStr::new("-in"), signature.as_os_str()]);
+        }
+        _ => return Ok(()),
+    }
+    args.extend([OsStr::new("-out"), output.as_os_str()]);
+
+    let mut trusted = args.clone();
+    trusted.extend([OsStr::new("-CAfile"), ca_file.as_os_str()]);
+    let result = super::run(openssl, &trusted)?;
+    if result.status.success() {
+        info.verification = Verification::Verified;
+        info.detail = None;
+        return Ok(());
+    }
+    let trust_error = failure_reason(&result.stderr);
+
+    // Tell a broken signature from a certificate that is merely not trusted
+    args.push(OsStr::new("-noverify"));
+    let untrusted = super::run(openssl, &args)?;
+    if untrusted.status.success() {
+        info.verification = Verification::UnknownSigner;
+        info.detail = Some(format!("certificate is not trusted: {}", trust_error));
+    } else {
+        info.verification = Verification::BadSignature;
+        info.detail = Some(failure_reason(&untrusted.stderr));
+    }
+    Ok(())
+}
+
+fn failure_reason(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    stderr.lines()
+        .find(|line| line.contains("verify error"))
+        .or_else(|| stderr.lines().rev().find(|line| !line.trim().is_empty()))
+        .unwrap_or("verification failed")
+        .trim()
+        .to_string()
+}
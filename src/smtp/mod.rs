@@ -0,0 +1,12 @@
+pub mod smtp_client;
+pub mod types;
+
+pub use self::smtp_client::SmtpClient;
+pub use self::types::{
+    CalendarPart,
+    OutgoingMessage,
+    Result,
+    SendReport,
+    SmtpError,
+    SmtpSettings,
+};
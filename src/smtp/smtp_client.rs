@@ -0,0 +1,133 @@
+use crate::smtp::types::{Result, SendReport, SmtpError, SmtpSettings};
+use lettre::address::{Address, Envelope};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use mail_parser::MessageParser;
+use secrecy::ExposeSecret;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct SmtpClient {
+    settings: SmtpSettings,
+    connection_timeout: Duration,
+}
+
+impl SmtpClient {
+    pub fn new(settings: SmtpSettings) -> Self {
+        Self {
+            settings,
+            connection_timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn from_address(&self) -> &str {
+        &self.settings.from
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let tls_parameters = TlsParameters::builder(self.settings.host.clone())
+            .dangerous_accept_invalid_certs(self.settings.skip_tls_verify)
+            .build()?;
+
+        let tls = if self.settings.use_tls {
+            Tls::Wrapper(tls_parameters)
+        } else {
+            Tls::Required(tls_parameters)
+        };
+
+        Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(self.settings.host.as_str())
+            .port(self.settings.port)
+            .tls(tls)
+            .timeout(Some(self.connection_timeout))
+            .credentials(Credentials::new(
+                self.settings.user.clone(),
+                self.settings.password.expose_secret().clone(),
+            ))
+            .build())
+    }
+
+    /// Submit a raw RFC 5322 message, deriving the envelope from its From/To/Cc/Bcc headers.
+    /// Bcc headers are stripped before submission.
+    pub async fn send_raw(&self, raw: &[u8]) -> Result<SendReport> {
+        let parsed = MessageParser::default()
+            .parse_headers(raw)
+            .ok_or_else(|| SmtpError::InvalidMessage("could not parse message headers".to_string()))?;
+
+        let from = parsed.from()
+            .and_then(|from| from.first())
+            .and_then(|addr| addr.address())
+            .unwrap_or(self.settings.from.as_str())
+            .to_string();
+
+        let mut recipients: Vec<String> = Vec::new();
+        for list in [parsed.to(), parsed.cc(), parsed.bcc()].into_iter().flatten() {
+            for addr in list.iter() {
+                if let Some(address) = addr.address()
+                    && !recipients.iter().any(|r| r.eq_ignore_ascii_case(address))
+                {
+                    recipients.push(address.to_string());
+                }
+            }
+        }
+        if recipients.is_empty() {
+            return Err(SmtpError::NoRecipients);
+        }
+
+        let parse_address = |value: &str| {
+            value.parse::<Address>()
+                .map_err(|e| SmtpError::Address(value.to_string(), e.to_string()))
+        };
+        let envelope = Envelope::new(
+            Some(parse_address(&from)?),
+            recipients.iter().map(|r| parse_address(r)).collect::<Result<Vec<_>>>()?,
+        )
+        .map_err(|e| SmtpError::InvalidMessage(e.to_string()))?;
+
+        let message_id = parsed.message_id().map(|id| id.to_string());
+        let body = strip_header(raw, "Bcc");
+
+        log::info!(
+            "Submitting message via {}:{} to {} recipient(s)...",
+            self.settings.host,
+            self.settings.port,
+            recipients.len()
+        );
+        let response = self.transport()?.send_raw(&envelope, &body).await?;
+
+        Ok(SendReport {
+            message_id,
+            from,
+            recipients,
+            server_response: response.message().collect::<Vec<_>>().join(" "),
+        })
+    }
+}
+
+/// Remove every occurrence of a header (including folded continuation lines) from the header block
+fn strip_header(raw: &[u8], name: &str) -> Vec<u8> {
+    let header_end = raw.windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|pos| pos + 2)
+        .or_else(|| raw.windows(2).position(|w| w == b"\n\n").map(|pos| pos + 1))
+        .unwrap_or(raw.len());
+
+    let (headers, body) = raw.split_at(header_end);
+    let mut output = Vec::with_capacity(raw.len());
+    let mut skipping = false;
+
+    for line in headers.split_inclusive(|b| *b == b'\n') {
+        let is_continuation = matches!(line.first(), Some(b' ') | Some(b'\t'));
+        if !is_continuation {
+            skipping = line.len() > name.len()
+                && line[..name.len()].eq_ignore_ascii_case(name.as_bytes())
+                && line[name.len()] == b':';
+        }
+        if !skipping {
+            output.extend_from_slice(line);
+        }
+    }
+
+    output.extend_from_slice(body);
+    output
+}
@@ -0,0 +1,127 @@
+use mail_builder::MessageBuilder;
+use mail_builder::headers::address::Address;
+use mail_builder::headers::content_type::ContentType;
+use mail_builder::mime::MimePart;
+use secrecy::Secret;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Secret<String>,
+    pub use_tls: bool,
+    pub skip_tls_verify: bool,
+    /// Address used in the From header of composed messages
+    pub from: String,
+}
+
+#[derive(Debug, Error)]
+pub enum SmtpError {
+    #[error("SMTP transport error: {0}")]
+    Transport(#[from] lettre::transport::smtp::Error),
+    #[error("Invalid address '{0}': {1}")]
+    Address(String, String),
+    #[error("Could not compose message: {0}")]
+    Compose(#[from] std::io::Error),
+    #[error("Message has no recipients")]
+    NoRecipients,
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+}
+
+pub type Result<T> = std::result::Result<T, SmtpError>;
+
+/// A plain message to be saved as a draft or submitted over SMTP
+#[derive(Debug, Clone, Default)]
+pub struct OutgoingMessage {
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+    /// Message-ID of the email being replied to, without angle brackets
+    pub in_reply_to: Option<String>,
+    /// iCalendar object sent as a text/calendar alternative (iMIP)
+    pub calendar: Option<CalendarPart>,
+}
+
+/// A `text/calendar` body part and its iTIP method
+#[derive(Debug, Clone)]
+pub struct CalendarPart {
+    pub method: String,
+    pub content: String,
+}
+
+impl OutgoingMessage {
+    /// Render the message as RFC 5322 bytes
+    pub fn to_rfc822(&self) -> Result<Vec<u8>> {
+        fn address_list(values: &[String]) -> Address<'_> {
+            Address::new_list(values.iter().map(|v| mailbox(v)).collect())
+        }
+
+        let mut builder = MessageBuilder::new()
+            .from(mailbox(&self.from))
+            .subject(self.subject.as_str())
+            .text_body(self.text_body.as_str());
+
+        if !self.to.is_empty() {
+            builder = builder.to(address_list(&self.to));
+        }
+        if !self.cc.is_empty() {
+            builder = builder.cc(address_list(&self.cc));
+        }
+        if !self.bcc.is_empty() {
+            builder = builder.bcc(address_list(&self.bcc));
+        }
+        if let Some(calendar) = &self.calendar {
+            // iMIP clients look for the calendar object inside multipart/alternative
+            let mut parts = vec![MimePart::new("text/plain", self.text_body.as_str())];
+            if let Some(html) = &self.html_body {
+                parts.push(MimePart::new("text/html", html.as_str()));
+            }
+            parts.push(MimePart::new(
+                ContentType::new("text/calendar")
+                    .attribute("method", calendar.method.as_str())
+                    .attribute("charset", "utf-8"),
+                calendar.content.as_str(),
+            ));
+            builder = builder.body(MimePart::new("multipart/alternative", parts));
+        } else if let Some(html) = &self.html_body {
+            builder = builder.html_body(html.as_str());
+        }
+        if let Some(parent) = &self.in_reply_to {
+            let parent = parent.trim().trim_start_matches('<').trim_end_matches('>').to_string();
+            builder = builder.in_reply_to(parent.clone()).references(parent);
+        }
+
+        Ok(builder.write_to_vec()?)
+    }
+}
+
+/// Split `"Alice <alice@example.com>"` into its display name and address, so the
+/// builder doesn't wrap the whole string in angle brackets
+fn mailbox(value: &str) -> Address<'_> {
+    let value = value.trim();
+    if let Some((name, rest)) = value.rsplit_once('<')
+        && let Some((email, _)) = rest.split_once('>')
+    {
+        let name = name.trim().trim_matches('"').trim();
+        return Address::new_address((!name.is_empty()).then_some(name), email.trim());
+    }
+    Address::new_address(None::<&str>, value)
+}
+
+/// Result of submitting a message over SMTP
+#[derive(Debug, Clone, Serialize)]
+pub struct SendReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub from: String,
+    pub recipients: Vec<String>,
+    pub server_response: String,
+}
@@ -0,0 +1,192 @@
+//! HTTPS for the HTTP transport: certificates reloaded on SIGHUP and optional
+//! client certificate authentication
+
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::Router;
+use axum::http::Request;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::{self, RootCertStore, ServerConfig};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::config::TlsConfig;
+
+/// How long a client may take to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long open connections may keep running after shutdown is requested
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Could not read '{0}': {1}")]
+    Pem(String, rustls::pki_types::pem::Error),
+    #[error("No certificates found in '{0}'")]
+    NoCertificates(String),
+    #[error("Invalid client CA bundle '{0}': {1}")]
+    ClientCa(String, String),
+    #[error("Invalid certificate or key: {0}")]
+    Rustls(#[from] rustls::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The verified client certificate a connection presented, attached to
+/// each of its requests
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// Subject common name, or the whole subject when it has none
+    pub subject: String,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+        let subject = certificate.subject();
+        let common_name = subject.iter_common_name().next().and_then(|cn| cn.as_str().ok());
+        Some(ClientCertificate {
+            subject: common_name.map_or_else(|| subject.to_string(), str::to_string),
+        })
+    }
+}
+
+fn read_certificates(path: &std::path::Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let display = path.display().to_string();
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::Pem(display.clone(), e))?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(display));
+    }
+    Ok(certificates)
+}
+
+/// Build the rustls configuration from the certificate, key and client CA
+/// files. Client certificates are requested but optional, so clients without
+/// one can still authenticate with a bearer token.
+pub fn server_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>, TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let certificates = read_certificates(&tls.cert_file)?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key_file)
+        .map_err(|e| TlsError::Pem(tls.key_file.display().to_string(), e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match &tls.client_ca_file {
+        Some(ca_file) => {
+            let display = ca_file.display().to_string();
+            let mut roots = RootCertStore::empty();
+            for certificate in read_certificates(ca_file)? {
+                roots.add(certificate).map_err(|e| TlsError::ClientCa(display.clone(), e.to_string()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| TlsError::ClientCa(display, e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(certificates, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Reload the certificate files whenever the process receives SIGHUP. A
+/// failed reload is logged and the previous certificate stays in use.
+#[cfg(unix)]
+fn reload_on_sighup(tls: TlsConfig, current: Arc<RwLock<Arc<ServerConfig>>>, shutdown: CancellationToken) -> io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                received = hangups.recv() => if received.is_none() { break },
+            }
+            match server_config(&tls) {
+                Ok(config) => {
+                    *current.write().unwrap_or_else(|e| e.into_inner()) = config;
+                    log::info!("Reloaded TLS certificate from {}", tls.cert_file.display());
+                }
+                Err(e) => log::error!("Keeping the previous TLS certificate: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reload_on_sighup(_tls: TlsConfig, _current: Arc<RwLock<Arc<ServerConfig>>>, _shutdown: CancellationToken) -> io::Result<()> {
+    Ok(())
+}
+
+/// Serve `router` over HTTPS until `shutdown` is cancelled
+pub async fn serve(listener: TcpListener, router: Router, tls: TlsConfig, shutdown: CancellationToken) -> Result<(), TlsError> {
+    let current = Arc::new(RwLock::new(server_config(&tls)?));
+    reload_on_sighup(tls, current.clone(), shutdown.clone())?;
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::warn!("Could not accept connection: {}", e);
+                    continue;
+                }
+            },
+        };
+        let acceptor = TlsAcceptor::from(current.read().unwrap_or_else(|e| e.into_inner()).clone());
+        let router = router.clone();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    log::debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    log::debug!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            let client = stream.get_ref().1.peer_certificates()
+                .and_then(|chain| chain.first())
+                .and_then(|leaf| ClientCertificate::from_der(leaf));
+
+            let service = router.map_request(move |mut request: Request<Incoming>| {
+                if let Some(client) = &client {
+                    request.extensions_mut().insert(client.clone());
+                }
+                request
+            });
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection(TokioIo::new(stream), TowerToHyperService::new(service));
+            if let Err(e) = watcher.watch(connection).await {
+                log::debug!("Connection from {} ended with an error: {}", peer, e);
+            }
+        });
+    }
+
+    if tokio::time::timeout(SHUTDOWN_GRACE, graceful.shutdown()).await.is_err() {
+        log::warn!("Closed connections still open after {}s", SHUTDOWN_GRACE.as_secs());
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Tests for named bearer tokens and their scopes
+
+use chrono::{TimeZone, Utc};
+use protonmail_mcp_server::access::{AuthFailure, Scope, TokenStore};
+use rmcp::model::{Tool, ToolAnnotations};
+use std::sync::Arc;
+
+const TOKENS: &str = r#"{
+    "tokens": [
+        {"name": "assistant", "token": "assistant-secret", "scopes": ["read", "write"],
+         "accounts": ["primary"], "mailboxes": ["INBOX", "Folders/Support"]},
+        {"name": "reporting", "token_sha256": "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08",
+         "scopes": ["read"], "expires_at": "2026-06-30T00:00:00Z"}
+    ]
+}"#;
+
+#[test]
+fn test_tokens_resolve_to_named_callers() {
+    let mut store = TokenStore::parse(TOKENS).unwrap();
+    store.add_shared("default", "shared-secret");
+    let now = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+
+    let caller = store.authenticate("assistant-secret", now).unwrap();
+    assert_eq!(caller.name, "assistant");
+    assert!(caller.has_scope(Scope::Write));
+    assert!(!caller.has_scope(Scope::Send));
+    assert!(caller.permits_account("primary"));
+    assert!(!caller.permits_account("support"));
+    assert!(caller.mailbox_policy.permits("Folders/Support/Escalations"));
+    assert!(!caller.mailbox_policy.permits("Sent"));
+
+    // Stored as the SHA-256 of "test"
+    assert_eq!(store.authenticate("test", now).unwrap().name, "reporting");
+
+    let shared = store.authenticate("shared-secret", now).unwrap();
+    assert_eq!(shared.scopes, Scope::ALL);
+    assert!(shared.permits_account("support"));
+
+    assert_eq!(store.authenticate("assistant-secre", now).unwrap_err(), AuthFailure::Unknown);
+    assert_eq!(store.authenticate("", now).unwrap_err(), AuthFailure::Unknown);
+
+    let later = Utc.with_ymd_and_hms(2026, 7, 1, 0, 0, 0).unwrap();
+    assert_eq!(store.authenticate("test", later).unwrap_err(), AuthFailure::Expired("reporting".to_string()));
+    assert!(store.authenticate("assistant-secret", later).is_ok());
+}
+
+#[test]
+fn test_invalid_token_files_are_rejected() {
+    let invalid = [
+        r#"{"tokens": [{"name": "a", "scopes": ["read"]}]}"#,
+        r#"{"tokens": [{"name": "a", "token": "x", "token_sha256": "00", "scopes": ["read"]}]}"#,
+        r#"{"tokens": [{"name": "a", "token_sha256": "abc", "scopes": ["read"]}]}"#,
+        r#"{"tokens": [{"name": "a", "token": "x", "scopes": []}]}"#,
+        r#"{"tokens": [{"name": "a", "token": "x", "scopes": ["root"]}]}"#,
+        r#"{"tokens": [{"name": "a", "token": "x", "scopes": ["read"]}, {"name": "a", "token": "y", "scopes": ["read"]}]}"#,
+        r#"{"tokens": [{"name": "a", "token": "x", "scope": ["read"]}]}"#,
+    ];
+    for text in invalid {
+        assert!(TokenStore::parse(text).is_err(), "accepted {}", text);
+    }
+}
+
+#[test]
+fn test_tools_require_scopes() {
+    let tool = |name: &'static str, read_only: bool| {
+        Tool::new(name, "", Arc::new(Default::default())).annotate(ToolAnnotations::new().read_only(read_only))
+    };
+    assert_eq!(Scope::required_for(&tool("get_email", true)), Scope::Read);
+    assert_eq!(Scope::required_for(&tool("export_emails", false)), Scope::Read);
+    assert_eq!(Scope::required_for(&tool("move_email", false)), Scope::Write);
+    assert_eq!(Scope::required_for(&tool("save_draft", false)), Scope::Write);
+    assert_eq!(Scope::required_for(&tool("send_draft", false)), Scope::Send);
+    assert_eq!(Scope::required_for(&tool("empty_folder", false)), Scope::Delete);
+    assert_eq!("Delete".parse::<Scope>(), Ok(Scope::Delete));
+}
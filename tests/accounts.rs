@@ -0,0 +1,57 @@
+//! Tests for loading several named accounts from the environment
+
+use protonmail_mcp_server::config::{self, load_config};
+use secrecy::ExposeSecret;
+
+// The only test in this binary that touches the environment, so nothing reads it concurrently
+#[test]
+fn test_named_accounts_inherit_primary_servers() {
+    let vars = [
+        ("IMAP_HOST", "127.0.0.1"),
+        ("IMAP_PORT", "1143"),
+        ("IMAP_USERNAME", "me@proton.me"),
+        ("IMAP_PASSWORD", "bridge-password"),
+        ("SMTP_PORT", "1025"),
+        ("MCP_MAILBOXES_ALLOW", ""),
+        ("MCP_ACCOUNTS", "support, shared-ops"),
+        ("ACCOUNT_SUPPORT_IMAP_USERNAME", "support@proton.me"),
+        ("ACCOUNT_SUPPORT_IMAP_PASSWORD", "support-password"),
+        ("ACCOUNT_SUPPORT_MAILBOXES_ALLOW", "INBOX, Folders/Support"),
+        ("ACCOUNT_SHARED_OPS_IMAP_HOST", "imap.example.com"),
+        ("ACCOUNT_SHARED_OPS_IMAP_USE_TLS", "true"),
+        ("ACCOUNT_SHARED_OPS_IMAP_USERNAME", "ops@example.com"),
+        ("ACCOUNT_SHARED_OPS_IMAP_PASSWORD", "ops-password"),
+        ("ACCOUNT_SHARED_OPS_SMTP_FROM", "Ops Team <ops@example.com>"),
+    ];
+    for (key, value) in vars {
+        // SAFETY: no other thread in this test binary reads or writes the environment
+        unsafe { std::env::set_var(key, value) };
+    }
+
+    let config = load_config().unwrap();
+    let names: Vec<&str> = config.accounts.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["primary", "support", "shared-ops"]);
+    assert_eq!(config.primary().imap.user, "me@proton.me");
+    assert!(config.primary().mailbox_policy.allow.is_none());
+
+    let support = &config.accounts[1];
+    assert_eq!(support.imap.host, "127.0.0.1");
+    assert_eq!(support.imap.port, 1143);
+    assert_eq!(support.smtp.port, 1025);
+    assert!(!support.smtp.use_tls);
+    assert_eq!(support.smtp.user, "support@proton.me");
+    assert_eq!(support.smtp.password.expose_secret(), "support-password");
+    assert_eq!(support.smtp.from, "support@proton.me");
+    assert_eq!(support.mailbox_policy.allow.as_deref(), Some(&["INBOX".to_string(), "Folders/Support".to_string()][..]));
+    assert!(!support.mailbox_policy.permits("Sent"));
+
+    let ops = &config.accounts[2];
+    assert_eq!(ops.imap.host, "imap.example.com");
+    assert_eq!(ops.imap.port, 993);
+    assert_eq!(ops.smtp.host, "imap.example.com");
+    assert!(ops.smtp.use_tls);
+    assert_eq!(ops.smtp.port, 465);
+    assert_eq!(ops.smtp.from, "Ops Team <ops@example.com>");
+
+    assert_eq!(config::account_prefix("shared-ops"), "ACCOUNT_SHARED_OPS_");
+}
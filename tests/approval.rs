@@ -0,0 +1,166 @@
+//! Tests for holding tool calls until a human approves them
+
+mod support;
+
+use protonmail_mcp_server::ImapMailboxServer;
+use protonmail_mcp_server::approval::{ApprovalConfig, ApprovalError, ApprovalQueue, ApprovalStatus};
+use serde_json::{Value, json};
+use std::time::Duration;
+use support::{Client, FakeImap, Mailbox, sources};
+
+fn arguments(value: Value) -> serde_json::Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn test_rules_select_sensitive_calls() {
+    let mut config = ApprovalConfig::default();
+    config.add_rules("send_draft, delete_emails, move_emails:INBOX, get_attachment").unwrap();
+    config.add_rules("move_emails:Archive").unwrap();
+    let requires = |tool: &str, args: Value| config.requires(tool, Some(&arguments(args)));
+
+    assert!(requires("send_draft", json!({"draft_id": "7"})));
+    assert!(!requires("list_mailboxes", json!({})));
+    // Dry runs change nothing, and delete_emails is one unless told otherwise
+    assert!(!requires("delete_emails", json!({"email_ids": ["1"]})));
+    assert!(requires("delete_emails", json!({"email_ids": ["1"], "dry_run": false})));
+    // Only downloads that write a file
+    assert!(!requires("get_attachment", json!({"email_id": "1"})));
+    assert!(requires("get_attachment", json!({"email_id": "1", "save": true})));
+    assert!(requires("get_attachment", json!({"email_id": "1", "save_path": "report.pdf"})));
+    assert!(!requires("get_attachment", json!({"email_id": "1", "save_path": null})));
+    // Only moves out of the listed mailboxes; INBOX is the default source
+    assert!(requires("move_emails", json!({"email_ids": ["1"], "to_mailbox": "Archive"})));
+    assert!(requires("move_emails", json!({"email_ids": ["1"], "from_mailbox": "inbox", "to_mailbox": "Archive"})));
+    assert!(requires("move_emails", json!({"email_ids": ["1"], "from_mailbox": "Archive", "to_mailbox": "INBOX"})));
+    assert!(!requires("move_emails", json!({"email_ids": ["1"], "from_mailbox": "Sent", "to_mailbox": "Archive"})));
+
+    assert!(ApprovalConfig::default().add_rules("move_emails:").is_err());
+}
+
+#[tokio::test]
+async fn test_held_call_lifecycle() {
+    let queue = ApprovalQueue::in_memory(Duration::from_secs(3600));
+    let held = queue.submit("assistant", "primary", "send_draft", arguments(json!({"draft_id": "7"}))).await.unwrap();
+    assert_eq!(held.status, ApprovalStatus::Pending);
+    assert_eq!(queue.list(Some(ApprovalStatus::Pending)).await.unwrap().len(), 1);
+
+    // Nothing runs before a decision, and a decision is final
+    assert!(matches!(queue.begin(&held.id).await, Err(ApprovalError::WrongStatus { status: ApprovalStatus::Pending, .. })));
+    let approved = queue.decide(&held.id, true, "alice", Some("looks fine".to_string())).await.unwrap();
+    assert_eq!(approved.decided_by.as_deref(), Some("alice"));
+    assert!(queue.decide(&held.id, false, "bob", None).await.is_err());
+
+    // An approved call is claimed once
+    queue.begin(&held.id).await.unwrap();
+    assert!(queue.begin(&held.id).await.is_err());
+    let done = queue.complete(&held.id, Ok(json!({"message_id": "<abc@example.com>"}))).await.unwrap();
+    assert_eq!(done.status, ApprovalStatus::Completed);
+    assert_eq!(queue.get(&held.id).await.unwrap().unwrap().result.unwrap()["message_id"], "<abc@example.com>");
+
+    let rejected = queue.submit("assistant", "primary", "empty_folder", arguments(json!({"mailbox": "Trash", "dry_run": false}))).await.unwrap();
+    assert_eq!(queue.decide(&rejected.id, false, "alice", None).await.unwrap().status, ApprovalStatus::Rejected);
+    assert!(matches!(queue.decide("apr-missing", true, "alice", None).await, Err(ApprovalError::Unknown(_))));
+
+    // Calls nobody decides on expire
+    let hasty = ApprovalQueue::in_memory(Duration::ZERO);
+    let held = hasty.submit("assistant", "primary", "send_draft", arguments(json!({"draft_id": "8"}))).await.unwrap();
+    assert_eq!(hasty.get(&held.id).await.unwrap().unwrap().status, ApprovalStatus::Expired);
+    assert!(hasty.decide(&held.id, true, "alice", None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_store_is_shared_with_the_cli() {
+    let dir = std::env::temp_dir().join(format!("approval-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ApprovalConfig { store: Some(dir.join("approvals.json")), ..ApprovalConfig::default() };
+
+    let server = ApprovalQueue::open(&config).unwrap();
+    let held = server.submit("assistant", "primary", "trash_emails", arguments(json!({"email_ids": ["12"]}))).await.unwrap();
+
+    // The approvals command opens the same store in another process
+    let cli = ApprovalQueue::open(&config).unwrap();
+    assert_eq!(cli.list(Some(ApprovalStatus::Pending)).await.unwrap()[0].id, held.id);
+    cli.decide(&held.id, true, "cli:alice", None).await.unwrap();
+
+    assert_eq!(server.get(&held.id).await.unwrap().unwrap().status, ApprovalStatus::Approved);
+    server.begin(&held.id).await.unwrap();
+    assert_eq!(cli.get(&held.id).await.unwrap().unwrap().status, ApprovalStatus::Executing);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn test_approved_call_runs_without_being_asked_again() {
+    let imap = FakeImap::start(vec![
+        Mailbox::new("INBOX").with_emails(3),
+        Mailbox::new("Trash").special_use("\\Trash"),
+    ]).await;
+    let queue = ApprovalQueue::in_memory(Duration::from_secs(3600));
+    let config = Client::config(&sources(&imap).with_env("MCP_APPROVAL_TOOLS", "trash_emails"));
+    let client = Client::with_server(ImapMailboxServer::with_config(config).with_approvals(queue.clone())).await;
+
+    let held = client.call("trash_emails", json!({"email_ids": ["2"]})).await.unwrap();
+    assert_eq!(held["status"], "pending");
+    let id = held["approval_id"].as_str().unwrap();
+    let checked = client.call("get_approval", json!({"approval_id": id})).await.unwrap();
+    assert_eq!(checked["status"], "pending");
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [1, 2, 3]);
+
+    // Approving it runs it; get_approval only reports the result
+    queue.decide(id, true, "alice", None).await.unwrap();
+    let mut done = Value::Null;
+    for _ in 0..50 {
+        done = client.call("get_approval", json!({"approval_id": id})).await.unwrap();
+        if done["status"] == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(done["status"], "completed");
+    assert_eq!(done["result"]["succeeded"], 1);
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [1, 3]);
+    assert_eq!(imap.mailbox("Trash", |trash| trash.subjects()), ["Email 2"]);
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn test_http_endpoint_decides_with_the_approver_token() {
+    use axum::middleware;
+    use protonmail_mcp_server::access::TokenStore;
+    use protonmail_mcp_server::approval;
+    use protonmail_mcp_server::http_auth::{self, HttpAuth};
+    use std::sync::Arc;
+
+    let queue = ApprovalQueue::in_memory(Duration::from_secs(3600));
+    let held = queue.submit("assistant", "primary", "send_draft", arguments(json!({"draft_id": "7"}))).await.unwrap();
+    let mut approvers = TokenStore::default();
+    approvers.add_shared("approver", "approver-secret");
+    let router = approval::http_routes(queue.clone())
+        .layer(middleware::from_fn_with_state(Arc::new(HttpAuth::new(approvers)), http_auth::middleware));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/approvals", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move { axum::serve(listener, router).await.ok() });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let client = reqwest::Client::new();
+
+    assert_eq!(client.get(&base).send().await.unwrap().status(), 401);
+    let pending: Value = client.get(format!("{}?status=pending", base)).bearer_auth("approver-secret")
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(pending[0]["id"], held.id);
+
+    let response = client.post(format!("{}/{}/approve", base, held.id)).bearer_auth("approver-secret")
+        .json(&json!({"note": "ok to send"})).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let approved: Value = response.json().await.unwrap();
+    assert_eq!(approved["status"], "approved");
+    assert_eq!(approved["decided_by"], "approver");
+    assert_eq!(approved["note"], "ok to send");
+
+    let again = client.post(format!("{}/{}/reject", base, held.id)).bearer_auth("approver-secret").send().await.unwrap();
+    assert_eq!(again.status(), 409);
+    let missing = client.get(format!("{}/apr-missing", base)).bearer_auth("approver-secret").send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+
+    server.abort();
+}
@@ -0,0 +1,193 @@
+//! Tests for reading and writing mbox files and Maildir directories
+
+mod support;
+
+use std::io::Cursor;
+
+use protonmail_mcp_server::archive::{self, MboxFormat, MboxReader};
+use protonmail_mcp_server::imap::Flag;
+use serde_json::json;
+use support::{Client, FakeImap, Mailbox, sources};
+
+const MBOX: &str = "\
+From alice@example.com Sat Jan  3 01:05:34 2004
+From: alice@example.com
+Subject: First
+Status: RO
+X-Status: F
+
+Hello.
+>From the archive, with love.
+
+From bob@example.com Mon Feb 16 10:00:00 2004
+From: bob@example.com
+Subject: Second
+
+Bye.
+";
+
+#[test]
+fn test_mbox_splits_messages_and_unescapes_from_lines() {
+    let messages: Vec<_> = MboxReader::new(Cursor::new(MBOX), "test.mbox".to_string())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].source, "test.mbox#1");
+
+    let first = String::from_utf8(messages[0].content.clone()).unwrap();
+    assert!(first.starts_with("From: alice@example.com\r\n"));
+    assert!(first.contains("\r\nFrom the archive, with love.\r\n"));
+    assert!(!first.contains(">From"));
+    assert!(first.ends_with("love.\r\n"), "separator blank line should be dropped: {:?}", first);
+
+    let second = String::from_utf8(messages[1].content.clone()).unwrap();
+    assert!(second.contains("Subject: Second"));
+}
+
+#[test]
+fn test_mboxo_keeps_quoted_from_lines() {
+    let messages: Vec<_> = MboxReader::new(Cursor::new(MBOX), "test.mbox".to_string())
+        .with_format(MboxFormat::Mboxo)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let first = String::from_utf8(messages[0].content.clone()).unwrap();
+    assert!(first.contains("\r\n>From the archive, with love.\r\n"));
+}
+
+#[test]
+fn test_mbox_reads_dates_and_status_flags() {
+    let messages: Vec<_> = MboxReader::new(Cursor::new(MBOX), "test.mbox".to_string())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(messages[0].flags, vec![Flag::Seen, Flag::Flagged]);
+    assert!(messages[1].flags.is_empty());
+    assert_eq!(
+        messages[0].internal_date.unwrap().to_rfc3339(),
+        "2004-01-03T01:05:34+00:00"
+    );
+}
+
+#[test]
+fn test_maildir_reads_flags_from_filenames() {
+    let root = std::env::temp_dir().join(format!("maildir-test-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("cur")).unwrap();
+    std::fs::create_dir_all(root.join("new")).unwrap();
+    std::fs::write(root.join("cur/1000.M1.host:2,FS"), "Subject: Seen\n\nbody\n").unwrap();
+    std::fs::write(root.join("new/1001.M2.host"), "Subject: New\n\nbody\n").unwrap();
+
+    let files = archive::list_maildir(&root).unwrap();
+    assert_eq!(files.len(), 2);
+
+    let seen = archive::read_maildir_message(&files[0]).unwrap();
+    assert_eq!(seen.flags, vec![Flag::Flagged, Flag::Seen]);
+    assert_eq!(seen.content, b"Subject: Seen\r\n\r\nbody\r\n");
+    assert!(seen.internal_date.is_some());
+
+    let new = archive::read_maildir_message(&files[1]).unwrap();
+    assert!(new.flags.is_empty());
+
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_list_maildir_rejects_plain_directory() {
+    let root = std::env::temp_dir().join(format!("not-maildir-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    assert!(archive::list_maildir(&root).is_err());
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_mbox_entry_round_trips_through_reader() {
+    let content = b"From: carol@example.com\r\nSubject: Quoting\r\n\r\nFrom here on.\r\n>From before.\r\n";
+    let date = chrono::DateTime::parse_from_rfc3339("2024-05-06T07:08:09+02:00").unwrap();
+    let entry = archive::mbox_entry(Some(date), content);
+
+    let text = String::from_utf8(entry.clone()).unwrap();
+    assert!(text.starts_with("From carol@example.com Mon May  6 05:08:09 2024\n"));
+    assert!(text.contains("\n>From here on.\n>>From before.\n"));
+
+    let messages: Vec<_> = MboxReader::new(Cursor::new(entry), "out.mbox".to_string())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].content, content);
+    assert_eq!(messages[0].internal_date.unwrap().timestamp(), date.timestamp());
+}
+
+#[test]
+fn test_maildir_writer_encodes_flags_and_refuses_overwrite() {
+    let root = std::env::temp_dir().join(format!("maildir-export-{}", std::process::id()));
+    let date = chrono::DateTime::parse_from_rfc3339("2024-05-06T07:08:09+02:00").unwrap();
+    let flags = [Flag::Seen, Flag::Keyword("$label1".to_string()), Flag::Answered];
+
+    let path = archive::write_maildir_message(&root, "42.test", &flags, Some(date), b"Subject: Hi\r\n\r\nbody\r\n", false).unwrap();
+    assert_eq!(path, root.join("cur").join("1714972089.42.test:2,RS"));
+    assert!(root.join("new").is_dir() && root.join("tmp").is_dir());
+    assert_eq!(std::fs::read(&path).unwrap(), b"Subject: Hi\n\nbody\n");
+
+    let read_back = archive::read_maildir_message(&path).unwrap();
+    assert_eq!(read_back.flags, vec![Flag::Answered, Flag::Seen]);
+
+    // The same message is found whatever its flags and timestamp are now
+    let again = archive::write_maildir_message(&root, "42.test", &[Flag::Flagged], None, b"x", false);
+    assert_eq!(again.unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+    let replaced = archive::write_maildir_message(&root, "42.test", &[Flag::Flagged], Some(date), b"x", true).unwrap();
+    assert_eq!(archive::list_maildir(&root).unwrap(), [replaced]);
+
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn test_maildir_unique_names_follow_the_message_id() {
+    let unique = archive::maildir_unique(b"Message-ID: <a@example.com>\r\nSubject: Hi\r\n\r\nbody\r\n");
+    assert!(unique.ends_with(".protonmail-mcp"));
+    assert_eq!(unique, archive::maildir_unique(b"Subject: Other\r\nMessage-ID: <a@example.com>\r\n\r\n"));
+    assert_ne!(unique, archive::maildir_unique(b"Message-ID: <b@example.com>\r\n\r\nbody\r\n"));
+
+    // Without a Message-ID the content decides
+    let anonymous = archive::maildir_unique(b"Subject: Hi\r\n\r\nbody\r\n");
+    assert_eq!(anonymous, archive::maildir_unique(b"Subject: Hi\r\n\r\nbody\r\n"));
+    assert_ne!(anonymous, archive::maildir_unique(b"Subject: Hi\r\n\r\nother\r\n"));
+}
+
+#[tokio::test]
+async fn test_export_emails_writes_eml_and_mbox_files() {
+    let root = std::env::temp_dir().join(format!("export-tool-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX").with_emails(2)]).await;
+    let client = Client::start(&sources(&imap).with_env("MCP_EXPORT_DIR", &root.to_string_lossy())).await;
+
+    let exported = client.call("export_emails", json!({"format": "eml", "path": "eml", "email_ids": ["1", "2"]})).await.unwrap();
+    assert_eq!(exported["count"], 2);
+    assert_eq!(exported["files"][0]["path"], "eml/1.eml");
+    assert!(std::fs::read_to_string(root.join("eml/2.eml")).unwrap().contains("Subject: Email 2"));
+    // Nothing is replaced unless asked
+    let error = client.call("export_emails", json!({"format": "eml", "path": "eml", "email_ids": ["2"]})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["reason"], "already_exists");
+
+    let exported = client.call("export_emails", json!({"format": "mbox", "path": "inbox.mbox", "email_ids": ["1", "2"]})).await.unwrap();
+    assert_eq!(exported["files"][0]["email_ids"], json!(["1", "2"]));
+    let messages = MboxReader::open(&root.join("inbox.mbox")).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(messages.len(), 2);
+
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[tokio::test]
+async fn test_import_refuses_mailbox_names_with_line_breaks() {
+    use base64::Engine;
+
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX")]).await;
+    let client = Client::start(&sources(&imap)).await;
+    let content = base64::engine::general_purpose::STANDARD.encode("Subject: Hi\r\n\r\nbody\r\n");
+
+    let error = client.call("import_message", json!({"mailbox": "INBOX\r\nA1 DELETE INBOX", "content_base64": content}))
+        .await
+        .unwrap_err();
+    assert!(error.message.contains("line breaks are not allowed"), "{}", error.message);
+    assert!(imap.mailbox("INBOX", |inbox| inbox.messages.is_empty()));
+}
@@ -0,0 +1,128 @@
+//! Tests for the audit log
+
+mod support;
+
+use protonmail_mcp_server::ImapMailboxServer;
+use protonmail_mcp_server::access::{Caller, Scope};
+use protonmail_mcp_server::audit::{AuditConfig, AuditEntry, AuditLog, Outcome};
+use protonmail_mcp_server::policy::MailboxPolicy;
+use serde_json::{Value, json};
+use support::{Client, FakeImap, Mailbox, sources};
+
+fn arguments(value: Value) -> serde_json::Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn test_entry_redacts_secrets_and_collects_targets() {
+    let args = arguments(json!({
+        "email_ids": ["12", "15"],
+        "from_mailbox": "INBOX",
+        "to_mailbox": "Archive",
+        "password": "hunter2",
+        "draft": {"body": "x".repeat(500), "smtp_token": "abc"},
+    }));
+    let mut entry = AuditEntry::new("assistant", "move_emails", "primary", Some(&args));
+
+    assert_eq!(entry.uids, ["12", "15"]);
+    assert_eq!(entry.mailboxes, ["INBOX", "Archive"]);
+    assert_eq!(entry.arguments["password"], "[redacted]");
+    assert_eq!(entry.arguments["draft"]["smtp_token"], "[redacted]");
+    let body = entry.arguments["draft"]["body"].as_str().unwrap();
+    assert!(body.len() < 250 && body.ends_with("(500 chars)"));
+
+    entry.add_result(&json!({"report": {"message_id": "<abc@example.com>", "recipients": ["bob@example.com"]}}));
+    assert_eq!(entry.message_ids, ["<abc@example.com>"]);
+}
+
+#[test]
+fn test_log_appends_rotates_and_reloads() {
+    let dir = std::env::temp_dir().join(format!("audit-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.jsonl");
+    let config = AuditConfig { path: path.clone(), max_bytes: Some(600), keep: 2 };
+
+    let log = AuditLog::open(config.clone()).unwrap();
+    for i in 0..8 {
+        let mut entry = AuditEntry::new("assistant", "apply_tag", "primary", Some(&arguments(json!({"email_id": i.to_string()}))));
+        if i == 7 {
+            entry.outcome = Outcome::Error;
+            entry.error = Some("mailbox not found".to_string());
+        }
+        log.record(entry);
+    }
+
+    let current = std::fs::read_to_string(&path).unwrap();
+    assert!(current.len() <= 600);
+    let line: Value = serde_json::from_str(current.lines().last().unwrap()).unwrap();
+    assert_eq!(line["tool"], "apply_tag");
+    assert_eq!(line["outcome"], "error");
+    assert!(dir.join("audit.jsonl.1").exists());
+    assert!(!dir.join("audit.jsonl.3").exists());
+
+    let newest = log.recent(2, |_| true);
+    assert_eq!(newest[0].uids, ["7"]);
+    assert_eq!(newest[1].uids, ["6"]);
+    assert_eq!(log.recent(10, |entry| entry.outcome == Outcome::Error).len(), 1);
+
+    // A restarted server sees the entries of the current file
+    let reopened = AuditLog::open(config).unwrap();
+    assert_eq!(reopened.recent(1, |_| true)[0].uids, ["7"]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+fn caller(name: &str, scopes: &[Scope], mailboxes: Option<&[&str]>) -> Caller {
+    Caller {
+        name: name.to_string(),
+        scopes: scopes.to_vec(),
+        accounts: Some(vec!["primary".to_string()]),
+        mailbox_policy: MailboxPolicy { allow: mailboxes.map(|mailboxes| mailboxes.iter().map(ToString::to_string).collect()) },
+        rate_limits: None,
+    }
+}
+
+#[test]
+fn test_callers_see_their_own_entries_unless_admin() {
+    let moved = AuditEntry::new("assistant", "move_emails", "primary", Some(&arguments(json!({"from_mailbox": "INBOX", "to_mailbox": "Archive"}))));
+    let other_account = AuditEntry::new("assistant", "apply_tag", "support", Some(&arguments(json!({"mailbox": "INBOX"}))));
+
+    assert!(moved.visible_to(&caller("assistant", &[Scope::Read], None)));
+    assert!(!moved.visible_to(&caller("reporting", &[Scope::Read], None)));
+    assert!(moved.visible_to(&caller("auditor", &[Scope::Read, Scope::Admin], None)));
+    // The admin scope does not widen the accounts and mailboxes a token may use
+    assert!(!other_account.visible_to(&caller("auditor", &[Scope::Read, Scope::Admin], None)));
+    assert!(!moved.visible_to(&caller("assistant", &[Scope::Read], Some(&["INBOX"]))));
+    assert!(moved.visible_to(&caller("assistant", &[Scope::Read], Some(&["INBOX", "Archive"]))));
+}
+
+#[tokio::test]
+async fn test_audit_log_hides_mailboxes_outside_the_allow_list() {
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX")]).await;
+    let audit = AuditLog::in_memory();
+    for mailbox in ["INBOX", "Private"] {
+        audit.record(AuditEntry::new("local", "apply_tag", "primary", Some(&arguments(json!({"mailbox": mailbox})))));
+    }
+    let config = Client::config(&sources(&imap).with_env("MCP_MAILBOXES_ALLOW", "INBOX"));
+    let client = Client::with_server(ImapMailboxServer::with_config(config).with_audit_log(audit)).await;
+
+    let log = client.call("get_audit_log", json!({})).await.unwrap();
+    assert_eq!(log["count"], 1);
+    assert_eq!(log["entries"][0]["mailboxes"], json!(["INBOX"]));
+}
+
+#[tokio::test]
+async fn test_attachment_saves_by_path_alone_are_audited() {
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX").with_emails(1)]).await;
+    let audit = AuditLog::in_memory();
+    let config = Client::config(&sources(&imap));
+    let client = Client::with_server(ImapMailboxServer::with_config(config).with_audit_log(audit)).await;
+
+    // Only calls that could write a file are recorded, whether or not they succeed
+    let _ = client.call("get_attachment", json!({"email_id": "1", "part_id": "2"})).await;
+    let _ = client.call("get_attachment", json!({"email_id": "1", "part_id": "2", "save_path": "report.pdf"})).await;
+
+    let log = client.call("get_audit_log", json!({})).await.unwrap();
+    assert_eq!(log["count"], 1);
+    assert_eq!(log["entries"][0]["tool"], "get_attachment");
+}
@@ -0,0 +1,136 @@
+//! Tests for sender authentication results and spoofing signals
+
+use mail_parser::MessageParser;
+use protonmail_mcp_server::authentication::{self, ResultsOrigin, SignalKind};
+
+const AUTHENTICATED: &str = "\
+Authentication-Results: mail.protonmail.ch; dkim=pass (2048-bit key)\r
+ header.d=news.example.com header.i=@news.example.com header.b=\"abc\";\r
+ spf=pass (mail.protonmail.ch: domain of bounce@mailer.example.com designates 1.2.3.4 as permitted sender)\r
+ smtp.mailfrom=bounce@mailer.example.com; dmarc=pass (p=reject dis=none) header.from=example.com;\r
+ arc=none\r
+Authentication-Results: attacker.example; dkim=pass header.d=paypal.com\r
+DKIM-Signature: v=1; a=rsa-sha256; d=news.example.com; s=sel1;\r
+ h=from:to:subject; bh=xyz; b=abc\r
+From: Example News <news@example.com>\r
+To: me@proton.me\r
+Subject: Hello\r
+\r
+Body\r
+";
+
+#[test]
+fn test_trusted_authentication_results_are_parsed_and_aligned() {
+    let message = MessageParser::default().parse(AUTHENTICATED.as_bytes()).unwrap();
+    let auth = authentication::analyze(&message, Some("me@proton.me"), Some("Mail.ProtonMail.ch"));
+
+    assert_eq!(auth.from_domain.as_deref(), Some("example.com"));
+    assert_eq!(auth.authserv_id.as_deref(), Some("mail.protonmail.ch"));
+    assert_eq!(auth.results, ResultsOrigin::Trusted);
+    assert!(auth.authenticated);
+
+    let spf = auth.spf.unwrap();
+    assert_eq!(spf.result, "pass");
+    assert_eq!(spf.domain.as_deref(), Some("mailer.example.com"));
+    assert!(spf.aligned);
+
+    // The second Authentication-Results header is from another server and is ignored
+    assert_eq!(auth.dkim.len(), 1);
+    assert_eq!(auth.dkim[0].domain.as_deref(), Some("news.example.com"));
+    assert!(auth.dkim[0].aligned);
+
+    let dmarc = auth.dmarc.unwrap();
+    assert_eq!(dmarc.result, "pass");
+    assert_eq!(dmarc.domain.as_deref(), Some("example.com"));
+    assert_eq!(auth.arc.unwrap().result.as_deref(), Some("none"));
+
+    assert_eq!(auth.dkim_signatures.len(), 1);
+    assert_eq!(auth.dkim_signatures[0].selector.as_deref(), Some("sel1"));
+    assert!(auth.signals.is_empty(), "{:?}", auth.signals);
+}
+
+#[test]
+fn test_results_from_untrusted_servers_are_unknown() {
+    let message = MessageParser::default().parse(AUTHENTICATED.as_bytes()).unwrap();
+    for trusted in [None, Some("mx.example.org")] {
+        let auth = authentication::analyze(&message, Some("me@proton.me"), trusted);
+        assert_eq!(auth.results, ResultsOrigin::Unknown);
+        assert!(!auth.authenticated);
+        assert!(auth.authserv_id.is_none() && auth.spf.is_none() && auth.dkim.is_empty() && auth.dmarc.is_none());
+        // Signatures and signals describe the message itself, not a server's verdict
+        assert_eq!(auth.dkim_signatures.len(), 1);
+    }
+
+    // A sender-written header with the trusted id is only believed when topmost
+    let forged = AUTHENTICATED.replace("attacker.example", "mail.protonmail.ch").replace("Authentication-Results: mail.protonmail.ch; dkim=pass (2048", "Authentication-Results: mail.protonmail.ch; dkim=fail (2048");
+    let message = MessageParser::default().parse(forged.as_bytes()).unwrap();
+    let auth = authentication::analyze(&message, None, Some("mail.protonmail.ch"));
+    assert_eq!(auth.dkim[0].result, "fail");
+}
+
+#[test]
+fn test_received_spf_and_arc_seals_are_read() {
+    let raw = "\
+ARC-Seal: i=2; a=rsa-sha256; cv=pass; d=lists.example.org; s=arc; b=x\r
+ARC-Seal: i=1; a=rsa-sha256; cv=none; d=example.net; s=arc; b=y\r
+Received-SPF: pass receiver=mx.attacker.example; envelope-from=a@example.net;\r
+Received-SPF: softfail (example.org: transitioning domain of a@example.net does not designate 5.6.7.8)\r
+ client-ip=5.6.7.8; envelope-from=a@example.net; helo=mx.example.net; receiver=mx.example.org;\r
+From: a@example.net\r
+Subject: List post\r
+\r
+Body\r
+";
+    let message = MessageParser::default().parse(raw.as_bytes()).unwrap();
+    let auth = authentication::analyze(&message, None, Some("mx.example.org"));
+
+    assert_eq!(auth.results, ResultsOrigin::Trusted);
+    let spf = auth.spf.unwrap();
+    assert_eq!(spf.result, "softfail");
+    assert_eq!(spf.domain.as_deref(), Some("example.net"));
+    assert!(!auth.authenticated);
+
+    let arc = auth.arc.unwrap();
+    assert_eq!(arc.instances, 2);
+    assert_eq!(arc.chain_validation.as_deref(), Some("pass"));
+    assert_eq!(arc.sealer.as_deref(), Some("lists.example.org"));
+}
+
+#[test]
+fn test_spoofing_signals() {
+    let signals = |from: &str, extra: &str| {
+        let raw = format!("From: {}\r\n{}Subject: Urgent\r\n\r\nPay now\r\n", from, extra);
+        let message = MessageParser::default().parse(raw.as_bytes()).unwrap();
+        authentication::analyze(&message, Some("me@acme-corp.com"), None).signals
+    };
+
+    let shown = signals("\"ceo@acme-corp.com\" <ceo@gmail.com>", "");
+    assert_eq!(shown.len(), 1);
+    assert_eq!(shown[0].kind, SignalKind::DisplayNameMismatch);
+
+    let brand = signals("PayPal Support <service@secure-billing.net>", "");
+    assert_eq!(brand[0].kind, SignalKind::DisplayNameMismatch);
+
+    for domain in ["paypa1.com", "acme-corp.co", "paypal.com.verify.net", "xn--pypal-4ve.com", "acrne-corp.com"] {
+        let found = signals(&format!("Billing <billing@{}>", domain), "");
+        assert!(
+            found.iter().any(|s| s.kind == SignalKind::LookalikeDomain),
+            "{} should look like a protected domain: {:?}",
+            domain,
+            found
+        );
+    }
+
+    let reply = signals("Bob <bob@partner.com>", "Reply-To: bob@partner-invoices.com\r\n");
+    assert_eq!(reply[0].kind, SignalKind::ReplyToMismatch);
+
+    assert!(signals("Pineapple Deliveries <orders@pineapple.shop>", "").is_empty());
+    assert!(signals("PayPal <service@mail.paypal.com>", "Reply-To: help@paypal.com\r\n").is_empty());
+}
+
+#[test]
+fn test_organizational_domain_handles_two_label_suffixes() {
+    assert_eq!(authentication::organizational_domain("mail.example.co.uk"), "example.co.uk");
+    assert_eq!(authentication::organizational_domain("a.b.example.com"), "example.com");
+    assert_eq!(authentication::organizational_domain("Example.COM."), "example.com");
+}
@@ -0,0 +1,136 @@
+//! Tests for parsing iMIP invitations and building replies
+
+use chrono::TimeZone;
+use mail_parser::MessageParser;
+use protonmail_mcp_server::calendar::{self, ReplyStatus};
+use protonmail_mcp_server::smtp::{CalendarPart, OutgoingMessage};
+
+const INVITE: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example//Calendar//EN\r
+METHOD:REQUEST\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:planning-42@example.com\r
+SEQUENCE:2\r
+DTSTAMP:20240110T120000Z\r
+DTSTART;TZID=Europe/Berlin:20240115T090000\r
+DTEND;TZID=Europe/Berlin:20240115T100000\r
+SUMMARY:Quarterly planning\\, Q1\r
+LOCATION:Room 4\\; second floor\r
+DESCRIPTION:Agenda:\\n1. Budget\\n2. Hiring plans for the next two quarter\r
+ s and beyond\r
+ORGANIZER;CN=\"Doe, Jane\":mailto:jane@example.com\r
+ATTENDEE;CN=Me;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:Me@Example.com\r
+ATTENDEE;PARTSTAT=ACCEPTED:mailto:bob@example.com\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Reminder\r
+TRIGGER:-PT15M\r
+END:VALARM\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+#[test]
+fn test_parse_invite_reads_event_properties() {
+    let invite = calendar::parse(INVITE).unwrap();
+    assert_eq!(invite.method.as_deref(), Some("REQUEST"));
+    assert_eq!(invite.events.len(), 1);
+
+    let event = &invite.events[0];
+    assert_eq!(event.uid, "planning-42@example.com");
+    assert_eq!(event.sequence, 2);
+    assert_eq!(event.summary.as_deref(), Some("Quarterly planning, Q1"));
+    assert_eq!(event.location.as_deref(), Some("Room 4; second floor"));
+    assert_eq!(
+        event.description.as_deref(),
+        Some("Agenda:\n1. Budget\n2. Hiring plans for the next two quarters and beyond")
+    );
+
+    let start = event.start.as_ref().unwrap();
+    assert_eq!(start.value, "2024-01-15T09:00:00");
+    assert_eq!(start.timezone.as_deref(), Some("Europe/Berlin"));
+    assert!(!start.all_day);
+
+    let organizer = event.organizer.as_ref().unwrap();
+    assert_eq!(organizer.email, "jane@example.com");
+    assert_eq!(organizer.name.as_deref(), Some("Doe, Jane"));
+
+    assert_eq!(event.attendees.len(), 2);
+    assert_eq!(event.attendees[0].email, "Me@Example.com");
+    assert_eq!(event.attendees[0].status.as_deref(), Some("NEEDS-ACTION"));
+    assert!(event.attendees[0].rsvp);
+    assert!(!event.attendees[1].rsvp);
+}
+
+#[test]
+fn test_parse_handles_utc_and_all_day_times() {
+    let text = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:a\nDTSTART:20240301T140000Z\nEND:VEVENT\n\
+BEGIN:VEVENT\nUID:b\nDTSTART;VALUE=DATE:20240302\nEND:VEVENT\nEND:VCALENDAR\n";
+    let invite = calendar::parse(text).unwrap();
+    assert_eq!(invite.method, None);
+
+    let utc = invite.events[0].start.as_ref().unwrap();
+    assert_eq!(utc.value, "2024-03-01T14:00:00Z");
+    assert_eq!(utc.timezone.as_deref(), Some("UTC"));
+
+    let all_day = invite.events[1].start.as_ref().unwrap();
+    assert_eq!(all_day.value, "2024-03-02");
+    assert!(all_day.all_day);
+
+    assert!(calendar::parse("BEGIN:VCALENDAR\nEND:VCALENDAR\n").is_none());
+}
+
+#[test]
+fn test_build_reply_copies_event_and_sets_partstat() {
+    let invite = calendar::parse(INVITE).unwrap();
+    let event = &invite.events[0];
+    let now = chrono::Utc.with_ymd_and_hms(2024, 1, 11, 8, 30, 0).unwrap();
+    let reply = calendar::build_reply(&invite, event, &event.attendees[0], ReplyStatus::Tentative, Some("Might be late, sorry"), now);
+
+    assert!(reply.lines().all(|line| line.len() <= 76), "lines must be folded: {}", reply);
+    assert!(reply.contains("METHOD:REPLY\r\n"));
+    assert!(reply.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\n"));
+    assert!(reply.contains("UID:planning-42@example.com\r\n"));
+    assert!(reply.contains("SEQUENCE:2\r\n"));
+    assert!(reply.contains("DTSTAMP:20240111T083000Z\r\n"));
+    assert!(reply.contains("DTSTART;TZID=Europe/Berlin:20240115T090000\r\n"));
+    assert!(reply.contains("ORGANIZER;CN=\"Doe, Jane\":mailto:jane@example.com\r\n"));
+    assert!(reply.contains("ATTENDEE;PARTSTAT=TENTATIVE;CN=\"Me\":mailto:Me@Example.com\r\n"));
+    assert!(reply.contains("COMMENT:Might be late\\, sorry\r\n"));
+    assert!(!reply.contains("bob@example.com"));
+    assert!(!reply.contains("VALARM"));
+
+    let parsed = calendar::parse(&reply).unwrap();
+    assert_eq!(parsed.method.as_deref(), Some("REPLY"));
+    assert_eq!(parsed.events[0].attendees[0].status.as_deref(), Some("TENTATIVE"));
+}
+
+#[test]
+fn test_invite_round_trips_through_calendar_message() {
+    let message = OutgoingMessage {
+        from: "jane@example.com".to_string(),
+        to: vec!["me@example.com".to_string()],
+        subject: "Invitation: Quarterly planning".to_string(),
+        text_body: "You are invited.".to_string(),
+        calendar: Some(CalendarPart { method: "REQUEST".to_string(), content: INVITE.to_string() }),
+        ..Default::default()
+    };
+    let raw = message.to_rfc822().unwrap();
+    let text = String::from_utf8_lossy(&raw);
+    assert!(text.contains("multipart/alternative"));
+    assert!(text.contains("method=\"REQUEST\"") || text.contains("method=REQUEST"));
+
+    let parsed = MessageParser::default().parse(&raw).unwrap();
+    assert_eq!(parsed.body_text(0).as_deref().map(str::trim), Some("You are invited."));
+    let invite = calendar::find_invite(&parsed).unwrap();
+    assert_eq!(invite.events[0].uid, "planning-42@example.com");
+}
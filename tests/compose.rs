@@ -0,0 +1,69 @@
+//! Tests for composing outgoing messages
+
+use mail_parser::MessageParser;
+use protonmail_mcp_server::smtp::OutgoingMessage;
+
+fn sample() -> OutgoingMessage {
+    OutgoingMessage {
+        from: "me@example.com".to_string(),
+        to: vec!["alice@example.com".to_string()],
+        cc: vec!["bob@example.com".to_string()],
+        bcc: vec!["carol@example.com".to_string()],
+        subject: "Quarterly report".to_string(),
+        text_body: "Draft for review.".to_string(),
+        html_body: None,
+        in_reply_to: Some("<parent@example.com>".to_string()),
+        calendar: None,
+    }
+}
+
+#[test]
+fn test_outgoing_message_renders_headers_and_body() {
+    let raw = sample().to_rfc822().unwrap();
+    let parsed = MessageParser::default().parse(&raw).unwrap();
+
+    assert_eq!(parsed.subject(), Some("Quarterly report"));
+    assert_eq!(parsed.from().and_then(|a| a.first()).and_then(|a| a.address()), Some("me@example.com"));
+    assert_eq!(parsed.to().and_then(|a| a.first()).and_then(|a| a.address()), Some("alice@example.com"));
+    assert_eq!(parsed.cc().and_then(|a| a.first()).and_then(|a| a.address()), Some("bob@example.com"));
+    assert_eq!(parsed.bcc().and_then(|a| a.first()).and_then(|a| a.address()), Some("carol@example.com"));
+    assert_eq!(parsed.in_reply_to().as_text(), Some("parent@example.com"));
+    assert!(parsed.message_id().is_some());
+    assert_eq!(parsed.body_text(0).as_deref().map(str::trim), Some("Draft for review."));
+}
+
+#[test]
+fn test_outgoing_message_without_recipients_still_renders() {
+    let message = OutgoingMessage {
+        from: "me@example.com".to_string(),
+        subject: "Notes".to_string(),
+        ..Default::default()
+    };
+    let raw = String::from_utf8(message.to_rfc822().unwrap()).unwrap();
+    assert!(!raw.contains("\r\nTo:"));
+    assert!(raw.contains("Subject: Notes"));
+}
+
+#[test]
+fn test_outgoing_message_keeps_display_names_apart_from_addresses() {
+    let message = OutgoingMessage {
+        from: "Me <me@example.com>".to_string(),
+        to: vec!["Alice <alice@example.com>".to_string(), "\"Bob B.\" <bob@example.com>".to_string()],
+        subject: "Notes".to_string(),
+        ..Default::default()
+    };
+    let raw = message.to_rfc822().unwrap();
+    let parsed = MessageParser::default().parse(&raw).unwrap();
+
+    let from = parsed.from().and_then(|a| a.first()).unwrap();
+    assert_eq!(from.name(), Some("Me"));
+    assert_eq!(from.address(), Some("me@example.com"));
+    let to: Vec<_> = parsed.to().unwrap().iter().map(|a| (a.name(), a.address())).collect();
+    assert_eq!(
+        to,
+        vec![
+            (Some("Alice"), Some("alice@example.com")),
+            (Some("Bob B."), Some("bob@example.com")),
+        ]
+    );
+}
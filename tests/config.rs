@@ -0,0 +1,159 @@
+//! Tests for loading settings from a config file, the environment and flags
+
+use protonmail_mcp_server::access::TokenStore;
+use protonmail_mcp_server::config::{self, ConfigError, Sources, load_config_from};
+use secrecy::ExposeSecret;
+
+const FILE: &str = r#"
+[server]
+transport = "http"
+http_bind = "0.0.0.0:9000"
+max_destructive_count = 20
+approvals.tools = ["send_draft", "move_emails:INBOX"]
+
+[server.tools]
+deny = ["delete_emails"]
+
+[server.rate_limits]
+send = "5/hour"
+
+[[server.tokens]]
+name = "reader"
+token = "reader-secret"
+scopes = ["read"]
+
+[[accounts]]
+name = "personal"
+imap = { host = "127.0.0.1", port = 1143, username = "me@proton.me", password = "bridge-password", authserv_id = "mail.protonmail.ch" }
+smtp.from = "Me <me@proton.me>"
+mailboxes_allow = ["INBOX", "Archive"]
+
+[[accounts]]
+name = "support"
+imap.username = "support@proton.me"
+imap.password = "support-password"
+"#;
+
+fn file() -> Sources {
+    Sources::default().with_file_contents(FILE, "config.toml").unwrap()
+}
+
+#[test]
+fn test_file_holds_every_kind_of_setting() {
+    let config = load_config_from(&file()).unwrap();
+    assert_eq!(config.server.transport, "http");
+    assert_eq!(config.server.http_bind, "0.0.0.0:9000");
+    assert_eq!(config.server.max_destructive_count, 20);
+    assert_eq!(config.server.tool_policy.deny, ["delete_emails"]);
+    assert_eq!(config.server.approvals.rules.len(), 2);
+    assert!(config.server.rate_limits.send.is_some());
+
+    let names: Vec<&str> = config.accounts.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["personal", "support"]);
+    let personal = config.primary();
+    assert_eq!(personal.imap.password.expose_secret(), "bridge-password");
+    assert_eq!(personal.smtp.from, "Me <me@proton.me>");
+    assert_eq!(personal.mailbox_policy.allow.as_deref(), Some(&["INBOX".to_string(), "Archive".to_string()][..]));
+    // Named accounts inherit the primary account's servers, as with env vars
+    assert_eq!(config.accounts[1].imap.host, "127.0.0.1");
+    assert_eq!(config.accounts[1].imap.user, "support@proton.me");
+    assert_eq!(config.accounts[1].imap.authserv_id.as_deref(), Some("mail.protonmail.ch"));
+
+    let mut tokens = TokenStore::default();
+    tokens.add_entries(config.server.auth_tokens).unwrap();
+    assert!(tokens.authenticate("reader-secret", chrono::Utc::now()).is_ok());
+}
+
+#[test]
+fn test_example_file_loads() {
+    let sources = Sources::default().with_file(std::path::Path::new("config.example.toml")).unwrap();
+    let config = load_config_from(&sources).unwrap();
+    assert_eq!(config.accounts.len(), 2);
+    assert_eq!(config.server.auth_tokens.len(), 2);
+}
+
+#[test]
+fn test_env_overrides_file_and_flags_override_env() {
+    let sources = file().with_env("MCP_HTTP_BIND", "127.0.0.1:9100").with_env("IMAP_PORT", "1144");
+    let config = load_config_from(&sources).unwrap();
+    assert_eq!(config.server.http_bind, "127.0.0.1:9100");
+    assert_eq!(config.primary().imap.port, 1144);
+
+    let sources = sources.with_flag("MCP_HTTP_BIND", "bind", "127.0.0.1:9200").with_flag("MCP_TRANSPORT", "transport", "stdio");
+    let config = load_config_from(&sources).unwrap();
+    assert_eq!(config.server.http_bind, "127.0.0.1:9200");
+    assert_eq!(config.server.transport, "stdio");
+    // Settings nobody overrides still come from the file
+    assert_eq!(config.server.max_destructive_count, 20);
+}
+
+#[test]
+fn test_errors_name_the_offending_key() {
+    let error = |text: &str| Sources::default().with_file_contents(text, "config.toml").unwrap_err().to_string();
+    assert_eq!(error("[server]\ntransprot = \"http\""), "Unknown setting server.transprot in config.toml");
+    assert_eq!(error("[[accounts]]\nimap.hots = \"x\""), "Unknown setting accounts[0].imap.hots in config.toml");
+    assert_eq!(error("[logging]\nlevel = 1"), "Unknown setting logging in config.toml");
+    assert!(error("[server]\nmax_destructive_count = [1]").contains("server.max_destructive_count in config.toml"));
+    assert!(error("[[accounts]]\n[[accounts]]\nimap.host = \"x\"").contains("accounts[1].name in config.toml"));
+    assert!(error("[server\n").starts_with("Could not read config file config.toml"));
+
+    let invalid = |sources: Sources| match load_config_from(&sources) {
+        Err(ConfigError::InvalidValue(key, _)) => key,
+        other => panic!("expected an invalid value, got {:?}", other.map(|_| ())),
+    };
+    assert_eq!(invalid(file().with_flag("IMAP_PORT", "imap-port", "abc")), "--imap-port");
+    assert_eq!(invalid(file().with_env("MCP_MAX_DESTRUCTIVE_COUNT", "0")), "MCP_MAX_DESTRUCTIVE_COUNT");
+    let in_file = "[server]\nmax_destructive_count = 0\n[[accounts]]\nimap = { host = \"h\", username = \"u\", password = \"p\" }";
+    let sources = Sources::default().with_file_contents(in_file, "config.toml").unwrap();
+    assert_eq!(invalid(sources), "server.max_destructive_count in config.toml");
+
+    // Booleans are strict, so a typo does not quietly turn a setting off
+    assert_eq!(invalid(file().with_env("MCP_READ_ONLY", "treu")), "MCP_READ_ONLY");
+    assert_eq!(invalid(file().with_env("ACCOUNT_SUPPORT_SMTP_USE_TLS", "on")), "ACCOUNT_SUPPORT_SMTP_USE_TLS");
+    let config = load_config_from(&file().with_env("MCP_READ_ONLY", " Yes").with_env("IMAP_USE_TLS", "0")).unwrap();
+    assert!(config.server.tool_policy.read_only);
+    assert!(!config.primary().imap.use_tls);
+
+    // Inline tokens are checked when the config loads, not when the HTTP server starts
+    let tokens = "[[server.tokens]]\nname = \"a\"\ntoken = \"x\"\nscopes = []\n[[accounts]]\nimap = { host = \"h\", username = \"u\", password = \"p\" }";
+    let sources = Sources::default().with_file_contents(tokens, "config.toml").unwrap();
+    assert_eq!(invalid(sources), "server.tokens in config.toml");
+
+    let missing = load_config_from(&Sources::default()).unwrap_err().to_string();
+    assert!(missing.contains("IMAP_HOST (or --imap-host, or accounts[0].imap.host in the config file)"), "{}", missing);
+}
+
+#[test]
+fn test_named_account_on_another_host_does_not_inherit_bridge_smtp() {
+    let other_host = file().with_env("ACCOUNT_SUPPORT_IMAP_HOST", "imap.example.com");
+    let config = load_config_from(&other_host).unwrap();
+    let support = &config.accounts[1];
+    assert_eq!(support.smtp.host, "imap.example.com");
+    assert!(support.smtp.use_tls);
+    assert_eq!(support.smtp.port, 465);
+
+    let config = load_config_from(&other_host.with_env("ACCOUNT_SUPPORT_SMTP_USE_TLS", "false")).unwrap();
+    assert!(!config.accounts[1].smtp.use_tls);
+    assert_eq!(config.accounts[1].smtp.port, 587);
+
+    // On the primary's host the account shares its Bridge
+    let config = load_config_from(&file()).unwrap();
+    assert!(!config.accounts[1].smtp.use_tls);
+    assert_eq!(config.accounts[1].smtp.port, config.primary().smtp.port);
+}
+
+#[test]
+fn test_check_reports_missing_files_and_unknown_tools() {
+    assert!(config::check(&load_config_from(&file()).unwrap(), &file()).is_empty());
+
+    let sources = file()
+        .with_env("MCP_TOOLS_ALLOW", "list_mailboxes, search_emails, fetch_everything")
+        .with_flag("MCP_EXPORT_DIR", "export-dir", "/nonexistent/export")
+        .with_env("MCP_TRANSPORT", "carrier-pigeon");
+    let problems = config::check(&load_config_from(&sources).unwrap(), &sources);
+    assert!(problems.contains(&"MCP_TRANSPORT: unknown transport 'carrier-pigeon', use stdio or http".to_string()), "{:?}", problems);
+    assert!(problems.contains(&"MCP_TOOLS_ALLOW: unknown tool 'fetch_everything'".to_string()), "{:?}", problems);
+    assert!(problems.contains(&"--export-dir: /nonexistent/export is not a directory".to_string()), "{:?}", problems);
+    // The approval rules name tools the allow list now disables
+    assert!(problems.contains(&"server.approvals.tools in config.toml: tool 'send_draft' is disabled by the tool policy".to_string()), "{:?}", problems);
+}
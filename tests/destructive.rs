@@ -0,0 +1,102 @@
+//! Tests for trash_emails, delete_emails and empty_folder
+
+mod support;
+
+use serde_json::json;
+use support::{Client, FakeImap, Mailbox, sources};
+
+async fn mail_server() -> FakeImap {
+    FakeImap::start(vec![
+        Mailbox::new("INBOX").with_emails(5),
+        Mailbox::new("Archive"),
+        Mailbox::new("Trash").special_use("\\Trash"),
+    ]).await
+}
+
+#[tokio::test]
+async fn test_email_ids_are_validated_and_deduplicated() {
+    let imap = mail_server().await;
+    let client = Client::start(&sources(&imap)).await;
+
+    for bad in ["abc", "0", "-1"] {
+        let error = client.call("delete_emails", json!({"email_ids": ["1", bad]})).await.unwrap_err();
+        let data = error.data.unwrap();
+        assert_eq!(data["field"], "email_ids[1]");
+        assert_eq!(data["reason"], "invalid_format");
+    }
+
+    let preview = client.call("delete_emails", json!({"email_ids": ["2", " 2", "3"]})).await.unwrap();
+    assert_eq!(preview["requested"], 2);
+    assert_eq!(preview["count"], 2);
+}
+
+#[tokio::test]
+async fn test_dry_runs_change_nothing() {
+    let imap = mail_server().await;
+    let client = Client::start(&sources(&imap)).await;
+
+    let preview = client.call("trash_emails", json!({"email_ids": ["1", "2", "99"], "dry_run": true})).await.unwrap();
+    assert_eq!(preview["dry_run"], true);
+    assert_eq!(preview["destination"], "Trash");
+    assert_eq!(preview["count"], 2);
+    assert_eq!(preview["not_found"], json!(["99"]));
+    assert_eq!(preview["emails"][0]["subject"], "Email 1");
+
+    // delete_emails and empty_folder are dry runs unless told otherwise
+    let preview = client.call("delete_emails", json!({"email_ids": ["3"]})).await.unwrap();
+    assert_eq!(preview["count"], 1);
+    let preview = client.call("empty_folder", json!({"mailbox": "INBOX"})).await.unwrap();
+    assert_eq!(preview["count"], 5);
+
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [1, 2, 3, 4, 5]);
+    assert!(imap.mailbox("Trash", |trash| trash.messages.is_empty()));
+}
+
+#[tokio::test]
+async fn test_max_count_limits_each_call() {
+    let imap = mail_server().await;
+    let client = Client::start(&sources(&imap).with_env("MCP_MAX_DESTRUCTIVE_COUNT", "2")).await;
+
+    let error = client.call("trash_emails", json!({"email_ids": ["1", "2", "3"]})).await.unwrap_err();
+    let data = error.data.unwrap();
+    assert_eq!(data["reason"], "too_many");
+    assert_eq!(data["value"], 3);
+    let error = client.call("empty_folder", json!({"mailbox": "INBOX", "dry_run": false})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["reason"], "too_many");
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()).len(), 5);
+
+    // A dry run previews the first max_count emails and counts only those
+    let preview = client.call("delete_emails", json!({"email_ids": ["1", "2", "3", "4"]})).await.unwrap();
+    assert_eq!(preview["requested"], 4);
+    assert_eq!(preview["count"], 2);
+    assert_eq!(preview["max_count"], 2);
+    assert_eq!(preview["emails"].as_array().unwrap().len(), 2);
+    let preview = client.call("delete_emails", json!({"email_ids": ["1", "98", "99", "4"]})).await.unwrap();
+    assert_eq!(preview["count"], 1);
+    assert_eq!(preview["not_found"], json!(["98"]));
+}
+
+#[tokio::test]
+async fn test_trash_and_delete() {
+    let imap = mail_server().await;
+    let client = Client::start(&sources(&imap)).await;
+
+    let trashed = client.call("trash_emails", json!({"email_ids": ["1", "2", "99"]})).await.unwrap_err();
+    let data = trashed.data.unwrap();
+    assert_eq!(data["succeeded"], 2);
+    assert_eq!(data["failed"], 1);
+    // Each result names the UID the email got in Trash, or why it was not moved
+    let results = data["results"].as_array().unwrap();
+    assert_eq!(results.iter().map(|r| r["new_email_id"].clone()).collect::<Vec<_>>(), [json!("1"), json!("2"), json!(null)]);
+    assert_eq!(results[2]["error"], "Email not found in mailbox 'INBOX'");
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [3, 4, 5]);
+    assert_eq!(imap.mailbox("Trash", |trash| trash.subjects()), ["Email 1", "Email 2"]);
+
+    let deleted = client.call("delete_emails", json!({"email_ids": ["4"], "dry_run": false})).await.unwrap();
+    assert_eq!(deleted["succeeded"], 1);
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [3, 5]);
+
+    let emptied = client.call("empty_folder", json!({"mailbox": "Trash", "dry_run": false})).await.unwrap();
+    assert_eq!(emptied["succeeded"], 2);
+    assert!(imap.mailbox("Trash", |trash| trash.messages.is_empty()));
+}
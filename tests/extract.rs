@@ -0,0 +1,92 @@
+//! Tests for attachment text extraction
+
+#![cfg(feature = "extract")]
+
+use std::io::{Cursor, Write};
+
+use protonmail_mcp_server::extract::{self, DocumentKind, ExtractError};
+
+fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in entries {
+        writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_detects_kind_from_type_then_extension() {
+    assert_eq!(DocumentKind::detect("application/pdf", None), Some(DocumentKind::Pdf));
+    assert_eq!(DocumentKind::detect("text/calendar; method=REQUEST", None), Some(DocumentKind::Calendar));
+    assert_eq!(DocumentKind::detect("application/octet-stream", Some("Report.XLSX")), Some(DocumentKind::Xlsx));
+    assert_eq!(DocumentKind::detect("application/octet-stream", Some("photo.jpg")), None);
+}
+
+#[test]
+fn test_extracts_docx_paragraphs() {
+    let document = br#"<?xml version="1.0"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:r><w:t>Invoice &amp; terms</w:t></w:r></w:p>
+<w:p><w:r><w:t>Total:</w:t><w:tab/><w:t>42 EUR</w:t></w:r></w:p>
+</w:body></w:document>"#;
+    let docx = zip_of(&[("word/document.xml", document)]);
+
+    let extracted = extract::extract_text(
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("invoice.docx"),
+        &docx,
+    )
+    .unwrap();
+    assert_eq!(extracted.format, DocumentKind::Docx);
+    assert!(extracted.text.contains("Invoice & terms\n"), "{:?}", extracted.text);
+    assert!(extracted.text.contains("Total:\t42 EUR\n"), "{:?}", extracted.text);
+    assert!(!extracted.truncated);
+}
+
+#[test]
+fn test_extracts_supported_zip_entries() {
+    let archive = zip_of(&[("data/rates.csv", b"a,b\n1,2\n"), ("logo.png", &[0x89, b'P', b'N', b'G'])]);
+
+    let extracted = extract::extract_text("application/zip", Some("bundle.zip"), &archive).unwrap();
+    assert!(extracted.text.contains("== data/rates.csv ==\na,b\n1,2"));
+    assert!(extracted.text.contains("== logo.png ==\n(not extracted: unsupported type)"));
+}
+
+#[test]
+fn test_unfolds_calendar_lines() {
+    let ics = b"BEGIN:VEVENT\r\nSUMMARY:Quarterly planning with a very long\r\n  title\r\nEND:VEVENT\r\n";
+    let extracted = extract::extract_text("text/calendar", None, ics).unwrap();
+    assert!(extracted.text.contains("SUMMARY:Quarterly planning with a very long title\r\n"));
+}
+
+#[test]
+fn test_rejects_unsupported_and_oversized_input() {
+    assert!(matches!(
+        extract::extract_text("image/png", Some("photo.png"), b"\x89PNG"),
+        Err(ExtractError::Unsupported(_))
+    ));
+
+    let huge = vec![b'a'; DocumentKind::Text.size_limit() + 1];
+    assert!(matches!(
+        extract::extract_text("text/plain", None, &huge),
+        Err(ExtractError::TooLarge { kind: DocumentKind::Text, .. })
+    ));
+}
+
+#[test]
+fn test_spreadsheet_that_inflates_too_far_is_rejected() {
+    let sheet = vec![b' '; 51 * 1024 * 1024];
+    let xlsx = zip_of(&[("xl/worksheets/sheet1.xml", &sheet)]);
+    assert!(xlsx.len() < DocumentKind::Xlsx.size_limit());
+
+    let error = extract::extract_text("application/octet-stream", Some("bomb.xlsx"), &xlsx).unwrap_err();
+    assert!(matches!(&error, ExtractError::Failed(DocumentKind::Xlsx, _)), "{}", error);
+    assert!(error.to_string().contains("expand to more than"), "{}", error);
+}
+
+#[test]
+fn test_malformed_pdf_is_an_error_not_a_panic() {
+    let result = extract::extract_text("application/pdf", None, b"%PDF-1.4 not really");
+    assert!(matches!(result, Err(ExtractError::Failed(DocumentKind::Pdf, _))));
+}
@@ -0,0 +1,55 @@
+//! Tests for IMAP flag parsing and serialization
+
+use protonmail_mcp_server::imap::Flag;
+
+#[test]
+fn test_system_flags_round_trip() {
+    for flag in [Flag::Seen, Flag::Answered, Flag::Flagged, Flag::Deleted, Flag::Draft] {
+        let parsed: Flag = flag.to_string().parse().unwrap();
+        assert_eq!(parsed, flag);
+    }
+    assert_eq!("\\Recent".parse::<Flag>().unwrap(), Flag::Recent);
+    assert_eq!("\\*".parse::<Flag>().unwrap(), Flag::MayCreate);
+}
+
+#[test]
+fn test_system_flags_parse_case_insensitively() {
+    assert_eq!("\\SEEN".parse::<Flag>().unwrap(), Flag::Seen);
+    assert_eq!("\\flagged".parse::<Flag>().unwrap(), Flag::Flagged);
+}
+
+#[test]
+fn test_keywords_round_trip_verbatim() {
+    for raw in ["$label1", "$Junk", "NonJunk", "\\Important"] {
+        let flag: Flag = raw.parse().unwrap();
+        assert_eq!(flag, Flag::Keyword(raw.to_string()));
+        assert_eq!(flag.to_string(), raw);
+    }
+}
+
+#[test]
+fn test_invalid_flags_are_rejected() {
+    for raw in ["", "\\", "two words", "Custom(\"$label1\")", "a)b", "x\"y", "50%", "with*star"] {
+        assert!(raw.parse::<Flag>().is_err(), "{:?} should be rejected", raw);
+    }
+}
+
+#[test]
+fn test_flag_serializes_as_wire_string() {
+    let flags = vec![Flag::Seen, Flag::Keyword("$label1".to_string())];
+    let json = serde_json::to_string(&flags).unwrap();
+    assert_eq!(json, r#"["\\Seen","$label1"]"#);
+}
+
+#[test]
+fn test_async_imap_flags_convert() {
+    use async_imap::types::Flag as ImapFlag;
+    use std::borrow::Cow;
+
+    assert_eq!(Flag::from(&ImapFlag::Deleted), Flag::Deleted);
+    assert_eq!(Flag::from(&ImapFlag::MayCreate), Flag::MayCreate);
+    assert_eq!(
+        Flag::from(&ImapFlag::Custom(Cow::Borrowed("$label1"))),
+        Flag::Keyword("$label1".to_string())
+    );
+}
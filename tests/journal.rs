@@ -0,0 +1,160 @@
+//! Tests for the undo journal and undo_operation
+
+mod support;
+
+use protonmail_mcp_server::ImapMailboxServer;
+use protonmail_mcp_server::journal::{Change, Journal, JournalConfig, MovedEmail};
+use serde_json::json;
+use std::sync::Arc;
+use support::{Client, FakeImap, Mailbox, sources};
+
+fn moved(uids: &[(&str, &str)]) -> Change {
+    Change::Move {
+        from_mailbox: "INBOX".to_string(),
+        to_mailbox: "Archive".to_string(),
+        emails: uids.iter()
+            .map(|(old, new)| MovedEmail { email_id: old.to_string(), new_email_id: new.to_string(), uid_validity: None })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_journal_is_bounded_and_tracks_undo() {
+    let journal = Journal::in_memory(2);
+    let first = journal.record("local", "primary", "move_emails", moved(&[("12", "301")]));
+    let second = journal.record("local", "primary", "apply_tag", Change::Flag {
+        mailbox: "INBOX".to_string(),
+        email_id: "12".to_string(),
+        flag: "\\Flagged".to_string(),
+        added: true,
+        was_set: false,
+    });
+    assert_ne!(first, second);
+    assert_eq!(journal.get(&first).unwrap().tool, "move_emails");
+
+    let third = journal.record("local", "primary", "move_email", moved(&[("14", "302")]));
+    assert!(journal.get(&first).is_none(), "the oldest operation is forgotten");
+    assert!(journal.get(&third).is_some());
+
+    journal.mark_undone(&second);
+    assert!(journal.get(&second).unwrap().undone_at.is_some());
+    assert!(journal.get(&third).unwrap().undone_at.is_none());
+}
+
+#[test]
+fn test_journal_survives_restart() {
+    let dir = std::env::temp_dir().join(format!("journal-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = JournalConfig { path: Some(dir.join("undo.json")), capacity: 10 };
+
+    let journal = Journal::open(config.clone()).unwrap();
+    let id = journal.record("assistant", "primary", "move_emails", moved(&[("12", "301"), ("15", "302")]));
+    journal.update(&id, moved(&[("15", "302")]));
+
+    let reopened = Journal::open(config).unwrap();
+    let operation = reopened.get(&id).unwrap();
+    assert_eq!(operation.caller, "assistant");
+    assert_eq!(operation.change, moved(&[("15", "302")]));
+    assert!(operation.undone_at.is_none());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+async fn journaled_client() -> (FakeImap, Arc<Journal>, Client) {
+    let imap = FakeImap::start(vec![
+        Mailbox::new("INBOX").with_emails(4),
+        Mailbox::new("Archive").with_emails(2),
+        Mailbox::new("Trash").special_use("\\Trash"),
+    ]).await;
+    imap.mailbox("Archive", |archive| archive.uid_validity = 7);
+    let journal = Journal::in_memory(10);
+    let server = ImapMailboxServer::with_config(Client::config(&sources(&imap))).with_journal(journal.clone());
+    let client = Client::with_server(server).await;
+    (imap, journal, client)
+}
+
+#[tokio::test]
+async fn test_moves_journal_new_uids_and_uid_validity() {
+    let (_imap, journal, client) = journaled_client().await;
+
+    // UID MOVE reports the new UIDs as ranges, e.g. COPYUID 1 1:3 1:3
+    let trashed = client.call("trash_emails", json!({"email_ids": ["1", "2", "3"]})).await.unwrap();
+    let operation = journal.get(trashed["operation_id"].as_str().unwrap()).unwrap();
+    let Change::Move { to_mailbox, emails, .. } = operation.change else { panic!("not a move") };
+    assert_eq!(to_mailbox, "Trash");
+    let pairs: Vec<(&str, &str)> = emails.iter().map(|email| (email.email_id.as_str(), email.new_email_id.as_str())).collect();
+    assert_eq!(pairs, [("1", "1"), ("2", "2"), ("3", "3")]);
+    assert!(emails.iter().all(|email| email.uid_validity == Some(1)));
+
+    // UID COPY reports them in the tagged OK
+    let moved = client.call("move_email", json!({"email_id": "4", "from_mailbox": "INBOX", "to_mailbox": "Archive"})).await.unwrap();
+    assert_eq!(moved["new_email_id"], "3");
+    let operation = journal.get(moved["operation_id"].as_str().unwrap()).unwrap();
+    let Change::Move { emails, .. } = operation.change else { panic!("not a move") };
+    assert_eq!(emails, [MovedEmail { email_id: "4".to_string(), new_email_id: "3".to_string(), uid_validity: Some(7) }]);
+}
+
+#[tokio::test]
+async fn test_undo_moves_emails_back() {
+    let (imap, journal, client) = journaled_client().await;
+
+    let moved = client.call("move_emails", json!({"email_ids": ["2", "3"], "from_mailbox": "INBOX", "to_mailbox": "Archive"})).await.unwrap();
+    let operation_id = moved["operation_id"].as_str().unwrap();
+    assert_eq!(imap.mailbox("Archive", |archive| archive.uids()), [1, 2, 3, 4]);
+
+    // Another client flagged an archived email \Deleted without expunging it
+    imap.mailbox("Archive", |archive| archive.messages[0].flags.push("\\Deleted".to_string()));
+
+    let undone = client.call("undo_operation", json!({"operation_id": operation_id})).await.unwrap();
+    assert_eq!(undone["results"].as_array().unwrap().len(), 2);
+    assert_eq!(imap.mailbox("Archive", |archive| archive.uids()), [1, 2]);
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.subjects()), ["Email 1", "Email 4", "Email 2", "Email 3"]);
+    assert!(journal.get(operation_id).unwrap().undone_at.is_some());
+
+    let again = client.call("undo_operation", json!({"operation_id": operation_id})).await.unwrap_err();
+    assert_eq!(again.data.unwrap()["reason"], "already_undone");
+}
+
+#[tokio::test]
+async fn test_undo_refuses_when_uid_validity_changed() {
+    let (imap, journal, client) = journaled_client().await;
+
+    let moved = client.call("move_emails", json!({"email_ids": ["1"], "from_mailbox": "INBOX", "to_mailbox": "Archive"})).await.unwrap();
+    let operation_id = moved["operation_id"].as_str().unwrap();
+    // The mailbox was recreated, so UID 3 may now be a different email
+    imap.mailbox("Archive", |archive| archive.uid_validity = 8);
+
+    let error = client.call("undo_operation", json!({"operation_id": operation_id})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["reason"], "uid_validity_changed");
+    assert_eq!(imap.mailbox("Archive", |archive| archive.uids()), [1, 2, 3]);
+    assert!(journal.get(operation_id).unwrap().undone_at.is_none());
+}
+
+#[tokio::test]
+async fn test_undo_restores_flags() {
+    let (imap, journal, client) = journaled_client().await;
+    let flags = |uid: u32| imap.mailbox("INBOX", |inbox| inbox.messages.iter().find(|message| message.uid == uid).unwrap().flags.clone());
+
+    let tagged = client.call("apply_tag", json!({"email_id": "2", "tag": "\\Flagged"})).await.unwrap();
+    let operation_id = tagged["operation_id"].as_str().unwrap();
+    assert_eq!(journal.get(operation_id).unwrap().change, Change::Flag {
+        mailbox: "INBOX".to_string(),
+        email_id: "2".to_string(),
+        flag: "\\Flagged".to_string(),
+        added: true,
+        was_set: false,
+    });
+    assert_eq!(flags(2), ["\\Flagged"]);
+    client.call("undo_operation", json!({"operation_id": operation_id})).await.unwrap();
+    assert!(flags(2).is_empty());
+
+    // Adding a flag the email already had leaves it set on undo
+    client.call("apply_tag", json!({"email_id": "3", "tag": "\\Seen"})).await.unwrap();
+    let again = client.call("apply_tag", json!({"email_id": "3", "tag": "\\Seen"})).await.unwrap();
+    let undone = client.call("undo_operation", json!({"operation_id": again["operation_id"]})).await.unwrap();
+    assert!(undone["message"].as_str().unwrap().starts_with("Nothing to revert"));
+    assert_eq!(flags(3), ["\\Seen"]);
+
+    let error = client.call("undo_operation", json!({"operation_id": "op-missing"})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["reason"], "unknown_operation");
+}
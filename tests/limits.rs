@@ -0,0 +1,162 @@
+//! Tests for rate limits and per-account concurrency limits
+
+mod support;
+
+use protonmail_mcp_server::ImapMailboxServer;
+use protonmail_mcp_server::access::{Scope, TokenStore};
+use protonmail_mcp_server::limits::{Limits, RateLimit, RateLimiter, RateLimits};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use support::{Client, FakeImap, Mailbox, sources};
+
+#[test]
+fn test_rate_limit_parsing() {
+    assert_eq!("60/min".parse::<RateLimit>().unwrap(), RateLimit { requests: 60, per: Duration::from_secs(60) });
+    assert_eq!(" 5 / s ".parse::<RateLimit>().unwrap().per, Duration::from_secs(1));
+    assert_eq!("1000/hour".parse::<RateLimit>().unwrap().to_string(), "1000/hour");
+    assert!("0/min".parse::<RateLimit>().is_err());
+    assert!("60".parse::<RateLimit>().is_err());
+    assert!("60/day".parse::<RateLimit>().is_err());
+}
+
+#[test]
+fn test_token_bucket_allows_burst_then_refills() {
+    let limiter = RateLimiter::default();
+    let limit: RateLimit = "3/min".parse().unwrap();
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        assert!(limiter.check("alice/read", limit, start).is_ok());
+    }
+    assert_eq!(limiter.check("alice/read", limit, start), Err(Duration::from_secs(20)));
+    // Other callers have their own bucket
+    assert!(limiter.check("bob/read", limit, start).is_ok());
+
+    // One call's worth refills every 20 seconds
+    assert!(limiter.check("alice/read", limit, start + Duration::from_secs(20)).is_ok());
+    assert!(limiter.check("alice/read", limit, start + Duration::from_secs(20)).is_err());
+    for _ in 0..3 {
+        assert!(limiter.check("alice/read", limit, start + Duration::from_secs(120)).is_ok());
+    }
+}
+
+#[test]
+fn test_refilled_buckets_are_dropped() {
+    let limiter = RateLimiter::default();
+    let limit: RateLimit = "3/min".parse().unwrap();
+    let start = Instant::now();
+
+    assert!(limiter.check("alice/read", limit, start).is_ok());
+    assert!(limiter.check("bob/read", limit, start).is_ok());
+    assert_eq!(limiter.tracked(), 2);
+
+    // Once both have refilled, the next sweep forgets them
+    assert!(limiter.check("carol/read", limit, start + Duration::from_secs(90)).is_ok());
+    assert_eq!(limiter.tracked(), 1);
+    for _ in 0..3 {
+        assert!(limiter.check("alice/read", limit, start + Duration::from_secs(90)).is_ok());
+    }
+}
+
+#[test]
+fn test_tool_categories_and_token_overrides() {
+    let rates = RateLimits { read: Some("2/min".parse().unwrap()), write: Some("1/min".parse().unwrap()), send: None };
+    let limits = Limits::new(rates, None, 4);
+
+    assert!(limits.check_tool("alice", None, Scope::Read).is_ok());
+    assert!(limits.check_tool("alice", None, Scope::Read).is_ok());
+    let throttled = limits.check_tool("alice", None, Scope::Read).unwrap_err();
+    assert_eq!(throttled.limit, "read");
+    assert_eq!(throttled.retry_after_secs(), 30);
+
+    // Deletes count against the write rate; sending is unlimited here
+    assert!(limits.check_tool("alice", None, Scope::Write).is_ok());
+    assert_eq!(limits.check_tool("alice", None, Scope::Delete).unwrap_err().limit, "write");
+    for _ in 0..10 {
+        assert!(limits.check_tool("alice", None, Scope::Send).is_ok());
+    }
+
+    let store = TokenStore::parse(r#"{"tokens": [
+        {"name": "batch", "token": "batch-secret", "scopes": ["read"], "rate_limits": {"read": "100/min"}}
+    ]}"#).unwrap();
+    let caller = store.caller_named("batch", chrono::Utc::now()).unwrap();
+    for _ in 0..50 {
+        assert!(limits.check_tool(&caller.name, caller.rate_limits.as_ref(), Scope::Read).is_ok());
+    }
+    assert!(TokenStore::parse(r#"{"tokens": [
+        {"name": "bad", "token": "x", "scopes": ["read"], "rate_limits": {"read": "lots"}}
+    ]}"#).is_err());
+}
+
+#[test]
+fn test_account_concurrency_limit() {
+    let limits = Limits::new(RateLimits::default(), None, 2);
+
+    let first = limits.try_acquire_account("primary").unwrap();
+    let _second = limits.try_acquire_account("primary").unwrap();
+    let throttled = limits.try_acquire_account("primary").unwrap_err();
+    assert_eq!(throttled.limit, "account");
+    assert_eq!(throttled.retry_after_secs(), 1);
+    assert!(limits.try_acquire_account("support").is_ok());
+
+    drop(first);
+    assert!(limits.try_acquire_account("primary").is_ok());
+}
+
+#[tokio::test]
+async fn test_busy_account_does_not_use_up_the_rate() {
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX")]).await;
+    let limits = Limits::new(RateLimits { read: Some("1/min".parse().unwrap()), ..Default::default() }, None, 1);
+    let server = ImapMailboxServer::with_config(Client::config(&sources(&imap))).with_limits(limits.clone());
+    let client = Client::with_server(server).await;
+
+    let busy = limits.try_acquire_account("primary").unwrap();
+    let error = client.call("list_mailboxes", json!({})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["limit"], "account");
+    drop(busy);
+
+    assert!(client.call("list_mailboxes", json!({})).await.is_ok());
+    let error = client.call("list_mailboxes", json!({})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["limit"], "read");
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn test_http_requests_over_the_rate_get_429() {
+    use axum::{Extension, Router, middleware, routing::post};
+    use protonmail_mcp_server::access::Caller;
+    use protonmail_mcp_server::http_auth::{self, HttpAuth};
+    use protonmail_mcp_server::limits;
+    use std::sync::Arc;
+
+    let mut tokens = TokenStore::default();
+    tokens.add_shared("alice", "alice-secret");
+    tokens.add_shared("bob", "bob-secret");
+    let auth = Arc::new(HttpAuth::new(tokens));
+    let limits = Limits::new(RateLimits::default(), Some("2/min".parse().unwrap()), 4);
+
+    let router = Router::new()
+        .route("/mcp", post(|Extension(caller): Extension<Caller>| async move { caller.name }))
+        .layer(middleware::from_fn_with_state(limits, limits::http_middleware))
+        .layer(middleware::from_fn_with_state(auth, http_auth::middleware));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = format!("http://{}/mcp", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move { axum::serve(listener, router).await.ok() });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let client = reqwest::Client::new();
+
+    for _ in 0..2 {
+        let response = client.post(&addr).bearer_auth("alice-secret").send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let response = client.post(&addr).bearer_auth("alice-secret").send().await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["retry-after"], "30");
+
+    // Unauthenticated requests are rejected before they use up anyone's rate
+    assert_eq!(client.post(&addr).send().await.unwrap().status(), 401);
+    let response = client.post(&addr).bearer_auth("bob-secret").send().await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "bob");
+
+    server.abort();
+}
@@ -0,0 +1,156 @@
+//! Tests for locating attachments through BODYSTRUCTURE
+
+use async_imap::imap_proto::{AttributeValue, BodyStructure, Response};
+use protonmail_mcp_server::imap::mime;
+
+fn parse_structure(fetch: &[u8]) -> BodyStructure<'_> {
+    let (_, response) = Response::from_bytes(fetch).unwrap();
+    match response {
+        Response::Fetch(_, attributes) => attributes
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                AttributeValue::BodyStructure(structure) => Some(structure),
+                _ => None,
+            })
+            .unwrap(),
+        other => panic!("unexpected response {:?}", other),
+    }
+}
+
+const MIXED: &[u8] = b"* 1 FETCH (BODYSTRUCTURE (\
+((\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 12 1 NIL NIL NIL)\
+(\"text\" \"html\" (\"charset\" \"utf-8\") NIL NIL \"quoted-printable\" 40 2 NIL NIL NIL) \"alternative\" (\"boundary\" \"b2\") NIL NIL)\
+(\"image\" \"png\" (\"name\" \"image.png\") \"<logo@example>\" NIL \"base64\" 1000 NIL (\"inline\" (\"filename\" \"image.png\")) NIL)\
+(\"image\" \"png\" (\"name\" \"image.png\") NIL NIL \"base64\" 2000 NIL (\"attachment\" (\"filename\" \"image.png\")) NIL)\
+(\"application\" \"octet-stream\" NIL NIL NIL \"base64\" 30 NIL NIL NIL)\
+(\"application\" \"pdf\" NIL NIL NIL \"base64\" 500 NIL (\"attachment\" (\"filename*\" \"utf-8''R%C3%A9sum%C3%A9.pdf\")) NIL) \
+\"mixed\" (\"boundary\" \"b1\") NIL NIL))\r\n";
+
+#[test]
+fn test_attachments_get_part_ids_and_metadata() {
+    let structure = parse_structure(MIXED);
+    let attachments = mime::attachments(&structure);
+
+    let ids: Vec<&str> = attachments.iter().map(|a| a.part_id.as_str()).collect();
+    assert_eq!(ids, ["2", "3", "4", "5"]);
+
+    assert_eq!(attachments[0].filename.as_deref(), Some("image.png"));
+    assert_eq!(attachments[0].content_type, "image/png");
+    assert_eq!(attachments[0].disposition.as_deref(), Some("inline"));
+    assert_eq!(attachments[0].content_id.as_deref(), Some("logo@example"));
+    assert_eq!(attachments[1].size, 2000);
+    assert_eq!(attachments[1].disposition.as_deref(), Some("attachment"));
+
+    // Unnamed parts are still listed
+    assert_eq!(attachments[2].filename, None);
+    assert_eq!(attachments[2].content_type, "application/octet-stream");
+
+    assert_eq!(attachments[3].filename.as_deref(), Some("Résumé.pdf"));
+}
+
+#[test]
+fn test_text_bodies_are_parts_but_not_attachments() {
+    let structure = parse_structure(MIXED);
+    let parts = mime::parts(&structure);
+
+    let html = parts.iter().find(|part| part.info.part_id == "1.2").unwrap();
+    assert!(!html.is_attachment);
+    assert_eq!(html.info.encoding, "quoted-printable");
+}
+
+#[test]
+fn test_single_part_message_is_part_one() {
+    let fetch = b"* 1 FETCH (BODYSTRUCTURE (\"application\" \"pdf\" (\"name\" \"=?utf-8?B?w6l0w6kucGRm?=\") NIL NIL \"base64\" 42 NIL NIL NIL))\r\n";
+    let structure = parse_structure(fetch);
+    let attachments = mime::attachments(&structure);
+
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].part_id, "1");
+    assert_eq!(attachments[0].filename.as_deref(), Some("été.pdf"));
+}
+
+#[test]
+fn test_part_ids_and_transfer_decoding() {
+    assert_eq!(mime::parse_part_id("1.2.3"), Some(vec![1, 2, 3]));
+    assert_eq!(mime::parse_part_id("0"), None);
+    assert_eq!(mime::parse_part_id("1..2"), None);
+    assert_eq!(mime::parse_part_id("TEXT"), None);
+
+    assert_eq!(mime::decode_transfer_encoding("base64", b"aGVs\r\nbG8="), b"hello");
+    assert_eq!(mime::decode_transfer_encoding("quoted-printable", b"caf=C3=A9"), "café".as_bytes());
+    assert_eq!(mime::decode_transfer_encoding("7bit", b"plain"), b"plain");
+}
+
+const FORWARDED: &[u8] = b"* 1 FETCH (BODYSTRUCTURE (\
+(\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 20 1 NIL NIL NIL)\
+(\"message\" \"rfc822\" NIL NIL NIL \"7bit\" 400 \
+(NIL \"Original\" ((\"Bob\" NIL \"bob\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) \
+((\"text\" \"plain\" NIL NIL NIL \"7bit\" 10 1 NIL NIL NIL)\
+(\"application\" \"pdf\" NIL NIL NIL \"base64\" 100 NIL (\"attachment\" (\"filename\" \"invoice.pdf\")) NIL) \
+\"mixed\" (\"boundary\" \"inner\") NIL NIL) 12 NIL (\"attachment\" (\"filename\" \"Original.eml\")) NIL) \
+\"mixed\" (\"boundary\" \"outer\") NIL NIL))\r\n";
+
+const FORWARDED_SOURCE: &str = "\
+From: alice@example.com\r
+To: carol@example.com\r
+Subject: Fwd: Original\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+--outer\r
+Content-Type: text/plain\r
+\r
+See the forwarded mail\r
+--outer\r
+Content-Type: message/rfc822\r
+Content-Disposition: attachment; filename=\"Original.eml\"\r
+\r
+From: bob@example.com\r
+To: alice@example.com\r
+Subject: Original\r
+Content-Type: multipart/mixed; boundary=\"inner\"\r
+\r
+--inner\r
+Content-Type: text/plain\r
+\r
+Invoice attached\r
+--inner\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=\"invoice.pdf\"\r
+Content-Transfer-Encoding: base64\r
+\r
+JVBERi0=\r
+--inner--\r
+--outer--\r
+";
+
+#[test]
+fn test_forwarded_message_parts_are_numbered_inside_it() {
+    let structure = parse_structure(FORWARDED);
+    let parts = mime::parts(&structure);
+
+    let top = mime::attachments_in(&parts, None);
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].part_id, "2");
+    assert_eq!(top[0].content_type, "message/rfc822");
+    assert_eq!(top[0].filename.as_deref(), Some("Original.eml"));
+
+    let inner = mime::attachments_in(&parts, Some("2"));
+    assert_eq!(inner.len(), 1);
+    assert_eq!(inner[0].part_id, "2.2");
+    assert_eq!(inner[0].filename.as_deref(), Some("invoice.pdf"));
+
+    let body = parts.iter().find(|part| part.info.part_id == "2.1").unwrap();
+    assert!(!body.is_attachment);
+}
+
+#[test]
+fn test_nested_messages_match_bodystructure_numbering() {
+    let parsed = mail_parser::MessageParser::default().parse(FORWARDED_SOURCE.as_bytes()).unwrap();
+    let nested = mime::nested_messages(&parsed, None);
+
+    assert_eq!(nested.len(), 1);
+    let (part_id, message) = &nested[0];
+    assert_eq!(part_id, "2");
+    assert_eq!(message.subject(), Some("Original"));
+    assert!(mime::nested_messages(message, Some(part_id)).is_empty());
+}
@@ -0,0 +1,147 @@
+//! Tests for tool and mailbox restrictions
+
+mod support;
+
+use protonmail_mcp_server::policy::{self, MailboxPolicy, ToolPolicy};
+use rmcp::model::{Tool, ToolAnnotations};
+use serde_json::json;
+use std::sync::Arc;
+use support::{Client, FakeImap, Mailbox, sources};
+
+fn tool(name: &'static str, read_only: bool) -> Tool {
+    Tool::new(name, "", Arc::new(Default::default())).annotate(ToolAnnotations::new().read_only(read_only))
+}
+
+#[test]
+fn test_read_only_mode_keeps_tools_that_do_not_change_the_mailbox() {
+    let policy = ToolPolicy { read_only: true, ..Default::default() };
+    assert!(policy.permits(&tool("get_email", true)));
+    assert!(!policy.permits(&tool("move_emails", false)));
+    assert!(!policy.permits(&tool("send_draft", false)));
+    // get_attachment stays but refuses to save; exports always write files
+    assert!(policy.permits(&tool("get_attachment", false)));
+    assert!(!policy.permits(&tool("export_emails", false)));
+}
+
+#[tokio::test]
+async fn test_read_only_mode_refuses_to_save_attachments() {
+    let imap = FakeImap::start(vec![Mailbox::new("INBOX").with_emails(1)]).await;
+    let client = Client::start(&sources(&imap).with_env("MCP_READ_ONLY", "true")).await;
+    for save in [json!({"save": true}), json!({"save_path": "invoice.pdf"})] {
+        let mut arguments = json!({"mailbox": "INBOX", "email_id": "1", "part_id": "2"});
+        arguments.as_object_mut().unwrap().extend(save.as_object().unwrap().clone());
+        let error = client.call("get_attachment", arguments).await.unwrap_err();
+        assert_eq!(error.data.unwrap()["reason"], "read_only");
+    }
+}
+
+#[tokio::test]
+async fn test_drafts_and_trash_follow_the_mailbox_allow_list() {
+    let imap = FakeImap::start(vec![
+        Mailbox::new("INBOX").with_emails(2),
+        Mailbox::new("Drafts").special_use("\\Drafts").with_emails(1),
+        Mailbox::new("Trash").special_use("\\Trash"),
+    ]).await;
+    let client = Client::start(&sources(&imap).with_env("MCP_MAILBOXES_ALLOW", "INBOX")).await;
+
+    let draft = json!({"to": ["bob@example.com"], "subject": "Hi", "body": "Hello"});
+    let mut update = draft.clone();
+    update["email_id"] = json!("1");
+    for (tool, arguments) in [
+        ("list_drafts", json!({})),
+        ("save_draft", draft),
+        ("update_draft", update),
+        ("send_draft", json!({"email_id": "1"})),
+        ("trash_emails", json!({"mailbox": "INBOX", "email_ids": ["1"]})),
+    ] {
+        let error = client.call(tool, arguments).await.unwrap_err();
+        let data = error.data.unwrap();
+        assert_eq!(data["reason"], "not_allowed", "{}", tool);
+    }
+    assert_eq!(imap.mailbox("INBOX", |inbox| inbox.uids()), [1, 2]);
+    assert_eq!(imap.mailbox("Drafts", |drafts| drafts.uids()), [1]);
+
+    let client = Client::start(&sources(&imap).with_env("MCP_MAILBOXES_ALLOW", "INBOX, Drafts, Trash")).await;
+    assert_eq!(client.call("list_drafts", json!({})).await.unwrap()["count"], 1);
+    assert_eq!(client.call("trash_emails", json!({"mailbox": "INBOX", "email_ids": ["1"]})).await.unwrap()["succeeded"], 1);
+}
+
+#[tokio::test]
+async fn test_restricted_accounts_search_their_mailboxes() {
+    let imap = FakeImap::start(vec![
+        Mailbox::new("INBOX").with_emails(2),
+        Mailbox::new("Folders/Support").with_emails(1),
+    ]).await;
+    let client = Client::start(&sources(&imap).with_env("MCP_MAILBOXES_ALLOW", "Folders/Support")).await;
+
+    // The default mailbox is INBOX, which only matters without mailboxes
+    let error = client.call("search_emails", json!({"query": "Email"})).await.unwrap_err();
+    assert_eq!(error.data.unwrap()["reason"], "not_allowed");
+    for mailboxes in [json!(["Folders/Support"]), json!(["*"])] {
+        let found = client.call("search_emails", json!({"query": "Email", "mailboxes": mailboxes})).await.unwrap();
+        assert_eq!(found["mailboxes"], json!(["Folders/Support"]));
+        assert_eq!(found["count"], 1);
+    }
+}
+
+#[test]
+fn test_allow_and_deny_lists() {
+    let policy = ToolPolicy {
+        read_only: false,
+        allow: Some(policy::parse_list(" get_email, search_emails,,move_email ")),
+        deny: policy::parse_list("move_email"),
+    };
+    assert!(policy.permits(&tool("get_email", true)));
+    assert!(policy.permits(&tool("search_emails", true)));
+    assert!(!policy.permits(&tool("list_mailboxes", true)));
+    assert!(!policy.permits(&tool("move_email", false)), "deny wins over allow");
+
+    let known = vec!["get_email".to_string(), "search_emails".to_string(), "move_email".to_string()];
+    assert!(policy.unknown_tools(&known).is_empty());
+    let typo = ToolPolicy { deny: vec!["delete_email".to_string()], ..Default::default() };
+    assert_eq!(typo.unknown_tools(&known), vec!["delete_email"]);
+}
+
+#[test]
+fn test_mailbox_allow_list_covers_subfolders() {
+    let policy = MailboxPolicy { allow: Some(policy::parse_list("INBOX,Folders/Support")) };
+    assert!(policy.permits("INBOX"));
+    assert!(policy.permits("inbox"));
+    assert!(policy.permits("Folders/Support"));
+    assert!(policy.permits("Folders/Support/Escalations"));
+    assert!(!policy.permits("Folders/Support-Archive"));
+    assert!(!policy.permits("Folders/support"));
+    assert!(!policy.permits("Folders"));
+    assert!(!policy.permits("Sent"));
+
+    assert!(MailboxPolicy::default().permits("Anything"));
+}
+
+#[test]
+fn test_search_targets_prefer_all_mail() {
+    use protonmail_mcp_server::imap::{MailboxEntry, SpecialUse};
+
+    let entry = |name: &str, selectable: bool, special_use: Option<SpecialUse>| MailboxEntry {
+        name: name.to_string(),
+        selectable,
+        special_use,
+    };
+    let entries = vec![
+        entry("INBOX", true, None),
+        entry("All Mail", true, Some(SpecialUse::All)),
+        entry("Starred", true, Some(SpecialUse::Flagged)),
+        entry("Trash", true, Some(SpecialUse::Trash)),
+        entry("Spam", true, Some(SpecialUse::Junk)),
+        entry("Folders", false, None),
+        entry("Folders/Support", true, None),
+    ];
+
+    let open = MailboxPolicy::default();
+    assert_eq!(open.search_targets(&entries, false), vec!["All Mail"]);
+    assert_eq!(open.search_targets(&entries, true), vec!["INBOX", "Trash", "Spam", "Folders/Support"]);
+
+    // All Mail would reach outside the allow-list, so permitted folders are searched one by one
+    let restricted = MailboxPolicy { allow: Some(policy::parse_list("INBOX,Folders/Support,Trash")) };
+    assert_eq!(restricted.search_targets(&entries, false), vec!["INBOX", "Folders/Support"]);
+    assert_eq!(restricted.search_targets(&entries, true), vec!["INBOX", "Trash", "Folders/Support"]);
+}
@@ -0,0 +1,131 @@
+//! Tests for the shared IMAP connection pool
+
+use protonmail_mcp_server::imap::pool::{Pool, PoolConnection};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// A stand-in connection that counts how often it was opened and closed
+struct FakeConnection {
+    id: usize,
+    usable: Arc<AtomicBool>,
+    closed: Arc<AtomicUsize>,
+}
+
+impl PoolConnection for FakeConnection {
+    async fn is_usable(&mut self) -> bool {
+        self.usable.load(Ordering::SeqCst)
+    }
+
+    async fn close(self) {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    opened: AtomicUsize,
+    usable: Arc<AtomicBool>,
+    closed: Arc<AtomicUsize>,
+}
+
+impl Server {
+    fn new() -> Arc<Self> {
+        let server = Server::default();
+        server.usable.store(true, Ordering::SeqCst);
+        Arc::new(server)
+    }
+
+    async fn connect(&self) -> Result<FakeConnection, Infallible> {
+        Ok(FakeConnection {
+            id: self.opened.fetch_add(1, Ordering::SeqCst),
+            usable: self.usable.clone(),
+            closed: self.closed.clone(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_released_connection_is_reused_for_same_account() {
+    let server = Server::new();
+    let pool = Pool::new(2, Duration::from_secs(300));
+
+    let first = pool.checkout("alice", || server.connect()).await.unwrap();
+    assert_eq!(first.id, 0);
+    first.release();
+    let again = pool.checkout("alice", || server.connect()).await.unwrap();
+    assert_eq!(again.id, 0);
+    assert_eq!(server.opened.load(Ordering::SeqCst), 1);
+
+    // Another account opens its own connection while alice's is in use
+    let bob = pool.checkout("bob", || server.connect()).await.unwrap();
+    assert_eq!(bob.id, 1);
+    assert_eq!(pool.open_connections(), 2);
+    again.release();
+    bob.release();
+    assert_eq!(pool.idle_connections(), 2);
+}
+
+#[tokio::test]
+async fn test_full_pool_waits_for_a_connection() {
+    let server = Server::new();
+    let pool = Pool::new(1, Duration::from_secs(300));
+
+    let held = pool.checkout("alice", || server.connect()).await.unwrap();
+    let waiting = {
+        let pool = pool.clone();
+        let server = server.clone();
+        tokio::spawn(async move {
+            let connection = pool.checkout("alice", || server.connect()).await.unwrap();
+            connection.id
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished(), "a second operation must not share the checked-out session");
+
+    held.release();
+    assert_eq!(waiting.await.unwrap(), 0);
+    assert_eq!(server.opened.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_full_pool_replaces_idle_connection_of_another_account() {
+    let server = Server::new();
+    let pool = Pool::new(1, Duration::from_secs(300));
+
+    pool.checkout("alice", || server.connect()).await.unwrap().release();
+    let bob = pool.checkout("bob", || server.connect()).await.unwrap();
+    assert_eq!(bob.id, 1);
+    assert_eq!(server.closed.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.open_connections(), 1);
+}
+
+#[tokio::test]
+async fn test_dropped_and_broken_connections_free_their_slot() {
+    let server = Server::new();
+    let pool = Pool::new(1, Duration::from_secs(300));
+
+    // Dropped without release, e.g. after an error mid-operation
+    drop(pool.checkout("alice", || server.connect()).await.unwrap());
+    assert_eq!(pool.open_connections(), 0);
+
+    pool.checkout("alice", || server.connect()).await.unwrap().release();
+    server.usable.store(false, Ordering::SeqCst);
+    let fresh = pool.checkout("alice", || server.connect()).await.unwrap();
+    assert_eq!(fresh.id, 2);
+    assert_eq!(pool.open_connections(), 1);
+}
+
+#[tokio::test]
+async fn test_idle_connections_are_evicted() {
+    let server = Server::new();
+    let pool = Pool::new(2, Duration::from_millis(20));
+
+    pool.checkout("alice", || server.connect()).await.unwrap().release();
+    assert_eq!(pool.evict_idle().await, 0);
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    assert_eq!(pool.evict_idle().await, 1);
+    assert_eq!(pool.open_connections(), 0);
+    assert_eq!(server.closed.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,62 @@
+//! Tests for confining file paths to configured directories
+
+use protonmail_mcp_server::sandbox::{self, SandboxError};
+
+fn temp_root(name: &str) -> std::path::PathBuf {
+    let root = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+#[test]
+fn test_resolve_for_write_rejects_traversal_and_absolute_paths() {
+    let root = temp_root("sandbox-traversal");
+
+    assert!(matches!(sandbox::resolve_for_write(&root, "../escape.txt"), Err(SandboxError::Escape(_))));
+    assert!(matches!(sandbox::resolve_for_write(&root, "a/../../escape.txt"), Err(SandboxError::Escape(_))));
+    assert!(matches!(sandbox::resolve_for_write(&root, "/etc/passwd"), Err(SandboxError::InvalidPath(..))));
+
+    let nested = sandbox::resolve_for_write(&root, "new/dir/file.pdf").unwrap();
+    assert!(nested.starts_with(root.canonicalize().unwrap()));
+
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resolve_for_write_rejects_symlink_escapes() {
+    let root = temp_root("sandbox-symlink");
+    let outside = temp_root("sandbox-outside");
+    std::os::unix::fs::symlink(&outside, root.join("linked-dir")).unwrap();
+    std::os::unix::fs::symlink(outside.join("missing"), root.join("dangling")).unwrap();
+
+    assert!(matches!(sandbox::resolve_for_write(&root, "linked-dir/file.txt"), Err(SandboxError::Escape(_))));
+    assert!(matches!(sandbox::resolve_for_write(&root, "dangling"), Err(SandboxError::Escape(_))));
+
+    std::fs::remove_dir_all(&root).ok();
+    std::fs::remove_dir_all(&outside).ok();
+}
+
+#[test]
+fn test_sanitize_filename() {
+    assert_eq!(sandbox::sanitize_filename("report.pdf"), "report.pdf");
+    assert_eq!(sandbox::sanitize_filename("../../.ssh/authorized_keys"), "authorized_keys");
+    assert_eq!(sandbox::sanitize_filename("C:\\Users\\me\\invoice.xlsx"), "invoice.xlsx");
+    assert_eq!(sandbox::sanitize_filename(".bashrc"), "bashrc");
+    assert_eq!(sandbox::sanitize_filename("a\u{0}b|c?.txt"), "a_b_c_.txt");
+    assert_eq!(sandbox::sanitize_filename(".."), "attachment");
+    assert_eq!(sandbox::sanitize_filename(""), "attachment");
+    assert!(sandbox::sanitize_filename(&"é".repeat(300)).len() <= 200);
+}
+
+#[test]
+fn test_disk_usage_counts_nested_files() {
+    let root = temp_root("sandbox-usage");
+    std::fs::create_dir_all(root.join("sub")).unwrap();
+    std::fs::write(root.join("a.bin"), [0u8; 10]).unwrap();
+    std::fs::write(root.join("sub/b.bin"), [0u8; 5]).unwrap();
+
+    assert_eq!(sandbox::disk_usage(&root).unwrap(), 15);
+
+    std::fs::remove_dir_all(&root).ok();
+}
@@ -0,0 +1,303 @@
+//! Tests for detecting and verifying PGP and S/MIME signatures
+
+use base64::Engine;
+use mail_parser::MessageParser;
+use protonmail_mcp_server::signature::{self, SecurityFormat, TrustStore, Verification};
+use std::path::PathBuf;
+
+/// Public key of "Alice Signer <alice@example.com>", as written by `gpg --export`
+const ALICE_KEYRING: &str = "\
+mDMEatTcgRYJKwYBBAHaRw8BAQdAM4qw6Mjnb8Hnb3u6rzLA8tJEw0L5068foDDMpxkGRi20IEFs\
+aWNlIFNpZ25lciA8YWxpY2VAZXhhbXBsZS5jb20+iJAEExYIADgWIQS5SpnrdS8mEVmPJpMGzxgY\
+vdym1wUCatTcgQIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRAGzxgYvdym105VAP4+EXyp\
+mcuw9r8Dq6k8OvjAPTEZVXCgqmEsUPvNdWqcQgEA7YQoVz6mIxlmo3VkoUAUV6LNsP7I0uqdaiSm\
+dLO0Bg0=";
+
+const ALICE_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----\r
+\r
+iIgEABYIADAWIQS5SpnrdS8mEVmPJpMGzxgYvdym1wUCatTcgRIcYWxpY2VAZXhh\r
+bXBsZS5jb20ACgkQBs8YGL3cptceKAEAwOIvCmflW4glZs2q1sVGrab/TbTdgDyt\r
+G1j7msBbfPEA/RAp8uaVMa5AlqReiFrfCAPnjgx7OcnivVYxefFnGjUB\r
+=RzNX\r
+-----END PGP SIGNATURE-----\r
+";
+
+/// Signature over the same content by a key that is not in the keyring
+const MALLORY_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----\r
+\r
+iIoEABYIADIWIQTa6OQ0bakBEOuwyYES+vIXJd9gNQUCatTcgRQcbWFsbG9yeUBl\r
+eGFtcGxlLmNvbQAKCRAS+vIXJd9gNboKAQDc6+TbJTzckzjM3Z5KOzBO5AIL2meC\r
+85buobbBftwGOAD8Dnbi6ItvsskmKnQE7hZJje2Sdr0AS8ZTVSLKON1X5QA=\r
+=rMNn\r
+-----END PGP SIGNATURE-----\r
+";
+
+const CLEARSIGNED: &str = "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+Meet at noon.
+-----BEGIN PGP SIGNATURE-----
+
+iIgEARYIADAWIQS5SpnrdS8mEVmPJpMGzxgYvdym1wUCatTcgRIcYWxpY2VAZXhh
+bXBsZS5jb20ACgkQBs8YGL3cptcYxQD/STn5/LqVyemfEbN9Ae22OVGTqxIf/j+a
+BJ+yiG3iRWkBAOCy4hy9pB8vuQ/0j5T2LJJ+4+wPOY0zACaDQKyApvED
+=C9Bh
+-----END PGP SIGNATURE-----
+";
+
+const ENCRYPTED: &str = "-----BEGIN PGP MESSAGE-----
+
+hF4DzC/8pPxF+nISAQdAvrm797HZqCos7xJaTkVjomozLT5+pkVj6S0DFDxGiWsw
+2ZCnJO8tPO2fEkyO75QO8hGzUU11I2ygEvoTv+tD2xZ817utEzbteZVDdWWY9pf0
+0kIB0T1dUr8LQnEB3HYs+fgJBWEvA98H8tbvJ+QFukuqJQcj7JcMMWWuyCCvIVzH
+kizQlOsrFX5+QI77Y99o8LC4Asg=
+=il7X
+-----END PGP MESSAGE-----
+";
+
+/// Detached CMS signature by "Bob Sender" over `SMIME_CONTENT`
+const SMIME_SIGNATURE: &str = "\
+MIIDvgYJKoZIhvcNAQcCoIIDrzCCA6sCAQExDTALBglghkgBZQMEAgEwCwYJKoZIhvcNAQcBoIIB\r
+7jCCAeowggGQoAMCAQICFHjtdPX1ifDWXBNrpQ9ohPdXFLwrMAoGCCqGSM49BAMCMCwxEzARBgNV\r
+BAoMCkV4YW1wbGUgQ0ExFTATBgNVBAMMDEV4YW1wbGUgUm9vdDAgFw0yNjEwMTgxNDQ5NTBaGA8y\r
+MTI2MDkyNDE0NDk1MFowOTELMAkGA1UEBhMCREUxFTATBgNVBAoMDEV4YW1wbGUgQ29ycDETMBEG\r
+A1UEAwwKQm9iIFNlbmRlcjBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABP3Xvs3q6ERFKljf19bD\r
+beP/qSSOUzQpPbQ5NfGaTRkwra/G6xwlVXcAg4CgxPqxqRbfqoZSb9s8bv6AJGilaSCjgYAwfjAa\r
+BgNVHREEEzARgQ9ib2JAZXhhbXBsZS5jb20wCwYDVR0PBAQDAgeAMBMGA1UdJQQMMAoGCCsGAQUF\r
+BwMEMB0GA1UdDgQWBBTAL9qLXeaAESv/icgcCcmW34msqzAfBgNVHSMEGDAWgBTwtshR8T6bvDYb\r
+2lKpqwDxdH0KgTAKBggqhkjOPQQDAgNIADBFAiEAjMg38tYMbIagmBogB8VJc3PNP6niPRvr2nP/\r
+YQjkgVMCICfY7EI+cRSEnHzz8XHWRr1TVPoVwf0SQLbSPKwKky1lMYIBljCCAZICAQEwRDAsMRMw\r
+EQYDVQQKDApFeGFtcGxlIENBMRUwEwYDVQQDDAxFeGFtcGxlIFJvb3QCFHjtdPX1ifDWXBNrpQ9o\r
+hPdXFLwrMAsGCWCGSAFlAwQCAaCB5DAYBgkqhkiG9w0BCQMxCwYJKoZIhvcNAQcBMBwGCSqGSIb3\r
+DQEJBTEPFw0yNjEwMTgxNDQ5NTBaMC8GCSqGSIb3DQEJBDEiBCCjxW1pA6mWNULEQN3y47rUJPDK\r
+viBjl7G0/WlzV45epzB5BgkqhkiG9w0BCQ8xbDBqMAsGCWCGSAFlAwQBKjALBglghkgBZQMEARYw\r
+CwYJYIZIAWUDBAECMAoGCCqGSIb3DQMHMA4GCCqGSIb3DQMCAgIAgDANBggqhkiG9w0DAgIBQDAH\r
+BgUrDgMCBzANBggqhkiG9w0DAgIBKDAKBggqhkjOPQQDAgRHMEUCIATlxssbPbz6sa7SkKY7SgaU\r
+UpVY5qyedj3So9YDNV2cAiEAhZxKMXUcSXN95hXaxNHcm0LBSA3uD9zNjseFofeiKqA=\r
+";
+
+const SMIME_CONTENT: &str = "Invoice attached, please pay.";
+
+const SMIME_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBnjCCAUSgAwIBAgIUeX+lhSOR9QPe2CvGt6+c2KJM1XUwCgYIKoZIzj0EAwIw
+LDETMBEGA1UECgwKRXhhbXBsZSBDQTEVMBMGA1UEAwwMRXhhbXBsZSBSb290MCAX
+DTI2MTAxODE0NDk1MFoYDzIxMjYwOTI0MTQ0OTUwWjAsMRMwEQYDVQQKDApFeGFt
+cGxlIENBMRUwEwYDVQQDDAxFeGFtcGxlIFJvb3QwWTATBgcqhkjOPQIBBggqhkjO
+PQMBBwNCAARj2IMJG7NXFJZzDBw9ec4mvgDbtNpdzprkFz+1J66znrndr07FzsAw
+Wo6HsYe3F3nnTaqB2+fFdhapB9q15MAJo0IwQDAPBgNVHRMBAf8EBTADAQH/MA4G
+A1UdDwEB/wQEAwICBDAdBgNVHQ4EFgQU8LbIUfE+m7w2G9pSqasA8XR9CoEwCgYI
+KoZIzj0EAwIDSAAwRQIgF1+h+WWs3FMttTYtD0D764pWjtxkTvuOZH8jC4RfHlQC
+IQCuwMUI4EKsxJQ2o/8kao1AYJDGPD88nRS9DS7dwMji5Q==
+-----END CERTIFICATE-----
+";
+
+fn pgp_signed_message(body: &str, signature: &str) -> Vec<u8> {
+    format!(
+        "From: alice@example.com\r\n\
+Subject: Payment\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/signed; micalg=pgp-sha256;\r\n \
+protocol=\"application/pgp-signature\"; boundary=\"sig\"\r\n\
+\r\n\
+--sig\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+{}\r\n\
+\r\n\
+--sig\r\n\
+Content-Type: application/pgp-signature; name=\"signature.asc\"\r\n\
+\r\n\
+{}\r\n\
+--sig--\r\n",
+        body, signature
+    )
+    .into_bytes()
+}
+
+fn smime_signed_message(body: &str) -> Vec<u8> {
+    format!(
+        "From: bob@example.com\r\n\
+Subject: Invoice\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/signed; protocol=\"application/pkcs7-signature\";\r\n \
+micalg=sha-256; boundary=\"smime\"\r\n\
+\r\n\
+--smime\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+{}\r\n\
+\r\n\
+--smime\r\n\
+Content-Type: application/pkcs7-signature; name=\"smime.p7s\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+{}\r\n\
+--smime--\r\n",
+        body, SMIME_SIGNATURE
+    )
+    .into_bytes()
+}
+
+/// Write a fixture into a per-test temporary file
+fn fixture_file(name: &str, data: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("signature-test-{}-{}", std::process::id(), name));
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn available(program: &str) -> bool {
+    let found = std::process::Command::new(program).arg("version").output().is_ok();
+    if !found {
+        eprintln!("skipping: {} is not installed", program);
+    }
+    found
+}
+
+#[test]
+fn test_pgp_mime_signature_names_signer_without_keyring() {
+    let raw = pgp_signed_message("Please wire the funds today.", ALICE_SIGNATURE);
+    let message = MessageParser::default().parse(&raw).unwrap();
+    let mut security = signature::inspect(&message).unwrap();
+
+    assert_eq!(security.format, SecurityFormat::PgpMime);
+    assert!(security.signed && !security.encrypted);
+    security.verify(&TrustStore::default());
+
+    let info = security.signature.unwrap();
+    assert_eq!(info.verification, Verification::NotChecked);
+    assert_eq!(info.key_id.as_deref(), Some("06CF1818BDDCA6D7"));
+    assert_eq!(info.fingerprint.as_deref(), Some("B94A99EB752F2611598F269306CF1818BDDCA6D7"));
+    assert_eq!(info.signer.as_deref(), Some("alice@example.com"));
+    assert!(info.signed_at.is_some());
+}
+
+#[test]
+fn test_gpgv_verifies_good_bad_and_unknown_signatures() {
+    if !available("gpgv") {
+        return;
+    }
+    let keyring = base64::engine::general_purpose::STANDARD.decode(ALICE_KEYRING).unwrap();
+    let trust = TrustStore {
+        pgp_keyring: Some(fixture_file("alice.gpg", &keyring)),
+        ..Default::default()
+    };
+    let check = |body: &str, sig: &str| {
+        let raw = pgp_signed_message(body, sig);
+        let mut security = signature::inspect(&MessageParser::default().parse(&raw).unwrap()).unwrap();
+        security.verify(&trust);
+        security.signature.unwrap()
+    };
+
+    let good = check("Please wire the funds today.", ALICE_SIGNATURE);
+    assert_eq!(good.verification, Verification::Verified, "{:?}", good.detail);
+    assert_eq!(good.signer.as_deref(), Some("Alice Signer <alice@example.com>"));
+
+    let tampered = check("Please wire the funds to account 1234 today.", ALICE_SIGNATURE);
+    assert_eq!(tampered.verification, Verification::BadSignature);
+
+    let unknown = check("Please wire the funds today.", MALLORY_SIGNATURE);
+    assert_eq!(unknown.verification, Verification::UnknownSigner);
+    assert_eq!(unknown.key_id.as_deref(), Some("12FAF21725DF6035"));
+
+    std::fs::remove_file(trust.pgp_keyring.unwrap()).ok();
+}
+
+#[test]
+fn test_inline_pgp_blocks_are_detected() {
+    let raw = format!("From: alice@example.com\r\nSubject: Inline\r\n\r\n{}", CLEARSIGNED);
+    let security = signature::inspect(&MessageParser::default().parse(raw.as_bytes()).unwrap()).unwrap();
+    assert_eq!(security.format, SecurityFormat::PgpInline);
+    assert!(security.signed);
+    assert_eq!(security.signature.unwrap().key_id.as_deref(), Some("06CF1818BDDCA6D7"));
+
+    let raw = format!("From: alice@example.com\r\nSubject: Secret\r\n\r\n{}", ENCRYPTED);
+    let security = signature::inspect(&MessageParser::default().parse(raw.as_bytes()).unwrap()).unwrap();
+    assert!(security.encrypted && !security.signed);
+    assert_eq!(security.encrypted_to, vec!["CC2FFCA4FC45FA72".to_string()]);
+
+    let plain = MessageParser::default().parse(b"Subject: Hi\r\n\r\nJust text.\r\n").unwrap();
+    assert!(signature::inspect(&plain).is_none());
+}
+
+#[test]
+fn test_inline_signature_covering_part_of_the_body() {
+    let whole = format!("From: alice@example.com\r\nSubject: Inline\r\n\r\n\r\n{}\r\n", CLEARSIGNED);
+    let security = signature::inspect(&MessageParser::default().parse(whole.as_bytes()).unwrap()).unwrap();
+    assert!(security.signed_text.is_none());
+
+    let partial = format!(
+        "From: alice@example.com\r\nSubject: Inline\r\n\r\nUpdate: the meeting moved to a new address.\r\n\r\n{}",
+        CLEARSIGNED.replace("Meet at noon.", "- -- Meet at noon.")
+    );
+    let security = signature::inspect(&MessageParser::default().parse(partial.as_bytes()).unwrap()).unwrap();
+    assert_eq!(security.signed_text.as_deref(), Some("-- Meet at noon."));
+
+    if !available("gpgv") {
+        return;
+    }
+    let keyring = base64::engine::general_purpose::STANDARD.decode(ALICE_KEYRING).unwrap();
+    let trust = TrustStore {
+        pgp_keyring: Some(fixture_file("alice-inline.gpg", &keyring)),
+        ..Default::default()
+    };
+    let verify = |raw: String| {
+        let mut security = signature::inspect(&MessageParser::default().parse(raw.as_bytes()).unwrap()).unwrap();
+        security.verify(&trust);
+        security.signature.unwrap().verification
+    };
+    assert_eq!(verify(whole), Verification::Verified);
+    let prefixed = format!("From: alice@example.com\r\nSubject: Inline\r\n\r\nPay the invoice below.\r\n{}", CLEARSIGNED);
+    assert_eq!(verify(prefixed), Verification::VerifiedPart);
+
+    std::fs::remove_file(trust.pgp_keyring.unwrap()).ok();
+}
+
+#[test]
+fn test_smime_signature_reports_certificate_subject() {
+    let raw = smime_signed_message(SMIME_CONTENT);
+    let security = signature::inspect(&MessageParser::default().parse(&raw).unwrap()).unwrap();
+    assert_eq!(security.format, SecurityFormat::Smime);
+    assert!(security.signed);
+
+    let info = security.signature.unwrap();
+    assert_eq!(info.verification, Verification::NotChecked);
+    assert_eq!(info.signer.as_deref(), Some("CN=Bob Sender, O=Example Corp, C=DE"));
+    assert_eq!(info.email.as_deref(), Some("bob@example.com"));
+    assert_eq!(info.signed_at.unwrap().to_rfc3339(), "2026-10-18T14:49:50+00:00");
+}
+
+#[test]
+fn test_deeply_nested_indefinite_lengths_are_rejected() {
+    let nested: Vec<u8> = std::iter::repeat_n([0x30u8, 0x80], 100_000).flatten().collect();
+    let raw = format!(
+        "From: bob@example.com\r\nContent-Type: application/pkcs7-mime; smime-type=signed-data\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        base64::engine::general_purpose::STANDARD.encode(&nested)
+    );
+    let security = signature::inspect(&MessageParser::default().parse(raw.as_bytes()).unwrap()).unwrap();
+    assert!(!security.signed);
+}
+
+#[test]
+fn test_openssl_verifies_smime_against_ca_bundle() {
+    if !available("openssl") {
+        return;
+    }
+    let trust = TrustStore {
+        smime_ca_file: Some(fixture_file("ca.pem", SMIME_CA.as_bytes())),
+        ..Default::default()
+    };
+    let check = |body: &str| {
+        let raw = smime_signed_message(body);
+        let mut security = signature::inspect(&MessageParser::default().parse(&raw).unwrap()).unwrap();
+        security.verify(&trust);
+        security.signature.unwrap()
+    };
+
+    let good = check(SMIME_CONTENT);
+    assert_eq!(good.verification, Verification::Verified, "{:?}", good.detail);
+
+    let tampered = check("Invoice attached, please pay to the new account.");
+    assert_eq!(tampered.verification, Verification::BadSignature);
+
+    std::fs::remove_file(trust.smime_ca_file.unwrap()).ok();
+}
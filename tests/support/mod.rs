@@ -0,0 +1,70 @@
+//! Runs the MCP server in-process against a fake IMAP server, so tests can
+//! call tools the way a client does
+
+#![allow(dead_code)]
+
+pub mod imap;
+
+use protonmail_mcp_server::ImapMailboxServer;
+use protonmail_mcp_server::config::{Config, Sources, load_config_from};
+use rmcp::model::CallToolRequestParam;
+use rmcp::service::{RunningService, ServiceError};
+use rmcp::{ErrorData, RoleClient, ServiceExt};
+use serde_json::Value;
+
+pub use self::imap::{FakeImap, Mailbox};
+
+/// Settings for one account on `imap`, to which tests add their own
+pub fn sources(imap: &FakeImap) -> Sources {
+    Sources::default()
+        .with_env("IMAP_HOST", "127.0.0.1")
+        .with_env("IMAP_PORT", &imap.port.to_string())
+        .with_env("IMAP_USERNAME", "me@example.com")
+        .with_env("IMAP_PASSWORD", "bridge-password")
+        .with_env("IMAP_USE_TLS", "true")
+        .with_env("IMAP_SKIP_TLS_VERIFY", "true")
+}
+
+pub struct Client {
+    service: RunningService<RoleClient, ()>,
+}
+
+impl Client {
+    pub async fn start(sources: &Sources) -> Self {
+        Self::with_server(ImapMailboxServer::with_config(Self::config(sources))).await
+    }
+
+    pub fn config(sources: &Sources) -> Config {
+        load_config_from(sources).unwrap()
+    }
+
+    pub async fn with_server(server: ImapMailboxServer) -> Self {
+        let (server_io, client_io) = tokio::io::duplex(1 << 20);
+        tokio::spawn(async move {
+            if let Ok(running) = server.serve(server_io).await {
+                running.waiting().await.ok();
+            }
+        });
+        Client { service: ().serve(client_io).await.unwrap() }
+    }
+
+    /// Call a tool, returning its JSON result or the error it failed with
+    pub async fn call(&self, tool: &str, arguments: Value) -> Result<Value, ErrorData> {
+        let request = CallToolRequestParam {
+            name: tool.to_string().into(),
+            arguments: arguments.as_object().cloned(),
+            task: None,
+        };
+        match self.service.call_tool(request).await {
+            Ok(result) => {
+                let text = result.content.first()
+                    .and_then(|content| content.as_text())
+                    .map(|text| text.text.clone())
+                    .unwrap_or_default();
+                Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+            }
+            Err(ServiceError::McpError(error)) => Err(error),
+            Err(other) => panic!("{} failed: {}", tool, other),
+        }
+    }
+}